@@ -3,27 +3,267 @@
 //! Owns all runtime components and drives the push-to-talk recording cycle,
 //! delegating each concern to the appropriate module.
 
-use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex, PoisonError};
 use std::thread::JoinHandle;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 
 use anyhow::{Context, Result};
-use tracing::{error, info};
+use global_hotkey::{GlobalHotKeyEvent, HotKeyState};
+use tracing::{debug, error, info, warn};
 
 #[cfg(windows)]
 use windows::Win32::UI::WindowsAndMessaging::{
     DispatchMessageW, MSG, PM_REMOVE, PeekMessageW, TranslateMessage,
 };
 
-use crate::audio::AudioRecorder;
-use crate::config::Config;
-use crate::feedback::FeedbackPlayer;
+use crate::audio::{self, AudioLevels, AudioRecorder, CapturedAudio};
+use crate::captions::CaptionServer;
+use crate::clipboard;
+use crate::commands::{self, CommandAction};
+use crate::config::{self, Config};
+use crate::correction::Correction;
+use crate::crash;
+use crate::devices::InputWatcher;
+use crate::diagnostics::HealthReport;
+use crate::dialog;
+use crate::duplicate::{DuplicateAction, DuplicateCheck};
+use crate::error::SttError;
+use crate::feedback::{FeedbackEvent, FeedbackPlayer};
+use crate::filter::{Filtered, WordFilter};
+use crate::foreground::{self, RefocusFallback, TargetWindow};
+use crate::history::{self, Attribution, TranscriptionHistory};
 use crate::hotkey::HotkeyListener;
+use crate::http;
 use crate::input::TextInjector;
-use crate::tray::{TrayManager, TrayState};
-use crate::volume::VolumeDucker;
-use crate::whisper::WhisperEngine;
+use crate::instance::InstanceLock;
+use crate::ipc::{AppStatus, IpcCommand, IpcResponse, IpcServer};
+use crate::logging;
+use crate::memory::{self, Shortfall};
+use crate::models::ModelPool;
+use crate::notify::Severity;
+use crate::output;
+use crate::overlay::{Overlay, OverlayPlacement};
+use crate::paths;
+use crate::pipeline::TextPipeline;
+use crate::privacy::PrivacyPolicy;
+use crate::punctuation::Punctuation;
+use crate::quiet;
+use crate::recovery::Recovery;
+use crate::resend::ResendHistory;
+use crate::session::{self, SessionWatcher, Shutdown};
+use crate::spacing::Spacing;
+use crate::state::StateStore;
+use crate::stats::{SessionStats, Timings};
+use crate::tokens::TokenDump;
+use crate::tray::{IconFiles, TrayCommand, TrayManager, TrayState};
+use crate::volume::{DuckSettings, DuckingWorker};
+use crate::wake;
+use crate::webhook::{Webhook, WebhookPayload};
+use crate::whisper::{LanguageCode, Transcript, TranscriptionTask, WhisperEngine, WhisperOptions};
+
+/// How often the configuration files are checked for modification
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often the physical hotkey state is read while a held recording runs
+const RELEASE_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Time before a silence auto-stop at which the warning cue plays
+const AUTO_STOP_WARNING: Duration = Duration::from_secs(1);
+
+/// Input ignored after the warning cue, which the microphone may pick up
+const AUTO_STOP_CUE_GRACE: Duration = Duration::from_millis(400);
+
+/// Peak level below which a recording counts as silent, in dB relative to full scale
+const SILENT_PEAK_DB: f32 = -45.0;
+
+/// Average level below which speech in a recording counts as too quiet, in dB
+const QUIET_RMS_DB: f32 = -50.0;
+
+/// Characters of a preview shown in the tooltip, from its end
+const PREVIEW_TOOLTIP_CHARS: usize = 80;
+
+/// Hold-to-lock state of the current recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LatchState {
+    /// Push-to-talk: releasing the hotkey stops the recording.
+    Unlatched,
+    /// Latched while the hotkey is still held; its release is ignored.
+    Held(Instant),
+    /// Latched and released; the next hotkey press stops the recording.
+    Released(Instant),
+}
+
+impl LatchState {
+    /// When the recording was latched, if it is.
+    const fn since(self) -> Option<Instant> {
+        match self {
+            Self::Unlatched => None,
+            Self::Held(since) | Self::Released(since) => Some(since),
+        }
+    }
+}
+
+/// A recording in progress, owned by the hotkey that started it.
+struct Recording {
+    /// Hotkey whose release, or next press once latched, stops the recording
+    hotkey: u32,
+    /// Whisper task the recording is transcribed with
+    task: TranscriptionTask,
+    /// Language the recording is transcribed in, chosen at the press
+    language: LanguageCode,
+    /// Hold-to-lock state
+    latch: LatchState,
+    /// Stop signal of this recording only, so an abandoned thread stays told to stop
+    stop: Arc<Mutex<bool>>,
+    /// Thread capturing the audio
+    thread: JoinHandle<Result<CapturedAudio, SttError>>,
+    /// When capturing started, for the tooltip and statistics
+    started: Instant,
+    /// Window that had focus when the hotkey was pressed
+    window: Option<TargetWindow>,
+    /// Preview transcription, when streaming is on
+    preview: Option<Preview>,
+    /// Silence tracking for the auto-stop, when it is on
+    silence: Option<SilenceWatch>,
+}
+
+/// Text typed last and the window it went to, for correcting it by voice.
+struct LastInjection {
+    /// Text as typed, with any corrections since applied
+    text: String,
+    /// Window that had focus once it was typed
+    window: TargetWindow,
+}
+
+/// Text that reached the user, as typed, written or copied.
+struct Delivered {
+    /// Text after post-processing and any inject template
+    text: String,
+    /// Words typed or written, zero when the text went to the clipboard
+    words: usize,
+}
+
+/// A repeated dictation held back until it is confirmed from the tray.
+struct HeldRepeat {
+    /// Text as it would have been typed
+    text: String,
+    /// Language code it was transcribed in
+    language: String,
+    /// Window the recording started in
+    window: Option<TargetWindow>,
+    /// Ticket of its audio kept for recovery
+    recovery: Option<u64>,
+}
+
+/// Input level of a recording, for stopping it once latched and silent.
+struct SilenceWatch {
+    /// Peak input since the last check, as `f32` bits, raised by the recorder
+    peak: Arc<AtomicU32>,
+    /// When the input was last above the threshold, or the recording not latched
+    heard: Instant,
+    /// Whether the warning cue for the current silence played
+    warned: bool,
+    /// End of the warning cue, which the microphone may pick up; the input
+    /// is ignored until the first check after it
+    muted_until: Option<Instant>,
+}
+
+impl SilenceWatch {
+    /// When the silence began, counted from the release of the latch at the earliest
+    fn silent_since(&self, latched: Instant) -> Instant {
+        self.heard.max(latched)
+    }
+}
+
+/// Preview of a recording still in progress, transcribed every few seconds.
+///
+/// Only shown, never typed; the full pass after release replaces it.
+struct Preview {
+    /// Buffers captured since they were last collected
+    chunks: Receiver<Vec<f32>>,
+    /// Everything captured so far
+    samples: Vec<f32>,
+    /// When the last pass started, or the recording did
+    last_pass: Instant,
+    /// Pass in progress, `None` inside when it gave way to a full pass
+    running: Option<JoinHandle<Result<Option<String>, SttError>>>,
+    /// Text of the latest finished pass
+    text: Option<String>,
+}
+
+/// Audio of a finished recording on its way to Whisper.
+///
+/// Downmixing and resampling run on a worker of their own, so they overlap
+/// with the wait for a model that is still loading.
+enum PreparedAudio {
+    /// Worker converting the captured audio, returning the samples, their
+    /// levels and the time it took
+    Preparing(JoinHandle<(Vec<f32>, AudioLevels, Duration)>),
+    /// Samples as Whisper expects them, handed to the thread when transcription starts
+    Ready(Vec<f32>),
+}
+
+/// Transcription thread, returning the text and, while tokens are dumped,
+/// the transcript it was joined from
+type TranscriptionRun = JoinHandle<Result<(String, Option<Transcript>), SttError>>;
+
+/// A finished recording whose text has not been typed yet.
+///
+/// Queued in recording order and transcribed one at a time, so results are
+/// typed in the order they were spoken even while a new recording runs.
+struct Transcription {
+    /// Recorded audio, being prepared or ready for Whisper
+    audio: PreparedAudio,
+    /// Loudness of the prepared audio, to explain an empty result
+    levels: AudioLevels,
+    /// Whisper task chosen by the hotkey
+    task: TranscriptionTask,
+    /// Language in effect when the recording stopped
+    language: LanguageCode,
+    /// Model picked for the recording's length
+    model: PathBuf,
+    /// Window the text is typed into
+    window: Option<TargetWindow>,
+    /// Time spent in each stage so far
+    timings: Timings,
+    /// Transcription thread, once running
+    running: Option<TranscriptionRun>,
+    /// Ticket of the audio saved for crash recovery, once saved
+    recovery: Option<u64>,
+    /// Whether this is a dictation recovered after a crash, copied instead of typed
+    recovered: bool,
+}
+
+impl Transcription {
+    /// Take the prepared samples once their worker finished, `None` while it runs.
+    ///
+    /// A worker that panicked is reported as [`SttError::Crashed`].
+    fn take_samples(&mut self) -> Option<Result<Vec<f32>, SttError>> {
+        if let PreparedAudio::Preparing(ref worker) = self.audio
+            && !worker.is_finished()
+        {
+            return None;
+        }
+        match std::mem::replace(&mut self.audio, PreparedAudio::Ready(Vec::new())) {
+            PreparedAudio::Ready(samples) => Some(Ok(samples)),
+            PreparedAudio::Preparing(worker) => Some(match worker.join() {
+                Ok((samples, levels, took)) => {
+                    self.timings.prepared(took);
+                    self.levels = levels;
+                    Ok(samples)
+                }
+                Err(payload) => Err(SttError::Crashed {
+                    task: "Audio preparation",
+                    message: crash::payload_message(payload.as_ref()).to_owned(),
+                }),
+            }),
+        }
+    }
+}
 
 /// Holds all runtime components and drives the event loop.
 pub struct App {
@@ -33,38 +273,173 @@ pub struct App {
     hotkey: HotkeyListener,
     /// Audio feedback player
     feedback: FeedbackPlayer,
+    /// On-screen recording indicator
+    overlay: Overlay,
     /// Text injection into the active window
     injector: TextInjector,
     /// Volume boost applied to recorded audio
     volume_boost: f32,
-    /// Loaded Whisper engine, or None if currently unloaded
-    whisper: Option<WhisperEngine>,
-    /// Background thread handle for in-progress model loading
-    model_load_handle: Option<JoinHandle<Result<WhisperEngine>>>,
-    /// Timestamp of the last completed transcription, used for cooldown-based unloading
-    last_model_use: Option<Instant>,
-    /// Path to the Whisper model file
-    model_path: PathBuf,
+    /// Loaded and loading Whisper models, each unloaded after `model_unload_delay_secs` idle
+    models: ModelPool,
     /// Number of CPU threads to use for Whisper inference
     whisper_threads: usize,
     /// Language code for transcription
-    whisper_language: String,
-    /// How long to keep the model loaded after the last use before unloading
-    model_unload_delay: Duration,
-    /// Active volume ducker, present only while recording is in progress
-    volume_ducker: Option<VolumeDucker>,
+    whisper_language: LanguageCode,
+    /// Longest wait for a stopped recording thread before it is abandoned
+    recording_stop_timeout: Duration,
+    /// Longest wait for the model to load before the load is abandoned
+    model_load_timeout: Duration,
+    /// Ducking worker, started with the app when ducking is on, and by the
+    /// next ducked recording after it was stopped or ducking turned on
+    volume_ducker: Option<DuckingWorker>,
+    /// Whether other audio is ducked or paused, shared by the worker and the feedback player
+    audio_ducked: Arc<AtomicBool>,
+    /// Flags microphones being plugged in or removed
+    input_watcher: InputWatcher,
+    /// Whether a microphone was connected at the last check
+    microphone_present: bool,
+    /// Longest a latched recording may run before it is stopped automatically
+    latch_max_duration: Duration,
+    /// How long the hotkey may be up without a release event, zero to never check
+    release_failsafe: Duration,
+    /// Since when the held hotkey has been seen up without a release event
+    key_up_since: Option<Instant>,
+    /// Lowercased executable names in which hotkey presses are ignored
+    hotkey_disabled_in: Vec<String>,
+    /// Whether to play a warning sound when a press is ignored
+    hotkey_disabled_sound: bool,
+    /// Whether the hotkey is paused from the tray menu
+    hotkey_paused: bool,
+    /// Recording in progress, if any
+    recording: Option<Recording>,
+    /// Finished recordings waiting to be transcribed and typed, oldest first
+    transcriptions: VecDeque<Transcription>,
+    /// Configuration currently in effect, kept to detect changes on reload
+    config: Config,
+    /// Most recent transcription, kept only when the privacy setting allows it
+    last_transcription: Option<String>,
+    /// Last typed text, kept while corrections by voice are on
+    last_injection: Option<LastInjection>,
+    /// Latest text per recent target app, for the tray's re-send menu
+    resend: ResendHistory,
+    /// Audio of the dictation in flight, kept until it is typed
+    recovery: Recovery,
+    /// Where Whisper's tokens of each dictation are written, if anywhere
+    token_dump: Option<TokenDump>,
+    /// The previous dictation, to spot an accidental repeat of it
+    duplicates: DuplicateCheck,
+    /// A repeat waiting to be confirmed, with `duplicate_action = "confirm"`
+    held_repeat: Option<HeldRepeat>,
+    /// Which sinks besides the target window may see transcribed text
+    privacy: PrivacyPolicy,
+    /// Sentence breaks added to unpunctuated transcriptions
+    punctuation: Punctuation,
+    /// How segments are joined into the typed text
+    spacing: Spacing,
+    /// Elapsed whole seconds last written to the tooltip, to throttle shell updates
+    shown_elapsed_secs: Option<u64>,
+    /// Dictation counters for the Statistics item
+    stats: SessionStats,
+    /// Opt-in log of typed transcriptions
+    history: TranscriptionHistory,
+    /// Endpoint each typed transcription is posted to, if configured
+    webhook: Option<Webhook>,
+    /// Post-processing of each transcription, in the order `text_pipeline` lists
+    text_pipeline: TextPipeline,
+    /// WebSocket server for live captions, if configured
+    captions: Option<CaptionServer>,
+    /// Modification times of the configuration files at the last check
+    config_stamp: Vec<Option<SystemTime>>,
+    /// When the configuration files were last checked for changes
+    config_checked: Instant,
+    /// A reload was requested and waits until no recording is in progress
+    config_reload_pending: bool,
+    /// Settings changed from the tray, waiting to be saved
+    state: StateStore,
+    /// Single-instance lock, held until the app exits
+    instance: InstanceLock,
+    /// Commands from scripts over the control pipe
+    ipc: IpcServer,
+    /// Most recent error shown to the user, reported by the `status` command
+    last_error: Option<String>,
+    /// When the app started, for the reported uptime
+    started: Instant,
+    /// Hidden window told by Windows when the session ends
+    #[allow(
+        dead_code,
+        reason = "only kept alive; its window procedure does the work"
+    )]
+    session: SessionWatcher,
 }
 
 impl App {
     /// Initialize all components from the provided configuration.
-    pub fn new(config: Config) -> Result<Self> {
-        let tray = TrayManager::new().context("Failed to create system tray")?;
-        let hotkey = HotkeyListener::new(&config.hotkey_modifier, &config.hotkey_key)
-            .context("Failed to create hotkey listener")?;
-        let feedback = FeedbackPlayer::new(config.enable_sound_feedback);
+    pub fn new(config: Config, instance: InstanceLock) -> Result<Self> {
+        // Hotkey and menu handlers wake the thread that builds the app
+        wake::init();
+        // The tray icon lives on GTK outside Windows, which must be running first
+        #[cfg(target_os = "linux")]
+        gtk::init().context("Failed to initialize GTK")?;
+        let session = SessionWatcher::create().context("Failed to watch for session end")?;
+        let mut tray = TrayManager::new(
+            config.enable_notifications,
+            config.enable_overlay,
+            config.whisper_language.as_str(),
+            &config.language_choices,
+            &IconFiles::from_config(&config),
+        )
+        .context("Failed to create system tray")?;
+        tray.set_input_devices(&audio::input_device_names(), config.input_device.as_deref())?;
+        let microphone_present = audio::has_input_device();
+        if !microphone_present {
+            warn!("No microphone connected at startup");
+        }
+        tray.set_microphone_present(microphone_present)?;
+        tray.set_profiles(&config.profiles, config.profile.as_deref())?;
+        let hotkey = HotkeyListener::new(
+            config.hotkey_modifier,
+            config.hotkey_key,
+            config.hotkey_latch_key,
+            config
+                .translate_hotkey_key
+                .map(|key| (config.translate_hotkey_modifier, key)),
+            config.secondary_language.is_some(),
+        )
+        .map_err(|e| {
+            dialog::show_error_blocking("Speedy STT hotkey", &e.user_message());
+            anyhow::Error::new(e).context("Failed to create hotkey listener")
+        })?;
+        let audio_ducked = Arc::new(AtomicBool::new(false));
+        let feedback = FeedbackPlayer::new(&config, Arc::clone(&audio_ducked));
+        let history = transcription_history(&config);
+        tray.set_history_available(history.path().is_some(), history.is_daily());
+        let overlay = Overlay::new(
+            config.enable_overlay,
+            OverlayPlacement::from_config(&config),
+        );
         let injector = TextInjector::new();
-        let model_path = PathBuf::from(format!("./assets/models/{}", config.whisper_model));
+        let model_path = paths::model_file(&config.whisper_model);
+        let ipc = IpcServer::start(config.enable_ipc);
+        if config.http_port != 0
+            && let Err(e) =
+                http::start(config.http_port, config.http_token.clone(), ipc.requester())
+        {
+            warn!("HTTP endpoint disabled: {:#}", e);
+            tray.notify(
+                "Speedy STT",
+                &format!("HTTP endpoint disabled: {e:#}"),
+                Severity::Warning,
+            );
+        }
+        let captions = caption_server(&config).unwrap_or_else(|e| {
+            tray.notify("Speedy STT", &format!("{e:#}"), Severity::Warning);
+            None
+        });
 
+        paths::log_locations(config.file.as_deref(), &model_path);
+        for warning in &config.warnings {
+            warn!("Configuration: {}", warning);
+        }
         info!(
             "Speedy-STT ready. Hold {} + {} to record.",
             config.hotkey_modifier, config.hotkey_key
@@ -74,219 +449,2706 @@ impl App {
             tray,
             hotkey,
             feedback,
+            overlay,
             injector,
             volume_boost: config.volume_boost,
-            whisper: None,
-            model_load_handle: None,
-            last_model_use: None,
-            model_path,
-            whisper_threads: config.whisper_threads,
-            whisper_language: config.whisper_language,
-            model_unload_delay: Duration::from_secs(config.model_unload_delay_secs),
-            volume_ducker: None,
+            models: model_pool(&config),
+            whisper_threads: WhisperEngine::effective_threads(config.whisper_threads),
+            whisper_language: config.whisper_language.clone(),
+            recording_stop_timeout: Duration::from_secs(config.recording_stop_timeout_secs),
+            model_load_timeout: Duration::from_secs(config.model_load_timeout_secs),
+            // Started with the app so its thread and COM are set up before the first press
+            volume_ducker: config
+                .enable_audio_ducking
+                .then(|| DuckingWorker::start(Arc::clone(&audio_ducked))),
+            audio_ducked,
+            input_watcher: InputWatcher::start(),
+            microphone_present,
+            latch_max_duration: Duration::from_secs(config.latch_max_secs),
+            release_failsafe: Duration::from_millis(config.release_failsafe_ms),
+            key_up_since: None,
+            hotkey_disabled_in: config.hotkey_disabled_in.clone(),
+            hotkey_disabled_sound: config.hotkey_disabled_sound,
+            hotkey_paused: false,
+            recording: None,
+            transcriptions: VecDeque::new(),
+            stats: SessionStats::new(config.stats_daily_rollup.then(paths::stats_file)),
+            history,
+            webhook: webhook(&config),
+            text_pipeline: text_pipeline(&config),
+            captions,
+            config_stamp: config_stamp(),
+            config_checked: Instant::now(),
+            config_reload_pending: false,
+            state: StateStore::new(paths::state_file()),
+            instance,
+            ipc,
+            last_error: None,
+            started: Instant::now(),
+            session,
+            privacy: PrivacyPolicy::new(&config),
+            punctuation: Punctuation::from_pause_ms(config.punctuation_pause_ms),
+            spacing: Spacing::new(&config.no_space_languages),
+            resend: ResendHistory::new(resend_max_age(&config)),
+            recovery: Recovery::new(paths::recovery_file(), recovery_enabled(&config)),
+            token_dump: TokenDump::new(
+                &config,
+                WhisperEngine::effective_threads(config.whisper_threads),
+            ),
+            duplicates: DuplicateCheck::new(
+                Duration::from_secs(config.duplicate_window_secs),
+                config.duplicate_similarity,
+            ),
+            config,
+            last_transcription: None,
+            last_injection: None,
+            held_repeat: None,
+            shown_elapsed_secs: None,
         })
     }
 
-    /// Run the event loop until the user requests quit.
+    /// Run the event loop until the user requests quit or the Windows session ends.
     pub fn run(mut self) -> Result<()> {
-        self.tray.set_state(TrayState::Idle)?;
-
-        let receiver = global_hotkey::GlobalHotKeyEvent::receiver();
-        let mut is_recording = false;
-        let stop_signal = Arc::new(Mutex::new(false));
-        let mut recording_thread: Option<JoinHandle<Result<Vec<f32>>>> = None;
+        self.set_state(TrayState::Idle)?;
+        self.startup_check();
+        if let Some(id) = self
+            .hotkey
+            .sample_held_keys(self.config.hotkey_held_at_startup)
+        {
+            self.handle_hotkey(GlobalHotKeyEvent {
+                id,
+                state: HotKeyState::Pressed,
+            })?;
+        }
+        if self.recovery.has_leftover() {
+            self.tray.set_recovery_available(true);
+            self.notify(
+                Severity::Warning,
+                "A dictation was not typed before Speedy STT closed. Choose \"Transcribe recovered dictation\" in the tray to copy its text.",
+            );
+        }
+        if self.config.preload_model {
+            self.start_likely_model_load();
+        }
 
         loop {
             Self::pump_messages();
 
-            if self.tray.should_quit() {
-                info!("Quit requested");
-                break;
+            match session::requested() {
+                Shutdown::Prepare => {
+                    self.wind_down();
+                    self.refresh_state()?;
+                    session::prepared();
+                    continue;
+                }
+                Shutdown::Exit => {
+                    info!("Windows session ended, exiting");
+                    break;
+                }
+                Shutdown::None => {}
+            }
+            let suspended = session::take_suspend();
+            let resumed = session::take_resume();
+            if suspended || resumed {
+                // A recording open across sleep is discarded either way
+                self.wind_down();
+                if resumed {
+                    self.after_resume()?;
+                }
+                self.refresh_state()?;
+                continue;
             }
 
-            if let Ok(event) = receiver.try_recv()
-                && event.id == self.hotkey.hotkey.id()
-            {
-                match event.state {
-                    global_hotkey::HotKeyState::Pressed => {
-                        if !is_recording {
-                            is_recording = true;
-                            recording_thread =
-                                Some(self.start_recording(Arc::clone(&stop_signal))?);
-                        }
-                    }
-                    global_hotkey::HotKeyState::Released => {
-                        if is_recording {
-                            is_recording = false;
-                            self.finish_recording(&stop_signal, &mut recording_thread)?;
-                        }
-                    }
+            let command = self.tray.poll_command();
+            let hotkey_events = self.hotkey.next_events();
+            let request = self.ipc.poll();
+            let handled_event = command.is_some() || !hotkey_events.is_empty() || request.is_some();
+            match command {
+                Some(TrayCommand::Quit) => {
+                    info!("Quit requested");
+                    break;
+                }
+                Some(TrayCommand::Pause(paused)) => self.set_paused(paused)?,
+                Some(TrayCommand::ReloadConfig) => self.config_reload_pending = true,
+                Some(TrayCommand::OpenLog) => self.open_log(),
+                Some(TrayCommand::About) => self.show_about(),
+                Some(TrayCommand::Statistics) => {
+                    dialog::show_info("Speedy STT statistics", &self.stats.summary());
                 }
+                Some(TrayCommand::Diagnostics) => self.show_diagnostics(),
+                Some(TrayCommand::CopyLast) => self.copy_last_transcription(),
+                Some(TrayCommand::OpenHistory) => self.open_history(),
+                Some(TrayCommand::Resend(app)) => self.resend_transcription(&app),
+                Some(TrayCommand::ForgetTranscriptions) => self.forget_transcriptions(),
+                Some(TrayCommand::RecoverDictation) => self.recover_dictation()?,
+                Some(TrayCommand::TypeRepeat) => self.type_held_repeat(),
+                Some(TrayCommand::SetLanguage(language)) => self.choose_tray_language(&language),
+                Some(TrayCommand::SelectDevice(device)) => self.select_input_device(device),
+                Some(TrayCommand::RescanDevices) => self.rescan_input_devices()?,
+                Some(TrayCommand::SetProfile(profile)) => self.switch_profile(profile.as_deref()),
+                Some(TrayCommand::ShowOverlay(enabled)) => self.show_overlay(enabled),
+                None => {}
+            }
+            for event in hotkey_events {
+                self.handle_hotkey(event)?;
+            }
+            if let Some(request) = request {
+                let response = self.run_ipc_command(request.command)?;
+                // The client may have given up waiting
+                let _ = request.reply.send(response);
+            }
+
+            // Stop a latched recording that was forgotten running
+            if let Some(since) = self.recording.as_ref().and_then(|r| r.latch.since())
+                && since.elapsed() >= self.latch_max_duration
+            {
+                warn!(
+                    "Latched recording exceeded {}s - stopping automatically",
+                    self.latch_max_duration.as_secs()
+                );
+                self.finish_recording()?;
             }
+            self.check_silence()?;
 
-            // Unload model if the cooldown period has expired
-            if self.whisper.is_some()
-                && !is_recording
-                && let Some(last_use) = self.last_model_use
-                && last_use.elapsed() >= self.model_unload_delay
+            // Stop a held recording whose release event was lost
+            if let Some(hotkey) = self
+                .recording
+                .as_ref()
+                .filter(|r| r.latch == LatchState::Unlatched)
+                .map(|r| r.hotkey)
+                && self.release_missed(hotkey)
             {
-                self.whisper = None;
-                self.last_model_use = None;
-                info!("Whisper model unloaded after cooldown");
+                warn!(
+                    "Hotkey up for {}ms without a release event - stopping recording",
+                    self.release_failsafe.as_millis()
+                );
+                self.hotkey.forget_press(hotkey);
+                self.finish_recording()?;
+            }
+
+            if self.input_watcher.take_change() {
+                self.input_devices_changed()?;
+            }
+            self.poll_model_load()?;
+            self.advance_transcriptions()?;
+
+            if self.recording.is_some() {
+                self.advance_preview();
+                self.update_elapsed_tooltip();
+            }
+            if let Err(e) = self.tray.tick() {
+                warn!("Failed to animate tray icon: {}", e);
+            }
+            self.overlay.tick();
+
+            if self.resend.expire(Instant::now()) {
+                self.refresh_resend_menu();
+            }
+            self.poll_config_changes();
+            self.poll_ducking_error();
+            if self.instance.activation_requested() {
+                info!("Another launch was attempted while running");
+                self.notify(Severity::Info, "Speedy STT is already running");
+            }
+            self.state.save_if_due(self.config.file.as_deref());
+
+            // Unload models once their cooldown expired, unless a job still
+            // holds them or an event handled in this tick, such as a press, may need them
+            if !self.is_busy() && !self.config.keep_model_loaded {
+                for path in self.models.unload_idle(Instant::now(), handled_event) {
+                    info!("Whisper model {} unloaded after cooldown", path.display());
+                }
             }
 
-            std::thread::sleep(std::time::Duration::from_millis(10));
+            // Further events may be queued; otherwise sleep until a message or the next timer
+            if !handled_event {
+                wake::wait(self.next_wake());
+            }
         }
 
+        self.wind_down();
+        if !self.transcriptions.is_empty() {
+            info!(
+                "Discarding {} transcription(s) not typed yet",
+                self.transcriptions.len()
+            );
+        }
+        session::finished();
         Ok(())
     }
 
-    /// Start recording audio in a background thread and trigger model loading in parallel.
-    fn start_recording(
-        &mut self,
-        stop_signal: Arc<Mutex<bool>>,
-    ) -> Result<JoinHandle<Result<Vec<f32>>>> {
-        info!("Hotkey pressed - starting recording");
+    /// Start, stop or latch the recording for a hotkey press or release.
+    ///
+    /// A press starts a new recording as soon as none is in progress, even
+    /// while earlier ones are still being transcribed.
+    fn handle_hotkey(&mut self, event: GlobalHotKeyEvent) -> Result<()> {
+        if let Some(task) = self.session_task(event.id) {
+            let Some((hotkey, latch)) = self.recording.as_ref().map(|r| (r.hotkey, r.latch)) else {
+                if event.state == HotKeyState::Pressed && !self.hotkey_suppressed() {
+                    self.start_recording(event.id, task)?;
+                }
+                return Ok(());
+            };
+            // Only the hotkey that started the session, or its SHIFT variant, can stop it
+            if !self.hotkey.same_session(event.id, hotkey) {
+                return Ok(());
+            }
+            match (event.state, latch) {
+                (HotKeyState::Pressed, LatchState::Released(_)) => {
+                    info!("Hotkey pressed - stopping latched recording");
+                    self.finish_recording()?;
+                }
+                (HotKeyState::Released, LatchState::Unlatched) => self.finish_recording()?,
+                (HotKeyState::Released, LatchState::Held(since)) => {
+                    if let Some(ref mut recording) = self.recording {
+                        recording.latch = LatchState::Released(since);
+                    }
+                }
+                _ => {}
+            }
+        } else if self.hotkey.is_cancel(event.id) && event.state == HotKeyState::Pressed {
+            self.cancel_recording()?;
+        } else if self.hotkey.is_latch(event.id)
+            && event.state == HotKeyState::Pressed
+            && let Some(ref mut recording) = self.recording
+            && recording.latch == LatchState::Unlatched
+        {
+            info!("Latch key pressed - recording continues after release");
+            recording.latch = LatchState::Held(Instant::now());
+            self.refresh_state()?;
+            self.shown_elapsed_secs = None;
+        }
+        Ok(())
+    }
+
+    /// Run a command from the control pipe as the matching hotkey or tray action.
+    ///
+    /// `start` presses the hotkey and latches the recording at once, so it
+    /// runs until `stop`, a hotkey press or the latch time limit.
+    fn run_ipc_command(&mut self, command: IpcCommand) -> Result<IpcResponse> {
+        match command {
+            IpcCommand::Start if self.recording.is_some() => {
+                return Ok(IpcResponse::failed("already recording"));
+            }
+            IpcCommand::Start if self.hotkey_paused => {
+                return Ok(IpcResponse::failed("the hotkeys are paused"));
+            }
+            IpcCommand::Start => {
+                self.handle_hotkey(GlobalHotKeyEvent {
+                    id: self.hotkey.hotkey.id(),
+                    state: HotKeyState::Pressed,
+                })?;
+                let Some(ref mut recording) = self.recording else {
+                    return Ok(IpcResponse::failed(
+                        "recording is disabled in the focused application",
+                    ));
+                };
+                recording.latch = LatchState::Released(Instant::now());
+                self.shown_elapsed_secs = None;
+                self.refresh_state()?;
+            }
+            IpcCommand::Stop | IpcCommand::Cancel if self.recording.is_none() => {
+                return Ok(IpcResponse::failed("not recording"));
+            }
+            IpcCommand::Stop => self.finish_recording()?,
+            IpcCommand::Cancel => self.cancel_recording()?,
+            IpcCommand::Status => {}
+            IpcCommand::SetLanguage { language } => match language.parse() {
+                Ok(language) => self.choose_language(&language)?,
+                Err(e) => return Ok(IpcResponse::failed(e.to_string())),
+            },
+            IpcCommand::TogglePause => self.set_paused(!self.hotkey_paused)?,
+        }
+        Ok(IpcResponse::done(self.status()))
+    }
+
+    /// What the app is doing, for the control pipe
+    fn status(&self) -> AppStatus {
+        let state = match self.recording {
+            Some(ref recording) if recording.latch != LatchState::Unlatched => "latched",
+            Some(_) => "recording",
+            None if !self.transcriptions.is_empty() => "transcribing",
+            None if self.hotkey_paused => "paused",
+            None => "idle",
+        };
+        AppStatus {
+            state: state.to_owned(),
+            model: self.config.whisper_model.clone(),
+            model_loaded: self.models.any_loaded(),
+            language: self.whisper_language.to_string(),
+            paused: self.hotkey_paused,
+            uptime_secs: self.started.elapsed().as_secs(),
+            dictations: self.stats.dictations(),
+            last_error: self.last_error.clone(),
+        }
+    }
+
+    /// Whether a recording or a transcription is in progress
+    fn is_busy(&self) -> bool {
+        self.recording.is_some() || !self.transcriptions.is_empty()
+    }
+
+    /// Discard an active recording, restore ducked audio and save settings,
+    /// leaving nothing to lose if the process ends right after.
+    fn wind_down(&mut self) {
+        if let Some(recording) = self.recording.take() {
+            info!("Discarding the active recording");
+            *recording
+                .stop
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) = true;
+            self.hotkey.disarm_recording_keys();
+            // Its release may be lost to sleep or the end of the session
+            self.hotkey.forget_press(recording.hotkey);
+            self.discard_recording(recording.thread);
+        }
+        self.feedback.stop();
+        // Dropping the worker restores every ducked session before it returns;
+        // the next recording starts a new one
+        self.volume_ducker = None;
+        self.state.save(self.config.file.as_deref());
+    }
 
-        self.tray.set_state(TrayState::Recording)?;
+    /// Time until the loop has work that no message announces: the next config
+    /// check, state save, model unload or re-send expiry, the release of a
+    /// hotkey held at startup, the next tooltip, indicator or hotkey state
+    /// check while recording, or the next animation frame or model load
+    /// timeout while transcribing.
+    ///
+    /// Never longer than [`CONFIG_POLL_INTERVAL`], so a missed deadline is
+    /// caught on the next config check.
+    fn next_wake(&self) -> Duration {
+        let mut deadlines = vec![self.config_checked + CONFIG_POLL_INTERVAL];
+        deadlines.extend(self.state.save_due());
+        deadlines.extend(self.overlay.next_frame());
+        deadlines.extend(self.tray.next_frame());
+        deadlines.extend(self.resend.next_expiry());
+        if self.hotkey.awaits_startup_release() {
+            deadlines.push(Instant::now() + RELEASE_POLL_INTERVAL);
+        }
+        if let Some(ref recording) = self.recording {
+            let next_second = recording.started.elapsed().as_secs() + 1;
+            deadlines.push(recording.started + Duration::from_secs(next_second));
+            if let Some(ref preview) = recording.preview
+                && preview.running.is_none()
+            {
+                deadlines.push(
+                    preview.last_pass + Duration::from_secs(self.config.streaming_interval_secs),
+                );
+            }
+            if let (LatchState::Released(latched), Some(watch)) =
+                (recording.latch, recording.silence.as_ref())
+            {
+                let stop = watch.silent_since(latched)
+                    + Duration::from_millis(self.config.auto_stop_silence_ms);
+                deadlines.push(if watch.warned {
+                    stop
+                } else {
+                    stop.checked_sub(AUTO_STOP_WARNING).unwrap_or(stop)
+                });
+                deadlines.extend(watch.muted_until);
+            }
+            if let Some(since) = recording.latch.since() {
+                deadlines.push(since + self.latch_max_duration);
+            } else if !self.release_failsafe.is_zero() {
+                deadlines.push(self.key_up_since.map_or_else(
+                    || Instant::now() + RELEASE_POLL_INTERVAL,
+                    |since| since + self.release_failsafe,
+                ));
+            }
+        } else if !self.transcriptions.is_empty() {
+            deadlines.extend(self.models.load_deadline(self.model_load_timeout));
+        } else if !self.config.keep_model_loaded {
+            deadlines.extend(self.models.cooldown_deadline());
+        }
+        let now = Instant::now();
+        deadlines
+            .into_iter()
+            .min()
+            .map_or(CONFIG_POLL_INTERVAL, |at| at.saturating_duration_since(now))
+            .min(CONFIG_POLL_INTERVAL)
+    }
 
-        if let Err(e) = self
-            .feedback
-            .play(&PathBuf::from("./assets/sounds/start.mp3"))
+    /// Stop a latched recording once its input stayed below the auto-stop
+    /// threshold for the configured time, playing a warning cue shortly before.
+    ///
+    /// Held recordings only keep the silence from counting until they are latched.
+    fn check_silence(&mut self) -> Result<()> {
+        let limit = Duration::from_millis(self.config.auto_stop_silence_ms);
+        let threshold = 10.0_f32.powf(self.config.auto_stop_threshold_db / 20.0);
+        let Some(ref mut recording) = self.recording else {
+            return Ok(());
+        };
+        let Some(ref mut watch) = recording.silence else {
+            return Ok(());
+        };
+        let peak = f32::from_bits(watch.peak.swap(0, Ordering::Relaxed));
+        let LatchState::Released(latched) = recording.latch else {
+            watch.heard = Instant::now();
+            return Ok(());
+        };
+        let muted = watch.muted_until.is_some();
+        if watch
+            .muted_until
+            .is_some_and(|until| Instant::now() >= until)
         {
-            error!("Failed to play start sound: {}", e);
+            watch.muted_until = None;
+        }
+        if peak >= threshold && !muted {
+            if watch.warned {
+                debug!("Speech heard after the auto-stop warning, recording continues");
+                watch.warned = false;
+            }
+            watch.heard = Instant::now();
+            return Ok(());
+        }
+        let silent = watch.silent_since(latched).elapsed();
+        if silent >= limit {
+            info!(
+                "No speech for {:.1}s - stopping latched recording",
+                silent.as_secs_f64()
+            );
+            return self.finish_recording();
         }
+        if !watch.warned && silent + AUTO_STOP_WARNING >= limit {
+            watch.warned = true;
+            watch.muted_until = Some(Instant::now() + AUTO_STOP_CUE_GRACE);
+            self.feedback.play_event(FeedbackEvent::AutoStop);
+        }
+        Ok(())
+    }
 
-        // Duck other applications' audio so they do not interfere with recording
-        #[cfg(windows)]
-        match VolumeDucker::duck() {
-            Ok(ducker) => self.volume_ducker = Some(ducker),
-            Err(e) => error!("Failed to duck audio: {}", e),
+    /// Whether the hotkey `id` has been physically up for the failsafe period
+    /// while no release event arrived.
+    fn release_missed(&mut self, id: u32) -> bool {
+        if self.release_failsafe.is_zero() || !self.hotkey.key_is_up(id) {
+            self.key_up_since = None;
+            return false;
         }
+        self.key_up_since.get_or_insert_with(Instant::now).elapsed() >= self.release_failsafe
+    }
 
-        // Start model loading in parallel if not already loaded or loading
-        if self.whisper.is_none() && self.model_load_handle.is_none() {
-            let path = self.model_path.clone();
-            let threads = self.whisper_threads;
-            let language = self.whisper_language.clone();
+    /// Whisper task for an event from one of the recording hotkeys, if it is one.
+    fn session_task(&self, id: u32) -> Option<TranscriptionTask> {
+        if id == self.hotkey.hotkey.id() || self.hotkey.is_secondary(id) {
+            Some(TranscriptionTask::Transcribe)
+        } else if self.hotkey.is_translate(id) {
+            Some(TranscriptionTask::Translate)
+        } else {
+            None
+        }
+    }
 
-            info!("Loading Whisper model in background...");
-            self.model_load_handle = Some(std::thread::spawn(move || {
-                WhisperEngine::load(&path, threads, language)
-            }));
+    /// Pause or resume the hotkey from the tray toggle.
+    fn set_paused(&mut self, paused: bool) -> Result<()> {
+        match self.hotkey.set_paused(paused) {
+            Ok(()) => {
+                self.hotkey_paused = paused;
+                info!("Hotkey {}", if paused { "paused" } else { "resumed" });
+            }
+            Err(e) => error!("Failed to change hotkey pause state: {}", e),
         }
+        self.tray.set_paused_checked(self.hotkey_paused);
 
-        *stop_signal.lock().unwrap() = false;
-        let recorder = AudioRecorder::new(self.volume_boost);
+        // A recording or transcription in progress keeps its tray state until it finishes
+        self.refresh_state()
+    }
 
-        Ok(std::thread::spawn(move || {
-            recorder.record_until_stopped(stop_signal)
-        }))
+    /// Show the current recording, else pending transcriptions, else the idle state.
+    fn refresh_state(&mut self) -> Result<()> {
+        let state = match self.recording {
+            Some(ref recording) if recording.latch != LatchState::Unlatched => TrayState::Latched,
+            Some(ref recording) => match recording.task {
+                TranscriptionTask::Transcribe => TrayState::Recording,
+                TranscriptionTask::Translate => TrayState::Translating,
+            },
+            None if !self.transcriptions.is_empty() => TrayState::Transcribing,
+            None => self.idle_state(),
+        };
+        self.set_state(state)
     }
 
-    /// Stop recording, wait for the model if still loading, then transcribe and inject the result.
-    fn finish_recording(
-        &mut self,
-        stop_signal: &Arc<Mutex<bool>>,
-        recording_thread: &mut Option<JoinHandle<Result<Vec<f32>>>>,
-    ) -> Result<()> {
-        info!("Hotkey released - stopping recording");
+    /// Show `state` in the tray and on the recording indicator.
+    fn set_state(&mut self, state: TrayState) -> Result<()> {
+        self.overlay.show(state);
+        self.tray.set_state(state)?;
+        self.broadcast_status();
+        Ok(())
+    }
 
-        *stop_signal.lock().unwrap() = true;
+    /// Tell caption clients the current state, if it changed.
+    fn broadcast_status(&self) {
+        if let Some(ref captions) = self.captions {
+            captions.status(&self.status());
+        }
+    }
 
-        // Restore other applications' audio now that recording has stopped
-        #[cfg(windows)]
-        if let Some(ducker) = self.volume_ducker.take()
-            && let Err(e) = ducker.restore()
-        {
-            error!("Failed to restore audio: {}", e);
+    /// Turn the recording indicator on or off from the tray and remember the choice.
+    fn show_overlay(&mut self, enabled: bool) {
+        info!(
+            "Recording indicator {}",
+            if enabled { "enabled" } else { "disabled" }
+        );
+        self.config.enable_overlay = enabled;
+        self.state.set(
+            "enable_overlay",
+            Some(if enabled { "true" } else { "false" }),
+        );
+        self.overlay.set_enabled(enabled);
+        // A recording in progress gets the indicator right away
+        self.overlay.show(self.tray.state());
+    }
+
+    /// Append the elapsed recording time to the tooltip, at most once per second.
+    fn update_elapsed_tooltip(&mut self) {
+        let Some(started) = self.recording.as_ref().map(|r| r.started) else {
+            return;
+        };
+        let secs = started.elapsed().as_secs();
+        if self.shown_elapsed_secs == Some(secs) {
+            return;
         }
+        self.shown_elapsed_secs = Some(secs);
 
-        // Resolve the model: wait for background load if needed
-        if self.whisper.is_none()
-            && let Some(handle) = self.model_load_handle.take()
+        let mut tooltip = format!("{} {}", self.tray.state().tooltip(), format_elapsed(secs));
+        if let Some(text) = self
+            .recording
+            .as_ref()
+            .and_then(|r| r.preview.as_ref())
+            .and_then(|preview| preview.text.as_deref())
+            .filter(|_| self.privacy.keeps_text())
         {
-            match handle.join() {
-                Ok(Ok(engine)) => {
-                    info!("Whisper model loaded successfully");
-                    self.whisper = Some(engine);
-                }
-                Ok(Err(e)) => {
-                    error!("Failed to load Whisper model: {}", e);
-                    self.tray.set_state(TrayState::Idle)?;
-                    if let Some(thread) = recording_thread.take() {
-                        let _ = thread.join();
-                    }
-                    return Ok(());
-                }
-                Err(_) => {
-                    error!("Model loading thread panicked");
-                    self.tray.set_state(TrayState::Idle)?;
-                    if let Some(thread) = recording_thread.take() {
-                        let _ = thread.join();
-                    }
-                    return Ok(());
-                }
+            tooltip.push('\n');
+            tooltip.push_str(&preview_tail(text));
+        }
+        if let Err(e) = self.tray.set_tooltip(&tooltip) {
+            warn!("Failed to update recording tooltip: {}", e);
+        }
+    }
+
+    /// Check the configuration files for edits and apply a pending reload once idle.
+    ///
+    /// Reloading never happens mid-recording or while transcriptions are
+    /// pending; it is deferred until both are done.
+    fn poll_config_changes(&mut self) {
+        if self.config_checked.elapsed() >= CONFIG_POLL_INTERVAL {
+            self.config_checked = Instant::now();
+            let stamp = config_stamp();
+            if stamp != self.config_stamp {
+                info!("Configuration file changed");
+                self.config_stamp = stamp;
+                self.config_reload_pending = true;
             }
         }
 
-        if let Some(thread) = recording_thread.take() {
-            match thread.join() {
-                Ok(Ok(samples)) => {
-                    if let Err(e) = self
-                        .feedback
-                        .play(&PathBuf::from("./assets/sounds/finish.mp3"))
-                    {
-                        error!("Failed to play stop sound: {}", e);
-                    }
+        if self.config_reload_pending && !self.is_busy() {
+            self.config_reload_pending = false;
+            // Unsaved tray choices would otherwise be reverted by the reload
+            self.state.save(self.config.file.as_deref());
+            self.reload_config();
+        }
+    }
 
-                    self.tray.set_state(TrayState::Idle)?;
-                    info!("Recording stopped, transcribing...");
+    /// Re-read the configuration and apply every value that can change at runtime.
+    ///
+    /// Hotkey and log destination changes are only reported, as they take effect after a
+    /// restart. A file that fails to parse keeps the current settings.
+    fn reload_config(&mut self) {
+        let config = match self.config.reload() {
+            Ok(config) => config,
+            Err(e) => {
+                error!(
+                    "Failed to reload configuration, keeping current settings: {:#}",
+                    e
+                );
+                self.notify(
+                    Severity::Error,
+                    &format!("Configuration error, keeping current settings: {e:#}"),
+                );
+                return;
+            }
+        };
 
-                    if let Some(ref whisper) = self.whisper {
-                        match whisper.transcribe(&samples) {
-                            Ok(text) if !text.is_empty() => {
-                                if let Err(e) = self.injector.inject(&text) {
-                                    error!("Failed to inject text: {}", e);
-                                }
-                                info!("Transcription complete");
-                            }
-                            Ok(_) => info!("Transcription complete (empty result)"),
-                            Err(e) => error!("Transcription failed: {}", e),
-                        }
-                    }
+        for warning in &config.warnings {
+            warn!("Configuration: {}", warning);
+        }
+        let changes = self.config.changes(&config);
+        if changes.is_empty() {
+            info!("Configuration reloaded, no changes");
+            return;
+        }
+        for change in &changes {
+            info!("Configuration changed: {}", change);
+        }
 
-                    // Start cooldown timer instead of dropping the model immediately
-                    self.last_model_use = Some(Instant::now());
-                }
-                Ok(Err(e)) => {
-                    error!("Recording failed: {}", e);
-                    self.tray.set_state(TrayState::Idle)?;
-                    self.last_model_use = Some(Instant::now());
-                }
-                Err(_) => {
-                    error!("Recording thread panicked");
-                    self.tray.set_state(TrayState::Idle)?;
-                    self.last_model_use = Some(Instant::now());
-                }
+        let old = &self.config;
+        let hotkeys_changed = (
+            &old.hotkey_modifier,
+            &old.hotkey_key,
+            &old.hotkey_latch_key,
+            &old.translate_hotkey_modifier,
+            &old.translate_hotkey_key,
+            old.secondary_language.is_some(),
+        ) != (
+            &config.hotkey_modifier,
+            &config.hotkey_key,
+            &config.hotkey_latch_key,
+            &config.translate_hotkey_modifier,
+            &config.translate_hotkey_key,
+            config.secondary_language.is_some(),
+        );
+        let log_destination_changed = old.log_to_file != config.log_to_file;
+        let ipc_changed = (old.enable_ipc, old.http_port, &old.http_token)
+            != (config.enable_ipc, config.http_port, &config.http_token);
+        let feedback_changed = (
+            old.enable_sound_feedback,
+            old.feedback_style,
+            &old.feedback_output_device,
+            (
+                old.mute_feedback_while_ducked,
+                old.honor_quiet_mode,
+                old.quiet_mode_plays_cues,
+            ),
+            (
+                old.enable_readback,
+                old.readback_rate,
+                old.readback_max_chars,
+            ),
+            &old.assets_dir,
+            [
+                &old.feedback_sound_start,
+                &old.feedback_sound_finish,
+                &old.feedback_sound_cancelled,
+                &old.feedback_sound_error,
+                &old.feedback_sound_empty,
+                &old.feedback_sound_auto_stop,
+            ],
+        ) != (
+            config.enable_sound_feedback,
+            config.feedback_style,
+            &config.feedback_output_device,
+            (
+                config.mute_feedback_while_ducked,
+                config.honor_quiet_mode,
+                config.quiet_mode_plays_cues,
+            ),
+            (
+                config.enable_readback,
+                config.readback_rate,
+                config.readback_max_chars,
+            ),
+            &config.assets_dir,
+            [
+                &config.feedback_sound_start,
+                &config.feedback_sound_finish,
+                &config.feedback_sound_cancelled,
+                &config.feedback_sound_error,
+                &config.feedback_sound_empty,
+                &config.feedback_sound_auto_stop,
+            ],
+        );
+        if hotkeys_changed {
+            warn!("Hotkey changes take effect after a restart");
+        }
+        if log_destination_changed {
+            warn!("Log destination changes take effect after a restart");
+        }
+        if old.log_level != config.log_level
+            && let Err(e) = logging::set_level(config.log_level)
+        {
+            warn!("Failed to apply log level: {:#}", e);
+        }
+        if ipc_changed {
+            warn!("Control pipe and HTTP endpoint changes take effect after a restart");
+        }
+        if old.language_choices != config.language_choices {
+            warn!("Language menu changes take effect after a restart");
+        }
+        if (&old.assets_dir, &old.icon_idle, &old.icon_recording)
+            != (
+                &config.assets_dir,
+                &config.icon_idle,
+                &config.icon_recording,
+            )
+        {
+            warn!("Tray icon changes take effect after a restart");
+        }
+
+        // Engines are built with the model, thread count and options, so a
+        // change to any drops them and the next recording loads fresh ones
+        let whisper_threads = WhisperEngine::effective_threads(config.whisper_threads);
+        let model_changed = (&config.whisper_model, &config.auto_model)
+            != (&self.config.whisper_model, &self.config.auto_model)
+            || whisper_threads != self.whisper_threads
+            || config.whisper_options() != self.config.whisper_options();
+        if model_changed {
+            // Loads still running would finish with the old settings and are detached
+            self.models = model_pool(&config);
+            info!("Whisper settings changed, model will be reloaded on next use");
+        }
+
+        // The volume applies to the next sound; other sound settings need a new player
+        if feedback_changed {
+            self.feedback = FeedbackPlayer::new(&config, Arc::clone(&self.audio_ducked));
+        } else {
+            self.feedback.set_volume(config.feedback_volume);
+        }
+        self.volume_boost = config.volume_boost;
+        self.whisper_threads = whisper_threads;
+        if let Err(e) = self.set_language(&config.whisper_language) {
+            warn!("Failed to update language: {}", e);
+        }
+        self.models
+            .set_delay(Duration::from_secs(config.model_unload_delay_secs));
+        self.recording_stop_timeout = Duration::from_secs(config.recording_stop_timeout_secs);
+        self.model_load_timeout = Duration::from_secs(config.model_load_timeout_secs);
+        if model_changed && config.preload_model {
+            self.start_likely_model_load();
+        }
+        self.latch_max_duration = Duration::from_secs(config.latch_max_secs);
+        self.release_failsafe = Duration::from_millis(config.release_failsafe_ms);
+        self.hotkey_disabled_in
+            .clone_from(&config.hotkey_disabled_in);
+        self.hotkey_disabled_sound = config.hotkey_disabled_sound;
+        self.tray
+            .set_notifications_enabled(config.enable_notifications);
+        self.overlay
+            .set_placement(OverlayPlacement::from_config(&config));
+        self.overlay.set_enabled(config.enable_overlay);
+        self.tray.set_overlay_checked(config.enable_overlay);
+        self.stats
+            .set_daily_file(config.stats_daily_rollup.then(paths::stats_file));
+        self.history = transcription_history(&config);
+        if (&config.webhook_url, config.webhook_retry)
+            != (&self.config.webhook_url, self.config.webhook_retry)
+        {
+            self.webhook = webhook(&config);
+        }
+        if (
+            &config.word_filter_file,
+            config.word_filter_action,
+            &config.text_pipeline,
+        ) != (
+            &self.config.word_filter_file,
+            self.config.word_filter_action,
+            &self.config.text_pipeline,
+        ) {
+            self.text_pipeline = text_pipeline(&config);
+        }
+        if config.caption_port != self.config.caption_port {
+            // The old server lets go of its port before the new one binds
+            self.captions = None;
+            self.captions = caption_server(&config).unwrap_or_else(|e| {
+                self.notify(Severity::Warning, &format!("{e:#}"));
+                None
+            });
+        }
+        self.tray
+            .set_history_available(self.history.path().is_some(), self.history.is_daily());
+        if config.input_device != self.config.input_device
+            && let Err(e) = self
+                .tray
+                .set_input_devices(&audio::input_device_names(), config.input_device.as_deref())
+        {
+            warn!("Failed to update microphone menu: {}", e);
+        }
+        if (&config.profiles, &config.profile) != (&self.config.profiles, &self.config.profile)
+            && let Err(e) = self
+                .tray
+                .set_profiles(&config.profiles, config.profile.as_deref())
+        {
+            warn!("Failed to update profile menu: {}", e);
+        }
+        self.privacy = PrivacyPolicy::new(&config);
+        self.duplicates.configure(
+            Duration::from_secs(config.duplicate_window_secs),
+            config.duplicate_similarity,
+        );
+        if !self.privacy.compares_text() {
+            self.duplicates.clear();
+        }
+        if config.duplicate_action != DuplicateAction::Confirm {
+            self.drop_held_repeat();
+        }
+        self.punctuation = Punctuation::from_pause_ms(config.punctuation_pause_ms);
+        self.spacing = Spacing::new(&config.no_space_languages);
+        if !self.privacy.keeps_text() {
+            self.last_transcription = None;
+            self.tray.set_last_transcription(None);
+        }
+        if !self.privacy.keeps_text() || config.correction_trigger.is_none() {
+            self.last_injection = None;
+        }
+        self.resend.set_max_age(resend_max_age(&config));
+        self.refresh_resend_menu();
+        self.recovery.set_enabled(recovery_enabled(&config));
+        self.token_dump = TokenDump::new(&config, self.whisper_threads);
+        self.tray
+            .set_recovery_available(self.recovery.has_leftover());
+        self.config = config;
+        if hotkeys_changed || log_destination_changed || ipc_changed {
+            self.notify(
+                Severity::Warning,
+                "Some configuration changes take effect after a restart",
+            );
+        }
+
+        info!("Configuration reloaded");
+    }
+
+    /// Switch to `language` from the tray and remember the choice.
+    fn choose_language(&mut self, language: &LanguageCode) -> Result<()> {
+        self.state.set("whisper_language", Some(language.as_str()));
+        language.clone_into(&mut self.config.whisper_language);
+        self.set_language(language)
+    }
+
+    /// Switch to `language` picked from the tray, notifying instead of failing.
+    fn choose_tray_language(&mut self, language: &str) {
+        let chosen = language
+            .parse()
+            .map_err(anyhow::Error::from)
+            .and_then(|language| self.choose_language(&language));
+        if let Err(e) = chosen {
+            warn!("Failed to switch the language to {}: {:#}", language, e);
+            self.notify(
+                Severity::Warning,
+                &format!("Could not switch the language to {language}: {e:#}"),
+            );
+        }
+    }
+
+    /// Use `language` for subsequent transcriptions and reflect it in the tray.
+    fn set_language(&mut self, language: &LanguageCode) -> Result<()> {
+        if *language != self.whisper_language {
+            info!("Transcription language set to {}", language);
+        }
+        language.clone_into(&mut self.whisper_language);
+        self.broadcast_status();
+        self.tray.set_language(language.as_str())
+    }
+
+    /// Layer `profile` over the defaults, `None` for the defaults alone.
+    ///
+    /// The switch is applied like an edited config file, once no recording is
+    /// in progress, and is remembered. Language and microphone choices made
+    /// from the tray are forgotten so the profile's own values take effect.
+    fn switch_profile(&mut self, profile: Option<&str>) {
+        let name = profile.unwrap_or(config::DEFAULT_PROFILE);
+        info!("Switching to profile {}", name);
+        let overrides = &mut self.config.overrides;
+        overrides.retain(|(key, _)| key != "active_profile");
+        overrides.push(("active_profile".to_owned(), name.to_owned()));
+        self.state.set("active_profile", Some(name));
+        self.state.set("whisper_language", None);
+        self.state.set("input_device", None);
+        self.config_reload_pending = true;
+    }
+
+    /// Record from `device` from the next recording on and save the choice.
+    fn select_input_device(&mut self, device: Option<String>) {
+        info!(
+            "Input device set to {}",
+            device.as_deref().unwrap_or("system default")
+        );
+        self.tray.set_selected_device(device.as_deref());
+        self.state
+            .set("input_device", Some(device.as_deref().unwrap_or_default()));
+        self.config.input_device = device;
+    }
+
+    /// Refresh the Microphone menu with the devices currently plugged in.
+    fn rescan_input_devices(&mut self) -> Result<()> {
+        let names = audio::input_device_names();
+        info!("Found {} input devices", names.len());
+        self.tray
+            .set_input_devices(&names, self.config.input_device.as_deref())
+    }
+
+    /// Re-check for a microphone and rebuild the Microphone menu after a device change.
+    fn input_devices_changed(&mut self) -> Result<()> {
+        self.set_microphone_present(audio::has_input_device())?;
+        self.rescan_input_devices()
+    }
+
+    /// Remember whether a microphone is connected and show it in the tray.
+    fn set_microphone_present(&mut self, present: bool) -> Result<()> {
+        if present != self.microphone_present {
+            if present {
+                info!("Microphone connected");
+            } else {
+                warn!("No microphone connected");
             }
         }
+        self.microphone_present = present;
+        self.tray.set_microphone_present(present)
+    }
+
+    /// Selected input device if it is plugged in, notifying once it went missing.
+    fn available_input_device(&mut self) -> Option<String> {
+        let name = self.config.input_device.clone()?;
+        if audio::input_device_names().contains(&name) {
+            return Some(name);
+        }
+        warn!("Input device {} not found, using default", name);
+        self.notify(
+            Severity::Warning,
+            &format!("Microphone \"{name}\" not found, using the default device"),
+        );
+        None
+    }
+
+    /// Keep the transcription for the tray preview, unless disabled for privacy.
+    fn remember_transcription(&mut self, text: String) {
+        if !self.privacy.keeps_text() {
+            return;
+        }
+        self.tray.set_last_transcription(Some(&text));
+        self.last_transcription = Some(text);
+    }
+
+    /// Drop the last transcription and every text kept for re-sending.
+    fn forget_transcriptions(&mut self) {
+        self.last_transcription = None;
+        self.last_injection = None;
+        self.tray.set_last_transcription(None);
+        self.resend.clear();
+        self.refresh_resend_menu();
+        self.recovery.discard();
+        self.tray.set_recovery_available(false);
+        self.duplicates.clear();
+        self.drop_held_repeat();
+        info!("Forgot recent transcriptions");
+    }
 
+    /// Queue the dictation left by a crash for transcription; its text goes
+    /// to the clipboard, as the window it was meant for is long gone.
+    fn recover_dictation(&mut self) -> Result<()> {
+        self.tray.set_recovery_available(false);
+        let (samples, ticket) = match self.recovery.take_leftover() {
+            Ok(found) => found,
+            Err(e) => {
+                error!("Failed to read the recovered dictation: {:#}", e);
+                self.recovery.discard();
+                self.feedback.play_event(FeedbackEvent::Error);
+                self.notify(
+                    Severity::Error,
+                    "The recovered dictation could not be read and was discarded",
+                );
+                return Ok(());
+            }
+        };
+        // Whisper input has 16 samples per millisecond
+        let length = Duration::from_millis(u64::try_from(samples.len() / 16).unwrap_or(u64::MAX));
+        info!(
+            "Transcribing the recovered dictation of {:.1}s",
+            length.as_secs_f64()
+        );
+        let model = self.models.select(length).to_owned();
+        self.start_model_load(&model);
+        self.transcriptions.push_back(Transcription {
+            levels: AudioLevels::of(&samples),
+            audio: PreparedAudio::Ready(samples),
+            task: TranscriptionTask::Transcribe,
+            language: self.whisper_language.clone(),
+            model,
+            window: None,
+            timings: Timings::released(length),
+            running: None,
+            recovery: Some(ticket),
+            recovered: true,
+        });
+        if self.recording.is_none() {
+            self.refresh_state()?;
+        }
         Ok(())
     }
 
-    /// Pump the Windows message queue so tray and hotkey events are delivered.
-    fn pump_messages() {
-        #[cfg(windows)]
-        // SAFETY: MSG is a plain Windows struct; PeekMessageW, TranslateMessage,
-        // and DispatchMessageW are standard message-loop calls with no invariants
-        // beyond what the Windows API guarantees.
-        unsafe {
-            let mut msg = MSG::default();
-            while PeekMessageW(&mut msg, None, 0, 0, PM_REMOVE).as_bool() {
-                let _ = TranslateMessage(&msg);
-                DispatchMessageW(&msg);
+    /// Copy the text of a recovered dictation to the clipboard, returning
+    /// whether it is there or there was none.
+    fn copy_recovered(&mut self, text: &str) -> bool {
+        if text.trim().is_empty() {
+            self.notify(
+                Severity::Info,
+                "Nothing was recognized in the recovered dictation",
+            );
+            return true;
+        }
+        match clipboard::set_text(text) {
+            Ok(()) => {
+                info!("Copied the recovered dictation to the clipboard");
+                self.notify(
+                    Severity::Info,
+                    "The recovered dictation was copied to the clipboard",
+                );
+                true
+            }
+            Err(e) => {
+                error!("Failed to copy the recovered dictation: {:#}", e);
+                self.feedback.play_event(FeedbackEvent::Error);
+                self.notify(
+                    Severity::Error,
+                    &format!("The recovered dictation could not be copied: {e}"),
+                );
+                false
+            }
+        }
+    }
+
+    /// Show the apps with a text kept for re-sending in the tray menu.
+    fn refresh_resend_menu(&mut self) {
+        if let Err(e) = self.tray.set_resend_targets(&self.resend.apps()) {
+            warn!("Failed to update re-send menu: {}", e);
+        }
+    }
+
+    /// Type the text last delivered to `app` into its most recently active window.
+    fn resend_transcription(&mut self, app: &str) {
+        let Some(text) = self.resend.text_for(app).map(str::to_owned) else {
+            return;
+        };
+        let Some(window) = TargetWindow::of_process(app).into_iter().next() else {
+            warn!("No window of {} to re-send the transcription to", app);
+            self.notify(Severity::Warning, &format!("No window of {app} is open"));
+            return;
+        };
+        if let Err(e) = window.restore() {
+            warn!("Could not switch to {:?}: {:#}", window.title(), e);
+            self.notify(
+                Severity::Warning,
+                &format!("Could not switch to {app} to re-send the transcription"),
+            );
+            return;
+        }
+        match self.injector.inject(&text) {
+            Ok(()) => info!("Re-sent the last transcription to {:?}", window.title()),
+            Err(e) => {
+                error!("Failed to inject text: {}", e.detail());
+                self.feedback.play_event(FeedbackEvent::Error);
+                self.notify(Severity::Error, &e.user_message());
             }
         }
     }
+
+    /// Put the full last transcription on the clipboard.
+    fn copy_last_transcription(&self) {
+        let Some(ref text) = self.last_transcription else {
+            return;
+        };
+        match clipboard::set_text(text) {
+            Ok(()) => info!("Copied last transcription to clipboard"),
+            Err(e) => error!("Failed to copy last transcription: {:#}", e),
+        }
+    }
+
+    /// Open the log file with the default viewer.
+    fn open_log(&self) {
+        if !self.config.log_to_file {
+            warn!("File logging is disabled; set LOG_TO_FILE=true to write a log file");
+            return;
+        }
+        let file = logging::current_file().unwrap_or_else(paths::log_file);
+        if let Err(e) = paths::open_in_default_app(&file) {
+            error!("Failed to open log file: {:#}", e);
+        }
+    }
+
+    /// Open the transcription history file, if one is configured and written yet.
+    fn open_history(&self) {
+        let Some(path) = self.history.path() else {
+            return;
+        };
+        if !path.exists() {
+            info!("No transcriptions recorded in {} yet", path.display());
+            return;
+        }
+        if let Err(e) = paths::open_in_default_app(&path) {
+            error!("Failed to open transcription history: {:#}", e);
+        }
+    }
+
+    /// Show version, model and hotkey information.
+    fn show_about(&self) {
+        let model_state = if self.models.any_loaded() {
+            "loaded"
+        } else {
+            "not loaded"
+        };
+        let body = format!(
+            "Speedy STT {}\n\nModel: {} ({})\nLanguage: {}\nThreads: {}\nHotkey: {} + {}",
+            env!("CARGO_PKG_VERSION"),
+            self.config.whisper_model,
+            model_state,
+            self.whisper_language,
+            self.whisper_threads,
+            self.config.hotkey_modifier,
+            self.config.hotkey_key
+        );
+        dialog::show_info("About Speedy STT", &body);
+    }
+
+    /// Log the self-check and point out failed checks in a notification.
+    fn startup_check(&mut self) {
+        let report = HealthReport::run(&self.config, &self.hotkey);
+        report.log();
+        let failed: Vec<_> = report.failures().map(|check| check.name).collect();
+        if !failed.is_empty() {
+            self.notify(
+                Severity::Warning,
+                &format!(
+                    "Startup check found problems with {}. Run diagnostics from the tray for details.",
+                    failed.join(", ")
+                ),
+            );
+        }
+    }
+
+    /// Run the self-check again and show every result.
+    fn show_diagnostics(&self) {
+        let report = HealthReport::run(&self.config, &self.hotkey);
+        report.log();
+        let verdict = match report.failures().count() {
+            0 => "Everything looks fine.".to_owned(),
+            count => format!("{count} check(s) failed."),
+        };
+        dialog::show_info(
+            "Speedy STT diagnostics",
+            &format!("{verdict}\n\n{}", report.summary()),
+        );
+    }
+
+    /// Notify once if ducking for a recording could not start.
+    fn poll_ducking_error(&mut self) {
+        if let Some(e) = self
+            .volume_ducker
+            .as_ref()
+            .and_then(DuckingWorker::take_error)
+        {
+            self.notify(Severity::Warning, &e.user_message());
+        }
+    }
+
+    /// Surface a failure as a tray notification.
+    fn notify(&mut self, severity: Severity, body: &str) {
+        if severity == Severity::Error {
+            self.last_error = Some(body.to_owned());
+        }
+        if self.config.honor_quiet_mode && quiet::is_quiet() {
+            info!("Notification held back while quiet: {}", body);
+            return;
+        }
+        self.tray.notify("Speedy STT", body, severity);
+    }
+
+    /// Tray state to show when no recording is in progress.
+    const fn idle_state(&self) -> TrayState {
+        if self.hotkey_paused {
+            TrayState::Paused
+        } else {
+            TrayState::Idle
+        }
+    }
+
+    /// Whether a hotkey press should be ignored because of the foreground application.
+    ///
+    /// The foreground process is only looked up when the disabled list is non-empty.
+    fn hotkey_suppressed(&self) -> bool {
+        if self.hotkey_disabled_in.is_empty() {
+            return false;
+        }
+        let Some(process) = foreground::foreground_process_name() else {
+            return false;
+        };
+        let process = process.to_lowercase();
+        if !self.hotkey_disabled_in.contains(&process) {
+            return false;
+        }
+
+        info!("Hotkey ignored: {} is on the disabled list", process);
+        if self.hotkey_disabled_sound {
+            self.feedback.play_denied();
+        }
+        true
+    }
+
+    /// Start recording audio in a background thread and trigger model loading in parallel.
+    fn start_recording(&mut self, hotkey: u32, task: TranscriptionTask) -> Result<()> {
+        info!("Hotkey pressed - starting recording ({:?})", task);
+
+        // Checked before any cue or state change, so a missing microphone
+        // fails on the press instead of after the release
+        if !audio::has_input_device() {
+            self.set_microphone_present(false)?;
+            self.feedback.play_event(FeedbackEvent::Error);
+            self.notify(
+                Severity::Error,
+                "No microphone detected. Connect one and press the hotkey again.",
+            );
+            return Ok(());
+        }
+        if !self.microphone_present {
+            self.set_microphone_present(true)?;
+        }
+
+        let language = match self.config.secondary_language {
+            Some(ref secondary) if self.hotkey.is_secondary(hotkey) => {
+                info!("SHIFT held - dictating in {}", secondary);
+                secondary.clone()
+            }
+            _ => self.whisper_language.clone(),
+        };
+
+        self.key_up_since = None;
+        self.shown_elapsed_secs = None;
+        self.hotkey.arm_recording_keys();
+
+        // A new dictation cuts off read-back of the previous one
+        self.feedback.stop();
+        self.feedback.play_event(FeedbackEvent::Start);
+
+        // Duck other applications' audio so they do not interfere with recording;
+        // the worker fades after any previous fade-in while capture starts
+        if self.config.enable_audio_ducking {
+            let ducked = &self.audio_ducked;
+            let ducker = self
+                .volume_ducker
+                .get_or_insert_with(|| DuckingWorker::start(Arc::clone(ducked)));
+            ducker.duck(DuckSettings {
+                mode: self.config.duck_mode,
+                level: self.config.duck_level,
+                fade_out: Duration::from_millis(self.config.duck_fade_ms),
+                fade_in: Duration::from_millis(self.config.duck_restore_fade_ms),
+                curve: self.config.duck_fade_curve,
+                excluded: self.config.duck_exclude_processes.clone(),
+                only_playing: self.config.duck_only_playing,
+            });
+        }
+
+        // Load the model in parallel with the recording
+        self.start_likely_model_load();
+
+        let recorder = AudioRecorder::new(
+            self.volume_boost,
+            self.available_input_device(),
+            self.overlay.level(),
+        );
+        let recorder = match spill_limit(self.config.recording_spill_mb) {
+            Some(limit) => recorder.with_spill(limit, paths::spill_dir()),
+            None => recorder,
+        };
+        let silence = (self.config.auto_stop_silence_ms > 0).then(|| SilenceWatch {
+            peak: Arc::new(AtomicU32::new(0)),
+            heard: Instant::now(),
+            warned: false,
+            muted_until: None,
+        });
+        let recorder = match silence {
+            Some(ref watch) => recorder.with_peak_watch(Arc::clone(&watch.peak)),
+            None => recorder,
+        };
+        let (recorder, preview) = if self.config.streaming_interval_secs > 0 {
+            let (sender, chunks) = mpsc::channel();
+            let preview = Preview {
+                chunks,
+                samples: Vec::new(),
+                last_pass: Instant::now(),
+                running: None,
+                text: None,
+            };
+            (recorder.with_chunks(sender), Some(preview))
+        } else {
+            (recorder, None)
+        };
+        let stop = Arc::new(Mutex::new(false));
+        let thread_stop = Arc::clone(&stop);
+        self.recording = Some(Recording {
+            hotkey,
+            task,
+            language,
+            latch: LatchState::Unlatched,
+            stop,
+            thread: std::thread::spawn(move || {
+                recorder
+                    .capture_until_stopped(thread_stop)
+                    .map_err(SttError::from)
+            }),
+            started: Instant::now(),
+            window: TargetWindow::capture(),
+            preview,
+            silence,
+        });
+        self.refresh_state()
+    }
+
+    /// Start loading the model the next recording most likely needs.
+    fn start_likely_model_load(&mut self) {
+        let likely = self.models.likely().to_owned();
+        self.start_model_load(&likely);
+    }
+
+    /// Restart the cooldown of the model loaded for a recording that ended without a transcription.
+    fn touch_likely_model(&mut self) {
+        let likely = self.models.likely().to_owned();
+        self.models.touch(&likely);
+    }
+
+    /// Start loading the model at `path` in the background unless it is loaded or loading.
+    ///
+    /// When it would not fit into memory, other models no transcription is
+    /// waiting for are unloaded first.
+    fn start_model_load(&mut self, path: &Path) {
+        if self.models.is_available(path) {
+            return;
+        }
+        let (threads, options) = (self.whisper_threads, self.config.whisper_options());
+        let mut shortfall = memory::check_model(path);
+        if shortfall.is_some() {
+            let mut needed: Vec<PathBuf> = self
+                .transcriptions
+                .iter()
+                .filter(|t| t.running.is_none())
+                .map(|t| t.model.clone())
+                .collect();
+            needed.push(path.to_owned());
+            let evicted = self.models.evict_except(&needed);
+            for other in &evicted {
+                info!(
+                    "Unloaded Whisper model {} to make room for {}",
+                    other.display(),
+                    path.display()
+                );
+            }
+            if !evicted.is_empty() {
+                shortfall = memory::check_model(path);
+            }
+        }
+        let refused = shortfall.filter(|_| !self.config.force_model_load);
+        if let Some(short) = shortfall
+            && refused.is_none()
+        {
+            warn!(
+                "Model needs about {} MB but only {} MB are free, loading anyway",
+                short.required_mb(),
+                short.available_mb()
+            );
+            self.notify(
+                Severity::Warning,
+                "The Whisper model may not fit into free memory; the system could slow down while it loads.",
+            );
+        }
+
+        info!("Loading Whisper model {} in background...", path.display());
+        let file = path.to_owned();
+        let load = std::thread::spawn(move || {
+            let engine = load_model(&file, threads, options, refused);
+            wake::wake();
+            engine
+        });
+        self.models.start_load(path, load);
+    }
+
+    /// Take over models that finished loading in the background, starting their cooldown.
+    ///
+    /// A load that fails, or that transcriptions waited on for longer than the
+    /// load timeout, drops the transcriptions still waiting for that model.
+    fn poll_model_load(&mut self) -> Result<()> {
+        for path in self.models.overdue_loads(self.model_load_timeout) {
+            if self.waiting_for(&path) {
+                error!(
+                    "Loading {} did not finish within {}s, abandoning it",
+                    path.display(),
+                    self.model_load_timeout.as_secs()
+                );
+                // Detached; the next recording starts a new load
+                self.models.abandon_load(&path);
+                self.drop_waiting_transcriptions(&path, "Loading the model timed out")?;
+            }
+        }
+        while let Some((path, load)) = self.models.finished_load() {
+            let failure = match load.join() {
+                Ok(Ok(engine)) => {
+                    info!("Whisper model {} loaded successfully", path.display());
+                    self.models.loaded(&path, engine);
+                    continue;
+                }
+                Ok(Err(e)) => {
+                    error!("Model loading failed: {}", e.detail());
+                    e.user_message()
+                }
+                Err(payload) => {
+                    let message = crash::payload_message(payload.as_ref());
+                    error!("Model loading thread panicked: {}", message);
+                    format!("Model loading crashed: {message}")
+                }
+            };
+            if self.waiting_for(&path) {
+                self.drop_waiting_transcriptions(&path, &failure)?;
+            } else {
+                self.notify(Severity::Error, &failure);
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether a transcription is waiting for the model at `path`
+    fn waiting_for(&self, path: &Path) -> bool {
+        self.transcriptions
+            .iter()
+            .any(|t| t.running.is_none() && t.model == path)
+    }
+
+    /// Drop the transcriptions that were waiting for the model at `path`, reporting `reason`.
+    fn drop_waiting_transcriptions(&mut self, path: &Path, reason: &str) -> Result<()> {
+        self.transcriptions
+            .retain(|t| t.running.is_some() || t.model != path);
+        self.feedback.play_event(FeedbackEvent::Error);
+        self.notify(Severity::Error, reason);
+        self.refresh_state()
+    }
+
+    /// Stop the recording and queue its audio for transcription.
+    fn finish_recording(&mut self) -> Result<()> {
+        let Some(recording) = self.recording.take() else {
+            return Ok(());
+        };
+        info!("Hotkey released - stopping recording");
+
+        *recording
+            .stop
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = true;
+        self.hotkey.disarm_recording_keys();
+        let timings = Timings::released(recording.started.elapsed());
+
+        // Restore other applications' audio now that recording has stopped,
+        // before any of the failure paths below
+        if let Some(ref ducker) = self.volume_ducker {
+            ducker.restore();
+        }
+
+        match join_within(recording.thread, self.recording_stop_timeout) {
+            None => self.recording_hung(),
+            Some(Ok(Ok(captured))) => {
+                self.feedback.play_event(FeedbackEvent::Finish);
+                info!("Recording stopped, queued for transcription");
+                let length = recording.started.elapsed();
+                let model = self.models.select(length).to_owned();
+                if self.models.is_auto() {
+                    info!(
+                        "Recording of {:.1}s is transcribed with {}",
+                        length.as_secs_f64(),
+                        model.display()
+                    );
+                }
+                self.models.set_likely(&model);
+                // Possibly another model than the one loaded at the press
+                self.start_model_load(&model);
+                // Prepared while the model loads or earlier dictations finish
+                let worker = std::thread::spawn(move || {
+                    let started = Instant::now();
+                    let samples = captured.to_whisper_input();
+                    let levels = AudioLevels::of(&samples);
+                    wake::wake();
+                    (samples, levels, started.elapsed())
+                });
+                self.transcriptions.push_back(Transcription {
+                    audio: PreparedAudio::Preparing(worker),
+                    levels: AudioLevels::default(),
+                    task: recording.task,
+                    window: recording.window,
+                    language: recording.language,
+                    model,
+                    timings,
+                    running: None,
+                    recovery: None,
+                    recovered: false,
+                });
+            }
+            Some(Ok(Err(e))) => {
+                error!("Recording failed: {}", e.detail());
+                self.feedback.play_event(FeedbackEvent::Error);
+                self.notify(Severity::Error, &e.user_message());
+                self.touch_likely_model();
+            }
+            Some(Err(payload)) => {
+                let message = crash::payload_message(payload.as_ref());
+                error!("Recording thread panicked: {}", message);
+                self.feedback.play_event(FeedbackEvent::Error);
+                self.notify(Severity::Error, &format!("Recording crashed: {message}"));
+                self.touch_likely_model();
+            }
+        }
+
+        self.advance_transcriptions()?;
+        self.refresh_state()
+    }
+
+    /// Discard the recording on Escape or a `cancel` command without transcribing it.
+    ///
+    /// The hotkey's later release finds no recording and is ignored.
+    fn cancel_recording(&mut self) -> Result<()> {
+        let Some(recording) = self.recording.take() else {
+            return Ok(());
+        };
+        info!("Recording cancelled - discarding it");
+
+        *recording
+            .stop
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = true;
+        self.hotkey.disarm_recording_keys();
+        if let Some(ref ducker) = self.volume_ducker {
+            ducker.restore();
+        }
+        self.discard_recording(recording.thread);
+        self.feedback.play_event(FeedbackEvent::Cancelled);
+        // The load this recording started idles out like after a failure
+        self.touch_likely_model();
+        self.refresh_state()
+    }
+
+    /// Collect a finished preview pass and start the next one once due.
+    ///
+    /// Passes wait while earlier recordings are still being transcribed and
+    /// never overlap; the model is loaded by the recording, not by previews.
+    fn advance_preview(&mut self) {
+        let interval = Duration::from_secs(self.config.streaming_interval_secs);
+        let idle_model = self
+            .models
+            .engine(self.models.likely())
+            .filter(|_| self.transcriptions.is_empty());
+        let (punctuation, spacing) = (self.punctuation, self.spacing.clone());
+        let Some(recording) = self.recording.as_mut() else {
+            return;
+        };
+        let (task, started) = (recording.task, recording.started);
+        let language = recording.language.clone();
+        let Some(preview) = recording.preview.as_mut() else {
+            return;
+        };
+        preview.samples.extend(preview.chunks.try_iter().flatten());
+
+        let finished = preview
+            .running
+            .take_if(|handle| handle.is_finished())
+            .map(|handle| match handle.join() {
+                Ok(Ok(text)) => text,
+                Ok(Err(e)) => {
+                    debug!("Preview transcription failed: {}", e.detail());
+                    None
+                }
+                Err(payload) => {
+                    warn!(
+                        "Preview transcription panicked: {}",
+                        crash::payload_message(payload.as_ref())
+                    );
+                    None
+                }
+            });
+        // Previews are shown, so they are processed like the text that is typed
+        let log_text = self.privacy.logs_text();
+        let shown = finished
+            .flatten()
+            .and_then(|text| match self.text_pipeline.run(text, log_text) {
+                Filtered::Text(processed) => Some(processed),
+                Filtered::Withheld => None,
+            })
+            .filter(|text| !text.is_empty() && preview.text.as_ref() != Some(text));
+        if let Some(ref text) = shown {
+            preview.text = Some(text.clone());
+        }
+
+        if preview.running.is_none()
+            && preview.last_pass.elapsed() >= interval
+            && !preview.samples.is_empty()
+            && let Some(whisper) = idle_model
+        {
+            preview.last_pass = Instant::now();
+            let samples = preview.samples.clone();
+            let language = language.clone();
+            preview.running = Some(std::thread::spawn(move || {
+                let text = whisper
+                    .transcribe_preview(&samples, &language, task)
+                    .map(|transcript| {
+                        transcript.map(|mut transcript| {
+                            punctuation.apply(&mut transcript);
+                            transcript.text(&spacing)
+                        })
+                    })
+                    .map_err(SttError::from);
+                wake::wake();
+                text
+            }));
+        }
+
+        if let Some(text) = shown {
+            debug!("Preview: {}", self.privacy.loggable(&text));
+            if let Some(ref captions) = self.captions
+                && self.privacy.captions()
+            {
+                captions.transcript(&text, language.as_str(), started.elapsed(), false);
+            }
+            // Redraw the tooltip with the new text
+            self.shown_elapsed_secs = None;
+        }
+    }
+
+    /// Type finished transcriptions in recording order, then start the oldest
+    /// waiting one once the model is loaded.
+    fn advance_transcriptions(&mut self) -> Result<()> {
+        loop {
+            let Some(front) = self.transcriptions.front() else {
+                return Ok(());
+            };
+            match front.running {
+                Some(ref handle) if !handle.is_finished() => return Ok(()),
+                Some(_) => {
+                    if let Some(done) = self.transcriptions.pop_front() {
+                        self.complete_transcription(done)?;
+                    }
+                }
+                None => {
+                    // Waits for the load the recording started, and for its
+                    // audio, prepared meanwhile; a failed load drops the queue
+                    // in poll_model_load
+                    let model = front.model.clone();
+                    let whisper = self.models.engine(&model);
+                    if whisper.is_none() {
+                        self.start_model_load(&model);
+                    }
+                    let Some(next) = self.transcriptions.front_mut() else {
+                        return Ok(());
+                    };
+                    let fresh = matches!(next.audio, PreparedAudio::Preparing(_));
+                    let samples = match next.take_samples() {
+                        None => return Ok(()),
+                        Some(Ok(samples)) => {
+                            // Saved once, before the model wait, so a crash
+                            // from here until it is typed leaves the audio behind
+                            if fresh && next.recovery.is_none() {
+                                next.recovery = self.recovery.save(&samples);
+                            }
+                            samples
+                        }
+                        Some(Err(e)) => {
+                            self.transcriptions.pop_front();
+                            error!("Preparing the recording failed: {}", e.detail());
+                            self.feedback.play_event(FeedbackEvent::Error);
+                            self.notify(Severity::Error, &e.user_message());
+                            self.models.touch(&model);
+                            self.refresh_state()?;
+                            continue;
+                        }
+                    };
+                    let Some(whisper) = whisper else {
+                        next.audio = PreparedAudio::Ready(samples);
+                        return Ok(());
+                    };
+                    info!("Transcribing...");
+                    let (language, task) = (next.language.clone(), next.task);
+                    let (punctuation, spacing) = (self.punctuation, self.spacing.clone());
+                    let tokens = self.token_dump.is_some();
+                    next.timings.model_ready();
+                    info!(
+                        "Audio prepared in {} ms during a {} ms wait for the model",
+                        next.timings.prepare.as_millis(),
+                        next.timings.model_wait.as_millis()
+                    );
+                    let handle = std::thread::spawn(move || {
+                        let text = whisper
+                            .transcribe(&samples, &language, task, tokens)
+                            .map(|mut transcript| {
+                                punctuation.apply(&mut transcript);
+                                (transcript.text(&spacing), tokens.then_some(transcript))
+                            })
+                            .map_err(SttError::from);
+                        wake::wake();
+                        text
+                    });
+                    next.running = Some(handle);
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Type the text of a finished transcription and record it.
+    fn complete_transcription(&mut self, done: Transcription) -> Result<()> {
+        let Some(handle) = done.running else {
+            return Ok(());
+        };
+        let mut timings = done.timings;
+        let joined = handle.join().unwrap_or_else(|payload| {
+            Err(SttError::Crashed {
+                task: "Transcription",
+                message: crash::payload_message(payload.as_ref()).to_owned(),
+            })
+        });
+        let (result, transcript) = match joined {
+            Ok((text, transcript)) => (Ok(text), transcript),
+            Err(e) => (Err(e), None),
+        };
+        timings.transcribed();
+        if let Ok(ref text) = result {
+            debug!("Transcribed text: {}", self.privacy.loggable(text));
+        }
+        // A held-back repeat is only offered until the next dictation
+        self.drop_held_repeat();
+        let command = result
+            .as_ref()
+            .ok()
+            .filter(|_| !done.recovered)
+            .and_then(|text| {
+                commands::find(
+                    &self.config.voice_commands,
+                    text,
+                    self.config.voice_command_max_edits,
+                )
+            });
+        let correction = result
+            .as_ref()
+            .ok()
+            .filter(|_| !done.recovered && command.is_none())
+            .and_then(|text| Correction::parse(text, self.config.correction_trigger.as_deref()?));
+        // Text as it reached the user, for the token dump
+        let mut typed = None;
+        // Whether the text reached the user, or there was none to deliver
+        let delivered = match (
+            result,
+            command.map(|command| command.action.clone()),
+            correction,
+        ) {
+            // A command or correction has no text of its own to recover
+            (Ok(_), Some(action), _) => {
+                // Its phrase is spent either way, and the ticket must still settle
+                if let Err(e) = self.run_voice_command(action) {
+                    warn!("Voice command failed: {:#}", e);
+                }
+                true
+            }
+            (Ok(_), None, Some(correction)) => {
+                self.correct_last(correction);
+                true
+            }
+            (Ok(text), None, None) if done.recovered => self.copy_recovered(&text),
+            (Ok(text), None, None) if !text.trim().is_empty() => {
+                match self.filter_text(text) {
+                    Ok(Some(text)) if self.duplicates.is_repeat(&text, Instant::now()) => {
+                        self.stats.record(&timings, 0);
+                        self.hold_back_repeat(HeldRepeat {
+                            text,
+                            language: done.language.as_str().to_owned(),
+                            window: done.window,
+                            recovery: done.recovery,
+                        })
+                    }
+                    Ok(Some(text)) => {
+                        let delivered = self.deliver_transcription(
+                            &text,
+                            done.language.as_str(),
+                            done.window,
+                            &mut timings,
+                        );
+                        let words = delivered.as_ref().map_or(0, |delivered| delivered.words);
+                        info!("Transcription complete: {}", timings);
+                        self.stats.record(&timings, words);
+                        // Only a delivered dictation can be repeated by accident
+                        if words > 0 {
+                            self.duplicates.remember(
+                                &text,
+                                self.privacy.compares_text(),
+                                Instant::now(),
+                            );
+                        }
+                        self.remember_transcription(text);
+                        typed = delivered.map(|delivered| delivered.text);
+                        typed.is_some()
+                    }
+                    Ok(None) => {
+                        self.stats.record(&timings, 0);
+                        true
+                    }
+                    Err(e) => {
+                        error!("Failed to copy filtered transcription: {:#}", e);
+                        self.feedback.play_event(FeedbackEvent::Error);
+                        self.notify(
+                            Severity::Error,
+                            &format!("The dictation contains a filtered word and could not be copied: {e}"),
+                        );
+                        self.stats.record(&timings, 0);
+                        false
+                    }
+                }
+            }
+            (Ok(_), None, None) => {
+                info!(
+                    "Transcription complete (empty result, peak {:.0} dB, RMS {:.0} dB): {}",
+                    done.levels.peak_db(),
+                    done.levels.rms_db(),
+                    timings
+                );
+                self.feedback.play_event(FeedbackEvent::Empty);
+                self.notify(Severity::Info, &empty_result_message(done.levels));
+                self.stats.record(&timings, 0);
+                true
+            }
+            (Err(e), _, _) => {
+                error!("Transcription failed: {}", e.detail());
+                self.feedback.play_event(FeedbackEvent::Error);
+                self.notify(Severity::Error, &e.user_message());
+                false
+            }
+        };
+
+        // Undelivered text keeps its audio, to recover should the app not survive
+        if let Some(ticket) = done.recovery {
+            self.recovery.settle(ticket, delivered);
+            if done.recovered {
+                self.tray
+                    .set_recovery_available(self.recovery.has_leftover());
+            }
+        }
+        if let (Some(dump), Some(transcript)) = (self.token_dump.as_ref(), transcript.as_ref()) {
+            dump.write(
+                &done.model,
+                &done.language,
+                done.task,
+                transcript,
+                typed.as_deref(),
+            );
+        }
+
+        // Start cooldown timer instead of dropping the model immediately
+        self.models.touch(&done.model);
+        if self.recording.is_none() {
+            self.refresh_state()?;
+        }
+        Ok(())
+    }
+
+    /// Run `text` through the text pipeline, `None` when nothing is left to deliver.
+    ///
+    /// Text withheld by the word filter is copied to the clipboard for the user
+    /// to check, failing when it cannot be.
+    fn filter_text(&mut self, text: String) -> Result<Option<String>> {
+        match self
+            .text_pipeline
+            .run(text.clone(), self.privacy.logs_text())
+        {
+            Filtered::Text(filtered) if filtered.is_empty() => {
+                info!("Post-processing left nothing to type");
+                Ok(None)
+            }
+            Filtered::Text(filtered) => Ok(Some(filtered)),
+            Filtered::Withheld => {
+                clipboard::set_text(&text)?;
+                self.notify(
+                    Severity::Warning,
+                    "The dictation contains a filtered word and was copied to the clipboard instead of typed",
+                );
+                Ok(None)
+            }
+        }
+    }
+
+    /// Apply a spoken "correct X to Y" to the text typed last, backspacing to
+    /// X and retyping from there with Y in its place.
+    ///
+    /// Y goes through the text pipeline like any dictation. Nothing is typed
+    /// and the error cue plays when the pipeline withholds Y, the focus moved
+    /// to another window since, or X is not found exactly once near the end.
+    fn correct_last(&mut self, correction: Correction) {
+        let replacement = self.text_pipeline.run(
+            correction.replacement().to_owned(),
+            self.privacy.logs_text(),
+        );
+        let correction = match replacement {
+            Filtered::Text(to) if !to.is_empty() => correction.with_replacement(to),
+            Filtered::Text(_) | Filtered::Withheld => {
+                info!("Correction not applied: the text pipeline left no replacement");
+                self.feedback.play_event(FeedbackEvent::Error);
+                self.notify(
+                    Severity::Warning,
+                    "Nothing corrected: the replacement contains a filtered word",
+                );
+                return;
+            }
+        };
+        let planned = match self.last_injection {
+            None => Err("There is no dictation to correct".to_owned()),
+            Some(ref last) if TargetWindow::capture() != Some(last.window) => {
+                Err("Corrections only work in the window the dictation went to".to_owned())
+            }
+            Some(ref last) => correction
+                .plan(&last.text)
+                .map_err(|e| format!("Nothing corrected: {e}")),
+        };
+        let edit = match planned {
+            Ok(edit) => edit,
+            Err(reason) => {
+                info!("Correction not applied: {}", reason);
+                self.feedback.play_event(FeedbackEvent::Error);
+                self.notify(Severity::Warning, &reason);
+                return;
+            }
+        };
+        info!(
+            "Correcting the last dictation: {} backspaces, then {}",
+            edit.backspaces,
+            self.privacy.loggable(&edit.retype)
+        );
+        match self.injector.erase_and_type(edit.backspaces, &edit.retype) {
+            Ok(()) => {
+                if let Some(ref mut last) = self.last_injection {
+                    last.text = edit.corrected;
+                }
+            }
+            Err(e) => {
+                error!("Failed to correct text: {}", e.detail());
+                // What was typed of the edit is unknown, so it cannot be corrected again
+                self.last_injection = None;
+                self.feedback.play_event(FeedbackEvent::Error);
+                self.notify(Severity::Error, &e.user_message());
+            }
+        }
+    }
+
+    /// Deliver nothing for a dictation that repeats the previous one, put it
+    /// on the clipboard, or hold it for confirmation, as `duplicate_action`
+    /// says; `true` once it is on the clipboard.
+    fn hold_back_repeat(&mut self, repeat: HeldRepeat) -> bool {
+        info!("Dictation repeats the previous one, not typing it");
+        // A third attempt still counts as a repeat
+        self.duplicates
+            .remember(&repeat.text, self.privacy.compares_text(), Instant::now());
+        match self.config.duplicate_action {
+            DuplicateAction::Skip => {
+                self.feedback.play_event(FeedbackEvent::Empty);
+                self.notify(
+                    Severity::Info,
+                    "The dictation repeats the previous one and was not typed",
+                );
+                false
+            }
+            DuplicateAction::Clipboard => {
+                match clipboard::set_text(&repeat.text) {
+                    Ok(()) => {
+                        self.notify(
+                        Severity::Warning,
+                        "The dictation repeats the previous one and was copied to the clipboard instead of typed",
+                    );
+                        true
+                    }
+                    Err(e) => {
+                        error!("Failed to copy repeated dictation: {:#}", e);
+                        self.feedback.play_event(FeedbackEvent::Error);
+                        self.notify(
+                        Severity::Error,
+                        &format!("The dictation repeats the previous one and could not be copied: {e}"),
+                    );
+                        false
+                    }
+                }
+            }
+            DuplicateAction::Confirm => {
+                self.held_repeat = Some(repeat);
+                self.tray.set_repeat_pending(true);
+                self.feedback.play_event(FeedbackEvent::Empty);
+                self.notify(
+                    Severity::Info,
+                    "The dictation repeats the previous one; choose \"Type held-back repeat\" in the tray menu to type it anyway",
+                );
+                false
+            }
+        }
+    }
+
+    /// Type the repeat held back for confirmation into the window it was
+    /// dictated in.
+    fn type_held_repeat(&mut self) {
+        self.tray.set_repeat_pending(false);
+        let Some(held) = self.held_repeat.take() else {
+            return;
+        };
+        info!("Typing the held-back repeat");
+        let mut timings = Timings::released(Duration::ZERO);
+        let delivered =
+            self.deliver_transcription(&held.text, &held.language, held.window, &mut timings);
+        if let Some(ticket) = held.recovery {
+            self.recovery.settle(ticket, delivered.is_some());
+        }
+        if delivered.is_some() {
+            self.remember_transcription(held.text);
+        }
+    }
+
+    /// Withdraw the offer to type a held-back repeat.
+    fn drop_held_repeat(&mut self) {
+        if self.held_repeat.take().is_some() {
+            self.tray.set_repeat_pending(false);
+        }
+    }
+
+    /// Append `text` to the output file and type it, as `output_mode` says,
+    /// returning what was delivered, `None` when the text reached nowhere.
+    fn deliver_transcription(
+        &mut self,
+        text: &str,
+        language: &str,
+        window: Option<TargetWindow>,
+        timings: &mut Timings,
+    ) -> Option<Delivered> {
+        let written =
+            self.config.output_mode.writes_file() && self.write_output_file(text, language);
+        if self.config.output_mode.types() {
+            let typed = self.type_transcription(text, language, window, timings);
+            return typed.or_else(|| {
+                written.then(|| Delivered {
+                    text: text.to_owned(),
+                    words: 0,
+                })
+            });
+        }
+        timings.post_processed();
+        timings.injected();
+        written.then(|| Delivered {
+            text: text.to_owned(),
+            words: self.record_delivery(text, language, Attribution::default(), timings),
+        })
+    }
+
+    /// Append `text` to the configured output file, reporting failures.
+    fn write_output_file(&mut self, text: &str, language: &str) -> bool {
+        let Some(path) = self.config.output_file.as_deref().map(paths::resolve) else {
+            return false;
+        };
+        match output::append(&path, &self.config.output_template, text, language) {
+            Ok(()) => {
+                info!("Appended transcription to {}", path.display());
+                true
+            }
+            Err(e) => {
+                error!("Failed to write output file: {:#}", e);
+                self.feedback.play_event(FeedbackEvent::Error);
+                self.notify(
+                    Severity::Error,
+                    &format!("Failed to write the output file: {e:#}"),
+                );
+                false
+            }
+        }
+    }
+
+    /// Type `text` into the window the recording started in, returning what
+    /// was typed, `None` when it reached neither a window nor the clipboard.
+    ///
+    /// With `inject_target_window` set, the text goes to that window instead
+    /// and focus returns to the current window afterwards. When the window
+    /// cannot be brought back the text goes to the focused window or, if so
+    /// configured, to the clipboard.
+    fn type_transcription(
+        &mut self,
+        text: &str,
+        language: &str,
+        window: Option<TargetWindow>,
+        timings: &mut Timings,
+    ) -> Option<Delivered> {
+        let detour = self.config.inject_target_window.as_deref().and_then(|pattern| {
+            let matches = TargetWindow::matching(pattern);
+            match *matches.as_slice() {
+                [] => {
+                    warn!(
+                        "No window matches inject_target_window {:?}, typing into the foreground window",
+                        pattern
+                    );
+                    None
+                }
+                [target] => Some(target),
+                [target, ..] => {
+                    info!(
+                        "{} windows match inject_target_window {:?}, using the most recently active, {:?}",
+                        matches.len(),
+                        pattern,
+                        target.title()
+                    );
+                    Some(target)
+                }
+            }
+        });
+        let previous = detour.and_then(|_| TargetWindow::capture());
+        let window = detour.or(window);
+        let typed = self.type_into(text, language, window, timings);
+        if let Some(previous) = previous
+            && detour != Some(previous)
+            && let Err(e) = previous.restore()
+        {
+            info!("Could not return to {:?}: {:#}", previous.title(), e);
+        }
+        typed
+    }
+
+    /// Type `dictated` into `window`, bringing it back first, returning what
+    /// was typed, with no words when it went to the clipboard instead.
+    ///
+    /// The text is wrapped in the inject template of the application it goes to.
+    fn type_into(
+        &mut self,
+        dictated: &str,
+        language: &str,
+        window: Option<TargetWindow>,
+        timings: &mut Timings,
+    ) -> Option<Delivered> {
+        let refocused = match window {
+            Some(window) => match window.restore() {
+                Ok(()) => {
+                    info!("Typing into {:?}", window.title());
+                    true
+                }
+                Err(e) => {
+                    warn!(
+                        "Could not return to {:?} ({:#}); the foreground window is {:?}",
+                        window.title(),
+                        e,
+                        foreground::foreground_title()
+                    );
+                    false
+                }
+            },
+            None => true,
+        };
+        let target = foreground::foreground_process_name();
+        // The last step, so the template sees the text as the pipeline left it
+        let wrapped = self
+            .config
+            .inject_template_for(target.as_deref())
+            .map(|template| template.render(dictated, language));
+        let text = wrapped.as_deref().unwrap_or(dictated);
+        timings.post_processed();
+
+        if !refocused && self.config.refocus_fallback == RefocusFallback::Clipboard {
+            let copied = clipboard::set_text(text);
+            timings.injected();
+            return match copied {
+                Ok(()) => {
+                    self.notify(
+                        Severity::Warning,
+                        "Could not return to the window you dictated into; the text is on the clipboard",
+                    );
+                    Some(Delivered {
+                        text: text.to_owned(),
+                        words: 0,
+                    })
+                }
+                Err(e) => {
+                    error!("Failed to copy transcription: {:#}", e);
+                    self.feedback.play_event(FeedbackEvent::Error);
+                    self.notify(
+                        Severity::Error,
+                        &format!("Failed to copy transcription: {e}"),
+                    );
+                    None
+                }
+            };
+        }
+
+        let injected = self.injector.inject(text);
+        timings.injected();
+        // Kept whether or not typing worked, so a failed attempt can be repeated
+        if let Some(ref app) = target {
+            self.resend.record(text, app, Instant::now());
+            self.refresh_resend_menu();
+        }
+        match injected {
+            Ok(()) => {
+                // Read-back would be picked up by a new recording
+                if self.privacy.keeps_text() && self.recording.is_none() {
+                    self.feedback.speak(text);
+                }
+                self.last_injection = TargetWindow::capture()
+                    .filter(|_| {
+                        self.privacy.keeps_text() && self.config.correction_trigger.is_some()
+                    })
+                    .map(|window| LastInjection {
+                        text: text.to_owned(),
+                        window,
+                    });
+                let attribution = Attribution {
+                    app: target,
+                    title: Some(foreground::foreground_title()),
+                };
+                Some(Delivered {
+                    text: text.to_owned(),
+                    words: self.record_delivery(text, language, attribution, timings),
+                })
+            }
+            Err(e) => {
+                error!("Failed to inject text: {}", e.detail());
+                self.feedback.play_event(FeedbackEvent::Error);
+                self.notify(Severity::Error, &e.user_message());
+                None
+            }
+        }
+    }
+
+    /// Post a delivered transcription to the webhook and the history, returning its words.
+    fn record_delivery(
+        &mut self,
+        text: &str,
+        language: &str,
+        attribution: Attribution,
+        timings: &Timings,
+    ) -> usize {
+        let words = text.split_whitespace().count();
+        if let Some(ref captions) = self.captions
+            && self.privacy.captions()
+        {
+            captions.transcript(text, language, timings.audio, true);
+        }
+        if let Some(ref webhook) = self.webhook {
+            webhook.send(WebhookPayload {
+                text: self.privacy.webhook_text().then(|| text.to_owned()),
+                words,
+                duration_secs: timings.audio.as_secs_f64(),
+                language: language.to_owned(),
+                timestamp: history::timestamp(history::now_secs()),
+                app: attribution.app.clone(),
+                title: attribution.title.clone(),
+            });
+        }
+        if self.privacy.history_detail().is_some() {
+            self.history.record(text, attribution);
+        }
+        words
+    }
+
+    /// Run the action of a spoken command instead of typing its phrase.
+    fn run_voice_command(&mut self, action: CommandAction) -> Result<()> {
+        info!("Voice command: {}", action);
+        match action {
+            CommandAction::Pause => self.set_paused(true),
+            CommandAction::Language(ref language) => self.choose_language(language),
+            CommandAction::Cancel => {
+                self.feedback.play_event(FeedbackEvent::Cancelled);
+                Ok(())
+            }
+        }
+    }
+
+    /// Drop state that may not have survived sleep; safe to repeat.
+    ///
+    /// The ducking worker was dropped by [`Self::wind_down`], so the next
+    /// recording enumerates audio sessions afresh.
+    fn after_resume(&mut self) -> Result<()> {
+        info!("System resumed, resetting audio output, devices and model");
+        self.feedback.reopen_output();
+        if self.models.unload_all() {
+            info!("Whisper models unloaded after sleep");
+        }
+        if self.config.preload_model {
+            self.start_likely_model_load();
+        }
+        self.rescan_input_devices()
+    }
+
+    /// Wait for a recording that will not be transcribed, reporting a crash.
+    fn discard_recording(&mut self, thread: JoinHandle<Result<CapturedAudio, SttError>>) {
+        match join_within(thread, self.recording_stop_timeout) {
+            None => self.recording_hung(),
+            Some(Err(payload)) => {
+                let message = crash::payload_message(payload.as_ref());
+                error!("Recording thread panicked: {}", message);
+                self.notify(Severity::Error, &format!("Recording crashed: {message}"));
+            }
+            Some(Ok(_)) => {}
+        }
+    }
+
+    /// Report a recording thread that did not stop in time and was left running.
+    fn recording_hung(&mut self) {
+        error!(
+            "Recording did not stop within {}s, abandoning it",
+            self.recording_stop_timeout.as_secs()
+        );
+        self.feedback.play_event(FeedbackEvent::Error);
+        self.notify(
+            Severity::Error,
+            "The microphone stopped responding; restart Speedy STT if recording fails again",
+        );
+    }
+
+    /// Pump the window system's messages so tray and hotkey events are delivered.
+    fn pump_messages() {
+        #[cfg(windows)]
+        // SAFETY: MSG is a plain Windows struct; PeekMessageW, TranslateMessage,
+        // and DispatchMessageW are standard message-loop calls with no invariants
+        // beyond what the Windows API guarantees.
+        unsafe {
+            let mut msg = MSG::default();
+            // Stops once the session is ending or the system is about to sleep,
+            // so the loop cleans up before Windows delivers further messages
+            while !session::pump_interrupted()
+                && PeekMessageW(&mut msg, None, 0, 0, PM_REMOVE).as_bool()
+            {
+                let _ = TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+        }
+        #[cfg(target_os = "linux")]
+        while gtk::events_pending() {
+            gtk::main_iteration_do(false);
+        }
+    }
+}
+
+/// Wait up to `timeout` for `handle` while keeping the tray responsive.
+///
+/// `None` when the thread is still running; it is then detached and left to
+/// finish or hang on its own.
+fn join_within<T>(handle: JoinHandle<T>, timeout: Duration) -> Option<std::thread::Result<T>> {
+    let deadline = Instant::now() + timeout;
+    while !handle.is_finished() {
+        if Instant::now() >= deadline {
+            return None;
+        }
+        App::pump_messages();
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    Some(handle.join())
+}
+
+/// Load the model at `path`, or refuse for a `refused` shortfall, logging the
+/// working set the load added so the estimate can be compared
+fn load_model(
+    path: &Path,
+    threads: usize,
+    options: WhisperOptions,
+    refused: Option<Shortfall>,
+) -> Result<WhisperEngine, SttError> {
+    if let Some(short) = refused {
+        return Err(SttError::LowMemory {
+            required_mb: short.required_mb(),
+            available_mb: short.available_mb(),
+        });
+    }
+    let before = memory::working_set();
+    let engine = WhisperEngine::load_with_options(path, threads, options)?;
+    if let (Some(before), Some(after), Ok(file)) =
+        (before, memory::working_set(), std::fs::metadata(path))
+    {
+        info!(
+            "Model load took {} MB of memory, estimated {} MB",
+            memory::mb(after.saturating_sub(before)),
+            memory::mb(memory::estimate(file.len()))
+        );
+    }
+    Ok(engine)
+}
+
+/// Why a recording with `levels` may have come back without text, for a notification.
+///
+/// Silence points at the microphone, quiet audio at the distance to it, and
+/// audio that was loud enough at the speech itself or the model.
+fn empty_result_message(levels: AudioLevels) -> String {
+    let (peak, rms) = (levels.peak_db(), levels.rms_db());
+    if peak < SILENT_PEAK_DB {
+        format!(
+            "Nothing transcribed, the peak level was {peak:.0} dB. Is the microphone muted, or is another one selected in the tray's Microphone menu?"
+        )
+    } else if rms < QUIET_RMS_DB {
+        format!(
+            "Nothing transcribed, the audio was very quiet ({rms:.0} dB on average). Speak closer to the microphone or raise volume_boost."
+        )
+    } else {
+        format!(
+            "Nothing transcribed, although the audio was loud enough (peak {peak:.0} dB). Try speaking more clearly, or a larger model."
+        )
+    }
+}
+
+/// Transcription history as configured, disabled without a `history_file`.
+fn transcription_history(config: &Config) -> TranscriptionHistory {
+    TranscriptionHistory::new(
+        config
+            .history_file
+            .as_deref()
+            .filter(|file| !file.trim().is_empty())
+            .map(paths::resolve),
+        config.history_format,
+        PrivacyPolicy::new(config)
+            .history_detail()
+            .unwrap_or(config.history_detail),
+        config.history_daily,
+        config.history_max_days,
+    )
+}
+
+/// Webhook of `config`, started only when a URL is set
+fn webhook(config: &Config) -> Option<Webhook> {
+    config
+        .webhook_url
+        .clone()
+        .map(|url| Webhook::start(url, config.webhook_retry))
+}
+
+/// How long texts are kept for re-sending under `config`, `None` when they are not
+fn resend_max_age(config: &Config) -> Option<Duration> {
+    (config.resend_keep_secs > 0 && PrivacyPolicy::new(config).keeps_text())
+        .then(|| Duration::from_secs(config.resend_keep_secs))
+}
+
+/// Whether dictations are saved for crash recovery under `config`
+fn recovery_enabled(config: &Config) -> bool {
+    config.crash_recovery && PrivacyPolicy::new(config).keeps_audio()
+}
+
+/// Buffered samples after which a recording spills to disk, `None` for never
+fn spill_limit(megabytes: u64) -> Option<usize> {
+    let bytes = megabytes.checked_mul(1024 * 1024)?;
+    usize::try_from(bytes / 4).ok().filter(|&limit| limit > 0)
+}
+
+/// Model pool of `config`, with the models of its `auto_model` rules
+fn model_pool(config: &Config) -> ModelPool {
+    let rules = config
+        .auto_model
+        .iter()
+        .map(|rule| (rule.under, paths::model_file(&rule.model)))
+        .collect();
+    ModelPool::new(
+        paths::model_file(&config.whisper_model),
+        rules,
+        Duration::from_secs(config.model_unload_delay_secs),
+    )
+}
+
+/// Text pipeline of `config`, with the word filter loaded only when a list file is set
+fn text_pipeline(config: &Config) -> TextPipeline {
+    let word_filter = config
+        .word_filter_file
+        .as_deref()
+        .map(|file| WordFilter::load(paths::resolve(file), config.word_filter_action));
+    TextPipeline::new(&config.text_pipeline, word_filter)
+}
+
+/// Caption server of `config`, started only when a port is set
+fn caption_server(config: &Config) -> Result<Option<CaptionServer>> {
+    if config.caption_port == 0 {
+        return Ok(None);
+    }
+    CaptionServer::start(config.caption_port)
+        .map(Some)
+        .inspect_err(|e| warn!("Live captions disabled: {:#}", e))
+        .context("Live captions disabled")
+}
+
+/// Modification times of the configuration files, `None` for missing files.
+fn config_stamp() -> Vec<Option<SystemTime>> {
+    Config::watched_files()
+        .iter()
+        .map(|path| {
+            std::fs::metadata(path)
+                .and_then(|meta| meta.modified())
+                .ok()
+        })
+        .collect()
+}
+
+/// The end of a preview that fits the tooltip, marked as cut when it is.
+fn preview_tail(text: &str) -> String {
+    let chars = text.chars().count();
+    if chars <= PREVIEW_TOOLTIP_CHARS {
+        return text.to_owned();
+    }
+    let tail: String = text.chars().skip(chars - PREVIEW_TOOLTIP_CHARS).collect();
+    format!("...{}", tail.trim_start())
+}
+
+/// Format whole seconds as `m:ss`, or `h:mm:ss` from one hour on.
+fn format_elapsed(secs: u64) -> String {
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes}:{seconds:02}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch::ScratchDir;
+    use crate::whisper::{Segment, Token};
+
+    /// Phrase dictated, which must not be found anywhere it was not typed
+    const SENTINEL: &str = "zebra quartz sentinel";
+
+    /// Log output collected from the test thread
+    #[derive(Clone, Default)]
+    struct CapturedLog(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for CapturedLog {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl CapturedLog {
+        /// Everything logged so far
+        fn text(&self) -> String {
+            let bytes = self
+                .0
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner);
+            String::from_utf8_lossy(&bytes).into_owned()
+        }
+    }
+
+    /// Config writing every sink of a dictation into `dir`, in `privacy_mode`
+    fn config_in(dir: &Path, privacy_mode: &str) -> Config {
+        let model = dir.join("ggml-test.bin");
+        std::fs::write(&model, b"lmgg").expect("model written");
+        let filter = dir.join("filter.txt");
+        std::fs::write(&filter, "unrelated\n").expect("filter list written");
+        let path = |name: &str| dir.join(name).display().to_string();
+        let overrides = [
+            ("whisper_model", model.display().to_string()),
+            ("privacy_mode", privacy_mode.to_owned()),
+            ("keep_last_transcription", "true".to_owned()),
+            ("history_file", path("history.jsonl")),
+            ("history_detail", "full-text".to_owned()),
+            ("crash_recovery", "true".to_owned()),
+            ("whisper_dump_tokens_dir", path("tokens")),
+            ("word_filter_file", filter.display().to_string()),
+            ("text_pipeline", "normalize, filter".to_owned()),
+        ];
+        Config::from_overrides(
+            overrides
+                .into_iter()
+                .map(|(key, value)| (key.to_owned(), value))
+                .collect(),
+        )
+        .expect("config loaded")
+    }
+
+    /// Run one dictation of `SENTINEL` through every sink `config` sets up,
+    /// as the app does on delivery, returning what was logged
+    fn dictate(config: &Config, dir: &Path) -> String {
+        let log = CapturedLog::default();
+        let writer = log.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::TRACE)
+            .with_writer(move || writer.clone())
+            .finish();
+        tracing::subscriber::with_default(subscriber, || {
+            let privacy = PrivacyPolicy::new(config);
+            let mut recovery = Recovery::new(dir.join("recovery.wav"), recovery_enabled(config));
+            let ticket = recovery.save(&[0.0; 16_000]);
+
+            let Filtered::Text(text) =
+                text_pipeline(config).run(format!("  {SENTINEL} "), privacy.logs_text())
+            else {
+                panic!("dictation withheld");
+            };
+            let transcript = Transcript {
+                segments: vec![Segment {
+                    start: Duration::ZERO,
+                    end: Duration::from_secs(1),
+                    text: format!(" {SENTINEL}"),
+                    tokens: vec![Token {
+                        id: 1,
+                        text: SENTINEL.to_owned(),
+                        probability: 1.0,
+                        start: Duration::ZERO,
+                        end: Duration::from_secs(1),
+                    }],
+                }],
+                language: "en".to_owned(),
+            };
+            if let Some(dump) = TokenDump::new(config, 1) {
+                dump.write(
+                    &dir.join("ggml-test.bin"),
+                    &config.whisper_language,
+                    TranscriptionTask::Transcribe,
+                    &transcript,
+                    Some(&text),
+                );
+            }
+            if privacy.history_detail().is_some() {
+                transcription_history(config).record(&text, Attribution::default());
+            }
+            if let Some(ticket) = ticket {
+                // Typing failed, so the audio would be kept for recovery
+                recovery.settle(ticket, false);
+            }
+        });
+        log.text()
+    }
+
+    /// Files under `dir` whose contents mention `SENTINEL`
+    fn files_mentioning_sentinel(dir: &Path) -> Vec<PathBuf> {
+        let mut found = Vec::new();
+        let mut pending = vec![dir.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            for entry in std::fs::read_dir(&dir).expect("directory listed") {
+                let path = entry.expect("directory entry").path();
+                if path.is_dir() {
+                    pending.push(path);
+                } else if String::from_utf8_lossy(&std::fs::read(&path).expect("file read"))
+                    .contains(SENTINEL)
+                {
+                    found.push(path);
+                }
+            }
+        }
+        found
+    }
+
+    #[test]
+    fn strict_privacy_keeps_the_text_out_of_logs_and_files() {
+        let dir = ScratchDir::new("privacy-strict");
+        let config = config_in(dir.path(), "strict");
+        let log = dictate(&config, dir.path());
+        assert!(!log.contains(SENTINEL), "text logged: {log}");
+        assert_eq!(
+            files_mentioning_sentinel(dir.path()),
+            Vec::<PathBuf>::new(),
+            "text written to disk"
+        );
+        assert!(
+            !dir.path().join("recovery.wav").exists(),
+            "audio kept on disk"
+        );
+        assert!(
+            dir.path().join("history.jsonl").exists(),
+            "metadata-only history not written"
+        );
+    }
+
+    #[test]
+    fn normal_privacy_keeps_the_text_where_configured() {
+        // The same dictation without strict mode shows the checks above can fail
+        let dir = ScratchDir::new("privacy-normal");
+        let config = config_in(dir.path(), "normal");
+        let log = dictate(&config, dir.path());
+        assert!(log.contains(SENTINEL), "text not logged: {log}");
+        let mut found = files_mentioning_sentinel(dir.path());
+        found.sort();
+        assert_eq!(
+            found.len(),
+            2,
+            "text not in history and token dump: {found:?}"
+        );
+        assert!(
+            dir.path().join("recovery.wav").exists(),
+            "audio of the undelivered dictation not kept"
+        );
+    }
 }
@@ -1,54 +1,346 @@
 //! Audio capture with volume boost
 
-use std::sync::{Arc, Mutex};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex, PoisonError};
 
-use anyhow::{Context, Result};
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use tracing::{info, warn};
+use tracing::{error, info, warn};
 
-/// Audio recorder that captures from default microphone
+use crate::spill::Spill;
+
+/// Why audio could not be captured or the input devices not queried
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum AudioError {
+    /// There is no input device to record from
+    #[error("No input device available")]
+    NoInputDevice,
+    /// The host could not list its input devices
+    #[error("Failed to enumerate input devices")]
+    Devices(#[source] cpal::DevicesError),
+    /// The device reports no format to record in
+    #[error("Failed to get default input config")]
+    Format(#[source] cpal::DefaultStreamConfigError),
+    /// The input stream could not be opened
+    #[error(transparent)]
+    Stream(#[from] cpal::BuildStreamError),
+    /// The input stream opened but would not start
+    #[error(transparent)]
+    Play(#[from] cpal::PlayStreamError),
+}
+
+/// Result of audio operations
+pub type Result<T, E = AudioError> = std::result::Result<T, E>;
+
+/// Samples of one recording in the format the device captured them
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct CapturedAudio {
+    /// Interleaved samples with the volume boost applied
+    pub samples: Vec<f32>,
+    /// Channels per frame
+    pub channels: u16,
+    /// Frames per second
+    pub sample_rate: u32,
+    /// Whisper input of the audio before `samples`, for a long recording
+    /// spilled to disk; see [`AudioRecorder::with_spill`]
+    pub spilled: Option<Arc<Spill>>,
+}
+
+impl CapturedAudio {
+    /// The samples as Whisper expects them, see [`to_whisper_input`],
+    /// after the spilled part of the recording
+    pub fn to_whisper_input(&self) -> Vec<f32> {
+        let rest = to_whisper_input(&self.samples, self.channels, self.sample_rate);
+        let Some(ref spilled) = self.spilled else {
+            return rest;
+        };
+        match spilled.read_back() {
+            Ok(mut samples) => {
+                samples.extend(rest);
+                samples
+            }
+            Err(e) => {
+                error!(
+                    "Failed to read back the spilled recording, transcribing only its end: {}",
+                    e
+                );
+                rest
+            }
+        }
+    }
+}
+
+/// Loudness of a recording, to tell silence from speech too quiet to make out
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[non_exhaustive]
+pub struct AudioLevels {
+    /// Largest absolute sample, 1.0 at full scale
+    pub peak: f32,
+    /// Root mean square of all samples
+    pub rms: f32,
+}
+
+impl AudioLevels {
+    /// Levels of `samples`, zero for none
+    #[allow(
+        clippy::as_conversions,
+        clippy::cast_precision_loss,
+        reason = "a mean over a recording needs no more precision than f64 has"
+    )]
+    pub fn of(samples: &[f32]) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+        let peak = samples.iter().fold(0.0_f32, |peak, s| peak.max(s.abs()));
+        let squares: f64 = samples.iter().map(|&s| f64::from(s) * f64::from(s)).sum();
+        let rms = (squares / samples.len() as f64).sqrt() as f32;
+        Self { peak, rms }
+    }
+
+    /// Peak in decibels relative to full scale, 0 at the loudest
+    pub fn peak_db(&self) -> f32 {
+        decibels(self.peak)
+    }
+
+    /// Root mean square in decibels relative to full scale
+    pub fn rms_db(&self) -> f32 {
+        decibels(self.rms)
+    }
+}
+
+/// `level` relative to full scale in decibels, floored at -100 for silence
+fn decibels(level: f32) -> f32 {
+    (20.0 * level.max(1e-5).log10()).max(-100.0)
+}
+
+/// Names of the input devices currently available, in host order
+pub fn input_device_names() -> Vec<String> {
+    match cpal::default_host().input_devices() {
+        Ok(devices) => devices.filter_map(|device| device_name(&device)).collect(),
+        Err(e) => {
+            warn!("Failed to enumerate input devices: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Whether any input device is connected, so a recording could start
+pub fn has_input_device() -> bool {
+    cpal::default_host().default_input_device().is_some()
+}
+
+/// One line per input device with its default format, the system default marked with `*`
+pub fn describe_input_devices() -> Result<Vec<String>> {
+    let host = cpal::default_host();
+    let default_name = host.default_input_device().as_ref().and_then(device_name);
+    let devices = host.input_devices().map_err(AudioError::Devices)?;
+
+    Ok(devices
+        .map(|device| {
+            let name = device_name(&device).unwrap_or_else(|| "Unknown".to_string());
+            let marker = if Some(&name) == default_name.as_ref() {
+                '*'
+            } else {
+                ' '
+            };
+            let format = device.default_input_config().map_or_else(
+                |e| format!("no default config: {e}"),
+                |config| {
+                    format!(
+                        "{} Hz, {} channels, {}",
+                        config.sample_rate(),
+                        config.channels(),
+                        config.sample_format()
+                    )
+                },
+            );
+            format!("{marker} {name} ({format})")
+        })
+        .collect())
+}
+
+/// Input device a recording would use and its format, failing when there is none.
+///
+/// Only the device's default format is queried, so the microphone is not opened.
+pub fn check_input_device(wanted: Option<&str>) -> Result<String> {
+    let host = cpal::default_host();
+    let selected = wanted.and_then(|name| {
+        host.input_devices()
+            .ok()?
+            .find(|device| device_name(device).as_deref() == Some(name))
+    });
+    let fallback = wanted.filter(|_| selected.is_none());
+    let device = selected
+        .or_else(|| host.default_input_device())
+        .ok_or(AudioError::NoInputDevice)?;
+    let config = device.default_input_config().map_err(AudioError::Format)?;
+    let name = device_name(&device).unwrap_or_else(|| "Unknown".to_string());
+    let detail = format!(
+        "{name} ({} Hz, {} channels)",
+        config.sample_rate(),
+        config.channels()
+    );
+    Ok(match fallback {
+        Some(wanted) => format!("{wanted} not found, using {detail}"),
+        None => detail,
+    })
+}
+
+/// Sample rate Whisper expects its input at
+pub const WHISPER_SAMPLE_RATE: u32 = 16_000;
+
+/// Downmix interleaved `samples` to mono and resample them from `sample_rate`
+/// to [`WHISPER_SAMPLE_RATE`] by linear interpolation.
+#[allow(
+    clippy::as_conversions,
+    reason = "sample positions stay far below the precision limits of f64"
+)]
+pub fn to_whisper_input(samples: &[f32], channels: u16, sample_rate: u32) -> Vec<f32> {
+    let channels = usize::from(channels.max(1));
+    let mono: Vec<f32> = samples
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect();
+    if sample_rate == WHISPER_SAMPLE_RATE || sample_rate == 0 {
+        return mono;
+    }
+
+    let step = f64::from(sample_rate) / f64::from(WHISPER_SAMPLE_RATE);
+    let len = (mono.len() as f64 / step) as usize;
+    (0..len)
+        .map(|i| {
+            let position = i as f64 * step;
+            let index = position as usize;
+            let fraction = (position - index as f64) as f32;
+            let current = mono.get(index).copied().unwrap_or(0.0);
+            let next = mono.get(index + 1).copied().unwrap_or(current);
+            (next - current).mul_add(fraction, current)
+        })
+        .collect()
+}
+
+/// Display name of a device, if the host can provide one
+fn device_name(device: &cpal::Device) -> Option<String> {
+    device.description().ok().map(|d| d.name().to_string())
+}
+
+/// Audio recorder that captures from the selected or default microphone
 pub struct AudioRecorder {
     /// Volume boost multiplier
     volume_boost: f32,
+    /// Name of the input device to open, `None` for the system default
+    device_name: Option<String>,
+    /// Peak of the boosted input as `f32` bits, raised for every buffer and reset by the reader
+    level: Arc<AtomicU32>,
+    /// Second peak like `level`, for a reader of its own such as silence detection
+    watch: Option<Arc<AtomicU32>>,
+    /// Receives a copy of every boosted buffer while recording, for previews
+    chunks: Option<Sender<Vec<f32>>>,
+    /// Buffered samples above which older audio moves to a file in the directory
+    spill: Option<(usize, PathBuf)>,
 }
 
 impl AudioRecorder {
-    /// Create new audio recorder
-    pub fn new(volume_boost: f32) -> Self {
-        Self { volume_boost }
+    /// Create new audio recorder reporting its input peak to `level`
+    pub const fn new(
+        volume_boost: f32,
+        device_name: Option<String>,
+        level: Arc<AtomicU32>,
+    ) -> Self {
+        Self {
+            volume_boost,
+            device_name,
+            level,
+            watch: None,
+            chunks: None,
+            spill: None,
+        }
+    }
+
+    /// Move older audio to a temporary file in `dir` whenever more than
+    /// `limit` samples are buffered, so a long recording stays small in memory
+    ///
+    /// Only [`CapturedAudio::to_whisper_input`] includes the spilled audio.
+    pub fn with_spill(mut self, limit: usize, dir: PathBuf) -> Self {
+        self.spill = Some((limit, dir));
+        self
+    }
+
+    /// Also raise `peak` for every buffer, reset by a reader independent of the level meter
+    pub fn with_peak_watch(mut self, peak: Arc<AtomicU32>) -> Self {
+        self.watch = Some(peak);
+        self
+    }
+
+    /// Also send each captured buffer to `chunks` as it arrives
+    pub fn with_chunks(mut self, chunks: Sender<Vec<f32>>) -> Self {
+        self.chunks = Some(chunks);
+        self
     }
 
-    /// Record audio until stopped
+    /// Record audio until `stop_signal` is set, returning the captured samples
     pub fn record_until_stopped(&self, stop_signal: Arc<Mutex<bool>>) -> Result<Vec<f32>> {
+        self.capture_until_stopped(stop_signal)
+            .map(|captured| captured.samples)
+    }
+
+    /// Record like [`Self::record_until_stopped`], keeping the device format
+    /// the samples are in
+    pub fn capture_until_stopped(&self, stop_signal: Arc<Mutex<bool>>) -> Result<CapturedAudio> {
         let host = cpal::default_host();
-        let device = host
-            .default_input_device()
-            .context("No input device available")?;
+        let selected = self.device_name.as_ref().and_then(|name| {
+            let device = host
+                .input_devices()
+                .ok()?
+                .find(|device| device_name(device).as_ref() == Some(name));
+            if device.is_none() {
+                warn!("Input device {} not found, using default", name);
+            }
+            device
+        });
+        let device = selected
+            .or_else(|| host.default_input_device())
+            .ok_or(AudioError::NoInputDevice)?;
 
         info!(
             "Using input device: {}",
-            device
-                .description()
-                .map(|d| d.name().to_string())
-                .unwrap_or_else(|_| "Unknown".to_string())
+            device_name(&device).unwrap_or_else(|| "Unknown".to_string())
         );
 
-        let config = device
-            .default_input_config()
-            .context("Failed to get default input config")?;
+        let config = device.default_input_config().map_err(AudioError::Format)?;
 
         info!("Input config: {:?}", config);
+        let (channels, sample_rate) = (config.channels(), config.sample_rate());
 
         let samples = Arc::new(Mutex::new(Vec::new()));
         let samples_clone = Arc::clone(&samples);
         let volume_boost = self.volume_boost;
+        let level = Arc::clone(&self.level);
+        let watch = self.watch.clone();
+        let chunks = self.chunks.clone();
 
         let stream = device.build_input_stream(
             &config.into(),
             move |data: &[f32], _: &cpal::InputCallbackInfo| {
                 let mut samples_lock = samples_clone.lock().unwrap_or_else(|e| e.into_inner());
+                let mut peak = 0.0_f32;
+                let start = samples_lock.len();
                 for &sample in data {
-                    samples_lock.push(sample * volume_boost);
+                    let boosted = sample * volume_boost;
+                    peak = peak.max(boosted.abs());
+                    samples_lock.push(boosted);
+                }
+                if let Some(ref chunks) = chunks {
+                    // The preview may have ended first; the recording goes on
+                    let _ = chunks.send(samples_lock[start..].to_vec());
+                }
+                // Bit patterns of non-negative floats order like their values
+                level.fetch_max(peak.to_bits(), Ordering::Relaxed);
+                if let Some(ref watch) = watch {
+                    watch.fetch_max(peak.to_bits(), Ordering::Relaxed);
                 }
             },
             |err| warn!("Audio stream error: {}", err),
@@ -58,23 +350,108 @@ impl AudioRecorder {
         stream.play()?;
 
         // Wait until stop signal is set
+        let mut spilled = self.spill.as_ref().map(|(_, dir)| Spill::new(dir.clone()));
         loop {
             std::thread::sleep(std::time::Duration::from_millis(50));
             let should_stop = *stop_signal.lock().unwrap_or_else(|e| e.into_inner());
             if should_stop {
                 break;
             }
+            if let (Some(&(limit, _)), Some(spill)) = (self.spill.as_ref(), spilled.as_mut()) {
+                spill_older(&samples, limit, channels, sample_rate, spill);
+            }
         }
 
         drop(stream);
+        let recorded_samples = drain(&samples);
+
+        let spilled = spilled.filter(|spill| !spill.is_empty());
+        match spilled {
+            Some(ref spill) => info!(
+                "Recorded {} samples after {} spilled at 16 kHz",
+                recorded_samples.len(),
+                spill.len()
+            ),
+            None => info!("Recorded {} samples", recorded_samples.len()),
+        }
+
+        Ok(CapturedAudio {
+            samples: recorded_samples,
+            channels,
+            sample_rate,
+            spilled: spilled.map(Arc::new),
+        })
+    }
+}
+
+/// Take the recorded samples out of the buffer shared with the callback
+///
+/// Some drivers still hold the callback, and with it a clone of the buffer,
+/// for a moment after the stream is dropped, so the samples are taken out
+/// from under the lock instead of waiting to own the buffer.
+fn drain(samples: &Arc<Mutex<Vec<f32>>>) -> Vec<f32> {
+    std::mem::take(&mut *samples.lock().unwrap_or_else(PoisonError::into_inner))
+}
 
-        let recorded_samples = Arc::try_unwrap(samples)
-            .unwrap_or_else(|_| panic!("Failed to unwrap samples"))
-            .into_inner()
-            .unwrap_or_else(|e| e.into_inner());
+/// Move the buffered `samples` to `spill` as Whisper input once there are
+/// more than `limit`
+///
+/// The buffer is swapped out under the lock and converted after, so the
+/// capture callback is not held up; a partial frame stays for the next buffer.
+fn spill_older(
+    samples: &Mutex<Vec<f32>>,
+    limit: usize,
+    channels: u16,
+    sample_rate: u32,
+    spill: &mut Spill,
+) {
+    let older = {
+        let mut buffer = samples.lock().unwrap_or_else(PoisonError::into_inner);
+        if buffer.len() <= limit {
+            return;
+        }
+        let mut older = std::mem::take(&mut *buffer);
+        let whole = older.len() - older.len() % usize::from(channels.max(1));
+        buffer.extend(older.drain(whole..));
+        older
+    };
+    spill.append(&to_whisper_input(&older, channels, sample_rate));
+}
 
-        info!("Recorded {} samples", recorded_samples.len());
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        Ok(recorded_samples)
+    #[test]
+    fn drain_takes_every_sample_while_the_callback_holds_the_buffer() {
+        let samples = Arc::new(Mutex::new(Vec::new()));
+        let callback = Arc::clone(&samples);
+        let writer = std::thread::spawn({
+            let callback = Arc::clone(&callback);
+            move || {
+                for chunk in 0..100_u16 {
+                    let mut buffer = callback.lock().expect("buffer lock");
+                    buffer.extend((0..10_u16).map(|i| f32::from(chunk * 10 + i)));
+                }
+            }
+        });
+        writer.join().expect("writer thread");
+
+        let drained = drain(&samples);
+        let expected: Vec<f32> = (0..1000_u16).map(f32::from).collect();
+        assert_eq!(drained, expected, "every sample once, in order");
+        assert_eq!(
+            Arc::strong_count(&samples),
+            2,
+            "the callback still holds it"
+        );
+
+        // A callback firing late writes into the emptied buffer only
+        callback.lock().expect("buffer lock").push(-1.0);
+        assert_eq!(drain(&samples), vec![-1.0], "no sample handed out twice");
+        assert!(
+            drained.iter().all(|&s| s >= 0.0),
+            "late sample not in the take"
+        );
     }
 }
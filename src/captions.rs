@@ -0,0 +1,453 @@
+//! Live captions for streaming software over WebSocket.
+//!
+//! A server on 127.0.0.1 broadcasts every finished transcription, and the
+//! app's state whenever it changes, as JSON text messages that an OBS browser
+//! source can render. Clients joining mid-session are sent the latest status
+//! first. Only pages served from this machine or local files may connect.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use tracing::{debug, info, warn};
+
+use crate::history;
+use crate::ipc::AppStatus;
+
+/// Longest a client may take to send its handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Longest a client may stall a broadcast before it is dropped
+const WRITE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Largest handshake accepted, request line and headers together
+const MAX_HEAD: u64 = 8192;
+
+/// Appended to a client's key to prove the server speaks WebSocket (RFC 6455)
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Opcode of a text frame
+const OPCODE_TEXT: u8 = 0x1;
+
+/// Opcode of a close frame
+const OPCODE_CLOSE: u8 = 0x8;
+
+/// One message as sent to clients
+#[derive(Debug, Serialize)]
+struct Message {
+    /// Increases by one with every message, starting at 1
+    id: u64,
+    /// UTC time the message was sent, ISO-8601
+    timestamp: String,
+    /// What happened
+    #[serde(flatten)]
+    event: CaptionEvent,
+}
+
+/// Something worth telling the clients about
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum CaptionEvent {
+    /// The app's state or language changed
+    Status {
+        /// `idle`, `paused`, `recording`, `latched` or `transcribing`
+        state: String,
+        /// Language recordings are transcribed in
+        language: String,
+        /// Whether the hotkey is paused
+        paused: bool,
+    },
+    /// Text of a recording
+    Transcript {
+        /// Transcribed text
+        text: String,
+        /// Whether this is the finished text rather than a preview that will be replaced
+        #[serde(rename = "final")]
+        is_final: bool,
+        /// Language the recording was transcribed in
+        language: String,
+        /// UTC time the recording started, ISO-8601
+        started: String,
+        /// Length of the recording in seconds
+        duration_secs: f64,
+    },
+}
+
+/// Work for the broadcast thread
+enum Job {
+    /// A client finished its handshake
+    Join(TcpStream),
+    /// Send an event to every client
+    Broadcast(CaptionEvent),
+}
+
+/// WebSocket server, stopped and its clients closed when dropped
+pub struct CaptionServer {
+    /// Jobs for the broadcast thread, `None` once shutting down
+    jobs: Option<Sender<Job>>,
+    /// Told to the accept loop before it is woken for shutdown
+    stopping: Arc<AtomicBool>,
+    /// Address the listener is bound to
+    address: SocketAddr,
+    /// Thread accepting clients
+    acceptor: Option<JoinHandle<()>>,
+    /// Thread writing to clients
+    broadcaster: Option<JoinHandle<()>>,
+}
+
+impl CaptionServer {
+    /// Listen on `port` of the loopback address.
+    ///
+    /// Fails only if the port cannot be bound; the app runs on without captions.
+    pub fn start(port: u16) -> Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+            .with_context(|| format!("Failed to listen on 127.0.0.1:{port}"))?;
+        let address = listener
+            .local_addr()
+            .context("Failed to read caption server address")?;
+        let (jobs, queue) = mpsc::channel();
+        let stopping = Arc::new(AtomicBool::new(false));
+
+        let broadcaster = std::thread::Builder::new()
+            .name("captions".to_owned())
+            .spawn(move || broadcast(&queue))
+            .context("Failed to start caption thread")?;
+        let joins = jobs.clone();
+        let accept_stopping = Arc::clone(&stopping);
+        let acceptor = std::thread::Builder::new()
+            .name("captions-accept".to_owned())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    if accept_stopping.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    let stream = match stream {
+                        Ok(stream) => stream,
+                        Err(e) => {
+                            debug!("Failed to accept caption client: {}", e);
+                            continue;
+                        }
+                    };
+                    match handshake(&stream, port) {
+                        Ok(()) => {
+                            if joins.send(Job::Join(stream)).is_err() {
+                                break;
+                            }
+                        }
+                        Err(e) => debug!("Refused caption client: {:#}", e),
+                    }
+                }
+            })
+            .context("Failed to start caption accept thread")?;
+        info!("Serving captions on ws://127.0.0.1:{}", port);
+
+        Ok(Self {
+            jobs: Some(jobs),
+            stopping,
+            address,
+            acceptor: Some(acceptor),
+            broadcaster: Some(broadcaster),
+        })
+    }
+
+    /// Tell clients the state in `status`; repeats of the last one are not sent.
+    pub fn status(&self, status: &AppStatus) {
+        self.send(CaptionEvent::Status {
+            state: status.state.clone(),
+            language: status.language.clone(),
+            paused: status.paused,
+        });
+    }
+
+    /// Send the text of a recording that lasted `duration` and ended just now.
+    pub fn transcript(&self, text: &str, language: &str, duration: Duration, is_final: bool) {
+        let started = history::now_secs().saturating_sub(duration.as_secs());
+        self.send(CaptionEvent::Transcript {
+            text: text.to_owned(),
+            is_final,
+            language: language.to_owned(),
+            started: history::timestamp(started),
+            duration_secs: duration.as_secs_f64(),
+        });
+    }
+
+    /// Queue `event` for the broadcast thread
+    fn send(&self, event: CaptionEvent) {
+        if let Some(ref jobs) = self.jobs
+            && jobs.send(Job::Broadcast(event)).is_err()
+        {
+            warn!("Caption thread is not running");
+        }
+    }
+}
+
+impl Drop for CaptionServer {
+    fn drop(&mut self) {
+        self.stopping.store(true, Ordering::Relaxed);
+        // accept() only returns for a connection, so make one
+        if let Err(e) = TcpStream::connect_timeout(&self.address, HANDSHAKE_TIMEOUT) {
+            // Both threads are left to end with the process
+            debug!("Failed to wake caption accept thread: {}", e);
+            return;
+        }
+        if let Some(acceptor) = self.acceptor.take() {
+            let _ = acceptor.join();
+        }
+        // With both senders gone the broadcast thread closes its clients and ends
+        self.jobs = None;
+        if let Some(broadcaster) = self.broadcaster.take() {
+            let _ = broadcaster.join();
+        }
+    }
+}
+
+/// Broadcast thread: owns the clients and writes every job's message to them.
+fn broadcast(queue: &Receiver<Job>) {
+    let mut clients: Vec<TcpStream> = Vec::new();
+    let mut next_id = 1;
+    let mut last_status: Option<(CaptionEvent, Vec<u8>)> = None;
+
+    for job in queue {
+        match job {
+            Job::Join(mut client) => {
+                if let Err(e) = client.set_write_timeout(Some(WRITE_TIMEOUT)) {
+                    debug!("Failed to set caption write timeout: {}", e);
+                }
+                if let Some((_, ref frame)) = last_status
+                    && client.write_all(frame).is_err()
+                {
+                    continue;
+                }
+                clients.push(client);
+                info!("Caption client connected ({} total)", clients.len());
+            }
+            Job::Broadcast(event) => {
+                let is_status = matches!(event, CaptionEvent::Status { .. });
+                if is_status && last_status.as_ref().is_some_and(|(last, _)| *last == event) {
+                    continue;
+                }
+                // Transcripts nobody receives are not worth encoding
+                if clients.is_empty() && !is_status {
+                    next_id += 1;
+                    continue;
+                }
+                let message = Message {
+                    id: next_id,
+                    timestamp: history::timestamp(history::now_secs()),
+                    event,
+                };
+                next_id += 1;
+                let json = match serde_json::to_string(&message) {
+                    Ok(json) => json,
+                    Err(e) => {
+                        warn!("Failed to encode caption message: {}", e);
+                        continue;
+                    }
+                };
+                let frame = frame(OPCODE_TEXT, json.as_bytes());
+                let before = clients.len();
+                clients.retain_mut(|client| client.write_all(&frame).is_ok());
+                if clients.len() < before {
+                    info!("{} caption client(s) disconnected", before - clients.len());
+                }
+                if is_status {
+                    last_status = Some((message.event, frame));
+                }
+            }
+        }
+    }
+
+    let close = frame(OPCODE_CLOSE, &[]);
+    for mut client in clients {
+        let _ = client.write_all(&close);
+    }
+}
+
+/// Read a client's upgrade request and accept it if it may see captions.
+fn handshake(stream: &TcpStream, port: u16) -> Result<()> {
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    let mut reader = BufReader::new(stream).take(MAX_HEAD);
+    let mut line = String::new();
+    reader
+        .read_line(&mut line)
+        .context("Failed to read request")?;
+    if !line.starts_with("GET ") {
+        refuse(stream, "405 Method Not Allowed");
+        anyhow::bail!("not a GET request: {:?}", line.trim());
+    }
+
+    let (mut host, mut origin, mut key, mut upgrade) = (None, None, None, false);
+    loop {
+        line.clear();
+        if reader
+            .read_line(&mut line)
+            .context("Failed to read headers")?
+            == 0
+        {
+            anyhow::bail!("request headers were cut off");
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match name.trim().to_lowercase().as_str() {
+            "host" => host = Some(value.to_lowercase()),
+            "origin" => origin = Some(value.to_lowercase()),
+            "sec-websocket-key" => key = Some(value.to_owned()),
+            "upgrade" => upgrade = value.eq_ignore_ascii_case("websocket"),
+            _ => {}
+        }
+    }
+
+    // Same DNS rebinding guard as the HTTP endpoint
+    let local_host = host.as_deref().is_some_and(|host| {
+        host == format!("127.0.0.1:{port}") || host == format!("localhost:{port}")
+    });
+    if !local_host {
+        refuse(stream, "403 Forbidden");
+        anyhow::bail!("not a localhost request");
+    }
+    // Any web page could otherwise read along with the dictation
+    if let Some(ref origin) = origin
+        && !local_origin(origin)
+    {
+        refuse(stream, "403 Forbidden");
+        anyhow::bail!("page from {origin} may not connect");
+    }
+    let (true, Some(key)) = (upgrade, key) else {
+        refuse(stream, "426 Upgrade Required");
+        anyhow::bail!("not a WebSocket upgrade");
+    };
+
+    let accept = base64(&sha1(format!("{key}{ACCEPT_GUID}").as_bytes()));
+    let response = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {accept}\r\n\r\n"
+    );
+    let mut stream = stream;
+    stream
+        .write_all(response.as_bytes())
+        .context("Failed to answer handshake")
+}
+
+/// Whether a page of `origin` runs on this machine, as local files and OBS pages do
+fn local_origin(origin: &str) -> bool {
+    if origin == "null" || origin.starts_with("file://") {
+        return true;
+    }
+    let Some((_, rest)) = origin.split_once("://") else {
+        return false;
+    };
+    let host = rest.rsplit_once(':').map_or(rest, |(host, _)| host);
+    matches!(host, "localhost" | "127.0.0.1" | "[::1]")
+}
+
+/// Answer a request that is not served with `status` and no body.
+fn refuse(mut stream: &TcpStream, status: &str) {
+    let response = format!("HTTP/1.1 {status}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+    let _ = stream.write_all(response.as_bytes());
+}
+
+/// Unmasked, unfragmented frame of `opcode` carrying `payload`
+fn frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(payload.len() + 10);
+    frame.push(0x80 | opcode);
+    match (u8::try_from(payload.len()), u16::try_from(payload.len())) {
+        (Ok(length), _) if length < 126 => frame.push(length),
+        (_, Ok(length)) => {
+            frame.push(126);
+            frame.extend_from_slice(&length.to_be_bytes());
+        }
+        _ => {
+            frame.push(127);
+            let length = u64::try_from(payload.len()).unwrap_or(u64::MAX);
+            frame.extend_from_slice(&length.to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// SHA-1 digest of `data`, needed only to answer the handshake
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [
+        0x6745_2301,
+        0xEFCD_AB89,
+        0x98BA_DCFE,
+        0x1032_5476,
+        0xC3D2_E1F0,
+    ];
+    let bits = u64::try_from(data.len())
+        .unwrap_or(u64::MAX)
+        .wrapping_mul(8);
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&bits.to_be_bytes());
+
+    for block in message.chunks_exact(64) {
+        let mut words = [0u32; 80];
+        for (word, bytes) in words.iter_mut().zip(block.chunks_exact(4)) {
+            *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        for i in 16..80 {
+            words[i] = (words[i - 3] ^ words[i - 8] ^ words[i - 14] ^ words[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (i, word) in words.into_iter().enumerate() {
+            let (f, k) = match i {
+                0..20 => ((b & c) | (!b & d), 0x5A82_7999),
+                20..40 => (b ^ c ^ d, 0x6ED9_EBA1),
+                40..60 => ((b & c) | (b & d) | (c & d), 0x8F1B_BCDC),
+                _ => (b ^ c ^ d, 0xCA62_C1D6),
+            };
+            let next = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(word);
+            (e, d, c, b, a) = (d, c, b.rotate_left(30), a, next);
+        }
+        for (value, add) in state.iter_mut().zip([a, b, c, d, e]) {
+            *value = value.wrapping_add(add);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (bytes, value) in digest.chunks_exact_mut(4).zip(state) {
+        bytes.copy_from_slice(&value.to_be_bytes());
+    }
+    digest
+}
+
+/// Standard base64 with padding
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+            group | u32::from(byte) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = usize::try_from(group >> (18 - 6 * i) & 0x3F).unwrap_or_default();
+                encoded.push(char::from(ALPHABET[index]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
@@ -0,0 +1,238 @@
+//! Command-line arguments and diagnostic subcommands.
+//!
+//! Flags override configuration values for a single session; subcommands run
+//! without the tray and print to the console of the shell that started them.
+
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+
+use crate::audio;
+use crate::config::Config;
+use crate::ipc::{self, IpcCommand};
+use crate::transcribe::{self, Output};
+
+/// Usage text printed by `help` and on invalid arguments
+const USAGE: &str = "\
+Usage: speedy-stt [COMMAND] [OPTIONS]
+
+Commands:
+  run            Start the tray app (default)
+  list-devices   Print input devices and their default formats
+  check          Validate the configuration and exit
+  send <COMMAND> Control the running instance: start, stop, cancel, status,
+                 set-language <CODE> or toggle-pause
+  transcribe <FILE>
+                 Transcribe a WAV, MP3, FLAC, Ogg or M4A file and print the text
+  help           Print this help
+
+Options:
+  --profile <NAME>        Settings profile from config.toml
+  --language <CODE>       Transcription language (e.g. de, auto)
+  --model <FILE>          Whisper model file name
+  --threads <N>           CPU threads for Whisper inference
+  --device <NAME>         Input device name
+  --volume-boost <X>      Microphone volume multiplier
+  --log-level <LEVEL>     trace | debug | info | warn | error
+  --set <KEY=VALUE>       Override any config.toml setting
+  --output <FILE>         transcribe: write the text to FILE instead
+  --srt <FILE>            transcribe: write SRT subtitles to FILE instead
+  --no-setup              Do not run the first-run setup when config.toml is missing
+  --version               Print the version";
+
+/// Flags that map directly onto a configuration setting
+const FLAG_SETTINGS: &[(&str, &str)] = &[
+    ("--profile", "active_profile"),
+    ("--language", "whisper_language"),
+    ("--model", "whisper_model"),
+    ("--threads", "whisper_threads"),
+    ("--device", "input_device"),
+    ("--volume-boost", "volume_boost"),
+    ("--log-level", "log_level"),
+];
+
+/// What the process was asked to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// Run the tray app
+    Run,
+    /// Print input devices
+    ListDevices,
+    /// Validate the configuration
+    Check,
+    /// Send a command to the running instance
+    Send,
+    /// Transcribe an audio file
+    Transcribe,
+    /// Print usage
+    Help,
+    /// Print the version
+    Version,
+}
+
+/// Parsed command line
+#[derive(Debug, Clone)]
+pub struct Cli {
+    /// Requested command
+    pub command: Command,
+    /// `(setting, value)` overrides in the order given
+    pub overrides: Vec<(String, String)>,
+    /// Whether to run the interactive setup when no config file exists
+    pub setup: bool,
+    /// Words after `send` or `transcribe`: the command for the running instance, or the file
+    pub arguments: Vec<String>,
+    /// Where `transcribe` writes its result
+    pub output: Output,
+}
+
+impl Cli {
+    /// Parse arguments, excluding the program name.
+    ///
+    /// Flags accept both `--flag value` and `--flag=value`.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut command = None;
+        let mut overrides = Vec::new();
+        let mut setup = true;
+        let mut arguments = Vec::new();
+        let mut output = Output::Stdout;
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => {
+                    (flag.to_owned(), Some(value.to_owned()))
+                }
+                _ => (arg.clone(), None),
+            };
+            let mut value = || {
+                inline_value
+                    .clone()
+                    .or_else(|| args.next())
+                    .with_context(|| format!("{flag} requires a value\n\n{USAGE}"))
+            };
+
+            match flag.as_str() {
+                "--help" | "-h" => command = Some(Command::Help),
+                "--version" | "-V" => command = Some(Command::Version),
+                "--no-setup" => setup = false,
+                "--output" | "--srt" if output != Output::Stdout => {
+                    anyhow::bail!("--output and --srt can only be given once\n\n{USAGE}")
+                }
+                "--output" => output = Output::Text(PathBuf::from(value()?)),
+                "--srt" => output = Output::Subtitles(PathBuf::from(value()?)),
+                "--set" => {
+                    let assignment = value()?;
+                    let (key, setting) = assignment
+                        .split_once('=')
+                        .with_context(|| format!("--set expects KEY=VALUE, got {assignment:?}"))?;
+                    overrides.push((key.trim().to_lowercase(), setting.to_owned()));
+                }
+                _ if flag.starts_with('-') => {
+                    let (_, setting) = FLAG_SETTINGS
+                        .iter()
+                        .find(|&&(name, _)| name == flag)
+                        .with_context(|| format!("Unknown option {flag}\n\n{USAGE}"))?;
+                    overrides.push(((*setting).to_owned(), value()?));
+                }
+                _ if matches!(command, Some(Command::Send | Command::Transcribe)) => {
+                    arguments.push(arg);
+                }
+                _ if command.is_some() => {
+                    anyhow::bail!("Unexpected argument {arg:?}\n\n{USAGE}")
+                }
+                "run" => command = Some(Command::Run),
+                "list-devices" => command = Some(Command::ListDevices),
+                "check" => command = Some(Command::Check),
+                "send" => command = Some(Command::Send),
+                "transcribe" => command = Some(Command::Transcribe),
+                "help" => command = Some(Command::Help),
+                _ => anyhow::bail!("Unknown command {arg:?}\n\n{USAGE}"),
+            }
+        }
+
+        Ok(Self {
+            command: command.unwrap_or(Command::Run),
+            overrides,
+            setup,
+            arguments,
+            output,
+        })
+    }
+
+    /// Run a console command; `Run` is handled by the caller.
+    pub fn run_console_command(&self) -> Result<()> {
+        attach_parent_console();
+        match self.command {
+            Command::Run => Ok(()),
+            Command::Help => {
+                println!("{USAGE}");
+                Ok(())
+            }
+            Command::Version => {
+                println!("speedy-stt {}", env!("CARGO_PKG_VERSION"));
+                Ok(())
+            }
+            Command::ListDevices => {
+                let devices = audio::describe_input_devices()?;
+                if devices.is_empty() {
+                    println!("No input devices found");
+                }
+                for device in devices {
+                    println!("{device}");
+                }
+                Ok(())
+            }
+            Command::Check => {
+                let config = Config::load(self.overrides.clone())?;
+                match config.file {
+                    Some(ref file) => println!("Configuration file: {}", file.display()),
+                    None => println!("No configuration file, using defaults"),
+                }
+                for warning in &config.warnings {
+                    println!("warning: {warning}");
+                }
+                println!("Configuration OK");
+                Ok(())
+            }
+            Command::Transcribe => {
+                let [ref input] = *self.arguments.as_slice() else {
+                    anyhow::bail!("transcribe expects one audio file\n\n{USAGE}");
+                };
+                let config = Config::load(self.overrides.clone())?;
+                transcribe::run(&config, input.as_ref(), &self.output)
+            }
+            Command::Send => {
+                let command: IpcCommand = self
+                    .arguments
+                    .join(" ")
+                    .parse()
+                    .with_context(|| format!("Invalid command\n\n{USAGE}"))?;
+                let response = ipc::send(&command)?;
+                println!(
+                    "{}",
+                    serde_json::to_string(&response).context("Failed to encode answer")?
+                );
+                if !response.ok {
+                    anyhow::bail!(
+                        "{}",
+                        response.error.as_deref().unwrap_or("the command failed")
+                    );
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Attach to the console of the parent shell so output from a GUI-subsystem
+/// binary is visible; does nothing when started without one.
+pub fn attach_parent_console() {
+    #[cfg(windows)]
+    // SAFETY: AttachConsole has no preconditions; failure (no parent console,
+    // or one already attached) is harmless and ignored.
+    unsafe {
+        let _ = windows::Win32::System::Console::AttachConsole(
+            windows::Win32::System::Console::ATTACH_PARENT_PROCESS,
+        );
+    }
+}
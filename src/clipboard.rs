@@ -0,0 +1,74 @@
+//! Clipboard access.
+//!
+//! Places Unicode text on the Windows clipboard using the raw Win32 API so
+//! no extra dependency is needed for this single operation.
+
+use anyhow::Result;
+
+#[cfg(windows)]
+use anyhow::Context;
+#[cfg(windows)]
+use windows::Win32::Foundation::{GlobalFree, HANDLE, HGLOBAL};
+#[cfg(windows)]
+use windows::Win32::System::DataExchange::{
+    CloseClipboard, EmptyClipboard, OpenClipboard, SetClipboardData,
+};
+#[cfg(windows)]
+use windows::Win32::System::Memory::{GMEM_MOVEABLE, GlobalAlloc, GlobalLock, GlobalUnlock};
+#[cfg(windows)]
+use windows::Win32::System::Ole::CF_UNICODETEXT;
+
+/// Replace the clipboard contents with the given text.
+#[cfg(windows)]
+pub fn set_text(text: &str) -> Result<()> {
+    let wide: Vec<u16> = text.encode_utf16().chain(std::iter::once(0)).collect();
+
+    // SAFETY: OpenClipboard with no owner window has no invariants; it is
+    // balanced by the CloseClipboard call below.
+    unsafe { OpenClipboard(None) }.context("Failed to open clipboard")?;
+    let result = write_clipboard(&wide);
+    // SAFETY: the clipboard was opened above by this thread.
+    let _ = unsafe { CloseClipboard() };
+    result
+}
+
+/// Copy a null-terminated UTF-16 buffer onto the already opened clipboard.
+#[cfg(windows)]
+fn write_clipboard(wide: &[u16]) -> Result<()> {
+    // SAFETY: the clipboard is open on this thread (see set_text).
+    unsafe { EmptyClipboard() }.context("Failed to empty clipboard")?;
+
+    // SAFETY: GlobalAlloc has no preconditions; the size covers the whole buffer.
+    let memory: HGLOBAL = unsafe { GlobalAlloc(GMEM_MOVEABLE, std::mem::size_of_val(wide)) }
+        .context("Failed to allocate clipboard memory")?;
+
+    // SAFETY: memory is a valid movable allocation owned by us until handed to the clipboard.
+    let target = unsafe { GlobalLock(memory) }.cast::<u16>();
+    if target.is_null() {
+        // SAFETY: memory was allocated above and not handed to the clipboard.
+        let _ = unsafe { GlobalFree(Some(memory)) };
+        anyhow::bail!("Failed to lock clipboard memory");
+    }
+    // SAFETY: target points to an allocation of exactly wide.len() u16 values
+    // and cannot overlap the source slice.
+    unsafe { std::ptr::copy_nonoverlapping(wide.as_ptr(), target, wide.len()) };
+    // SAFETY: balances the GlobalLock above; a zero lock count is reported as an
+    // "error" with no error code, which is the expected outcome here.
+    let _ = unsafe { GlobalUnlock(memory) };
+
+    // SAFETY: on success the clipboard takes ownership of the memory handle.
+    if let Err(e) = unsafe { SetClipboardData(u32::from(CF_UNICODETEXT.0), Some(HANDLE(memory.0))) }
+    {
+        // SAFETY: ownership was not transferred, so the allocation is still ours.
+        let _ = unsafe { GlobalFree(Some(memory)) };
+        return Err(e).context("Failed to set clipboard data");
+    }
+
+    Ok(())
+}
+
+/// Clipboard access is only implemented on Windows.
+#[cfg(not(windows))]
+pub fn set_text(_text: &str) -> Result<()> {
+    anyhow::bail!("Clipboard access is not supported on this platform")
+}
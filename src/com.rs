@@ -0,0 +1,65 @@
+//! COM apartment setup for threads that talk to Windows audio and WinRT APIs.
+//!
+//! Each such thread enters an apartment once and keeps the guard alive for as
+//! long as it holds COM objects; dropping the guard uninitializes COM again.
+//! Threads that only call out, such as the ducking worker, join the
+//! multithreaded apartment; those whose callers expect an STA enter their own.
+
+use anyhow::{Context, Result};
+use tracing::info;
+use windows::Win32::System::Com::{
+    COINIT, COINIT_APARTMENTTHREADED, COINIT_MULTITHREADED, CoInitializeEx, CoUninitialize,
+};
+use windows::core::HRESULT;
+
+/// `RPC_E_CHANGED_MODE`: COM is already initialized on this thread with a
+/// different apartment model. The thread is usable; we must not uninitialize.
+const RPC_E_CHANGED_MODE: HRESULT = HRESULT(0x80010106_u32 as i32);
+
+/// COM initialization of the calling thread, undone on drop.
+pub struct ComApartment {
+    /// Whether CoInitializeEx succeeded and must be balanced by CoUninitialize
+    initialized: bool,
+}
+
+impl ComApartment {
+    /// Initialize COM for the calling thread in a single-threaded apartment.
+    pub fn enter() -> Result<Self> {
+        Self::enter_with(COINIT_APARTMENTTHREADED)
+    }
+
+    /// Initialize COM for the calling thread in the multithreaded apartment.
+    ///
+    /// For worker threads without a message loop: an STA would need one to
+    /// receive calls from other apartments, such as device notifications.
+    pub fn enter_multithreaded() -> Result<Self> {
+        Self::enter_with(COINIT_MULTITHREADED)
+    }
+
+    /// Initialize COM for the calling thread with the apartment model `model`
+    fn enter_with(model: COINIT) -> Result<Self> {
+        // SAFETY: CoInitializeEx initializes COM for this thread. S_OK means we
+        // initialized it fresh; S_FALSE means already initialized with the same
+        // apartment (both require a matching CoUninitialize). RPC_E_CHANGED_MODE
+        // means the thread already has COM in a different apartment — we can still
+        // use COM but must not call CoUninitialize since we did not initialize it.
+        let com_hr = unsafe { CoInitializeEx(None, model) };
+        info!("CoInitializeEx HRESULT: {:?}", com_hr);
+        if com_hr == RPC_E_CHANGED_MODE {
+            info!("COM already initialized with different apartment; proceeding without re-init");
+            return Ok(Self { initialized: false });
+        }
+        com_hr.ok().context("Failed to initialize COM")?;
+        Ok(Self { initialized: true })
+    }
+}
+
+impl Drop for ComApartment {
+    fn drop(&mut self) {
+        // SAFETY: Balances the CoInitializeEx call in enter(), but only when we
+        // actually initialized COM (not when RPC_E_CHANGED_MODE was returned).
+        if self.initialized {
+            unsafe { CoUninitialize() };
+        }
+    }
+}
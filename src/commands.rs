@@ -0,0 +1,249 @@
+//! Spoken commands.
+//!
+//! A transcription that consists of nothing but a configured phrase runs an
+//! action instead of being typed. Only whole utterances match, optionally
+//! allowing a few misheard characters, so a sentence that merely contains a
+//! phrase is typed as usual.
+
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+
+use crate::whisper::LanguageCode;
+
+/// What a spoken command does
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandAction {
+    /// Ignore the hotkeys until resumed from the tray
+    Pause,
+    /// Transcribe in this language from now on
+    Language(LanguageCode),
+    /// Discard the dictation without typing anything
+    Cancel,
+}
+
+impl FromStr for CommandAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim().to_lowercase();
+        match s.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["pause"] => Ok(Self::Pause),
+            ["cancel"] => Ok(Self::Cancel),
+            ["language", code] => Ok(Self::Language(code.parse()?)),
+            _ => anyhow::bail!("unknown action {s:?}, use pause, cancel or language <code>"),
+        }
+    }
+}
+
+impl fmt::Display for CommandAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Pause => write!(f, "pause"),
+            Self::Language(ref code) => write!(f, "language {code}"),
+            Self::Cancel => write!(f, "cancel"),
+        }
+    }
+}
+
+/// A phrase and the action it triggers, written `phrase = action`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VoiceCommand {
+    /// Normalized phrase the whole utterance is compared with
+    phrase: String,
+    /// Action run when it matches
+    pub action: CommandAction,
+}
+
+impl FromStr for VoiceCommand {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (phrase, action) = s.split_once('=').context("expected \"phrase = action\"")?;
+        let phrase = normalize(phrase);
+        if phrase.is_empty() {
+            anyhow::bail!("the phrase is empty");
+        }
+        Ok(Self {
+            phrase,
+            action: action.parse()?,
+        })
+    }
+}
+
+/// Command whose phrase is the whole of `text`, allowing up to `max_edits`
+/// misheard characters; the closest one wins.
+pub fn find<'a>(
+    commands: &'a [VoiceCommand],
+    text: &str,
+    max_edits: usize,
+) -> Option<&'a VoiceCommand> {
+    let utterance = normalize(text);
+    if utterance.is_empty() {
+        return None;
+    }
+    commands
+        .iter()
+        .map(|command| (edit_distance(&command.phrase, &utterance), command))
+        .filter(|&(distance, command)| {
+            // Short phrases are easily confused with ordinary words
+            distance <= max_edits && distance * 4 <= command.phrase.chars().count()
+        })
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, command)| command)
+}
+
+/// Lowercase words separated by single spaces, without punctuation
+pub fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .filter(|&c| c != '\'' && c != '\u{2019}')
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Levenshtein distance between `a` and `b`, counted in characters
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = Vec::with_capacity(b.len() + 1);
+        current.push(i + 1);
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Commands parsed from `phrase = action` entries
+    fn commands(entries: &[&str]) -> Vec<VoiceCommand> {
+        entries
+            .iter()
+            .map(|entry| entry.parse().expect("valid command"))
+            .collect()
+    }
+
+    /// Phrase of the command `text` runs with `max_edits`, `None` for none
+    fn found(commands: &[VoiceCommand], text: &str, max_edits: usize) -> Option<String> {
+        find(commands, text, max_edits).map(|command| command.phrase.clone())
+    }
+
+    #[test]
+    fn edit_distance_counts_single_character_edits() {
+        assert_eq!(edit_distance("pause", "pause"), 0, "identical");
+        assert_eq!(edit_distance("", "abc"), 3, "insertions into nothing");
+        assert_eq!(edit_distance("abc", ""), 3, "deletions down to nothing");
+        assert_eq!(edit_distance("pause", "paus"), 1, "deletion");
+        assert_eq!(edit_distance("pause", "pauses"), 1, "insertion");
+        assert_eq!(edit_distance("pause", "pouse"), 1, "substitution");
+        assert_eq!(edit_distance("kitten", "sitting"), 3, "mixed edits");
+        assert_eq!(edit_distance("sitting", "kitten"), 3, "symmetric");
+        assert_eq!(edit_distance("café", "cafe"), 1, "counted in characters");
+    }
+
+    #[test]
+    fn an_exact_phrase_matches_regardless_of_case_and_punctuation() {
+        let commands = commands(&["pause dictation = pause"]);
+        assert_eq!(
+            found(&commands, " Pause, dictation! ", 0),
+            Some("pause dictation".to_owned()),
+            "exact phrase not found"
+        );
+    }
+
+    #[test]
+    fn a_near_miss_matches_up_to_max_edits() {
+        let commands = commands(&["pause dictation = pause"]);
+        assert_eq!(
+            found(&commands, "pause dictations", 1),
+            Some("pause dictation".to_owned()),
+            "one misheard character within one edit"
+        );
+        assert_eq!(
+            found(&commands, "pause dictations", 0),
+            None,
+            "one misheard character with exact matching"
+        );
+        assert_eq!(
+            found(&commands, "paws dictations", 3),
+            Some("pause dictation".to_owned()),
+            "three edits within three"
+        );
+        assert_eq!(
+            found(&commands, "paws dictations", 2),
+            None,
+            "three edits within two"
+        );
+    }
+
+    #[test]
+    fn short_phrases_allow_an_edit_per_four_characters() {
+        let commands = commands(&["stop = cancel", "go = pause", "scratch = cancel"]);
+        assert_eq!(
+            found(&commands, "stap", 3),
+            Some("stop".to_owned()),
+            "one edit in four characters"
+        );
+        assert_eq!(
+            found(&commands, "so", 3),
+            None,
+            "one edit in two characters"
+        );
+        assert_eq!(
+            found(&commands, "scratc", 3),
+            Some("scratch".to_owned()),
+            "one edit in seven characters"
+        );
+        assert_eq!(
+            found(&commands, "scrach", 3),
+            Some("scratch".to_owned()),
+            "one edit in seven characters"
+        );
+        assert_eq!(
+            found(&commands, "skrach", 3),
+            None,
+            "two edits in seven characters"
+        );
+    }
+
+    #[test]
+    fn the_closest_phrase_wins() {
+        let commands = commands(&["cancel that = cancel", "cancel this = pause"]);
+        assert_eq!(
+            found(&commands, "cancel thiss", 2),
+            Some("cancel this".to_owned()),
+            "farther phrase chosen"
+        );
+        assert_eq!(
+            found(&commands, "cancel that", 2),
+            Some("cancel that".to_owned()),
+            "exact phrase lost to a near one"
+        );
+    }
+
+    #[test]
+    fn only_whole_utterances_match() {
+        let commands = commands(&["pause dictation = pause"]);
+        assert_eq!(
+            found(&commands, "please pause dictation now", 3),
+            None,
+            "sentence containing the phrase matched"
+        );
+        assert_eq!(
+            found(&commands, " ... ", 3),
+            None,
+            "empty utterance matched"
+        );
+    }
+}
@@ -1,57 +1,1190 @@
-//! Configuration loading from .env file
+//! Configuration loading from `config.toml` with environment overrides.
+//!
+//! Every setting has a default except the Whisper model. Values are looked up
+//! on the command line, then in the environment (including a legacy `.env`
+//! file), then in the config file, so existing `.env` setups keep working.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 use anyhow::{Context, Result};
 
-/// Application configuration loaded from .env
+use crate::commands::VoiceCommand;
+use crate::duplicate::DuplicateAction;
+use crate::feedback::FeedbackStyle;
+use crate::filter::FilterAction;
+use crate::foreground::RefocusFallback;
+use crate::history::{HistoryDetail, HistoryFormat};
+use crate::hotkey::{HeldAtStartup, HotkeyModifier, KeyName};
+use crate::logging::LogLevel;
+use crate::models::ModelRule;
+use crate::output::OutputMode;
+use crate::overlay::{DEFAULT_MARGIN, OverlayCorner, OverlayMonitor};
+use crate::paths;
+use crate::pipeline::StageKind;
+use crate::privacy::PrivacyMode;
+use crate::spacing;
+use crate::speech;
+use crate::state;
+use crate::template::InjectTemplate;
+use crate::volume::{DuckMode, FadeCurve};
+use crate::webhook::WebhookUrl;
+use crate::whisper::{LanguageCode, WhisperEngine, WhisperOptions};
+
+/// Largest accepted microphone volume multiplier
+const MAX_VOLUME_BOOST: f32 = 20.0;
+
+/// Longer model unload delays are allowed but reported as likely mistakes
+const MAX_SANE_DELAY_SECS: u64 = 86_400;
+
+/// Longest accepted ducking fade in milliseconds
+const MAX_FADE_MS: u64 = 10_000;
+
+/// Shortest pause that reliably separates sentences rather than words
+const MIN_PUNCTUATION_PAUSE_MS: u64 = 300;
+
+/// Commented default configuration written on first run
+const DEFAULT_CONFIG: &str = include_str!("../config.example.toml");
+
+/// File name of the configuration file
+const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// Profile name that selects the settings outside any `[profile.<name>]` section
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Application configuration loaded from `config.toml` and the environment
 #[derive(Debug, Clone)]
 #[allow(missing_docs)]
 pub struct Config {
     pub volume_boost: f32,
     pub whisper_model: String,
-    pub whisper_language: String,
+    pub auto_model: Vec<ModelRule>,
+    pub whisper_language: LanguageCode,
+    pub secondary_language: Option<LanguageCode>,
     pub whisper_threads: usize,
-    pub hotkey_modifier: String,
-    pub hotkey_key: String,
+    pub whisper_use_gpu: bool,
+    pub whisper_gpu_device: u32,
+    pub whisper_flash_attn: bool,
+    pub whisper_dump_tokens_dir: Option<String>,
+    pub hotkey_modifier: HotkeyModifier,
+    pub hotkey_key: KeyName,
     pub enable_sound_feedback: bool,
+    pub feedback_style: FeedbackStyle,
+    pub feedback_volume: f32,
+    pub feedback_output_device: Option<String>,
+    pub mute_feedback_while_ducked: bool,
+    pub enable_readback: bool,
+    pub readback_rate: f64,
+    pub readback_max_chars: usize,
+    pub assets_dir: Option<String>,
+    pub icon_idle: Option<String>,
+    pub icon_recording: Option<String>,
+    pub feedback_sound_start: Option<String>,
+    pub feedback_sound_finish: Option<String>,
+    pub feedback_sound_cancelled: Option<String>,
+    pub feedback_sound_error: Option<String>,
+    pub feedback_sound_empty: Option<String>,
+    pub feedback_sound_auto_stop: Option<String>,
     pub log_to_file: bool,
-    pub log_level: String,
+    pub log_level: LogLevel,
+    pub log_max_size_mb: u64,
+    pub log_keep_files: usize,
     pub model_unload_delay_secs: u64,
+    pub preload_model: bool,
+    pub keep_model_loaded: bool,
+    pub force_model_load: bool,
+    pub recording_stop_timeout_secs: u64,
+    pub recording_spill_mb: u64,
+    pub model_load_timeout_secs: u64,
+    pub hotkey_latch_key: Option<KeyName>,
+    pub translate_hotkey_modifier: HotkeyModifier,
+    pub translate_hotkey_key: Option<KeyName>,
+    pub latch_max_secs: u64,
+    pub auto_stop_silence_ms: u64,
+    pub auto_stop_threshold_db: f32,
+    pub release_failsafe_ms: u64,
+    pub hotkey_held_at_startup: HeldAtStartup,
+    pub streaming_interval_secs: u64,
+    pub hotkey_disabled_in: Vec<String>,
+    pub language_choices: Vec<String>,
+    pub no_space_languages: Vec<String>,
+    pub punctuation_pause_ms: u64,
+    pub hotkey_disabled_sound: bool,
+    pub keep_last_transcription: bool,
+    pub resend_keep_secs: u64,
+    pub crash_recovery: bool,
+    pub privacy_mode: PrivacyMode,
+    pub refocus_fallback: RefocusFallback,
+    pub inject_target_window: Option<String>,
+    pub output_mode: OutputMode,
+    pub output_file: Option<String>,
+    pub output_template: String,
+    pub inject_template: Option<InjectTemplate>,
+    pub app_inject_templates: Vec<(String, Option<InjectTemplate>)>,
+    pub voice_commands: Vec<VoiceCommand>,
+    pub voice_command_max_edits: usize,
+    pub correction_trigger: Option<String>,
+    pub duplicate_window_secs: u64,
+    pub duplicate_similarity: f64,
+    pub duplicate_action: DuplicateAction,
+    pub word_filter_file: Option<String>,
+    pub word_filter_action: FilterAction,
+    pub text_pipeline: Vec<StageKind>,
+    pub enable_notifications: bool,
+    pub honor_quiet_mode: bool,
+    pub quiet_mode_plays_cues: bool,
+    pub enable_overlay: bool,
+    pub enable_ipc: bool,
+    pub http_port: u16,
+    pub http_token: Option<String>,
+    pub caption_port: u16,
+    pub overlay_corner: OverlayCorner,
+    pub overlay_monitor: OverlayMonitor,
+    pub overlay_margin: u32,
+    pub input_device: Option<String>,
+    pub stats_daily_rollup: bool,
+    pub history_file: Option<String>,
+    pub history_format: HistoryFormat,
+    pub history_detail: HistoryDetail,
+    pub history_daily: bool,
+    pub history_max_days: u64,
+    pub webhook_url: Option<WebhookUrl>,
+    pub webhook_include_text: bool,
+    pub webhook_retry: bool,
+    pub enable_audio_ducking: bool,
+    pub duck_mode: DuckMode,
+    pub duck_level: f32,
+    pub duck_fade_ms: u64,
+    pub duck_restore_fade_ms: u64,
+    pub duck_fade_curve: FadeCurve,
+    pub duck_exclude_processes: Vec<String>,
+    pub duck_only_playing: bool,
+    /// Active `[profile.<name>]` section, `None` for the defaults
+    pub profile: Option<String>,
+    /// Profiles defined in the config file
+    pub profiles: Vec<String>,
+    /// Config file the values were read from, `None` when running on defaults
+    pub file: Option<PathBuf>,
+    /// Non-fatal problems found while validating, to be logged once logging is set up
+    pub warnings: Vec<String>,
+    /// `(setting, value)` pairs from the command line, re-applied on every reload
+    pub overrides: Vec<(String, String)>,
 }
 
 impl Config {
-    /// Load configuration from .env file
-    pub fn load() -> Result<Self> {
-        dotenvy::dotenv().context(
-            "Missing .env file. Copy .env.example to .env and fill in the required values",
-        )?;
-
-        Ok(Self {
-            volume_boost: Self::get_env("VOLUME_BOOST")?
-                .parse()
-                .context("Invalid VOLUME_BOOST")?,
-            whisper_model: Self::get_env("WHISPER_MODEL")?,
-            whisper_language: Self::get_env("WHISPER_LANGUAGE")?,
-            whisper_threads: Self::get_env("WHISPER_THREADS")?
-                .parse()
-                .context("Invalid WHISPER_THREADS")?,
-            hotkey_modifier: Self::get_env("HOTKEY_MODIFIER")?,
-            hotkey_key: Self::get_env("HOTKEY_KEY")?,
-            enable_sound_feedback: Self::get_env("ENABLE_SOUND_FEEDBACK")?
-                .parse()
-                .context("Invalid ENABLE_SOUND_FEEDBACK")?,
-            log_to_file: Self::get_env("LOG_TO_FILE")?
-                .parse()
-                .context("Invalid LOG_TO_FILE")?,
-            log_level: Self::get_env("LOG_LEVEL")?,
-            model_unload_delay_secs: Self::get_env("MODEL_UNLOAD_DELAY_SECS")?
-                .parse()
-                .context("Invalid MODEL_UNLOAD_DELAY_SECS")?,
+    /// Load configuration, writing a default `config.toml` on first run
+    ///
+    /// `overrides` are `(setting, value)` pairs that take precedence over every other source.
+    pub fn load(overrides: Vec<(String, String)>) -> Result<Self> {
+        let env_path = paths::resolve(".env");
+        if env_path.exists() {
+            dotenvy::from_path(&env_path)
+                .with_context(|| format!("Failed to read {}", env_path.display()))?;
+        }
+
+        // A default file that cannot be written is not fatal; the defaults
+        // still apply and the missing model is reported below
+        let file = Self::find_file().or_else(|| Self::write_default_file().ok());
+        Self::from_sources(Sources::read(file, overrides)?)
+    }
+
+    /// Re-read the config file and `.env`, replacing values loaded by a previous call
+    pub fn reload(&self) -> Result<Self> {
+        let env_path = paths::resolve(".env");
+        if env_path.exists() {
+            dotenvy::from_path_override(&env_path)
+                .with_context(|| format!("Failed to read {}", env_path.display()))?;
+        }
+
+        Self::from_sources(Sources::read(Self::find_file(), self.overrides.clone())?)
+    }
+
+    /// Files whose modification marks a configuration change
+    pub fn watched_files() -> Vec<PathBuf> {
+        let mut files = vec![paths::resolve(".env")];
+        files.extend(Self::find_file());
+        files
+    }
+
+    /// Human-readable `setting: old -> new` lines for every value that differs in `new`
+    pub fn changes(&self, new: &Self) -> Vec<String> {
+        let mut changes = Vec::new();
+        macro_rules! compare {
+            ($($field:ident),* $(,)?) => {
+                $(
+                    if self.$field != new.$field {
+                        changes.push(format!(
+                            "{}: {:?} -> {:?}",
+                            stringify!($field),
+                            self.$field,
+                            new.$field
+                        ));
+                    }
+                )*
+            };
+        }
+        compare!(
+            volume_boost,
+            whisper_model,
+            auto_model,
+            whisper_language,
+            secondary_language,
+            whisper_threads,
+            whisper_use_gpu,
+            whisper_gpu_device,
+            whisper_flash_attn,
+            whisper_dump_tokens_dir,
+            hotkey_modifier,
+            hotkey_key,
+            enable_sound_feedback,
+            feedback_style,
+            feedback_volume,
+            feedback_output_device,
+            mute_feedback_while_ducked,
+            enable_readback,
+            readback_rate,
+            readback_max_chars,
+            assets_dir,
+            icon_idle,
+            icon_recording,
+            feedback_sound_start,
+            feedback_sound_finish,
+            feedback_sound_cancelled,
+            feedback_sound_error,
+            feedback_sound_empty,
+            feedback_sound_auto_stop,
+            log_to_file,
+            log_level,
+            log_max_size_mb,
+            log_keep_files,
+            model_unload_delay_secs,
+            preload_model,
+            keep_model_loaded,
+            force_model_load,
+            recording_stop_timeout_secs,
+            recording_spill_mb,
+            model_load_timeout_secs,
+            hotkey_latch_key,
+            translate_hotkey_modifier,
+            translate_hotkey_key,
+            latch_max_secs,
+            auto_stop_silence_ms,
+            auto_stop_threshold_db,
+            release_failsafe_ms,
+            hotkey_held_at_startup,
+            streaming_interval_secs,
+            hotkey_disabled_in,
+            language_choices,
+            no_space_languages,
+            punctuation_pause_ms,
+            hotkey_disabled_sound,
+            keep_last_transcription,
+            resend_keep_secs,
+            crash_recovery,
+            privacy_mode,
+            refocus_fallback,
+            inject_target_window,
+            output_mode,
+            output_file,
+            output_template,
+            inject_template,
+            app_inject_templates,
+            voice_commands,
+            voice_command_max_edits,
+            correction_trigger,
+            duplicate_window_secs,
+            duplicate_similarity,
+            duplicate_action,
+            word_filter_file,
+            word_filter_action,
+            text_pipeline,
+            enable_notifications,
+            honor_quiet_mode,
+            quiet_mode_plays_cues,
+            enable_overlay,
+            enable_ipc,
+            http_port,
+            http_token,
+            caption_port,
+            overlay_corner,
+            overlay_monitor,
+            overlay_margin,
+            input_device,
+            stats_daily_rollup,
+            history_file,
+            history_format,
+            history_detail,
+            history_daily,
+            history_max_days,
+            webhook_url,
+            webhook_include_text,
+            webhook_retry,
+            enable_audio_ducking,
+            duck_mode,
+            duck_level,
+            duck_fade_ms,
+            duck_restore_fade_ms,
+            duck_fade_curve,
+            duck_exclude_processes,
+            duck_only_playing,
+            profile,
+            profiles,
+            file,
+        );
+        changes
+    }
+
+    /// First existing config file: next to the executable, then in the user config directory
+    fn find_file() -> Option<PathBuf> {
+        let portable = paths::resolve(CONFIG_FILE_NAME);
+        if portable.exists() {
+            return Some(portable);
+        }
+        paths::config_dir()
+            .map(|dir| dir.join(CONFIG_FILE_NAME))
+            .filter(|path| path.exists())
+    }
+
+    /// Write the commented default config, preferring the user config directory
+    fn write_default_file() -> Result<PathBuf> {
+        let path = paths::config_dir().map_or_else(
+            || paths::resolve(CONFIG_FILE_NAME),
+            |dir| dir.join(CONFIG_FILE_NAME),
+        );
+        paths::ensure_parent(&path)?;
+        std::fs::write(&path, DEFAULT_CONFIG)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(path)
+    }
+
+    /// Configuration of the command-line `overrides` alone, without a config
+    /// or state file
+    #[cfg(test)]
+    pub fn from_overrides(overrides: Vec<(String, String)>) -> Result<Self> {
+        Self::from_sources(Sources::overridden(overrides))
+    }
+
+    /// Build the configuration from the environment and config file
+    fn from_sources(mut sources: Sources) -> Result<Self> {
+        let config = Self {
+            volume_boost: sources.get("volume_boost", 1.0),
+            whisper_model: sources.get_required("whisper_model"),
+            auto_model: sources.get_parsed_list("auto_model"),
+            whisper_language: sources.get("whisper_language", LanguageCode::default()),
+            secondary_language: sources.get_optional_parsed("secondary_language"),
+            whisper_threads: sources.get("whisper_threads", 0),
+            whisper_use_gpu: sources.get("whisper_use_gpu", WhisperOptions::gpu_built()),
+            whisper_gpu_device: sources.get("whisper_gpu_device", 0),
+            whisper_flash_attn: sources.get("whisper_flash_attn", false),
+            whisper_dump_tokens_dir: sources.get_optional("whisper_dump_tokens_dir"),
+            hotkey_modifier: sources.get("hotkey_modifier", HotkeyModifier::CTRL),
+            hotkey_key: sources.get("hotkey_key", KeyName::SPACE),
+            enable_sound_feedback: sources.get("enable_sound_feedback", true),
+            feedback_style: sources.get("feedback_style", FeedbackStyle::Files),
+            feedback_volume: sources.get("feedback_volume", 1.0),
+            feedback_output_device: sources.get_optional("feedback_output_device"),
+            mute_feedback_while_ducked: sources.get("mute_feedback_while_ducked", false),
+            enable_readback: sources.get("enable_readback", false),
+            readback_rate: sources.get("readback_rate", 1.0),
+            readback_max_chars: sources.get("readback_max_chars", 300),
+            assets_dir: sources.get_optional("assets_dir"),
+            icon_idle: sources.get_optional("icon_idle"),
+            icon_recording: sources.get_optional("icon_recording"),
+            feedback_sound_start: sources.get_optional("feedback_sound_start"),
+            feedback_sound_finish: sources.get_optional("feedback_sound_finish"),
+            feedback_sound_cancelled: sources.get_optional("feedback_sound_cancelled"),
+            feedback_sound_error: sources.get_optional("feedback_sound_error"),
+            feedback_sound_empty: sources.get_optional("feedback_sound_empty"),
+            feedback_sound_auto_stop: sources.get_optional("feedback_sound_auto_stop"),
+            log_to_file: sources.get("log_to_file", true),
+            log_level: sources.get("log_level", LogLevel::Info),
+            log_max_size_mb: sources.get("log_max_size_mb", 10),
+            log_keep_files: sources.get("log_keep_files", 7),
+            model_unload_delay_secs: sources.get("model_unload_delay_secs", 15),
+            preload_model: sources.get("preload_model", false),
+            keep_model_loaded: sources.get("keep_model_loaded", false),
+            force_model_load: sources.get("force_model_load", false),
+            recording_stop_timeout_secs: sources.get("recording_stop_timeout_secs", 5),
+            recording_spill_mb: sources.get("recording_spill_mb", 64),
+            model_load_timeout_secs: sources.get("model_load_timeout_secs", 120),
+            hotkey_latch_key: sources.get_optional_parsed("hotkey_latch_key"),
+            translate_hotkey_modifier: sources
+                .get("translate_hotkey_modifier", HotkeyModifier::NONE),
+            translate_hotkey_key: sources.get_optional_parsed("translate_hotkey_key"),
+            latch_max_secs: sources.get("latch_max_secs", 600),
+            auto_stop_silence_ms: sources.get("auto_stop_silence_ms", 0),
+            auto_stop_threshold_db: sources.get("auto_stop_threshold_db", -45.0),
+            release_failsafe_ms: sources.get("release_failsafe_ms", 750),
+            hotkey_held_at_startup: sources.get("hotkey_held_at_startup", HeldAtStartup::Ignore),
+            streaming_interval_secs: sources.get("streaming_interval_secs", 0),
+            hotkey_disabled_in: sources.get_list("hotkey_disabled_in"),
+            language_choices: sources.get_list("language_choices"),
+            no_space_languages: sources
+                .get_list_or("no_space_languages", spacing::DEFAULT_NO_SPACE_LANGUAGES),
+            punctuation_pause_ms: sources.get("punctuation_pause_ms", 0),
+            hotkey_disabled_sound: sources.get("hotkey_disabled_sound", false),
+            keep_last_transcription: sources.get("keep_last_transcription", true),
+            resend_keep_secs: sources.get("resend_keep_secs", 600),
+            crash_recovery: sources.get("crash_recovery", false),
+            privacy_mode: sources.get("privacy_mode", PrivacyMode::Normal),
+            refocus_fallback: sources.get("refocus_fallback", RefocusFallback::Type),
+            inject_target_window: sources
+                .get_optional("inject_target_window")
+                .filter(|target| !target.trim().is_empty()),
+            output_mode: sources.get("output_mode", OutputMode::Inject),
+            output_file: sources.get_optional("output_file"),
+            output_template: sources
+                .get_optional("output_template")
+                .unwrap_or_else(|| "{text}\n".to_owned()),
+            inject_template: sources.get_optional_untrimmed("inject_template"),
+            app_inject_templates: sources.get_per_app("inject_template"),
+            voice_commands: sources.get_parsed_list("voice_commands"),
+            voice_command_max_edits: sources.get("voice_command_max_edits", 0),
+            correction_trigger: sources
+                .get_optional("correction_trigger")
+                .map(|trigger| trigger.trim().to_lowercase())
+                .filter(|trigger| !trigger.is_empty()),
+            duplicate_window_secs: sources.get("duplicate_window_secs", 0),
+            duplicate_similarity: sources.get("duplicate_similarity", 0.9),
+            duplicate_action: sources.get("duplicate_action", DuplicateAction::Skip),
+            word_filter_file: sources.get_optional("word_filter_file"),
+            word_filter_action: sources.get("word_filter_action", FilterAction::Mask),
+            text_pipeline: if sources.get_optional("text_pipeline").is_some() {
+                sources.get_parsed_list("text_pipeline")
+            } else {
+                StageKind::DEFAULT_ORDER.to_vec()
+            },
+            enable_notifications: sources.get("enable_notifications", true),
+            honor_quiet_mode: sources.get("honor_quiet_mode", true),
+            quiet_mode_plays_cues: sources.get("quiet_mode_plays_cues", true),
+            enable_overlay: sources.get("enable_overlay", false),
+            enable_ipc: sources.get("enable_ipc", true),
+            http_port: sources.get("http_port", 0),
+            http_token: sources.get_optional("http_token"),
+            caption_port: sources.get("caption_port", 0),
+            overlay_corner: sources.get("overlay_corner", OverlayCorner::TopRight),
+            overlay_monitor: sources.get("overlay_monitor", OverlayMonitor::Active),
+            overlay_margin: sources.get("overlay_margin", DEFAULT_MARGIN),
+            input_device: sources.get_optional("input_device"),
+            stats_daily_rollup: sources.get("stats_daily_rollup", false),
+            history_file: sources.get_optional("history_file"),
+            history_format: sources.get("history_format", HistoryFormat::Jsonl),
+            history_detail: sources.get("history_detail", HistoryDetail::FullText),
+            history_daily: sources.get("history_daily", false),
+            history_max_days: sources.get("history_max_days", 0),
+            webhook_url: sources.get_optional_parsed("webhook_url"),
+            webhook_include_text: sources.get("webhook_include_text", true),
+            webhook_retry: sources.get("webhook_retry", true),
+            enable_audio_ducking: sources.get("enable_audio_ducking", true),
+            duck_mode: sources.get("duck_mode", DuckMode::Duck),
+            duck_level: sources.get("duck_level", 0.0),
+            duck_fade_ms: sources.get("duck_fade_ms", 500),
+            duck_restore_fade_ms: sources.get("duck_restore_fade_ms", 500),
+            duck_fade_curve: sources.get("duck_fade_curve", FadeCurve::Linear),
+            duck_exclude_processes: sources.get_list("duck_exclude_processes"),
+            duck_only_playing: sources.get("duck_only_playing", true),
+            profile: sources.active_profile.clone(),
+            profiles: sources.profile_names(),
+            file: sources.file.as_ref().map(|(path, _)| path.clone()),
+            warnings: Vec::new(),
+            overrides: sources.overrides.clone(),
+        };
+        config.validated(sources.errors, sources.warnings)
+    }
+
+    /// Template for text typed into the executable `app`, from its
+    /// `[app.<exe>]` section if it has one
+    pub fn inject_template_for(&self, app: Option<&str>) -> Option<&InjectTemplate> {
+        app.and_then(|app| {
+            self.app_inject_templates
+                .iter()
+                .find(|entry| entry.0.eq_ignore_ascii_case(app))
         })
+        .map_or(self.inject_template.as_ref(), |entry| entry.1.as_ref())
+    }
+
+    /// Context options Whisper models are loaded with
+    pub const fn whisper_options(&self) -> WhisperOptions {
+        WhisperOptions {
+            use_gpu: self.whisper_use_gpu,
+            gpu_device: self.whisper_gpu_device,
+            flash_attn: self.whisper_flash_attn,
+        }
+    }
+
+    /// Check ranges and formats of every setting, adding to the problems found while reading.
+    ///
+    /// Fails with all problems listed at once; non-fatal issues become warnings.
+    fn validated(mut self, mut errors: Vec<String>, mut warnings: Vec<String>) -> Result<Self> {
+        if !(self.volume_boost > 0.0 && self.volume_boost <= MAX_VOLUME_BOOST) {
+            errors.push(format!(
+                "volume_boost must be greater than 0 and at most {MAX_VOLUME_BOOST}, got {}",
+                self.volume_boost
+            ));
+        }
+
+        if !(0.0..=1.0).contains(&self.feedback_volume) {
+            errors.push(format!(
+                "feedback_volume must be between 0.0 and 1.0, got {}",
+                self.feedback_volume
+            ));
+        }
+
+        if !speech::RATE_RANGE.contains(&self.readback_rate) {
+            errors.push(format!(
+                "readback_rate must be between {} and {}, got {}",
+                speech::RATE_RANGE.start(),
+                speech::RATE_RANGE.end(),
+                self.readback_rate
+            ));
+        }
+        if self.output_mode.writes_file() && self.output_file.is_none() {
+            errors.push("output_mode file and both need output_file to be set".to_owned());
+        }
+        if self.output_mode.writes_file() && !self.output_template.contains("{text}") {
+            warnings.push("output_template has no {text} placeholder".to_owned());
+        }
+        if self.caption_port != 0 && self.caption_port == self.http_port {
+            errors.push(format!(
+                "caption_port and http_port must differ, both are {}",
+                self.caption_port
+            ));
+        }
+        if self.whisper_dump_tokens_dir.is_some() && self.privacy_mode == PrivacyMode::Strict {
+            warnings.push("whisper_dump_tokens_dir is ignored in strict privacy mode".to_owned());
+        }
+        if self.caption_port != 0 && self.privacy_mode == PrivacyMode::Strict {
+            warnings
+                .push("caption_port only broadcasts the state in strict privacy mode".to_owned());
+        }
+        if let Some(ref file) = self.word_filter_file
+            && !paths::resolve(file).is_file()
+        {
+            warnings.push(format!("word_filter_file {file:?} does not exist yet"));
+        }
+        for (i, stage) in self.text_pipeline.iter().enumerate() {
+            if self
+                .text_pipeline
+                .get(..i)
+                .is_some_and(|earlier| earlier.contains(stage))
+            {
+                errors.push(format!("text_pipeline lists {stage} more than once"));
+            }
+        }
+        if self.word_filter_file.is_some() && !self.text_pipeline.contains(&StageKind::Filter) {
+            warnings.push(
+                "word_filter_file has no effect while text_pipeline has no filter".to_owned(),
+            );
+        }
+        if let Some(ref dir) = self.assets_dir
+            && !paths::resolve(dir).is_dir()
+        {
+            warnings.push(format!("assets_dir {dir:?} does not exist"));
+        }
+        let missing: Vec<String> = [
+            &self.icon_idle,
+            &self.icon_recording,
+            &self.feedback_sound_start,
+            &self.feedback_sound_finish,
+            &self.feedback_sound_cancelled,
+            &self.feedback_sound_error,
+            &self.feedback_sound_empty,
+            &self.feedback_sound_auto_stop,
+        ]
+        .into_iter()
+        .flatten()
+        .map(|file| self.asset_file(Some(file), ""))
+        .filter(|path| !path.is_file())
+        .map(|path| path.display().to_string())
+        .collect();
+        if !missing.is_empty() {
+            warnings.push(format!(
+                "asset file(s) not found, using the defaults instead: {}",
+                missing.join(", ")
+            ));
+        }
+        if self.history_file.is_some() && !self.keep_last_transcription {
+            warnings
+                .push("history_file has no effect while keep_last_transcription is off".to_owned());
+        }
+        if self.secondary_language.is_some() && self.hotkey_modifier.with_shift().is_none() {
+            warnings.push(
+                "secondary_language has no effect while hotkey_modifier already includes SHIFT"
+                    .to_owned(),
+            );
+        }
+        if self.correction_trigger.is_some()
+            && !(self.keep_last_transcription && self.privacy_mode == PrivacyMode::Normal)
+        {
+            warnings.push(
+                "correction_trigger needs keep_last_transcription on and privacy_mode normal"
+                    .to_owned(),
+            );
+        }
+        if self.enable_readback && !self.keep_last_transcription {
+            warnings.push(
+                "enable_readback has no effect while keep_last_transcription is off".to_owned(),
+            );
+        }
+        // Ducking and the control pipe are on by default, so only opt-ins are flagged
+        #[cfg(not(windows))]
+        for (enabled, setting) in [
+            (self.enable_readback, "enable_readback"),
+            (self.enable_overlay, "enable_overlay"),
+            (!self.hotkey_disabled_in.is_empty(), "hotkey_disabled_in"),
+        ] {
+            if enabled {
+                warnings.push(format!(
+                    "{setting} is only supported on Windows and is ignored"
+                ));
+            }
+        }
+
+        if !(0.0..=1.0).contains(&self.duck_level) {
+            errors.push(format!(
+                "duck_level must be between 0.0 and 1.0, got {}",
+                self.duck_level
+            ));
+        }
+
+        if let Ok(cores) = std::thread::available_parallelism()
+            && self.whisper_threads > cores.get()
+        {
+            warnings.push(format!(
+                "whisper_threads is {} but only {} logical cores are available; using {}",
+                self.whisper_threads, cores, cores
+            ));
+        }
+
+        for language in self
+            .no_space_languages
+            .iter()
+            .filter(|&language| language == "auto" || !WhisperEngine::is_known_language(language))
+        {
+            errors.push(format!(
+                "Unknown language {language:?} in no_space_languages: expected an ISO 639-1 code such as \"ja\""
+            ));
+        }
+        if (1..MIN_PUNCTUATION_PAUSE_MS).contains(&self.punctuation_pause_ms) {
+            warnings.push(format!(
+                "punctuation_pause_ms is {}, which ends sentences at ordinary pauses between words",
+                self.punctuation_pause_ms
+            ));
+        }
+        for language in &self.language_choices {
+            if !WhisperEngine::is_known_language(language) {
+                errors.push(format!(
+                    "Unknown language {language:?}: expected an ISO 639-1 code such as \"en\", or \"auto\""
+                ));
+            }
+        }
+
+        let model_path = paths::model_file(&self.whisper_model);
+        if !self.whisper_model.is_empty()
+            && let Err(e) = WhisperEngine::check_model(&model_path)
+        {
+            errors.push(format!("whisper_model: {e:#}"));
+        }
+        for rule in &self.auto_model {
+            if let Err(e) = WhisperEngine::check_model(&paths::model_file(&rule.model)) {
+                errors.push(format!("auto_model: {e:#}"));
+            }
+        }
+
+        for (name, millis) in [
+            ("duck_fade_ms", self.duck_fade_ms),
+            ("duck_restore_fade_ms", self.duck_restore_fade_ms),
+        ] {
+            if millis > MAX_FADE_MS {
+                errors.push(format!(
+                    "{name} must be at most {MAX_FADE_MS}, got {millis}"
+                ));
+            }
+        }
+
+        if self.model_unload_delay_secs > MAX_SANE_DELAY_SECS {
+            warnings.push(format!(
+                "model_unload_delay_secs is {}, which keeps the model loaded for over a day",
+                self.model_unload_delay_secs
+            ));
+        }
+        if !(self.duplicate_similarity > 0.0 && self.duplicate_similarity <= 1.0) {
+            errors.push(format!(
+                "duplicate_similarity must be above 0.0 and at most 1.0, got {}",
+                self.duplicate_similarity
+            ));
+        }
+        if self.auto_stop_silence_ms != 0 && self.auto_stop_silence_ms < 1000 {
+            errors.push(format!(
+                "auto_stop_silence_ms must be 0 or at least 1000, got {}",
+                self.auto_stop_silence_ms
+            ));
+        }
+        if !(-100.0..=0.0).contains(&self.auto_stop_threshold_db) {
+            errors.push(format!(
+                "auto_stop_threshold_db must be between -100 and 0, got {}",
+                self.auto_stop_threshold_db
+            ));
+        }
+        if self.latch_max_secs == 0 {
+            errors.push("latch_max_secs must be at least 1".to_owned());
+        }
+        if self.recording_stop_timeout_secs == 0 {
+            errors.push("recording_stop_timeout_secs must be at least 1".to_owned());
+        }
+        if self.model_load_timeout_secs == 0 {
+            errors.push("model_load_timeout_secs must be at least 1".to_owned());
+        }
+        if self.log_keep_files == 0 {
+            errors.push("log_keep_files must be at least 1".to_owned());
+        }
+
+        if let Some(ref profile) = self.profile
+            && !self.profiles.contains(profile)
+        {
+            errors.push(format!(
+                "Unknown profile {profile:?}; defined profiles: {}",
+                if self.profiles.is_empty() {
+                    "none".to_owned()
+                } else {
+                    self.profiles.join(", ")
+                }
+            ));
+        }
+
+        if !errors.is_empty() {
+            anyhow::bail!(
+                "Invalid configuration:\n{}",
+                errors
+                    .iter()
+                    .map(|error| format!("  - {error}"))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            );
+        }
+        self.warnings = warnings;
+        Ok(self)
+    }
+
+    /// Location of the sound or icon `file` set in the config, or of `default`
+    /// inside the assets directory, see [`paths::asset`]
+    pub fn asset_file(&self, file: Option<&str>, default: &str) -> PathBuf {
+        paths::asset(self.assets_dir.as_deref(), file, default)
+    }
+
+    /// Whether a config file exists in any of the searched locations
+    pub fn file_exists() -> bool {
+        Self::find_file().is_some()
+    }
+
+    /// Write the commented default config with `values` filled in, returning its path
+    pub fn create_file(values: &[(&str, &str)]) -> Result<PathBuf> {
+        let path = Self::write_default_file()?;
+        for &(key, value) in values {
+            Self::write_value(&path, key, value)?;
+        }
+        Ok(path)
+    }
+
+    /// Set `key` in the config file at `path`, replacing an existing assignment or appending one
+    fn write_value(path: &Path, key: &str, value: &str) -> Result<()> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+
+        let assignment = format!("{key} = {}", toml::Value::String(value.to_owned()));
+        let mut replaced = false;
+        let mut lines: Vec<String> = contents
+            .lines()
+            .map(|line| {
+                let assigns_key = line
+                    .trim_start()
+                    .strip_prefix(key)
+                    .is_some_and(|rest| rest.trim_start().starts_with('='));
+                if assigns_key && !replaced {
+                    replaced = true;
+                    assignment.clone()
+                } else {
+                    line.to_owned()
+                }
+            })
+            .collect();
+        if !replaced {
+            lines.push(assignment);
+        }
+
+        let mut updated = lines.join("\n");
+        updated.push('\n');
+        std::fs::write(path, updated).with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+/// Where a raw value was found, for error messages
+enum Origin<'a> {
+    /// Command-line flag
+    CommandLine,
+    /// Process environment, including values loaded from `.env`
+    Env,
+    /// The config file at this path
+    File(&'a Path),
+    /// A `[profile.<name>]` section of the config file at this path
+    Profile(&'a Path, &'a str),
+    /// An `[app.<exe>]` section of the config file at this path
+    App(&'a Path, &'a str),
+    /// A choice made from the tray, saved in the state file at this path
+    State(&'a Path),
+}
+
+impl fmt::Display for Origin<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::CommandLine => write!(f, "the command line"),
+            Self::Env => write!(f, "the environment"),
+            Self::File(path) => write!(f, "{}", path.display()),
+            Self::Profile(path, name) => write!(f, "{} [profile.{name}]", path.display()),
+            Self::App(path, name) => write!(f, "{} [app.{name:?}]", path.display()),
+            Self::State(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// Raw configuration values, looked up on the command line, then in the
+/// environment, then in the saved tray choices, then in the active profile,
+/// then in the rest of the config file
+struct Sources {
+    /// Command-line `(setting, value)` overrides
+    overrides: Vec<(String, String)>,
+    /// Saved tray choices and the state file path
+    state: (PathBuf, toml::Table),
+    /// Parsed config file and its path
+    file: Option<(PathBuf, toml::Table)>,
+    /// Name of the `[profile.<name>]` section layered over the defaults
+    active_profile: Option<String>,
+    /// Values that failed to parse, collected so all of them are reported together
+    errors: Vec<String>,
+    /// Non-fatal problems found while reading the sources
+    warnings: Vec<String>,
+}
+
+impl Sources {
+    /// Parse the config file, if any, and the saved tray choices
+    fn read(path: Option<PathBuf>, overrides: Vec<(String, String)>) -> Result<Self> {
+        let file = path
+            .map(|path| {
+                let contents = std::fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                let table: toml::Table = toml::from_str(&contents)
+                    .with_context(|| format!("Invalid TOML in {}", path.display()))?;
+                anyhow::Ok((path, table))
+            })
+            .transpose()?;
+
+        let mut warnings = Vec::new();
+        let state_path = paths::state_file();
+        let state = state::load_overlay(
+            &state_path,
+            file.as_ref().map(|(path, table)| (path.as_path(), table)),
+            &mut warnings,
+        );
+        let mut sources = Self {
+            overrides,
+            state: (state_path, state),
+            file,
+            active_profile: None,
+            errors: Vec::new(),
+            warnings,
+        };
+        sources.active_profile = sources
+            .raw("active_profile")
+            .map(|(name, _)| name.trim().to_owned())
+            .filter(|name| name != DEFAULT_PROFILE);
+        Ok(sources)
+    }
+
+    /// Sources with nothing but the command-line `overrides`
+    #[cfg(test)]
+    fn overridden(overrides: Vec<(String, String)>) -> Self {
+        Self {
+            overrides,
+            state: (PathBuf::new(), toml::Table::new()),
+            file: None,
+            active_profile: None,
+            errors: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Names of the `[profile.<name>]` sections in the config file
+    fn profile_names(&self) -> Vec<String> {
+        self.file
+            .as_ref()
+            .and_then(|(_, table)| table.get("profile"))
+            .and_then(toml::Value::as_table)
+            .map(|profiles| profiles.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Raw value for `key`, from a command-line override, the uppercase
+    /// environment variable, a saved tray choice, the active profile, the
+    /// `[default]` section or the top level of the file.
+    ///
+    /// Empty values count as unset, except that an empty saved choice still
+    /// hides the file value; arrays are joined with commas.
+    fn raw(&self, key: &str) -> Option<(String, Origin<'_>)> {
+        if let Some((_, value)) = self.overrides.iter().rev().find(|(name, _)| name == key) {
+            return Some((value.clone(), Origin::CommandLine));
+        }
+
+        if let Ok(value) = std::env::var(key.to_uppercase())
+            && !value.trim().is_empty()
+        {
+            return Some((value, Origin::Env));
+        }
+
+        let (ref state_path, ref state) = self.state;
+        if let Some(value) = state.get(key) {
+            let value = raw_value(value);
+            return (!value.trim().is_empty()).then_some((value, Origin::State(state_path)));
+        }
+
+        let (path, table) = self.file.as_ref()?;
+        let section = |name: &str| table.get(name).and_then(toml::Value::as_table);
+        let profile = self.active_profile.as_deref().and_then(|name| {
+            let value = section("profile")?.get(name)?.as_table()?.get(key)?;
+            Some((value, Origin::Profile(path, name)))
+        });
+        let (value, origin) = profile
+            .or_else(|| {
+                section("default")
+                    .and_then(|defaults| defaults.get(key))
+                    .map(|value| (value, Origin::File(path)))
+            })
+            .or_else(|| table.get(key).map(|value| (value, Origin::File(path))))?;
+
+        let value = raw_value(value);
+        (!value.trim().is_empty()).then_some((value, origin))
+    }
+
+    /// Parse `key`, falling back to `default` when it is unset or invalid
+    ///
+    /// Invalid values are recorded in `errors` rather than returned.
+    fn get<T>(&mut self, key: &str, default: T) -> T
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        let Some((raw, origin)) = self.raw(key) else {
+            return default;
+        };
+        match raw.trim().parse() {
+            Ok(value) => value,
+            Err(e) => {
+                let error = format!(
+                    "Invalid value {raw:?} for {key} in {origin}: expected {} ({e})",
+                    expected_type::<T>()
+                );
+                self.errors.push(error);
+                default
+            }
+        }
+    }
+
+    /// Value for a setting that has no default
+    fn get_required(&mut self, key: &str) -> String {
+        if let Some((raw, _)) = self.raw(key) {
+            return raw;
+        }
+        self.errors.push(format!(
+            "Missing required setting {key}. Set it in {CONFIG_FILE_NAME} or as the {} environment variable",
+            key.to_uppercase()
+        ));
+        String::new()
+    }
+
+    /// Optional string value, `None` when unset or empty
+    fn get_optional(&self, key: &str) -> Option<String> {
+        self.raw(key).map(|(raw, _)| raw)
+    }
+
+    /// Optional value parsed like [`Self::get`], `None` when unset or invalid
+    fn get_optional_parsed<T>(&mut self, key: &str) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        let (raw, origin) = self.raw(key)?;
+        match raw.trim().parse() {
+            Ok(value) => Some(value),
+            Err(e) => {
+                self.errors
+                    .push(format!("Invalid value {raw:?} for {key} in {origin}: {e}"));
+                None
+            }
+        }
+    }
+
+    /// Optional value parsed as written, keeping whitespace such as a closing
+    /// line break; `None` when unset or invalid
+    fn get_optional_untrimmed<T>(&mut self, key: &str) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        let (raw, origin) = self.raw(key)?;
+        match raw.parse() {
+            Ok(value) => Some(value),
+            Err(e) => {
+                self.errors
+                    .push(format!("Invalid value {raw:?} for {key} in {origin}: {e}"));
+                None
+            }
+        }
+    }
+
+    /// Values of `key` in the `[app.<exe>]` sections of the config file, by
+    /// lowercased executable name and parsed as written
+    ///
+    /// An empty value turns the setting off for that application.
+    fn get_per_app<T>(&mut self, key: &str) -> Vec<(String, Option<T>)>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        let Some((ref path, ref table)) = self.file else {
+            return Vec::new();
+        };
+        let Some(apps) = table.get("app").and_then(toml::Value::as_table) else {
+            return Vec::new();
+        };
+        let mut values = Vec::new();
+        for (app, section) in apps {
+            let Some(value) = section.as_table().and_then(|section| section.get(key)) else {
+                continue;
+            };
+            let raw = raw_value(value);
+            if raw.trim().is_empty() {
+                values.push((app.to_lowercase(), None));
+                continue;
+            }
+            match raw.parse() {
+                Ok(value) => values.push((app.to_lowercase(), Some(value))),
+                Err(e) => self.errors.push(format!(
+                    "Invalid value {raw:?} for {key} in {}: {e}",
+                    Origin::App(path, app)
+                )),
+            }
+        }
+        values
+    }
+
+    /// Optional comma-separated list, lowercased with empty entries dropped
+    fn get_list(&self, key: &str) -> Vec<String> {
+        self.get_entries(key)
+            .into_iter()
+            .map(|item| item.to_lowercase())
+            .collect()
+    }
+
+    /// Optional comma-separated list as written, trimmed with empty entries dropped
+    fn get_entries(&self, key: &str) -> Vec<String> {
+        self.get_optional(key)
+            .map(|v| {
+                v.split(',')
+                    .map(|item| item.trim().to_owned())
+                    .filter(|item| !item.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Comma-separated list, `default` when the setting is missing
+    fn get_list_or(&self, key: &str, default: &[&str]) -> Vec<String> {
+        if self.get_optional(key).is_none() {
+            return default.iter().map(|&item| item.to_owned()).collect();
+        }
+        self.get_list(key)
+    }
+
+    /// Optional comma-separated list parsed entry by entry, as written so
+    /// file names keep their case
+    ///
+    /// Invalid entries are recorded in `errors` and left out.
+    fn get_parsed_list<T>(&mut self, key: &str) -> Vec<T>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        let mut parsed = Vec::new();
+        for entry in self.get_entries(key) {
+            match entry.parse() {
+                Ok(value) => parsed.push(value),
+                Err(e) => self
+                    .errors
+                    .push(format!("Invalid entry {entry:?} in {key}: {e}")),
+            }
+        }
+        parsed
+    }
+}
+
+/// Text form of a TOML value as it would appear in an environment variable.
+pub fn raw_value(value: &toml::Value) -> String {
+    match *value {
+        toml::Value::String(ref value) => value.clone(),
+        toml::Value::Array(ref items) => items
+            .iter()
+            .map(|item| {
+                item.as_str()
+                    .map_or_else(|| item.to_string(), str::to_owned)
+            })
+            .collect::<Vec<_>>()
+            .join(","),
+        ref other => other.to_string(),
+    }
+}
+
+/// Human-readable description of the type a setting is parsed into
+fn expected_type<T>() -> &'static str {
+    match std::any::type_name::<T>() {
+        "f32" | "f64" => "a number",
+        "u8" | "u16" | "u32" | "u64" | "usize" => "a non-negative whole number",
+        "bool" => "true or false",
+        _ => "a supported value",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sources with nothing but the setting `key` overridden to `value`
+    fn overridden(key: &str, value: &str) -> Sources {
+        Sources::overridden(vec![(key.to_owned(), value.to_owned())])
+    }
+
+    #[test]
+    fn auto_model_files_keep_their_case() {
+        let mut sources = overridden(
+            "auto_model",
+            "5 = Models/ggml-Tiny.EN.bin, 30=ggml-Base.bin",
+        );
+        let rules: Vec<ModelRule> = sources.get_parsed_list("auto_model");
+        let models: Vec<&str> = rules.iter().map(|rule| rule.model.as_str()).collect();
+        assert_eq!(
+            models,
+            ["Models/ggml-Tiny.EN.bin", "ggml-Base.bin"],
+            "model file names changed"
+        );
+        assert!(sources.errors.is_empty(), "errors: {:?}", sources.errors);
     }
 
-    /// Get environment variable with context
-    fn get_env(key: &str) -> Result<String> {
-        std::env::var(key)
-            .context(format!("Missing or invalid environment variable: {key}. See .env.example for required configuration"))
+    #[test]
+    fn plain_lists_are_still_lowercased() {
+        let sources = overridden("hotkey_disabled_in", " Game.EXE ,, mstsc.exe ");
+        assert_eq!(
+            sources.get_list("hotkey_disabled_in"),
+            ["game.exe", "mstsc.exe"],
+            "entries not lowercased and trimmed"
+        );
     }
 }
@@ -0,0 +1,156 @@
+//! When an idle model is unloaded.
+//!
+//! The cooldown starts whenever the model was last needed and expires after
+//! `model_unload_delay_secs`. The app's handle is only dropped once nothing
+//! else holds the engine, and never in a tick that handled an event, so a
+//! hotkey press that arrives just as the cooldown expires keeps the model.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Cooldown of a loaded model
+#[derive(Debug, Clone, Copy)]
+pub struct ModelCooldown {
+    /// Idle time after which the model is unloaded
+    delay: Duration,
+    /// When the model was last needed, `None` while it is not counting down
+    since: Option<Instant>,
+}
+
+impl ModelCooldown {
+    /// Cooldown that unloads after `delay` of idleness, not yet counting down
+    pub const fn new(delay: Duration) -> Self {
+        Self { delay, since: None }
+    }
+
+    /// Change the idle time for the countdown in progress and later ones
+    pub const fn set_delay(&mut self, delay: Duration) {
+        self.delay = delay;
+    }
+
+    /// Count down from `now`, when the model was last needed
+    pub const fn start(&mut self, now: Instant) {
+        self.since = Some(now);
+    }
+
+    /// Stop counting down, once the model is gone
+    pub const fn reset(&mut self) {
+        self.since = None;
+    }
+
+    /// When the countdown expires, `None` while not counting down
+    pub fn deadline(&self) -> Option<Instant> {
+        self.since.map(|since| since + self.delay)
+    }
+
+    /// Whether the app should drop its handle to `engine` at `now`.
+    ///
+    /// False while a job still holds the engine, or when the app handled an
+    /// event in this tick that may be about to use it.
+    pub fn should_unload<T>(&self, now: Instant, engine: &Arc<T>, handled_event: bool) -> bool {
+        !handled_event
+            && Arc::strong_count(engine) == 1
+            && self.deadline().is_some_and(|deadline| now >= deadline)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Idle time of the cooldowns under test
+    const DELAY: Duration = Duration::from_secs(30);
+
+    #[test]
+    fn stays_loaded_until_the_delay_expired() {
+        let start = Instant::now();
+        let mut cooldown = ModelCooldown::new(DELAY);
+        cooldown.start(start);
+        let engine = Arc::new(());
+        assert!(
+            !cooldown.should_unload(start + DELAY - Duration::from_millis(1), &engine, false),
+            "unloaded before the delay expired"
+        );
+        assert!(
+            cooldown.should_unload(start + DELAY, &engine, false),
+            "kept once the delay expired"
+        );
+    }
+
+    #[test]
+    fn never_unloads_while_not_counting_down() {
+        let start = Instant::now();
+        let mut cooldown = ModelCooldown::new(DELAY);
+        let engine = Arc::new(());
+        assert!(
+            !cooldown.should_unload(start + DELAY * 10, &engine, false),
+            "unloaded without a countdown"
+        );
+        cooldown.start(start);
+        cooldown.reset();
+        assert_eq!(cooldown.deadline(), None, "deadline left after a reset");
+        assert!(
+            !cooldown.should_unload(start + DELAY * 10, &engine, false),
+            "unloaded after a reset"
+        );
+    }
+
+    #[test]
+    fn keeps_an_engine_a_job_still_holds() {
+        let start = Instant::now();
+        let mut cooldown = ModelCooldown::new(DELAY);
+        cooldown.start(start);
+        let engine = Arc::new(());
+        let job = Arc::clone(&engine);
+        assert!(
+            !cooldown.should_unload(start + DELAY, &engine, false),
+            "unloaded while a job holds the engine"
+        );
+        drop(job);
+        assert!(
+            cooldown.should_unload(start + DELAY, &engine, false),
+            "kept after the job let go"
+        );
+    }
+
+    #[test]
+    fn a_handled_event_keeps_the_model_and_restarts_the_countdown() {
+        let start = Instant::now();
+        let mut cooldown = ModelCooldown::new(DELAY);
+        cooldown.start(start);
+        let engine = Arc::new(());
+        let expired = start + DELAY;
+        assert!(
+            !cooldown.should_unload(expired, &engine, true),
+            "unloaded in a tick that handled an event"
+        );
+        // The event needed the model, which restarts the countdown
+        cooldown.start(expired);
+        assert!(
+            !cooldown.should_unload(expired + DELAY / 2, &engine, false),
+            "unloaded before the restarted countdown expired"
+        );
+        assert!(
+            cooldown.should_unload(expired + DELAY, &engine, false),
+            "kept after the restarted countdown expired"
+        );
+    }
+
+    #[test]
+    fn a_new_delay_applies_to_the_countdown_in_progress() {
+        let start = Instant::now();
+        let mut cooldown = ModelCooldown::new(DELAY);
+        cooldown.start(start);
+        cooldown.set_delay(DELAY * 2);
+        let engine = Arc::new(());
+        assert!(
+            !cooldown.should_unload(start + DELAY, &engine, false),
+            "unloaded after the old delay"
+        );
+        assert_eq!(
+            cooldown.deadline(),
+            Some(start + DELAY * 2),
+            "deadline not moved to the new delay"
+        );
+    }
+}
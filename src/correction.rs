@@ -0,0 +1,263 @@
+//! Fixing a word of the last dictation by voice.
+//!
+//! Saying "correct banana to bandana" backspaces from the end of the text
+//! typed last to the start of "banana" and retypes the rest with "bandana"
+//! in its place. Positions are counted in graphemes, one backspace each, and
+//! the word must occur exactly once near the end, so the edit never guesses.
+
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Graphemes at the end of the last dictation a correction may reach into
+const TAIL_GRAPHEMES: usize = 200;
+
+/// A spoken "correct X to Y"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Correction {
+    /// Words to replace, as spoken
+    from: String,
+    /// Words to put in their place
+    to: String,
+}
+
+/// Keystrokes that apply a correction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    /// Backspaces from the end of the text to the start of the replaced words
+    pub backspaces: usize,
+    /// Text typed after them, the replacement and whatever followed it
+    pub retype: String,
+    /// The whole text once corrected
+    pub corrected: String,
+}
+
+/// Why a correction cannot be applied
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum CorrectionError {
+    /// The words do not occur near the end of the last dictation
+    #[error("{0:?} is not near the end of the last dictation")]
+    NotFound(String),
+    /// The words occur more than once there
+    #[error("{0:?} occurs more than once near the end of the last dictation")]
+    Ambiguous(String),
+}
+
+impl Correction {
+    /// The correction `text` asks for, if it is `trigger` followed by
+    /// "X to Y"; case and surrounding punctuation are ignored
+    pub fn parse(text: &str, trigger: &str) -> Option<Self> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let trigger: Vec<String> = trigger.split_whitespace().map(str::to_lowercase).collect();
+        if trigger.is_empty() || words.len() < trigger.len() + 3 {
+            return None;
+        }
+        let (spoken, rest) = words.split_at(trigger.len());
+        if spoken
+            .iter()
+            .zip(&trigger)
+            .any(|(&word, expected)| bare(word).to_lowercase() != *expected)
+        {
+            return None;
+        }
+        let split = rest
+            .iter()
+            .position(|&word| bare(word).eq_ignore_ascii_case("to"))?;
+        let (from, to) = rest.split_at(split);
+        let from = bare(&from.join(" ")).to_owned();
+        let to = bare(&to.get(1..)?.join(" ")).to_owned();
+        (!from.is_empty() && !to.is_empty()).then_some(Self { from, to })
+    }
+
+    /// Words to put in place of the replaced ones
+    pub fn replacement(&self) -> &str {
+        &self.to
+    }
+
+    /// The same correction putting `to` in place of the words instead
+    pub fn with_replacement(self, to: String) -> Self {
+        Self { to, ..self }
+    }
+
+    /// The edit that replaces the words in `typed`, matched case-insensitively
+    /// as whole words within its last [`TAIL_GRAPHEMES`]
+    ///
+    /// A replacement of a capitalized word is capitalized the same way.
+    pub fn plan(&self, typed: &str) -> Result<Edit, CorrectionError> {
+        let graphemes: Vec<&str> = typed.graphemes(true).collect();
+        let needle: Vec<String> = self.from.graphemes(true).map(str::to_lowercase).collect();
+        let tail = graphemes.len().saturating_sub(TAIL_GRAPHEMES);
+        let is_word = |index: usize| {
+            graphemes
+                .get(index)
+                .is_some_and(|grapheme| grapheme.chars().any(char::is_alphanumeric))
+        };
+        let mut found = (tail..=graphemes.len().saturating_sub(needle.len())).filter(|&start| {
+            let matches = graphemes
+                .get(start..start + needle.len())
+                .is_some_and(|window| {
+                    window
+                        .iter()
+                        .zip(&needle)
+                        .all(|(grapheme, expected)| grapheme.to_lowercase() == *expected)
+                });
+            matches && !(start > 0 && is_word(start - 1)) && !is_word(start + needle.len())
+        });
+        let start = found
+            .next()
+            .ok_or_else(|| CorrectionError::NotFound(self.from.clone()))?;
+        if found.next().is_some() {
+            return Err(CorrectionError::Ambiguous(self.from.clone()));
+        }
+
+        let capitalized = graphemes
+            .get(start)
+            .is_some_and(|grapheme| grapheme.chars().any(char::is_uppercase));
+        let mut retype = if capitalized {
+            capitalize(&self.to)
+        } else {
+            self.to.clone()
+        };
+        retype.extend(graphemes.iter().skip(start + needle.len()).copied());
+        let mut corrected: String = graphemes.iter().take(start).copied().collect();
+        corrected.push_str(&retype);
+        Ok(Edit {
+            backspaces: graphemes.len() - start,
+            retype,
+            corrected,
+        })
+    }
+}
+
+/// `word` without the punctuation Whisper puts around it
+fn bare(word: &str) -> &str {
+    word.trim_matches(|c: char| !c.is_alphanumeric())
+}
+
+/// `text` with its first letter in uppercase
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    chars.next().map_or_else(String::new, |first| {
+        first.to_uppercase().chain(chars).collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Correction of `from` to `to`, as if parsed
+    fn correction(from: &str, to: &str) -> Correction {
+        Correction {
+            from: from.to_owned(),
+            to: to.to_owned(),
+        }
+    }
+
+    #[test]
+    fn parse_splits_at_the_first_to() {
+        assert_eq!(
+            Correction::parse("correct walk to to run to", "correct"),
+            Some(correction("walk", "to run to")),
+            "later occurrences of to belong to the replacement"
+        );
+        assert_eq!(
+            Correction::parse("Correct into to onto.", "correct"),
+            Some(correction("into", "onto")),
+            "to inside a word is not a split"
+        );
+    }
+
+    #[test]
+    fn parse_ignores_case_and_punctuation_of_the_words() {
+        assert_eq!(
+            Correction::parse("Please fix, banana to bandana!", "please fix"),
+            Some(correction("banana", "bandana")),
+            "a trigger of several words"
+        );
+    }
+
+    #[test]
+    fn parse_rejects_other_text() {
+        for text in [
+            "banana to bandana",
+            "correct banana",
+            "correct banana to",
+            "correct to bandana",
+            "corrected banana to bandana",
+        ] {
+            assert_eq!(Correction::parse(text, "correct"), None, "{text:?}");
+        }
+        assert_eq!(Correction::parse("x a to b", " "), None, "empty trigger");
+    }
+
+    #[test]
+    fn plan_retypes_from_the_replaced_word() {
+        let edit = correction("banana", "bandana")
+            .plan("I like banana bread")
+            .expect("found");
+        assert_eq!(edit.backspaces, "banana bread".len(), "back to the word");
+        assert_eq!(edit.retype, "bandana bread", "replacement and the rest");
+        assert_eq!(edit.corrected, "I like bandana bread", "whole text");
+    }
+
+    #[test]
+    fn plan_matches_whole_words_only() {
+        let edit = correction("banana", "bandana")
+            .plan("Bananas, banana split")
+            .expect("only the whole word counts");
+        assert_eq!(edit.corrected, "Bananas, bandana split", "plural untouched");
+        assert_eq!(
+            correction("nan", "x").plan("banana"),
+            Err(CorrectionError::NotFound("nan".to_owned())),
+            "no match inside a word"
+        );
+    }
+
+    #[test]
+    fn plan_keeps_a_capital_and_counts_graphemes() {
+        // The accent is a combining mark: two characters, one backspace
+        let edit = correction("cafe\u{301}", "bistro")
+            .plan("Cafe\u{301} e\u{301}toile")
+            .expect("found");
+        assert_eq!(edit.backspaces, 11, "one per grapheme, not per character");
+        assert_eq!(edit.retype, "Bistro e\u{301}toile", "capital kept");
+    }
+
+    #[test]
+    fn plan_reaches_exactly_the_last_graphemes() {
+        let filler = |n: usize| "x".repeat(n);
+        let at_edge = format!("banana {}", filler(TAIL_GRAPHEMES - 7));
+        assert!(
+            correction("banana", "bandana").plan(&at_edge).is_ok(),
+            "a word starting at the edge of the tail"
+        );
+        let past_edge = format!("banana {}", filler(TAIL_GRAPHEMES - 6));
+        assert_eq!(
+            correction("banana", "bandana").plan(&past_edge),
+            Err(CorrectionError::NotFound("banana".to_owned())),
+            "a word starting one grapheme before the tail"
+        );
+    }
+
+    #[test]
+    fn plan_refuses_an_ambiguous_word() {
+        assert_eq!(
+            correction("banana", "bandana").plan("banana and Banana"),
+            Err(CorrectionError::Ambiguous("banana".to_owned())),
+            "two matches, whatever their case"
+        );
+    }
+
+    #[test]
+    fn plan_reports_a_missing_word() {
+        assert_eq!(
+            correction("cherry", "berry").plan("banana bread"),
+            Err(CorrectionError::NotFound("cherry".to_owned())),
+            "not in the text"
+        );
+        assert_eq!(
+            correction("cherry", "berry").plan(""),
+            Err(CorrectionError::NotFound("cherry".to_owned())),
+            "nothing typed"
+        );
+    }
+}
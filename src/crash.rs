@@ -0,0 +1,69 @@
+//! Panic reporting.
+//!
+//! Every panic is logged with its location and a backtrace. A panic on the
+//! main thread also restores ducked audio from the journal, tells the user in
+//! a message box and exits; worker panics are reported when they are joined.
+
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use tracing::error;
+
+use crate::dialog;
+use crate::volume;
+
+/// Whether this process owns the ducking journal, set by [`own_ducked_audio`]
+static OWNS_JOURNAL: AtomicBool = AtomicBool::new(false);
+
+/// Replace the default panic hook, whose output the hidden console swallows.
+pub fn install_hook() {
+    std::panic::set_hook(Box::new(|info| {
+        let location = info.location().map_or_else(
+            || "an unknown location".to_owned(),
+            |location| format!("{}:{}", location.file(), location.line()),
+        );
+        let message = info.payload_as_str().unwrap_or("no message");
+        let thread = std::thread::current();
+        let name = thread.name().unwrap_or("unnamed");
+        error!(
+            "Thread '{}' panicked at {}: {}\n{}",
+            name,
+            location,
+            message,
+            Backtrace::force_capture()
+        );
+        if name != "main" {
+            return;
+        }
+
+        // Exiting here skips every destructor, the ducking worker's included
+        if OWNS_JOURNAL.load(Ordering::Acquire) {
+            volume::restore_from_journal();
+        }
+        dialog::show_error_blocking(
+            "Speedy STT crashed",
+            &format!(
+                "Speedy STT stopped because of an internal error:\n\n{message}\n\nat {location}\n\nDetails are in the log file."
+            ),
+        );
+        std::process::exit(1);
+    }));
+}
+
+/// Restore ducked audio on a panic from now on.
+///
+/// Called once this is the running instance; before that the journal may
+/// belong to another one.
+pub fn own_ducked_audio() {
+    OWNS_JOURNAL.store(true, Ordering::Release);
+}
+
+/// Message of a panic payload returned by joining a thread
+pub fn payload_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("no message")
+}
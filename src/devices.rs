@@ -0,0 +1,171 @@
+//! Notice microphones being plugged in or removed while the app runs.
+//!
+//! A notification client registered on a helper thread flags every audio
+//! endpoint change and wakes the event loop. The loop then re-checks whether
+//! a microphone is present and rebuilds the Microphone menu, so the tray can
+//! show a missing microphone before the hotkey is pressed.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::JoinHandle;
+
+#[cfg(windows)]
+use anyhow::Context;
+use anyhow::Result;
+use tracing::{error, warn};
+#[cfg(windows)]
+use windows::Win32::Foundation::PROPERTYKEY;
+#[cfg(windows)]
+use windows::Win32::Media::Audio::{
+    DEVICE_STATE, EDataFlow, ERole, IMMDeviceEnumerator, IMMNotificationClient,
+    IMMNotificationClient_Impl, MMDeviceEnumerator, eCapture,
+};
+#[cfg(windows)]
+use windows::Win32::System::Com::{CLSCTX_ALL, CoCreateInstance};
+#[cfg(windows)]
+use windows::core::{PCWSTR, implement};
+
+#[cfg(windows)]
+use crate::com::ComApartment;
+#[cfg(windows)]
+use crate::wake;
+
+/// Watches for audio devices coming and going
+pub struct InputWatcher {
+    /// Set by the notification client on every device change
+    changed: Arc<AtomicBool>,
+    /// Keeps the watcher thread registered; dropped to let it exit
+    stop: Option<Sender<()>>,
+    /// Watcher thread, joined on drop
+    handle: Option<JoinHandle<()>>,
+}
+
+impl InputWatcher {
+    /// Start listening for device changes on a helper thread
+    pub fn start() -> Self {
+        let changed = Arc::new(AtomicBool::new(false));
+        let (stop, stopped) = mpsc::channel();
+        let flag = Arc::clone(&changed);
+        let handle = std::thread::Builder::new()
+            .name("input-devices".to_owned())
+            .spawn(move || {
+                if let Err(e) = watch(&flag, &stopped) {
+                    warn!("Not watching for microphone changes: {:#}", e);
+                }
+            })
+            .map_err(|e| warn!("Failed to start input device thread: {}", e))
+            .ok();
+        Self {
+            changed,
+            stop: Some(stop),
+            handle,
+        }
+    }
+
+    /// Whether devices changed since the last call
+    pub fn take_change(&self) -> bool {
+        self.changed.swap(false, Ordering::AcqRel)
+    }
+}
+
+impl Drop for InputWatcher {
+    fn drop(&mut self) {
+        // Closing the channel tells the thread to unregister and exit
+        self.stop = None;
+        if let Some(handle) = self.handle.take()
+            && handle.join().is_err()
+        {
+            error!("Input device thread panicked");
+        }
+    }
+}
+
+/// Register for device notifications until `stop` is closed
+#[cfg(windows)]
+fn watch(changed: &Arc<AtomicBool>, stop: &Receiver<()>) -> Result<()> {
+    let _com = ComApartment::enter_multithreaded()?;
+    // SAFETY: COM was initialized on this thread above, and MMDeviceEnumerator
+    // is a well-known CLSID with no additional invariants.
+    let enumerator: IMMDeviceEnumerator =
+        unsafe { CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL) }
+            .context("Failed to create IMMDeviceEnumerator")?;
+    let listener: IMMNotificationClient = DeviceListener {
+        changed: Arc::clone(changed),
+    }
+    .into();
+    // SAFETY: the listener is a valid COM object; it is unregistered below
+    // before the enumerator is released.
+    unsafe { enumerator.RegisterEndpointNotificationCallback(&listener) }
+        .context("Failed to register for audio device notifications")?;
+
+    // Nothing is ever sent; recv returns once the watcher is dropped
+    let _ = stop.recv();
+
+    // SAFETY: the listener was registered with this enumerator above.
+    unsafe { enumerator.UnregisterEndpointNotificationCallback(&listener) }
+        .context("Failed to unregister audio device notifications")
+}
+
+/// Register for device notifications until `stop` is closed
+#[cfg(not(windows))]
+const fn watch(_: &Arc<AtomicBool>, _: &Receiver<()>) -> Result<()> {
+    Ok(())
+}
+
+/// Notification client that flags a device change and wakes the event loop.
+///
+/// Called on system threads, so it only sets a flag.
+#[cfg(windows)]
+#[implement(IMMNotificationClient)]
+struct DeviceListener {
+    /// Shared with [`InputWatcher`]
+    changed: Arc<AtomicBool>,
+}
+
+#[cfg(windows)]
+impl DeviceListener {
+    /// Flag the change and let the event loop pick it up.
+    fn mark_changed(&self) {
+        self.changed.store(true, Ordering::Release);
+        wake::wake();
+    }
+}
+
+#[cfg(windows)]
+#[allow(
+    non_snake_case,
+    reason = "method names are defined by the COM interface"
+)]
+impl IMMNotificationClient_Impl for DeviceListener_Impl {
+    fn OnDeviceStateChanged(&self, _: &PCWSTR, _: DEVICE_STATE) -> windows::core::Result<()> {
+        self.mark_changed();
+        Ok(())
+    }
+
+    fn OnDeviceAdded(&self, _: &PCWSTR) -> windows::core::Result<()> {
+        self.mark_changed();
+        Ok(())
+    }
+
+    fn OnDeviceRemoved(&self, _: &PCWSTR) -> windows::core::Result<()> {
+        self.mark_changed();
+        Ok(())
+    }
+
+    fn OnDefaultDeviceChanged(
+        &self,
+        flow: EDataFlow,
+        _: ERole,
+        _: &PCWSTR,
+    ) -> windows::core::Result<()> {
+        if flow == eCapture {
+            self.mark_changed();
+        }
+        Ok(())
+    }
+
+    fn OnPropertyValueChanged(&self, _: &PCWSTR, _: &PROPERTYKEY) -> windows::core::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,284 @@
+//! Startup self-check.
+//!
+//! Verifies each subsystem a dictation depends on and collects the outcomes
+//! into one report, so a missing model or microphone shows up at startup
+//! instead of at the first recording. Also run from the tray on demand.
+
+use anyhow::Result;
+use tracing::{info, warn};
+
+use crate::audio;
+use crate::config::Config;
+use crate::feedback;
+use crate::hotkey::HotkeyListener;
+use crate::input::TextInjector;
+use crate::paths;
+use crate::whisper::WhisperEngine;
+
+/// Outcome of checking one subsystem
+#[derive(Debug)]
+pub struct Check {
+    /// Subsystem that was checked
+    pub name: &'static str,
+    /// What was found on success, or why the check failed
+    pub result: Result<String, String>,
+}
+
+impl Check {
+    /// Check named `name` with the outcome of `result`
+    fn new<E: Into<anyhow::Error>>(name: &'static str, result: Result<String, E>) -> Self {
+        Self {
+            name,
+            result: result.map_err(|e| format!("{:#}", e.into())),
+        }
+    }
+
+    /// Whether the subsystem is usable
+    pub const fn passed(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+/// Results of every check of one run
+#[derive(Debug)]
+pub struct HealthReport {
+    /// Checks in the order they ran
+    pub checks: Vec<Check>,
+}
+
+impl HealthReport {
+    /// Check every subsystem against `config` and the registered `hotkey`.
+    pub fn run(config: &Config, hotkey: &HotkeyListener) -> Self {
+        Self {
+            checks: vec![
+                check_config(config),
+                check_model(config),
+                Check::new(
+                    "Microphone",
+                    audio::check_input_device(config.input_device.as_deref()),
+                ),
+                check_output(config),
+                Check::new("Sounds", feedback::check_sounds(config)),
+                check_hotkey(config, hotkey.has_translate()),
+                Check::new(
+                    "Typing",
+                    TextInjector::check().map(|()| "keyboard input available".to_owned()),
+                ),
+            ],
+        }
+    }
+
+    /// Checks that failed
+    pub fn failures(&self) -> impl Iterator<Item = &Check> {
+        self.checks.iter().filter(|check| !check.passed())
+    }
+
+    /// One line per check, marked pass or fail
+    pub fn summary(&self) -> String {
+        self.checks
+            .iter()
+            .map(|check| match check.result {
+                Ok(ref detail) => format!("[ok] {}: {detail}", check.name),
+                Err(ref reason) => format!("[FAIL] {}: {reason}", check.name),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Write every result to the log, failures as warnings
+    pub fn log(&self) {
+        for check in &self.checks {
+            match check.result {
+                Ok(ref detail) => info!("Self-check {}: {}", check.name, detail),
+                Err(ref reason) => warn!("Self-check {} failed: {}", check.name, reason),
+            }
+        }
+    }
+}
+
+/// The configuration loaded; its warnings are counted but do not fail it.
+fn check_config(config: &Config) -> Check {
+    let source = config.file.as_ref().map_or_else(
+        || "defaults, no config file".to_owned(),
+        |file| format!("loaded from {}", file.display()),
+    );
+    let detail = match config.warnings.len() {
+        0 => source,
+        count => format!("{source}, {count} warning(s) in the log"),
+    };
+    Check::new("Configuration", Ok::<_, anyhow::Error>(detail))
+}
+
+/// The model file exists and looks like a ggml model.
+fn check_model(config: &Config) -> Check {
+    let path = paths::model_file(&config.whisper_model);
+    let result = WhisperEngine::check_model(&path).map(|()| {
+        let megabytes = std::fs::metadata(&path).map_or(0, |meta| meta.len() / 1_000_000);
+        format!("{} ({megabytes} MB)", path.display())
+    });
+    Check::new("Model", result)
+}
+
+/// An output device exists for feedback sounds and read-back, if either is on.
+fn check_output(config: &Config) -> Check {
+    let result = if config.enable_sound_feedback || config.enable_readback {
+        feedback::check_output_device(config.feedback_output_device.as_deref())
+    } else {
+        Ok("not used, feedback is disabled".to_owned())
+    };
+    Check::new("Speakers", result)
+}
+
+/// The hotkeys were registered; a translate hotkey may have been disabled,
+/// as when `has_translate` is false.
+fn check_hotkey(config: &Config, has_translate: bool) -> Check {
+    let main = format!("{} + {}", config.hotkey_modifier, config.hotkey_key);
+    let result = match config.translate_hotkey_key {
+        Some(ref key) if !has_translate => Err(anyhow::anyhow!(
+            "{main} registered, but translate hotkey {} + {key} could not be",
+            config.translate_hotkey_modifier
+        )),
+        Some(ref key) => Ok(format!(
+            "{main} and translate {} + {key} registered",
+            config.translate_hotkey_modifier
+        )),
+        None => Ok(format!("{main} registered")),
+    };
+    Check::new("Hotkey", result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hotkey::KeyName;
+    use crate::scratch::ScratchDir;
+
+    /// Config whose model is a file in `dir` that passes for a ggml model
+    fn config_in(dir: &ScratchDir) -> Config {
+        let model = dir.path().join("ggml-test.bin");
+        std::fs::write(&model, b"lmgg").expect("model written");
+        Config::from_overrides(vec![(
+            "whisper_model".to_owned(),
+            model.display().to_string(),
+        )])
+        .expect("config loaded")
+    }
+
+    /// Detail of a passed `check`, failing the test otherwise
+    fn detail(check: &Check) -> &str {
+        match check.result {
+            Ok(ref detail) => detail,
+            Err(ref reason) => panic!("{} failed: {reason}", check.name),
+        }
+    }
+
+    /// Reason a failed `check` gives, failing the test otherwise
+    fn reason(check: &Check) -> &str {
+        match check.result {
+            Ok(ref detail) => panic!("{} passed: {detail}", check.name),
+            Err(ref reason) => reason,
+        }
+    }
+
+    #[test]
+    fn the_config_check_names_its_source_and_counts_warnings() {
+        let dir = ScratchDir::new("diagnostics-config");
+        let mut config = config_in(&dir);
+        config.warnings.clear();
+        assert_eq!(
+            detail(&check_config(&config)),
+            "defaults, no config file",
+            "source of a config without a file"
+        );
+        config.file = Some(dir.path().join("config.toml"));
+        config.warnings = vec!["one".to_owned(), "two".to_owned()];
+        let check = check_config(&config);
+        assert!(check.passed(), "warnings failed the check");
+        assert!(
+            detail(&check).ends_with("config.toml, 2 warning(s) in the log"),
+            "file or warnings not reported: {}",
+            detail(&check)
+        );
+    }
+
+    #[test]
+    fn the_model_check_accepts_a_ggml_file() {
+        let dir = ScratchDir::new("diagnostics-model");
+        let config = config_in(&dir);
+        assert!(
+            detail(&check_model(&config)).ends_with("ggml-test.bin (0 MB)"),
+            "model not reported"
+        );
+    }
+
+    #[test]
+    fn the_model_check_fails_for_a_missing_or_foreign_file() {
+        let dir = ScratchDir::new("diagnostics-bad-model");
+        let mut config = config_in(&dir);
+        let foreign = dir.path().join("notes.txt");
+        std::fs::write(&foreign, "not a model").expect("file written");
+        config.whisper_model = foreign.display().to_string();
+        assert!(
+            reason(&check_model(&config)).contains("notes.txt"),
+            "foreign file not named"
+        );
+        config.whisper_model = dir.path().join("missing.bin").display().to_string();
+        assert!(
+            reason(&check_model(&config)).contains("missing.bin"),
+            "missing file not named"
+        );
+    }
+
+    #[test]
+    fn the_speaker_check_is_skipped_without_feedback() {
+        let dir = ScratchDir::new("diagnostics-speakers");
+        let mut config = config_in(&dir);
+        config.enable_sound_feedback = false;
+        config.enable_readback = false;
+        assert_eq!(
+            detail(&check_output(&config)),
+            "not used, feedback is disabled",
+            "speakers checked without feedback"
+        );
+    }
+
+    #[test]
+    fn the_hotkey_check_fails_when_the_translate_hotkey_was_disabled() {
+        let dir = ScratchDir::new("diagnostics-hotkey");
+        let mut config = config_in(&dir);
+        config.translate_hotkey_key = None;
+        assert!(
+            detail(&check_hotkey(&config, false)).ends_with("registered"),
+            "main hotkey alone not reported"
+        );
+        config.translate_hotkey_key = Some("T".parse::<KeyName>().expect("key"));
+        assert!(
+            detail(&check_hotkey(&config, true)).contains("and translate"),
+            "translate hotkey not reported"
+        );
+        assert!(
+            reason(&check_hotkey(&config, false)).contains("could not be"),
+            "disabled translate hotkey passed"
+        );
+    }
+
+    #[test]
+    fn the_report_lists_failures_and_marks_each_line() {
+        let report = HealthReport {
+            checks: vec![
+                Check::new("Model", Ok::<_, anyhow::Error>("found".to_owned())),
+                Check::new(
+                    "Microphone",
+                    Err::<String, _>(anyhow::anyhow!("no device").context("Failed to open")),
+                ),
+            ],
+        };
+        let failed: Vec<&str> = report.failures().map(|check| check.name).collect();
+        assert_eq!(failed, ["Microphone"], "failures not picked out");
+        assert_eq!(
+            report.summary(),
+            "[ok] Model: found\n[FAIL] Microphone: Failed to open: no device",
+            "summary lines or error chain wrong"
+        );
+    }
+}
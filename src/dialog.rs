@@ -0,0 +1,47 @@
+//! Native message boxes.
+//!
+//! Dialogs run on their own thread so the event loop keeps pumping tray and
+//! hotkey messages while the user reads them. Startup errors block instead,
+//! since the process exits right after.
+
+use tracing::{error, info};
+
+#[cfg(windows)]
+use windows::Win32::UI::WindowsAndMessaging::{
+    MB_ICONERROR, MB_ICONINFORMATION, MB_OK, MessageBoxW,
+};
+#[cfg(windows)]
+use windows::core::HSTRING;
+
+/// Show an informational message box without blocking the caller.
+pub fn show_info(title: &str, body: &str) {
+    info!("{}: {}", title, body.replace('\n', " | "));
+
+    #[cfg(windows)]
+    {
+        let title = HSTRING::from(title);
+        let body = HSTRING::from(body);
+        std::thread::spawn(move || {
+            // SAFETY: both strings are valid null-terminated UTF-16 buffers that
+            // outlive the modal call; no owner window is required.
+            unsafe { MessageBoxW(None, &body, &title, MB_OK | MB_ICONINFORMATION) };
+        });
+    }
+}
+
+/// Show an error message box and wait until the user dismisses it.
+pub fn show_error_blocking(title: &str, body: &str) {
+    error!("{}: {}", title, body.replace('\n', " | "));
+
+    #[cfg(windows)]
+    // SAFETY: both strings are valid null-terminated UTF-16 buffers that
+    // outlive the modal call; no owner window is required.
+    unsafe {
+        MessageBoxW(
+            None,
+            &HSTRING::from(body),
+            &HSTRING::from(title),
+            MB_OK | MB_ICONERROR,
+        )
+    };
+}
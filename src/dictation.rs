@@ -0,0 +1,313 @@
+//! Push-to-talk dictation without the tray shell.
+//!
+//! A [`DictationSession`] records from the microphone between
+//! [`DictationSession::start`] and [`DictationSession::stop`], then
+//! transcribes, joins and filters the text on a helper thread. The outcome
+//! arrives as a [`DictationEvent`], through a callback or a channel.
+
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use tracing::info;
+
+use crate::audio::{AudioError, AudioRecorder, WHISPER_SAMPLE_RATE};
+use crate::filter::{Filtered, WordFilter};
+use crate::punctuation::Punctuation;
+use crate::spacing::{DEFAULT_NO_SPACE_LANGUAGES, Spacing};
+use crate::whisper::{EngineError, LanguageCode, TranscriptionTask, WhisperEngine};
+
+/// Why a dictation could not be started or finished
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum DictationError {
+    /// [`DictationSession::start`] was called while recording
+    #[error("Already recording")]
+    AlreadyRecording,
+    /// The microphone could not be recorded
+    #[error("Recording failed")]
+    Audio(#[from] AudioError),
+    /// Whisper could not transcribe the recording
+    #[error("Transcription failed")]
+    Engine(#[from] EngineError),
+    /// The helper thread could not be started
+    #[error("Failed to start dictation thread")]
+    Thread(#[source] io::Error),
+}
+
+/// Text of one finished dictation
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Dictation {
+    /// Joined and filtered text
+    pub text: String,
+    /// Language code the text is written in
+    pub language: String,
+    /// Length of the recording
+    pub duration: Duration,
+}
+
+/// Outcome of a dictation, reported once it has been stopped
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum DictationEvent {
+    /// The text is ready to use
+    Transcribed(Dictation),
+    /// A filtered word matched; the text is as transcribed and should not be typed
+    Withheld(Dictation),
+    /// Nothing was said, or the word filter left nothing
+    Empty,
+    /// Recording or transcription failed
+    Failed(DictationError),
+}
+
+/// Receives the outcome of each dictation, on the thread that transcribed it
+type EventSink = Arc<dyn Fn(DictationEvent) + Send + Sync>;
+
+/// Recording a session is making
+struct Active {
+    /// Set to end the recording
+    stop: Arc<Mutex<bool>>,
+    /// Set to drop the recording instead of transcribing it
+    cancelled: Arc<AtomicBool>,
+}
+
+/// Records and transcribes one dictation at a time.
+///
+/// ```no_run
+/// use std::path::Path;
+/// use std::sync::Arc;
+///
+/// use speedy_stt::{DictationEvent, DictationSession, WhisperEngine};
+///
+/// let engine = Arc::new(WhisperEngine::load(Path::new("ggml-base.en.bin"), 4)?);
+/// let (mut session, events) = DictationSession::channel(engine);
+/// session.start()?;
+/// std::thread::sleep(std::time::Duration::from_secs(3));
+/// session.stop();
+/// if let Ok(DictationEvent::Transcribed(dictation)) = events.recv() {
+///     assert!(!dictation.text.is_empty());
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct DictationSession {
+    /// Model every dictation is transcribed with
+    engine: Arc<WhisperEngine>,
+    /// Where outcomes go
+    events: EventSink,
+    /// Language dictated in
+    language: LanguageCode,
+    /// Transcribe or translate
+    task: TranscriptionTask,
+    /// Multiplier applied to the input
+    volume_boost: f32,
+    /// Input device name, `None` for the system default
+    input_device: Option<String>,
+    /// Sentence breaks added to unpunctuated text
+    punctuation: Punctuation,
+    /// How segments are joined
+    spacing: Spacing,
+    /// Applied to the joined text, re-read when its file changes
+    word_filter: Option<Arc<Mutex<WordFilter>>>,
+    /// Peak input level as `f32` bits, see [`Self::level`]
+    level: Arc<AtomicU32>,
+    /// Recording in progress
+    active: Option<Active>,
+}
+
+impl DictationSession {
+    /// Session transcribing with `engine` and calling `on_event` with each outcome
+    pub fn new(
+        engine: Arc<WhisperEngine>,
+        on_event: impl Fn(DictationEvent) + Send + Sync + 'static,
+    ) -> Self {
+        let no_space: Vec<String> = DEFAULT_NO_SPACE_LANGUAGES
+            .iter()
+            .map(|&language| language.to_owned())
+            .collect();
+        Self {
+            engine,
+            events: Arc::new(on_event),
+            language: LanguageCode::default(),
+            task: TranscriptionTask::Transcribe,
+            volume_boost: 1.0,
+            input_device: None,
+            punctuation: Punctuation::default(),
+            spacing: Spacing::new(&no_space),
+            word_filter: None,
+            level: Arc::new(AtomicU32::new(0)),
+            active: None,
+        }
+    }
+
+    /// Session transcribing with `engine` whose outcomes arrive on the returned channel
+    pub fn channel(engine: Arc<WhisperEngine>) -> (Self, Receiver<DictationEvent>) {
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let session = Self::new(engine, move |event| {
+            // The receiver may be gone; the session goes on
+            let _ = sender
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .send(event);
+        });
+        (session, receiver)
+    }
+
+    /// Dictate in `language`; `en` by default
+    pub fn with_language(mut self, language: LanguageCode) -> Self {
+        self.language = language;
+        self
+    }
+
+    /// Transcribe or translate to English; transcribes by default
+    pub const fn with_task(mut self, task: TranscriptionTask) -> Self {
+        self.task = task;
+        self
+    }
+
+    /// Record from the input device named `name` instead of the system default
+    pub fn with_input_device(mut self, name: impl Into<String>) -> Self {
+        self.input_device = Some(name.into());
+        self
+    }
+
+    /// Multiply the input by `boost`; 1.0 by default
+    pub const fn with_volume_boost(mut self, boost: f32) -> Self {
+        self.volume_boost = boost;
+        self
+    }
+
+    /// Add sentence breaks to unpunctuated text as `punctuation` says; off by default
+    pub const fn with_punctuation(mut self, punctuation: Punctuation) -> Self {
+        self.punctuation = punctuation;
+        self
+    }
+
+    /// Join segments with `spacing`; the default uses [`DEFAULT_NO_SPACE_LANGUAGES`]
+    pub fn with_spacing(mut self, spacing: Spacing) -> Self {
+        self.spacing = spacing;
+        self
+    }
+
+    /// Run the joined text through `filter`
+    pub fn with_word_filter(mut self, filter: WordFilter) -> Self {
+        self.word_filter = Some(Arc::new(Mutex::new(filter)));
+        self
+    }
+
+    /// Peak input level as `f32` bits, raised while recording; reset it after reading
+    pub fn level(&self) -> Arc<AtomicU32> {
+        Arc::clone(&self.level)
+    }
+
+    /// Whether a dictation is being recorded
+    pub const fn is_recording(&self) -> bool {
+        self.active.is_some()
+    }
+
+    /// Start recording a dictation.
+    ///
+    /// The microphone is opened on a helper thread; a device that cannot be
+    /// opened is reported as [`DictationEvent::Failed`].
+    pub fn start(&mut self) -> Result<(), DictationError> {
+        if self.active.is_some() {
+            return Err(DictationError::AlreadyRecording);
+        }
+        let recorder = AudioRecorder::new(
+            self.volume_boost,
+            self.input_device.clone(),
+            Arc::clone(&self.level),
+        );
+        let active = Active {
+            stop: Arc::new(Mutex::new(false)),
+            cancelled: Arc::new(AtomicBool::new(false)),
+        };
+        let stop = Arc::clone(&active.stop);
+        let cancelled = Arc::clone(&active.cancelled);
+        let engine = Arc::clone(&self.engine);
+        let events = Arc::clone(&self.events);
+        let (language, task) = (self.language.clone(), self.task);
+        let (punctuation, spacing) = (self.punctuation, self.spacing.clone());
+        let word_filter = self.word_filter.clone();
+        std::thread::Builder::new()
+            .name("dictation".to_owned())
+            .spawn(move || {
+                let captured = match recorder.capture_until_stopped(stop) {
+                    Ok(captured) => captured,
+                    Err(e) => return events(DictationEvent::Failed(e.into())),
+                };
+                if cancelled.load(Ordering::SeqCst) {
+                    info!("Dictation cancelled");
+                    return;
+                }
+                let samples = captured.to_whisper_input();
+                if samples.is_empty() {
+                    return events(DictationEvent::Empty);
+                }
+                let mut transcript = match engine.transcribe(&samples, &language, task, false) {
+                    Ok(transcript) => transcript,
+                    Err(e) => return events(DictationEvent::Failed(e.into())),
+                };
+                punctuation.apply(&mut transcript);
+                let dictation = Dictation {
+                    text: transcript.text(&spacing),
+                    language: transcript.language,
+                    duration: duration_of(samples.len()),
+                };
+                events(finish(dictation, word_filter.as_deref()));
+            })
+            .map_err(DictationError::Thread)?;
+        self.active = Some(active);
+        Ok(())
+    }
+
+    /// Stop recording and transcribe what was said; the outcome follows as an event.
+    pub fn stop(&mut self) {
+        if let Some(active) = self.active.take() {
+            *active.stop.lock().unwrap_or_else(PoisonError::into_inner) = true;
+        }
+    }
+
+    /// Stop recording and discard it without an event.
+    pub fn cancel(&mut self) {
+        if let Some(active) = self.active.take() {
+            active.cancelled.store(true, Ordering::SeqCst);
+            *active.stop.lock().unwrap_or_else(PoisonError::into_inner) = true;
+        }
+    }
+}
+
+impl Drop for DictationSession {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+/// Outcome of a transcribed `dictation` once `word_filter` has seen it
+fn finish(mut dictation: Dictation, word_filter: Option<&Mutex<WordFilter>>) -> DictationEvent {
+    if let Some(filter) = word_filter {
+        let mut filter = filter.lock().unwrap_or_else(PoisonError::into_inner);
+        filter.reload_if_changed();
+        match filter.apply(&dictation.text) {
+            Filtered::Text(text) => dictation.text = text,
+            Filtered::Withheld => return DictationEvent::Withheld(dictation),
+        }
+    }
+    if dictation.text.is_empty() {
+        DictationEvent::Empty
+    } else {
+        DictationEvent::Transcribed(dictation)
+    }
+}
+
+/// Length of `samples` samples at [`WHISPER_SAMPLE_RATE`]
+fn duration_of(samples: usize) -> Duration {
+    let millis = u64::try_from(samples)
+        .unwrap_or(u64::MAX)
+        .saturating_mul(1000)
+        / u64::from(WHISPER_SAMPLE_RATE);
+    Duration::from_millis(millis)
+}
@@ -0,0 +1,264 @@
+//! Spotting a dictation that repeats the previous one.
+//!
+//! When nothing appears quickly enough, the same sentence is easily dictated
+//! twice. A transcription nearly identical to the one delivered just before
+//! is skipped, put on the clipboard, or held until confirmed from the tray
+//! instead of being typed a second time.
+//! In strict privacy mode only hashes of the previous text are kept.
+
+use std::collections::HashSet;
+use std::hash::{BuildHasher, RandomState};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+
+use crate::commands;
+
+/// Characters in each shingle hashed in strict privacy mode
+const SHINGLE_CHARS: usize = 3;
+
+/// What happens to a dictation that repeats the previous one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateAction {
+    /// Type nothing
+    Skip,
+    /// Type nothing and put the text on the clipboard, to paste if it was meant
+    Clipboard,
+    /// Hold the text back until it is confirmed from the tray
+    Confirm,
+}
+
+impl FromStr for DuplicateAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "skip" => Ok(Self::Skip),
+            "clipboard" => Ok(Self::Clipboard),
+            "confirm" => Ok(Self::Confirm),
+            other => {
+                anyhow::bail!("unknown duplicate action {other:?}, use skip, clipboard or confirm")
+            }
+        }
+    }
+}
+
+/// What is kept of the previous dictation
+enum Remembered {
+    /// Its normalized text
+    Text(String),
+    /// Hashes of the shingles of its normalized text
+    Shingles(HashSet<u64>),
+}
+
+/// The previous dictation, for comparing the next one with
+pub struct DuplicateCheck {
+    /// How soon after the previous dictation a repeat counts, zero for never
+    window: Duration,
+    /// Similarity from 0 to 1 at which a dictation counts as a repeat
+    threshold: f64,
+    /// Hash keys of this run, so shingle hashes cannot be looked up elsewhere
+    hasher: RandomState,
+    /// Previous dictation and when it was delivered
+    previous: Option<(Remembered, Instant)>,
+}
+
+impl DuplicateCheck {
+    /// Check counting dictations within `window` of each other and at least
+    /// `threshold` similar as repeats
+    pub fn new(window: Duration, threshold: f64) -> Self {
+        Self {
+            window,
+            threshold,
+            hasher: RandomState::new(),
+            previous: None,
+        }
+    }
+
+    /// Change the window and threshold, keeping the previous dictation
+    pub const fn configure(&mut self, window: Duration, threshold: f64) {
+        self.window = window;
+        self.threshold = threshold;
+    }
+
+    /// Whether `text` repeats the previous dictation, delivered within the
+    /// window; a text with no words repeats nothing
+    pub fn is_repeat(&self, text: &str, now: Instant) -> bool {
+        let Some((ref remembered, at)) = self.previous else {
+            return false;
+        };
+        if self.window.is_zero() || now.saturating_duration_since(at) > self.window {
+            return false;
+        }
+        let text = commands::normalize(text);
+        if text.is_empty() {
+            return false;
+        }
+        let similarity = match *remembered {
+            Remembered::Text(ref previous) => similarity(previous, &text),
+            Remembered::Shingles(ref previous) => jaccard(previous, &self.shingles(&text)),
+        };
+        similarity >= self.threshold
+    }
+
+    /// Remember `text` as the previous dictation, keeping only hashes of it
+    /// unless `keep_text`; a text with no words is not remembered
+    pub fn remember(&mut self, text: &str, keep_text: bool, now: Instant) {
+        let text = commands::normalize(text);
+        if self.window.is_zero() || text.is_empty() {
+            return;
+        }
+        let remembered = if keep_text {
+            Remembered::Text(text)
+        } else {
+            Remembered::Shingles(self.shingles(&text))
+        };
+        self.previous = Some((remembered, now));
+    }
+
+    /// Forget the previous dictation
+    pub fn clear(&mut self) {
+        self.previous = None;
+    }
+
+    /// Hashes of every run of [`SHINGLE_CHARS`] characters in `text`, or of
+    /// the whole of a shorter text
+    fn shingles(&self, text: &str) -> HashSet<u64> {
+        let chars: Vec<char> = text.chars().collect();
+        if chars.len() < SHINGLE_CHARS {
+            return HashSet::from([self.hasher.hash_one(text)]);
+        }
+        chars
+            .windows(SHINGLE_CHARS)
+            .map(|shingle| self.hasher.hash_one(shingle))
+            .collect()
+    }
+}
+
+/// One minus the edit distance of `a` and `b` relative to the longer of them,
+/// 1 for two empty texts
+#[allow(
+    clippy::as_conversions,
+    clippy::cast_precision_loss,
+    reason = "dictations are far shorter than an f64 represents exactly"
+)]
+fn similarity(a: &str, b: &str) -> f64 {
+    let longest = a.chars().count().max(b.chars().count());
+    if longest == 0 {
+        return 1.0;
+    }
+    1.0 - commands::edit_distance(a, b) as f64 / longest as f64
+}
+
+/// Shared shingles of `a` and `b` relative to all of theirs
+#[allow(
+    clippy::as_conversions,
+    clippy::cast_precision_loss,
+    reason = "dictations are far shorter than an f64 represents exactly"
+)]
+fn jaccard(a: &HashSet<u64>, b: &HashSet<u64>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 1.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Window the tests dictate within
+    const WINDOW: Duration = Duration::from_secs(10);
+
+    /// Check with `threshold` that remembered `previous` a moment ago,
+    /// as text or, in strict mode, as hashes
+    fn after(previous: &str, threshold: f64, keep_text: bool) -> (DuplicateCheck, Instant) {
+        let mut check = DuplicateCheck::new(WINDOW, threshold);
+        let start = Instant::now();
+        check.remember(previous, keep_text, start);
+        (check, start + Duration::from_secs(1))
+    }
+
+    #[test]
+    fn similarity_is_relative_to_the_longer_text() {
+        assert!(
+            (similarity("abcd", "abce") - 0.75).abs() < 1e-9,
+            "one of four"
+        );
+        assert!((similarity("abc", "abc") - 1.0).abs() < 1e-9, "identical");
+        assert!(similarity("abc", "xyz").abs() < 1e-9, "nothing shared");
+        assert!((similarity("", "") - 1.0).abs() < 1e-9, "both empty");
+        assert!(similarity("", "abc").abs() < 1e-9, "one empty");
+    }
+
+    #[test]
+    fn the_threshold_itself_counts_as_a_repeat() {
+        let (check, now) = after("abcd", 0.75, true);
+        assert!(check.is_repeat("abce", now), "exactly at the threshold");
+        let (check, now) = after("abcd", 0.76, true);
+        assert!(!check.is_repeat("abce", now), "just below it");
+    }
+
+    #[test]
+    fn case_and_punctuation_do_not_matter() {
+        let (check, now) = after("Send the report today.", 1.0, true);
+        assert!(check.is_repeat("send the report, today", now), "normalized");
+        assert!(
+            !check.is_repeat("send the reports today", now),
+            "a letter more"
+        );
+    }
+
+    #[test]
+    fn only_repeats_within_the_window_count() {
+        let (check, now) = after("hello world", 0.9, true);
+        assert!(
+            check.is_repeat("hello world", now + WINDOW - Duration::from_secs(1)),
+            "inside"
+        );
+        assert!(
+            !check.is_repeat("hello world", now + WINDOW),
+            "after the window"
+        );
+
+        let mut never = DuplicateCheck::new(Duration::ZERO, 0.9);
+        never.remember("hello world", true, now);
+        assert!(!never.is_repeat("hello world", now), "a zero window is off");
+        assert!(
+            !DuplicateCheck::new(WINDOW, 0.9).is_repeat("hello world", now),
+            "nothing remembered yet"
+        );
+    }
+
+    #[test]
+    fn empty_and_whitespace_transcripts_repeat_nothing() {
+        let (check, now) = after("hello world", 0.1, true);
+        assert!(!check.is_repeat("", now), "empty");
+        assert!(!check.is_repeat("  \t ", now), "whitespace only");
+        assert!(!check.is_repeat("...", now), "punctuation only");
+
+        let (check, now) = after("   ", 0.1, true);
+        assert!(!check.is_repeat("   ", now), "blank is never remembered");
+        assert!(!check.is_repeat("hello", now), "nor compared with");
+    }
+
+    #[test]
+    fn strict_mode_compares_hashed_shingles() {
+        let (check, now) = after("please send the report", 0.9, false);
+        assert!(check.is_repeat("Please send the report.", now), "same text");
+        assert!(!check.is_repeat("cancel the meeting", now), "other text");
+
+        let (check, now) = after("ok", 0.9, false);
+        assert!(check.is_repeat("OK", now), "shorter than a shingle");
+        assert!(!check.is_repeat("no", now), "short and different");
+    }
+
+    #[test]
+    fn clear_forgets_the_previous_dictation() {
+        let (mut check, now) = after("hello world", 0.9, true);
+        check.clear();
+        assert!(!check.is_repeat("hello world", now), "forgotten");
+    }
+}
@@ -0,0 +1,150 @@
+//! Failures the user is told about.
+//!
+//! Each subsystem's errors are wrapped in [`SttError`], which knows what the
+//! user can do about them. Notifications show [`SttError::user_message`];
+//! the log gets the whole cause chain from [`SttError::detail`].
+
+use std::error::Error as _;
+use std::io;
+
+use speedy_stt::{AudioError, EngineError};
+
+/// A failure in one of the app's subsystems
+#[derive(Debug, thiserror::Error)]
+pub enum SttError {
+    /// Loading the model or transcribing failed
+    #[error(transparent)]
+    Model(#[from] EngineError),
+    /// The microphone could not be found or opened
+    #[error(transparent)]
+    Audio(#[from] AudioError),
+    /// Global hotkeys could not be set up at all
+    #[error("Failed to create hotkey manager")]
+    HotkeyManager(#[source] global_hotkey::Error),
+    /// The configured hotkey is registered by someone else
+    #[error("Failed to register hotkey {hotkey}")]
+    HotkeyTaken {
+        /// The hotkey as configured, e.g. `CTRL+SPACE`
+        hotkey: String,
+        /// Common combinations that are currently free
+        free: Vec<String>,
+        /// Why registering failed
+        #[source]
+        source: global_hotkey::Error,
+    },
+    /// The text could not be typed into the target window
+    #[error("Failed to type text")]
+    Injection(#[source] enigo::InputError),
+    /// Other audio could not be lowered or paused for the recording
+    #[cfg_attr(
+        not(windows),
+        allow(dead_code, reason = "audio is only ducked on Windows")
+    )]
+    #[error("Failed to duck audio: {0}")]
+    Ducking(String),
+    /// The model would not fit into the physical memory that is free
+    #[error("Model needs about {required_mb} MB but only {available_mb} MB are free")]
+    LowMemory {
+        /// Estimated need of the loaded model
+        required_mb: u64,
+        /// Physical memory free before the load
+        available_mb: u64,
+    },
+    /// A helper thread panicked
+    #[error("{task} crashed: {message}")]
+    Crashed {
+        /// What the thread was doing, e.g. `Transcription`
+        task: &'static str,
+        /// The panic message
+        message: String,
+    },
+}
+
+impl SttError {
+    /// What went wrong and what to do about it, for a notification
+    pub fn user_message(&self) -> String {
+        match *self {
+            Self::Model(EngineError::Unreadable {
+                ref path,
+                ref source,
+            }) if source.kind() == io::ErrorKind::NotFound => format!(
+                "The Whisper model {} was not found. Download a model and set whisper_model in config.toml.",
+                path.display()
+            ),
+            Self::Model(EngineError::Unreadable { ref path, .. }) => format!(
+                "The Whisper model {} could not be read. Check that no other program has it open.",
+                path.display()
+            ),
+            Self::Model(EngineError::NotAModel(ref path)) => format!(
+                "{} is not a Whisper model. Download a ggml model file and set whisper_model to it.",
+                path.display()
+            ),
+            Self::Model(EngineError::InvalidPath) => {
+                "The model path has characters Whisper cannot open. Move the model to a folder with a plain name.".to_owned()
+            }
+            Self::Model(EngineError::Load(_)) => {
+                "The Whisper model could not be loaded. The file may be damaged or incomplete; download it again.".to_owned()
+            }
+            Self::Model(EngineError::Unsupported(reason)) => format!(
+                "The Whisper settings cannot be used: {reason}. Change whisper_use_gpu, whisper_gpu_device or whisper_flash_attn in config.toml."
+            ),
+            Self::Model(_) => {
+                "Transcription failed. Try again; if it keeps failing, try a smaller model.".to_owned()
+            }
+            Self::Audio(AudioError::NoInputDevice) => {
+                "No microphone was found. Connect one, or pick another from the tray's Microphone menu.".to_owned()
+            }
+            Self::Audio(AudioError::Devices(_)) => {
+                "The microphones could not be listed. Check that Windows audio is running.".to_owned()
+            }
+            Self::Audio(_) => {
+                "The microphone could not be opened. Close other apps using it, and check the microphone privacy settings.".to_owned()
+            }
+            Self::HotkeyManager(_) => {
+                "Global hotkeys are unavailable. Restart Speedy STT, or sign out and back in.".to_owned()
+            }
+            Self::HotkeyTaken {
+                ref hotkey,
+                ref free,
+                ..
+            } => {
+                let suggestion = if free.is_empty() {
+                    "None of the common alternatives are free either; try another combination in your config.toml.".to_owned()
+                } else {
+                    format!(
+                        "These combinations are currently free: {}. Set hotkey_modifier and hotkey_key in your config.toml accordingly.",
+                        free.join(", ")
+                    )
+                };
+                format!(
+                    "The hotkey {hotkey} may be reserved by Windows or another application. {suggestion}"
+                )
+            }
+            Self::Injection(_) => {
+                "The transcription could not be typed. A window running as administrator only accepts typing from Speedy STT running as administrator.".to_owned()
+            }
+            Self::Ducking(ref e) => format!("Could not lower other audio: {e}"),
+            Self::LowMemory {
+                required_mb,
+                available_mb,
+            } => format!(
+                "The Whisper model needs about {required_mb} MB but only {available_mb} MB of memory are free. Close other programs or pick a smaller model; set force_model_load = true to load it anyway."
+            ),
+            Self::Crashed { task, ref message } => {
+                format!("{task} crashed: {message}. The log has details.")
+            }
+        }
+    }
+
+    /// The error and each of its causes, for the log
+    pub fn detail(&self) -> String {
+        let mut detail = self.to_string();
+        let mut source = self.source();
+        while let Some(cause) = source {
+            detail.push_str(": ");
+            detail.push_str(&cause.to_string());
+            source = cause.source();
+        }
+        detail
+    }
+}
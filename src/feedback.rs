@@ -1,43 +1,651 @@
 //! Audio feedback for recording state
+//!
+//! Sounds play on a dedicated thread so they never delay recording or
+//! transcription. The output stream is opened on first use and reopened when
+//! the default output device changes or the stream reports an error. Sounds
+//! are decoded once when the thread starts; short synthesized tones stand in
+//! for sound files that are missing or broken.
 
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::BufReader;
-use std::path::Path;
+use std::io::{BufReader, Cursor, Read, Seek};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Duration;
 
 use anyhow::{Context, Result};
-use rodio::{Decoder, OutputStreamBuilder, Sink};
-use tracing::info;
+use rodio::buffer::SamplesBuffer;
+use rodio::cpal::traits::{DeviceTrait, HostTrait};
+use rodio::source::{SineWave, SquareWave, Zero};
+use rodio::{ChannelCount, Decoder, OutputStream, OutputStreamBuilder, SampleRate, Sink, Source};
+use tracing::{debug, info, warn};
+
+use crate::config::Config;
+use crate::quiet;
+use crate::speech;
+
+#[cfg(windows)]
+use windows::Win32::System::Diagnostics::Debug::MessageBeep;
+#[cfg(windows)]
+use windows::Win32::UI::WindowsAndMessaging::MB_ICONWARNING;
+
+/// Loudness of the synthesized tones relative to full scale
+const TONE_AMPLITUDE: f32 = 0.2;
+
+/// Sample rate of the pauses between notes of a tone
+const TONE_SAMPLE_RATE: u32 = 48_000;
+
+/// Rising pair of notes for a started recording, as (Hz, ms); 0 Hz is a pause
+const START_TONE: &[(f32, u64)] = &[(660.0, 70), (880.0, 90)];
+
+/// Falling pair of notes for a finished recording
+const FINISH_TONE: &[(f32, u64)] = &[(880.0, 70), (660.0, 90)];
+
+/// Single low note for a discarded recording
+const CANCELLED_TONE: &[(f32, u64)] = &[(440.0, 140)];
+
+/// Two short level notes for a recording in which no speech was recognized
+const EMPTY_TONE: &[(f32, u64)] = &[(520.0, 60), (0.0, 50), (520.0, 60)];
+
+/// Three quick high notes for a latched recording about to stop after silence
+const AUTO_STOP_TONE: &[(f32, u64)] =
+    &[(990.0, 50), (0.0, 40), (990.0, 50), (0.0, 40), (990.0, 50)];
+
+/// Low double buzz for a failure, played as a square wave so it never sounds like success
+const ERROR_TONE: &[(f32, u64)] = &[(220.0, 150), (0.0, 70), (220.0, 150)];
+
+/// Events announced with a feedback sound
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FeedbackEvent {
+    /// Recording started
+    Start,
+    /// Recording finished
+    Finish,
+    /// Recording discarded without transcribing
+    Cancelled,
+    /// Recording, model loading, transcription or typing failed
+    Error,
+    /// Transcription came back without any text
+    Empty,
+    /// Latched recording stops in a moment unless speech is heard
+    AutoStop,
+}
+
+impl FeedbackEvent {
+    /// Every event, for building the sound file table
+    const ALL: [Self; 6] = [
+        Self::Start,
+        Self::Finish,
+        Self::Cancelled,
+        Self::Error,
+        Self::Empty,
+        Self::AutoStop,
+    ];
+
+    /// Sound file in the assets directory used unless the config names another
+    const fn default_path(self) -> &'static str {
+        match self {
+            Self::Start => "sounds/start.mp3",
+            Self::Finish => "sounds/finish.mp3",
+            Self::Cancelled => "sounds/cancelled.mp3",
+            Self::Error => "sounds/error.mp3",
+            Self::Empty => "sounds/empty.mp3",
+            Self::AutoStop => "sounds/auto-stop.mp3",
+        }
+    }
+
+    /// Default sound data embedded in the binary, if the event has a recording
+    const fn embedded(self) -> Option<&'static [u8]> {
+        match self {
+            Self::Start => Some(include_bytes!("../assets/sounds/start.mp3")),
+            Self::Finish => Some(include_bytes!("../assets/sounds/finish.mp3")),
+            Self::Cancelled | Self::Error | Self::Empty | Self::AutoStop => None,
+        }
+    }
+
+    /// Notes of the synthesized tone for this event
+    const fn tone(self) -> &'static [(f32, u64)] {
+        match self {
+            Self::Start => START_TONE,
+            Self::Finish => FINISH_TONE,
+            Self::Cancelled => CANCELLED_TONE,
+            Self::Error => ERROR_TONE,
+            Self::Empty => EMPTY_TONE,
+            Self::AutoStop => AUTO_STOP_TONE,
+        }
+    }
+
+    /// Sound file configured for this event, if any
+    fn configured_file(self, config: &Config) -> Option<&str> {
+        match self {
+            Self::Start => config.feedback_sound_start.as_deref(),
+            Self::Finish => config.feedback_sound_finish.as_deref(),
+            Self::Cancelled => config.feedback_sound_cancelled.as_deref(),
+            Self::Error => config.feedback_sound_error.as_deref(),
+            Self::Empty => config.feedback_sound_empty.as_deref(),
+            Self::AutoStop => config.feedback_sound_auto_stop.as_deref(),
+        }
+    }
+}
+
+/// Sound sources and output handed to the playback thread
+struct SoundSources {
+    /// Whether files are used at all
+    style: FeedbackStyle,
+    /// Sound file of each event
+    files: HashMap<FeedbackEvent, PathBuf>,
+    /// Part of the output device name to play through, `None` for the default device
+    device: Option<String>,
+    /// Playback volume as `f32` bits, shared so it can change without a restart
+    volume: Arc<AtomicU32>,
+}
+
+/// Where feedback sounds come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeedbackStyle {
+    /// Sound files, with tones for missing or undecodable ones
+    Files,
+    /// Synthesized tones only
+    Tones,
+}
+
+impl FromStr for FeedbackStyle {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "files" => Ok(Self::Files),
+            "tones" => Ok(Self::Tones),
+            other => anyhow::bail!("unknown style {other:?}, use files or tones"),
+        }
+    }
+}
+
+/// Request to the playback thread
+enum Playback {
+    /// Play the sound for an event
+    Event(FeedbackEvent),
+    /// Read text aloud at the given speaking rate
+    Speak(String, f64),
+    /// Stop whatever is playing
+    Stop,
+    /// Stop and close the output stream so the next sound opens a fresh one
+    Reopen,
+}
+
+/// Spoken read-back settings
+#[derive(Debug, Clone, Copy)]
+struct Readback {
+    /// Speaking rate relative to normal speed
+    rate: f64,
+    /// Longest text read aloud, in characters
+    max_chars: usize,
+}
 
 /// Audio feedback player
+///
+/// Sounds play through an output stream of this process, whose audio session
+/// the ducking worker always skips by process ID.
 pub struct FeedbackPlayer {
     /// Whether sound feedback is enabled
     enabled: bool,
+    /// Whether event sounds are dropped while other audio is ducked
+    mute_while_ducked: bool,
+    /// Whether sounds are dropped while Windows reports the user as busy
+    honor_quiet: bool,
+    /// Whether the start and finish sounds still play while quiet
+    quiet_plays_cues: bool,
+    /// Set by the ducking worker from a duck request until its restore finished
+    ducked: Arc<AtomicBool>,
+    /// Read-back settings, `None` when transcriptions are not read aloud
+    readback: Option<Readback>,
+    /// Requests queued for the playback thread, `None` if it is not running
+    requests: Option<Sender<Playback>>,
+    /// Playback volume as `f32` bits, read by the thread for each sound
+    volume: Arc<AtomicU32>,
 }
 
 impl FeedbackPlayer {
-    /// Create new feedback player
-    pub fn new(enabled: bool) -> Self {
-        Self { enabled }
+    /// Create new feedback player from the sound settings, starting its
+    /// playback thread when sounds or read-back are enabled
+    ///
+    /// `ducked` is the ducking worker's flag, checked when muting while ducked.
+    pub fn new(config: &Config, ducked: Arc<AtomicBool>) -> Self {
+        let enabled = config.enable_sound_feedback;
+        let readback = config.enable_readback.then_some(Readback {
+            rate: config.readback_rate,
+            max_chars: config.readback_max_chars,
+        });
+        let volume = Arc::new(AtomicU32::new(config.feedback_volume.to_bits()));
+        let requests = if enabled || readback.is_some() {
+            let files = FeedbackEvent::ALL
+                .into_iter()
+                .map(|event| {
+                    let path =
+                        config.asset_file(event.configured_file(config), event.default_path());
+                    (event, path)
+                })
+                .collect();
+            spawn_playback(SoundSources {
+                style: config.feedback_style,
+                files,
+                device: config
+                    .feedback_output_device
+                    .clone()
+                    .filter(|name| !name.trim().is_empty()),
+                volume: Arc::clone(&volume),
+            })
+        } else {
+            None
+        };
+        Self {
+            enabled,
+            mute_while_ducked: config.mute_feedback_while_ducked,
+            honor_quiet: config.honor_quiet_mode,
+            quiet_plays_cues: config.quiet_mode_plays_cues,
+            ducked,
+            readback,
+            requests,
+            volume,
+        }
     }
 
-    /// Play sound file
-    pub fn play(&self, path: &Path) -> Result<()> {
+    /// Change the volume of sounds played from now on (0.0 to 1.0)
+    pub fn set_volume(&self, volume: f32) {
+        self.volume.store(volume.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Play the sound for `event` in the background, cutting off one still playing
+    pub fn play_event(&self, event: FeedbackEvent) {
         if !self.enabled {
-            return Ok(());
+            return;
+        }
+        if self.is_muted() {
+            debug!("Feedback sound {:?} muted while audio is ducked", event);
+            return;
+        }
+        if self.is_held_back(event) {
+            debug!("Feedback sound {:?} held back while quiet", event);
+            return;
+        }
+        self.send(Playback::Event(event));
+    }
+
+    /// Read `text` aloud when read-back is enabled, cut to the configured length
+    pub fn speak(&self, text: &str) {
+        if let Some(readback) = self.readback {
+            let text = speech::truncate(text, readback.max_chars);
+            self.send(Playback::Speak(text.to_owned(), readback.rate));
+        }
+    }
+
+    /// Stop the sound or read-back currently playing
+    pub fn stop(&self) {
+        if self.requests.is_some() {
+            self.send(Playback::Stop);
+        }
+    }
+
+    /// Close the output stream, for a device that may not have survived sleep
+    pub fn reopen_output(&self) {
+        if self.requests.is_some() {
+            self.send(Playback::Reopen);
+        }
+    }
+
+    /// Whether sounds are suppressed because other audio is ducked right now
+    fn is_muted(&self) -> bool {
+        self.mute_while_ducked && self.ducked.load(Ordering::Relaxed)
+    }
+
+    /// Whether `event` is silenced because the user is presenting or busy;
+    /// the recording cues are exempt when configured
+    fn is_held_back(&self, event: FeedbackEvent) -> bool {
+        let cue = matches!(
+            event,
+            FeedbackEvent::Start | FeedbackEvent::Finish | FeedbackEvent::AutoStop
+        );
+        self.honor_quiet && !(cue && self.quiet_plays_cues) && quiet::is_quiet()
+    }
+
+    /// Queue a request for the playback thread
+    fn send(&self, request: Playback) {
+        if let Some(ref requests) = self.requests
+            && requests.send(request).is_err()
+        {
+            warn!("Feedback sound thread is not running");
         }
+    }
 
-        let file = File::open(path).context("Failed to open sound file")?;
-        let source = Decoder::new(BufReader::new(file)).context("Failed to decode sound file")?;
+    /// Play the system warning sound, used when a hotkey press is ignored
+    pub fn play_denied(&self) {
+        #[cfg(windows)]
+        if self.enabled && !self.is_muted() {
+            // SAFETY: MessageBeep only queues a system sound and has no invariants.
+            if let Err(e) = unsafe { MessageBeep(MB_ICONWARNING) } {
+                info!("Failed to play denied sound: {}", e);
+            }
+        }
+    }
+}
 
-        let stream =
-            OutputStreamBuilder::open_default_stream().context("Failed to get audio output")?;
-        let sink = Sink::connect_new(stream.mixer());
+/// Output stream of the playback thread and the device it was opened on
+struct Output {
+    /// Open stream whose mixer the sounds are played through
+    stream: OutputStream,
+    /// Name of the default output device when the stream was opened on it,
+    /// `None` when playing through a configured device
+    default_device: Option<String>,
+    /// Set by the stream's error callback, e.g. when the device was unplugged
+    failed: Arc<AtomicBool>,
+}
 
-        sink.append(source);
-        sink.sleep_until_end();
+impl Output {
+    /// Open a stream on the output device whose name contains `wanted`,
+    /// or on the default device when none is configured or none matches
+    fn open(wanted: Option<&str>) -> Result<Self> {
+        let configured = wanted.and_then(|wanted| {
+            let device = find_output_device(wanted);
+            if device.is_none() {
+                warn!(
+                    "No output device matches {:?}, playing feedback on the default device",
+                    wanted
+                );
+            }
+            device
+        });
+        let default_device = if configured.is_some() {
+            None
+        } else {
+            default_output_name()
+        };
+        let builder = match configured {
+            Some(device) => {
+                info!("Playing feedback on {}", device.name().unwrap_or_default());
+                OutputStreamBuilder::from_device(device)
+            }
+            None => OutputStreamBuilder::from_default_device(),
+        };
 
-        info!("Played sound: {}", path.display());
+        let failed = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&failed);
+        let mut stream = builder
+            .context("Failed to get audio output")?
+            .with_error_callback(move |e| {
+                debug!("Feedback output stream error: {}", e);
+                flag.store(true, Ordering::Release);
+            })
+            .open_stream_or_fallback()
+            .context("Failed to open audio output")?;
+        stream.log_on_drop(false);
+        Ok(Self {
+            stream,
+            default_device,
+            failed,
+        })
+    }
 
-        Ok(())
+    /// Whether the stream should be reopened before the next sound
+    fn is_stale(&self) -> bool {
+        self.failed.load(Ordering::Acquire)
+            || (self.default_device.is_some() && default_output_name() != self.default_device)
     }
 }
+
+/// Start the playback thread, returning the queue feeding it.
+fn spawn_playback(sources: SoundSources) -> Option<Sender<Playback>> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::Builder::new()
+        .name("feedback-sounds".to_owned())
+        .spawn(move || run_playback(&receiver, &sources))
+        .map_err(|e| warn!("Failed to start feedback sound thread: {}", e))
+        .ok()
+        .map(|_| sender)
+}
+
+/// Decode the sounds, then play queued requests until the player is dropped.
+///
+/// A new sound stops the previous one, so quick repeated presses do not pile up.
+fn run_playback(requests: &Receiver<Playback>, sources: &SoundSources) {
+    let clips = load_clips(sources);
+    let mut output: Option<Output> = None;
+    let mut current: Option<Sink> = None;
+    for request in requests {
+        if let Some(sink) = current.take() {
+            sink.stop();
+        }
+        match request {
+            Playback::Stop => continue,
+            Playback::Reopen => {
+                output = None;
+                continue;
+            }
+            _ => {}
+        }
+        if output.as_ref().is_some_and(Output::is_stale) {
+            info!("Audio output changed, reopening feedback stream");
+            output = None;
+        }
+        if output.is_none() {
+            match Output::open(sources.device.as_deref()) {
+                Ok(opened) => output = Some(opened),
+                Err(e) => {
+                    warn!("Failed to play feedback sound: {:#}", e);
+                    continue;
+                }
+            }
+        }
+        let Some(ref opened) = output else {
+            continue;
+        };
+        current = match request {
+            Playback::Event(event) => Some(start_sound(opened, event, &clips, sources)),
+            Playback::Speak(text, rate) => start_speech(opened, &text, rate, sources),
+            Playback::Stop | Playback::Reopen => None,
+        };
+    }
+}
+
+/// Start reading `text` aloud; failures are logged and nothing plays.
+fn start_speech(output: &Output, text: &str, rate: f64, sources: &SoundSources) -> Option<Sink> {
+    let clip = speech::synthesize(text, rate)
+        .and_then(|wav| Clip::decode(Cursor::new(wav)).context("Failed to decode speech"));
+    match clip {
+        Ok(clip) => {
+            let sink = Sink::connect_new(output.stream.mixer());
+            sink.set_volume(f32::from_bits(sources.volume.load(Ordering::Relaxed)));
+            sink.append(clip.source());
+            debug!("Reading back {} characters", text.chars().count());
+            Some(sink)
+        }
+        Err(e) => {
+            warn!("Failed to read transcription aloud: {:#}", e);
+            None
+        }
+    }
+}
+
+/// Start playing the decoded sound for `event`, or its tone when it has none.
+fn start_sound(
+    output: &Output,
+    event: FeedbackEvent,
+    clips: &HashMap<FeedbackEvent, Clip>,
+    sources: &SoundSources,
+) -> Sink {
+    let sink = Sink::connect_new(output.stream.mixer());
+    sink.set_volume(f32::from_bits(sources.volume.load(Ordering::Relaxed)));
+    match clips.get(&event) {
+        Some(clip) => {
+            sink.append(clip.source());
+            debug!("Playing sound: {:?}", event);
+        }
+        None => {
+            append_tone(&sink, event);
+            debug!("Playing tone: {:?}", event);
+        }
+    }
+    sink
+}
+
+/// Sound decoded into memory, replayed without touching the disk
+struct Clip {
+    /// Interleaved channel count
+    channels: ChannelCount,
+    /// Samples per second
+    sample_rate: SampleRate,
+    /// Decoded samples
+    samples: Vec<f32>,
+}
+
+impl Clip {
+    /// Decode a whole sound file
+    fn decode<R>(reader: R) -> Result<Self>
+    where
+        R: Read + Seek + Send + Sync + 'static,
+    {
+        let decoder = Decoder::new(reader)?;
+        Ok(Self {
+            channels: decoder.channels(),
+            sample_rate: decoder.sample_rate(),
+            samples: decoder.collect(),
+        })
+    }
+
+    /// A fresh source playing the clip from the start
+    fn source(&self) -> SamplesBuffer {
+        SamplesBuffer::new(self.channels, self.sample_rate, self.samples.clone())
+    }
+}
+
+/// Decode the sound of every event once, preferring its file over the embedded default.
+///
+/// Events with neither, or whose file cannot be decoded, are reported here
+/// once and get their tone from then on.
+fn load_clips(sources: &SoundSources) -> HashMap<FeedbackEvent, Clip> {
+    if sources.style == FeedbackStyle::Tones {
+        return HashMap::new();
+    }
+    FeedbackEvent::ALL
+        .into_iter()
+        .filter_map(|event| match load_clip(event, sources.files.get(&event)) {
+            Ok(Some(clip)) => Some((event, clip)),
+            Ok(None) => {
+                debug!("No sound file for {:?}, using a tone", event);
+                None
+            }
+            Err(e) => {
+                warn!("{:#}, using a tone instead", e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Decode the sound file or embedded recording of `event`; `None` when it has neither.
+fn load_clip(event: FeedbackEvent, file: Option<&PathBuf>) -> Result<Option<Clip>> {
+    if let Some(path) = file.filter(|path| path.exists()) {
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open sound file {}", path.display()))?;
+        let clip = Clip::decode(BufReader::new(file))
+            .with_context(|| format!("Failed to decode sound file {}", path.display()))?;
+        info!("Loaded sound: {}", path.display());
+        return Ok(Some(clip));
+    }
+    event
+        .embedded()
+        .map(|data| Clip::decode(Cursor::new(data)).context("Failed to decode embedded sound"))
+        .transpose()
+}
+
+/// Queue the notes of the synthesized tone for `event`.
+fn append_tone(sink: &Sink, event: FeedbackEvent) {
+    for &(frequency, millis) in event.tone() {
+        let length = Duration::from_millis(millis);
+        if frequency <= 0.0 {
+            sink.append(Zero::new(1, TONE_SAMPLE_RATE).take_duration(length));
+        } else if event == FeedbackEvent::Error {
+            sink.append(
+                SquareWave::new(frequency)
+                    .take_duration(length)
+                    .amplify(TONE_AMPLITUDE),
+            );
+        } else {
+            sink.append(
+                SineWave::new(frequency)
+                    .take_duration(length)
+                    .amplify(TONE_AMPLITUDE),
+            );
+        }
+    }
+}
+
+/// First output device whose name contains `wanted`, ignoring case
+fn find_output_device(wanted: &str) -> Option<rodio::cpal::Device> {
+    let wanted = wanted.to_lowercase();
+    rodio::cpal::default_host()
+        .output_devices()
+        .ok()?
+        .find(|device| {
+            device
+                .name()
+                .is_ok_and(|name| name.to_lowercase().contains(&wanted))
+        })
+}
+
+/// Output device feedback would play on and its format, failing when there is none
+pub fn check_output_device(wanted: Option<&str>) -> Result<String> {
+    let configured = wanted
+        .filter(|wanted| !wanted.trim().is_empty())
+        .and_then(find_output_device);
+    let device = configured
+        .or_else(|| rodio::cpal::default_host().default_output_device())
+        .context("No output device available")?;
+    let config = device
+        .default_output_config()
+        .context("Output device has no usable format")?;
+    Ok(format!(
+        "{} ({} Hz)",
+        device.name().unwrap_or_default(),
+        config.sample_rate().0
+    ))
+}
+
+/// Configured sound files that are missing or cannot be decoded.
+///
+/// Failing ones would be replaced by tones, so this only reports them.
+pub fn check_sounds(config: &Config) -> Result<String> {
+    if !config.enable_sound_feedback || config.feedback_style == FeedbackStyle::Tones {
+        return Ok("tones or no sounds, nothing to load".to_owned());
+    }
+    let mut problems = Vec::new();
+    let mut custom = 0;
+    for event in FeedbackEvent::ALL {
+        let Some(file) = event.configured_file(config) else {
+            continue;
+        };
+        custom += 1;
+        let path = config.asset_file(Some(file), event.default_path());
+        if !path.exists() {
+            problems.push(format!("{} not found", path.display()));
+        } else if let Err(e) = load_clip(event, Some(&path)) {
+            problems.push(format!("{e:#}"));
+        }
+    }
+    if !problems.is_empty() {
+        anyhow::bail!("{}", problems.join("; "));
+    }
+    Ok(match custom {
+        0 => "default sounds".to_owned(),
+        count => format!("{count} custom sound file(s) loaded"),
+    })
+}
+
+/// Name of the current default output device, if there is one
+fn default_output_name() -> Option<String> {
+    rodio::cpal::default_host()
+        .default_output_device()
+        .and_then(|device| device.name().ok())
+}
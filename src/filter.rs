@@ -0,0 +1,245 @@
+//! Filtering sensitive words out of transcriptions.
+//!
+//! Words and phrases listed in `word_filter_file`, one per line, are matched
+//! case-insensitively on word boundaries. A match is masked, dropped, or
+//! keeps the whole transcription from being typed so it lands on the
+//! clipboard instead. The list is re-read whenever the file changes.
+
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use tracing::{info, warn};
+
+/// What happens to a transcription containing a listed word
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FilterAction {
+    /// Keep the first letter of each matched word and star out the rest
+    Mask,
+    /// Remove matched words
+    Drop,
+    /// Type nothing and put the unfiltered text on the clipboard
+    Clipboard,
+}
+
+/// A filter action name that is not `mask`, `drop` or `clipboard`
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("unknown filter action {0:?}, use mask, drop or clipboard")]
+pub struct UnknownFilterAction(pub String);
+
+impl FromStr for FilterAction {
+    type Err = UnknownFilterAction;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "mask" => Ok(Self::Mask),
+            "drop" => Ok(Self::Drop),
+            "clipboard" => Ok(Self::Clipboard),
+            other => Err(UnknownFilterAction(other.to_owned())),
+        }
+    }
+}
+
+/// Outcome of filtering one transcription
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Filtered {
+    /// Text to deliver, unchanged when nothing matched
+    Text(String),
+    /// A listed word matched and the text must not be typed
+    Withheld,
+}
+
+/// Word list loaded from a file, with the action taken on matches
+pub struct WordFilter {
+    /// List file, resolved
+    path: PathBuf,
+    /// Modification time when the list was read, `None` if it could not be
+    modified: Option<SystemTime>,
+    /// Lowercase words of each listed entry
+    entries: Vec<Vec<String>>,
+    /// Action on a match
+    action: FilterAction,
+}
+
+impl WordFilter {
+    /// Filter with the list in `path`; a missing or unreadable file filters nothing.
+    pub fn load(path: PathBuf, action: FilterAction) -> Self {
+        let mut filter = Self {
+            path,
+            modified: None,
+            entries: Vec::new(),
+            action,
+        };
+        filter.read();
+        filter
+    }
+
+    /// Re-read the list if its file changed since it was read.
+    pub fn reload_if_changed(&mut self) {
+        if modified(&self.path) != self.modified {
+            self.read();
+        }
+    }
+
+    /// Apply the list to `text`.
+    pub fn apply(&self, text: &str) -> Filtered {
+        let matches = self.find(text);
+        if matches.is_empty() {
+            return Filtered::Text(text.to_owned());
+        }
+        info!("Word filter matched {} time(s)", matches.len());
+        let mut filtered = String::with_capacity(text.len());
+        let mut copied = 0;
+        match self.action {
+            FilterAction::Clipboard => Filtered::Withheld,
+            FilterAction::Mask => {
+                for word in matches.into_iter().flat_map(|matched| matched.words) {
+                    filtered.push_str(&text[copied..word.start]);
+                    let mut chars = text[word.clone()].chars();
+                    filtered.extend(chars.next());
+                    filtered.extend(chars.map(|_| '*'));
+                    copied = word.end;
+                }
+                filtered.push_str(&text[copied..]);
+                Filtered::Text(filtered)
+            }
+            FilterAction::Drop => {
+                for matched in matches {
+                    filtered.push_str(&text[copied..matched.span.start]);
+                    copied = matched.span.end;
+                }
+                filtered.push_str(&text[copied..]);
+                Filtered::Text(tidy(&filtered))
+            }
+        }
+    }
+
+    /// Non-overlapping matches of listed entries, in text order
+    fn find(&self, text: &str) -> Vec<Match> {
+        let words = word_spans(text);
+        let lowered: Vec<String> = words
+            .iter()
+            .map(|w| text[w.clone()].to_lowercase())
+            .collect();
+        let mut matches = Vec::new();
+        let mut i = 0;
+        while i < words.len() {
+            let found = self.entries.iter().find(|entry| {
+                lowered
+                    .get(i..i + entry.len())
+                    .is_some_and(|candidate| candidate == entry.as_slice())
+            });
+            let Some(entry) = found else {
+                i += 1;
+                continue;
+            };
+            let matched = &words[i..i + entry.len()];
+            matches.push(Match {
+                span: matched[0].start..matched[matched.len() - 1].end,
+                words: matched.to_vec(),
+            });
+            i += entry.len();
+        }
+        matches
+    }
+
+    /// Read the list, keeping the previous one if the file cannot be read
+    fn read(&mut self) {
+        self.modified = modified(&self.path);
+        match read_entries(&self.path) {
+            Ok(entries) => {
+                info!(
+                    "Loaded {} filtered word(s) from {}",
+                    entries.len(),
+                    self.path.display()
+                );
+                self.entries = entries;
+            }
+            Err(e) => warn!(
+                "Word filter list not loaded: Failed to read {}: {}",
+                self.path.display(),
+                e
+            ),
+        }
+    }
+}
+
+/// Listed words found together
+struct Match {
+    /// From the start of the first word to the end of the last
+    span: Range<usize>,
+    /// Each word on its own
+    words: Vec<Range<usize>>,
+}
+
+/// Entries of a list file, skipping blank lines and `#` comments
+fn read_entries(path: &Path) -> io::Result<Vec<Vec<String>>> {
+    let contents = std::fs::read_to_string(path)?;
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            word_spans(line)
+                .into_iter()
+                .map(|word| line[word].to_lowercase())
+                .collect::<Vec<_>>()
+        })
+        .filter(|entry| !entry.is_empty())
+        .collect())
+}
+
+/// Byte ranges of the words in `text`: letters, digits and inner apostrophes
+fn word_spans(text: &str) -> Vec<Range<usize>> {
+    let mut spans: Vec<Range<usize>> = Vec::new();
+    let mut start = None;
+    let mut chars = text.char_indices().peekable();
+    while let Some((at, c)) = chars.next() {
+        let inner_apostrophe = matches!(c, '\'' | '’')
+            && start.is_some()
+            && chars
+                .peek()
+                .is_some_and(|&(_, next)| next.is_alphanumeric());
+        if c.is_alphanumeric() || inner_apostrophe {
+            start.get_or_insert(at);
+        } else if let Some(from) = start.take() {
+            spans.push(from..at);
+        }
+    }
+    if let Some(from) = start {
+        spans.push(from..text.len());
+    }
+    spans
+}
+
+/// Text with the whitespace a dropped word leaves behind collapsed, and no
+/// space before punctuation that followed it or commas left at the start
+fn tidy(text: &str) -> String {
+    let joined = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut tidied = String::with_capacity(joined.len());
+    let mut chars = joined
+        .trim_start_matches([',', ';', ':', ' '])
+        .chars()
+        .peekable();
+    while let Some(c) = chars.next() {
+        if c == ' '
+            && chars
+                .peek()
+                .is_some_and(|&next| matches!(next, '.' | ',' | ';' | ':' | '!' | '?'))
+        {
+            continue;
+        }
+        tidied.push(c);
+    }
+    tidied
+}
+
+/// Modification time of `path`, `None` if it cannot be read
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
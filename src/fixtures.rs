@@ -0,0 +1,70 @@
+//! Whisper transcripts kept as test fixtures.
+//!
+//! A fixture is a token file as `whisper_dump_tokens_dir` writes it, so the
+//! dump of a dictation can be copied into `tests/fixtures/transcripts` as is.
+//! Only the segments and the language settings are read from it.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::whisper::{Segment, Transcript};
+
+/// The parts of a token file a fixture is made from
+#[derive(Deserialize)]
+struct Dump {
+    /// Settings of the pass
+    parameters: Parameters,
+    /// Segments in the order Whisper produced them
+    segments: Vec<DumpedSegment>,
+}
+
+/// Language settings of the pass
+#[derive(Deserialize)]
+struct Parameters {
+    /// Language asked for, `auto` to detect it
+    language: String,
+    /// Language Whisper recognised
+    detected_language: String,
+    /// `transcribe` or `translate`
+    task: String,
+}
+
+/// One segment with its place in the audio
+#[derive(Deserialize)]
+struct DumpedSegment {
+    /// Start in the audio, in milliseconds
+    start_ms: u64,
+    /// End in the audio, in milliseconds
+    end_ms: u64,
+    /// Raw text of the segment
+    text: String,
+}
+
+/// Transcript stored in the fixture `name`, without its tokens
+pub fn transcript(name: &str) -> Transcript {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/transcripts")
+        .join(format!("{name}.json"));
+    let json = std::fs::read_to_string(&path).expect("read transcript fixture");
+    let dump: Dump = serde_json::from_str(&json).expect("parse transcript fixture");
+    let language = match dump.parameters.task.as_str() {
+        "translate" => "en".to_owned(),
+        _ if dump.parameters.language == "auto" => dump.parameters.detected_language,
+        _ => dump.parameters.language,
+    };
+    Transcript {
+        segments: dump
+            .segments
+            .into_iter()
+            .map(|segment| Segment {
+                start: Duration::from_millis(segment.start_ms),
+                end: Duration::from_millis(segment.end_ms),
+                text: segment.text,
+                tokens: Vec::new(),
+            })
+            .collect(),
+        language,
+    }
+}
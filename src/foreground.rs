@@ -0,0 +1,332 @@
+//! Foreground window inspection and restoration.
+//!
+//! Resolves the executable name of the process owning the foreground window,
+//! used to suppress dictation in applications listed in the configuration,
+//! and brings back the window a recording started in, or the one named by
+//! `inject_target_window`, before its text is typed.
+
+use std::str::FromStr;
+
+use anyhow::Result;
+
+#[cfg(windows)]
+use windows::Win32::Foundation::{CloseHandle, HWND, LPARAM};
+#[cfg(windows)]
+use windows::Win32::System::Threading::{
+    AttachThreadInput, GetCurrentThreadId, OpenProcess, PROCESS_NAME_WIN32,
+    PROCESS_QUERY_LIMITED_INFORMATION, QueryFullProcessImageNameW,
+};
+#[cfg(windows)]
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    INPUT, INPUT_0, INPUT_KEYBOARD, KEYBD_EVENT_FLAGS, KEYBDINPUT, KEYEVENTF_KEYUP, SendInput,
+    VK_MENU,
+};
+#[cfg(windows)]
+use windows::Win32::UI::WindowsAndMessaging::{
+    BringWindowToTop, EnumWindows, GetForegroundWindow, GetWindowTextW, GetWindowThreadProcessId,
+    IsIconic, IsWindow, IsWindowVisible, SW_RESTORE, SetForegroundWindow, ShowWindow,
+};
+#[cfg(windows)]
+use windows::core::{BOOL, PWSTR};
+
+/// What to do with a transcription when its window cannot be brought back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefocusFallback {
+    /// Type into whatever window has focus instead
+    Type,
+    /// Put the text on the clipboard to paste by hand
+    Clipboard,
+}
+
+impl FromStr for RefocusFallback {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "type" => Ok(Self::Type),
+            "clipboard" => Ok(Self::Clipboard),
+            other => anyhow::bail!("unknown fallback {other:?}, use type or clipboard"),
+        }
+    }
+}
+
+/// Window that had focus when a recording started
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TargetWindow {
+    /// Handle of the window, possibly closed since
+    #[cfg(windows)]
+    hwnd: HWND,
+}
+
+impl TargetWindow {
+    /// The current foreground window, if there is one
+    #[cfg(windows)]
+    pub fn capture() -> Option<Self> {
+        // SAFETY: GetForegroundWindow has no preconditions and may return a null handle.
+        let hwnd = unsafe { GetForegroundWindow() };
+        (!hwnd.is_invalid()).then_some(Self { hwnd })
+    }
+
+    /// Window handles are only captured on Windows.
+    #[cfg(not(windows))]
+    pub const fn capture() -> Option<Self> {
+        None
+    }
+
+    /// Visible windows whose title contains `pattern` or whose process is
+    /// named `pattern`, ignoring case, the most recently active first
+    #[cfg(windows)]
+    pub fn matching(pattern: &str) -> Vec<Self> {
+        let pattern = pattern.trim().to_lowercase();
+        titled_windows()
+            .into_iter()
+            .filter(|&(hwnd, ref title)| {
+                title.to_lowercase().contains(&pattern) || is_process(hwnd, &pattern)
+            })
+            .map(|(hwnd, _)| Self { hwnd })
+            .collect()
+    }
+
+    /// Windows are only looked up on Windows.
+    #[cfg(not(windows))]
+    pub const fn matching(_pattern: &str) -> Vec<Self> {
+        Vec::new()
+    }
+
+    /// Visible windows of processes named `process`, ignoring case, the most
+    /// recently active first
+    #[cfg(windows)]
+    pub fn of_process(process: &str) -> Vec<Self> {
+        let process = process.trim().to_lowercase();
+        titled_windows()
+            .into_iter()
+            .filter(|&(hwnd, _)| is_process(hwnd, &process))
+            .map(|(hwnd, _)| Self { hwnd })
+            .collect()
+    }
+
+    /// Windows are only looked up on Windows.
+    #[cfg(not(windows))]
+    pub const fn of_process(_process: &str) -> Vec<Self> {
+        Vec::new()
+    }
+
+    /// Title of the window, empty when it has none or was closed
+    #[cfg(windows)]
+    pub fn title(&self) -> String {
+        window_title(self.hwnd)
+    }
+
+    /// Window titles are only read on Windows.
+    #[cfg(not(windows))]
+    pub fn title(&self) -> String {
+        String::new()
+    }
+
+    /// Make this the foreground window again, failing when it was closed or
+    /// Windows' foreground lock refuses.
+    ///
+    /// A plain request is tried first, then one with this thread's input
+    /// attached to the current foreground window's thread, then one after a
+    /// simulated Alt tap, which Windows counts as user input to this process.
+    #[cfg(windows)]
+    pub fn restore(&self) -> Result<()> {
+        // SAFETY: every call takes the captured handle, which IsWindow
+        // checks first; a handle closed in between makes the calls fail
+        // harmlessly. Attached input is detached before returning.
+        unsafe {
+            if !IsWindow(Some(self.hwnd)).as_bool() {
+                anyhow::bail!("the window was closed");
+            }
+            if GetForegroundWindow() == self.hwnd {
+                return Ok(());
+            }
+            if IsIconic(self.hwnd).as_bool() {
+                let _ = ShowWindow(self.hwnd, SW_RESTORE);
+            }
+            if SetForegroundWindow(self.hwnd).as_bool() {
+                return Ok(());
+            }
+
+            let foreground_thread = GetWindowThreadProcessId(GetForegroundWindow(), None);
+            let own_thread = GetCurrentThreadId();
+            if foreground_thread != 0 && foreground_thread != own_thread {
+                let attached = AttachThreadInput(own_thread, foreground_thread, true).as_bool();
+                let _ = BringWindowToTop(self.hwnd);
+                let restored = SetForegroundWindow(self.hwnd).as_bool();
+                if attached {
+                    let _ = AttachThreadInput(own_thread, foreground_thread, false);
+                }
+                if restored {
+                    return Ok(());
+                }
+            }
+
+            tap_alt();
+            if SetForegroundWindow(self.hwnd).as_bool() && GetForegroundWindow() == self.hwnd {
+                return Ok(());
+            }
+        }
+        anyhow::bail!("Windows refused to switch the foreground window")
+    }
+
+    /// Nothing to restore off Windows.
+    #[cfg(not(windows))]
+    pub fn restore(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Title of the current foreground window, empty when there is none
+#[cfg(windows)]
+pub fn foreground_title() -> String {
+    // SAFETY: GetForegroundWindow has no preconditions and may return a null handle.
+    window_title(unsafe { GetForegroundWindow() })
+}
+
+/// Window titles are only read on Windows.
+#[cfg(not(windows))]
+pub fn foreground_title() -> String {
+    String::new()
+}
+
+/// Visible top-level windows with a title and their titles, from the top of
+/// the Z order down
+#[cfg(windows)]
+fn titled_windows() -> Vec<(HWND, String)> {
+    let mut windows: Vec<HWND> = Vec::new();
+    // SAFETY: the callback only runs during the call and casts the
+    // parameter back to the vector, which outlives it.
+    let _ = unsafe {
+        EnumWindows(
+            Some(collect_window),
+            LPARAM(
+                std::ptr::from_mut(&mut windows)
+                    .expose_provenance()
+                    .cast_signed(),
+            ),
+        )
+    };
+    windows
+        .into_iter()
+        .map(|hwnd| (hwnd, window_title(hwnd)))
+        .filter(|(_, title)| !title.is_empty())
+        .collect()
+}
+
+/// Whether `hwnd` belongs to a process named `name`, given in lower case
+/// with or without `.exe`
+#[cfg(windows)]
+fn is_process(hwnd: HWND, name: &str) -> bool {
+    let name = name.strip_suffix(".exe").unwrap_or(name);
+    window_process_name(hwnd).is_some_and(|process| {
+        let process = process.to_lowercase();
+        process.strip_suffix(".exe").unwrap_or(&process) == name
+    })
+}
+
+/// Collect each visible top-level window into the `Vec<HWND>` that `windows` points to
+#[cfg(windows)]
+unsafe extern "system" fn collect_window(hwnd: HWND, windows: LPARAM) -> BOOL {
+    // SAFETY: IsWindowVisible accepts any handle EnumWindows passes.
+    if unsafe { IsWindowVisible(hwnd) }.as_bool() {
+        // SAFETY: `matching` passes a pointer to its vector, which is not
+        // otherwise used while EnumWindows runs.
+        let windows = unsafe {
+            &mut *std::ptr::with_exposed_provenance_mut::<Vec<HWND>>(windows.0.cast_unsigned())
+        };
+        windows.push(hwnd);
+    }
+    BOOL::from(true)
+}
+
+/// Title of `hwnd`, empty for a null or closed window
+#[cfg(windows)]
+fn window_title(hwnd: HWND) -> String {
+    let mut buffer = [0u16; 512];
+    // SAFETY: buffer outlives the call, which writes at most its length.
+    let len = unsafe { GetWindowTextW(hwnd, &mut buffer) };
+    usize::try_from(len)
+        .ok()
+        .and_then(|len| buffer.get(..len))
+        .map(String::from_utf16_lossy)
+        .unwrap_or_default()
+}
+
+/// Press and release Alt, which lifts the foreground lock for this process
+#[cfg(windows)]
+fn tap_alt() {
+    let key = |flags: KEYBD_EVENT_FLAGS| INPUT {
+        r#type: INPUT_KEYBOARD,
+        Anonymous: INPUT_0 {
+            ki: KEYBDINPUT {
+                wVk: VK_MENU,
+                dwFlags: flags,
+                ..Default::default()
+            },
+        },
+    };
+    let inputs = [key(KEYBD_EVENT_FLAGS(0)), key(KEYEVENTF_KEYUP)];
+    let size = i32::try_from(std::mem::size_of::<INPUT>()).unwrap_or(i32::MAX);
+    // SAFETY: the inputs are fully initialized keyboard events and `size` is
+    // the size of one INPUT, as SendInput requires.
+    unsafe { SendInput(&inputs, size) };
+}
+
+/// Executable file name (e.g. `game.exe`) of the foreground window's process.
+///
+/// Returns `None` when there is no foreground window or the process cannot be queried.
+#[cfg(windows)]
+pub fn foreground_process_name() -> Option<String> {
+    // SAFETY: GetForegroundWindow has no preconditions and may return a null handle.
+    window_process_name(unsafe { GetForegroundWindow() })
+}
+
+/// Executable file name of the process owning `hwnd`
+#[cfg(windows)]
+fn window_process_name(hwnd: HWND) -> Option<String> {
+    if hwnd.is_invalid() {
+        return None;
+    }
+
+    let mut pid = 0u32;
+    // SAFETY: pid is a valid out pointer for the duration of the call.
+    unsafe { GetWindowThreadProcessId(hwnd, Some(&raw mut pid)) };
+    if pid == 0 {
+        return None;
+    }
+
+    process_name(pid)
+}
+
+/// Executable file name of the process with the given PID.
+#[cfg(windows)]
+pub fn process_name(pid: u32) -> Option<String> {
+    // SAFETY: OpenProcess with limited query rights has no invariants; the
+    // returned handle is closed below.
+    let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) }.ok()?;
+
+    let mut buffer = [0u16; 1024];
+    let mut len = u32::try_from(buffer.len()).unwrap_or(u32::MAX);
+    // SAFETY: buffer outlives the call and len holds its capacity in characters.
+    let result = unsafe {
+        QueryFullProcessImageNameW(
+            process,
+            PROCESS_NAME_WIN32,
+            PWSTR(buffer.as_mut_ptr()),
+            &raw mut len,
+        )
+    };
+    // SAFETY: process is a valid handle returned by OpenProcess above.
+    let _ = unsafe { CloseHandle(process) };
+    result.ok()?;
+
+    let path = String::from_utf16_lossy(buffer.get(..usize::try_from(len).ok()?)?);
+    path.rsplit(['\\', '/']).next().map(str::to_owned)
+}
+
+/// Foreground process lookup is only implemented on Windows.
+#[cfg(not(windows))]
+pub const fn foreground_process_name() -> Option<String> {
+    None
+}
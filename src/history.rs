@@ -0,0 +1,448 @@
+//! Opt-in transcription history file.
+//!
+//! Each typed transcription is appended as one line with a UTC timestamp, the
+//! application and window it was typed into and its word count, as JSON, plain
+//! text or Markdown, with or without the text itself. The history is one file
+//! or one file per day, readable by the current user only, and entries older
+//! than the configured number of days are pruned once per day.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+#[cfg(windows)]
+use windows::Win32::Foundation::{HLOCAL, LocalFree};
+#[cfg(windows)]
+use windows::Win32::Security::Authorization::{
+    ConvertStringSecurityDescriptorToSecurityDescriptorW, SDDL_REVISION_1, SE_FILE_OBJECT,
+    SetNamedSecurityInfoW,
+};
+#[cfg(windows)]
+use windows::Win32::Security::{
+    ACL, DACL_SECURITY_INFORMATION, GetSecurityDescriptorDacl, PROTECTED_DACL_SECURITY_INFORMATION,
+    PSECURITY_DESCRIPTOR,
+};
+#[cfg(windows)]
+use windows::core::{BOOL, HSTRING, w};
+
+use crate::paths;
+use crate::stats;
+
+/// Seconds in a day
+const SECS_PER_DAY: u64 = 86_400;
+
+/// Line format of the history file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryFormat {
+    /// One JSON object per line with `time`, `app`, `title`, `words` and `text`
+    Jsonl,
+    /// `time [app] text`, one transcription per line
+    Text,
+    /// Markdown list items, one transcription per item
+    Markdown,
+}
+
+impl FromStr for HistoryFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "jsonl" | "json" => Ok(Self::Jsonl),
+            "text" | "txt" => Ok(Self::Text),
+            "markdown" | "md" => Ok(Self::Markdown),
+            other => anyhow::bail!("unknown format {other:?}, use jsonl, text or markdown"),
+        }
+    }
+}
+
+/// How much of each dictation the history keeps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryDetail {
+    /// Time, target and word count together with the text
+    FullText,
+    /// Time, target and word count only, for time tracking
+    MetadataOnly,
+}
+
+impl FromStr for HistoryDetail {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "full-text" => Ok(Self::FullText),
+            "metadata-only" => Ok(Self::MetadataOnly),
+            other => anyhow::bail!("unknown detail {other:?}, use full-text or metadata-only"),
+        }
+    }
+}
+
+/// Where a transcription was typed, captured right after typing
+#[derive(Debug, Clone, Default)]
+pub struct Attribution {
+    /// Executable of the foreground window, if it could be resolved
+    pub app: Option<String>,
+    /// Title of the foreground window, if it has one
+    pub title: Option<String>,
+}
+
+/// One line of a JSONL history file
+#[derive(Debug, Serialize, Deserialize)]
+struct HistoryEntry {
+    /// UTC time the text was typed, ISO-8601
+    time: String,
+    /// Executable of the foreground window, if it could be resolved
+    app: Option<String>,
+    /// Title of the foreground window
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    /// Words typed
+    #[serde(default)]
+    words: usize,
+    /// Transcribed text, left out in metadata-only histories
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+}
+
+/// Appends transcriptions to the history file, if one is configured
+pub struct TranscriptionHistory {
+    /// Configured history file, `None` when the history is disabled
+    path: Option<PathBuf>,
+    /// Line format
+    format: HistoryFormat,
+    /// Whether the text itself is kept
+    detail: HistoryDetail,
+    /// Whether each day is written to its own file named after the date
+    daily: bool,
+    /// Days of entries to keep, 0 to keep everything
+    max_days: u64,
+    /// Day number (since 1970-01-01) on which old entries were last pruned
+    pruned_on: Option<u64>,
+}
+
+impl TranscriptionHistory {
+    /// History written to `path`, or to one file per day next to it, disabled with `None`
+    pub const fn new(
+        path: Option<PathBuf>,
+        format: HistoryFormat,
+        detail: HistoryDetail,
+        daily: bool,
+        max_days: u64,
+    ) -> Self {
+        Self {
+            path,
+            format,
+            detail,
+            daily,
+            max_days,
+            pruned_on: None,
+        }
+    }
+
+    /// File written to today, if the history is enabled
+    pub fn path(&self) -> Option<PathBuf> {
+        let path = self.path.as_deref()?;
+        Some(if self.daily {
+            daily_file(path, &date(now_secs() / SECS_PER_DAY))
+        } else {
+            path.to_path_buf()
+        })
+    }
+
+    /// Whether the history is split into one file per day
+    pub const fn is_daily(&self) -> bool {
+        self.daily
+    }
+
+    /// Append `text` typed into `target`; failures are logged and never propagated
+    pub fn record(&mut self, text: &str, target: Attribution) {
+        let Some(ref path) = self.path else {
+            return;
+        };
+        let secs = now_secs();
+        let today = secs / SECS_PER_DAY;
+
+        if self.max_days > 0 && self.pruned_on != Some(today) {
+            self.pruned_on = Some(today);
+            let cutoff = date(today.saturating_sub(self.max_days - 1));
+            let pruned = if self.daily {
+                prune_daily(path, &cutoff)
+            } else {
+                prune(path, self.format, &cutoff)
+            };
+            if let Err(e) = pruned {
+                warn!("Failed to prune transcription history: {:#}", e);
+            }
+        }
+
+        let entry = HistoryEntry {
+            time: timestamp(secs),
+            app: target.app,
+            title: target.title.filter(|title| !title.trim().is_empty()),
+            words: text.split_whitespace().count(),
+            text: (self.detail == HistoryDetail::FullText).then(|| text.to_owned()),
+        };
+        let file = if self.daily {
+            daily_file(path, &date(today))
+        } else {
+            path.clone()
+        };
+        let mut line = format_line(&entry, self.format);
+        if self.format == HistoryFormat::Markdown && !file.exists() {
+            line = format!("# Dictations {}\n\n{line}", date(today));
+        }
+        if let Err(e) = append(&file, &line) {
+            warn!("Failed to write transcription history: {:#}", e);
+        }
+    }
+}
+
+/// Seconds since the Unix epoch
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// File of `day` (`YYYY-MM-DD`) for the configured `path`, e.g. `transcriptions-2024-05-01.jsonl`
+fn daily_file(path: &Path, day: &str) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map_or_else(|| "transcriptions".into(), |stem| stem.to_string_lossy());
+    let name = match path.extension() {
+        Some(extension) => format!("{stem}-{day}.{}", extension.to_string_lossy()),
+        None => format!("{stem}-{day}"),
+    };
+    path.with_file_name(name)
+}
+
+/// Delete the daily files of `path` dated before `cutoff` (`YYYY-MM-DD`).
+fn prune_daily(path: &Path, cutoff: &str) -> Result<()> {
+    let Some(dir) = path.parent().filter(|dir| dir.exists()) else {
+        return Ok(());
+    };
+    // The date sits where daily_file puts it; the placeholder marks the spot
+    let pattern = daily_file(path, "YYYY-MM-DD");
+    let pattern = pattern
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let Some(start) = pattern.find("YYYY-MM-DD") else {
+        return Ok(());
+    };
+    let (prefix, suffix) = (&pattern[..start], &pattern[start + 10..]);
+    let entries =
+        std::fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let Some(day) = name
+            .strip_prefix(prefix)
+            .and_then(|rest| rest.strip_suffix(suffix))
+            .filter(|day| day.len() == 10)
+        else {
+            continue;
+        };
+        if day < cutoff {
+            std::fs::remove_file(entry.path())
+                .with_context(|| format!("Failed to remove {}", entry.path().display()))?;
+            removed += 1;
+        }
+    }
+    if removed > 0 {
+        info!(
+            "Transcription history: removed {} daily files before {}",
+            removed, cutoff
+        );
+    }
+    Ok(())
+}
+
+/// `entry` as one line of `format`, newline included
+fn format_line(entry: &HistoryEntry, format: HistoryFormat) -> String {
+    match format {
+        HistoryFormat::Jsonl => {
+            let json = serde_json::to_string(entry).unwrap_or_default();
+            format!("{json}\n")
+        }
+        HistoryFormat::Text => {
+            let mut line = entry.time.clone();
+            if let Some(ref app) = entry.app {
+                line.push_str(&format!(" [{app}]"));
+            }
+            match entry.text {
+                Some(ref text) => {
+                    line.push(' ');
+                    line.push_str(&single_line(text));
+                }
+                None => {
+                    if let Some(ref title) = entry.title {
+                        line.push_str(&format!(" {}", single_line(title)));
+                    }
+                    line.push_str(&format!(" ({} words)", entry.words));
+                }
+            }
+            line.push('\n');
+            line
+        }
+        HistoryFormat::Markdown => {
+            let mut line = format!("- {}", entry.time);
+            if let Some(ref app) = entry.app {
+                line.push_str(&format!(" **{app}**"));
+            }
+            if let Some(ref title) = entry.title {
+                line.push_str(&format!(" _{}_", single_line(title)));
+            }
+            line.push_str(&format!(" ({} words)", entry.words));
+            if let Some(ref text) = entry.text {
+                line.push_str(&format!(": {}", single_line(text)));
+            }
+            line.push('\n');
+            line
+        }
+    }
+}
+
+/// `text` with every run of whitespace, line breaks included, turned into one space
+fn single_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// UTC date of a line, `None` when it cannot be read
+fn line_date(line: &str, format: HistoryFormat) -> Option<String> {
+    let time = match format {
+        HistoryFormat::Jsonl => serde_json::from_str::<HistoryEntry>(line).ok()?.time,
+        HistoryFormat::Text => line.to_owned(),
+        HistoryFormat::Markdown => line.strip_prefix("- ")?.to_owned(),
+    };
+    time.get(..10).map(str::to_owned)
+}
+
+/// Append `line` with a single write, so a crash never leaves half an entry
+/// in the middle of the file.
+fn append(path: &Path, line: &str) -> Result<()> {
+    let mut file = open_private(path, true)?;
+    file.write_all(line.as_bytes())
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Rewrite the history without entries dated before `cutoff` (`YYYY-MM-DD`).
+///
+/// Lines without a readable date are kept.
+fn prune(path: &Path, format: HistoryFormat, cutoff: &str) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let kept: Vec<&str> = contents
+        .lines()
+        .filter(|line| line_date(line, format).is_none_or(|day| day.as_str() >= cutoff))
+        .collect();
+    let removed = contents.lines().count() - kept.len();
+    if removed == 0 {
+        return Ok(());
+    }
+
+    // Written under a temporary name so a crash mid-write keeps the old history
+    let temporary = path.with_extension("tmp");
+    if temporary.exists() {
+        std::fs::remove_file(&temporary)
+            .with_context(|| format!("Failed to remove {}", temporary.display()))?;
+    }
+    let mut file = open_private(&temporary, false)?;
+    for line in kept {
+        writeln!(file, "{line}")
+            .with_context(|| format!("Failed to write {}", temporary.display()))?;
+    }
+    drop(file);
+    std::fs::rename(&temporary, path)
+        .with_context(|| format!("Failed to replace {}", path.display()))?;
+    info!(
+        "Transcription history: pruned {} entries before {}",
+        removed, cutoff
+    );
+    Ok(())
+}
+
+/// Open `path` for writing, creating it readable by the current user only.
+fn open_private(path: &Path, append: bool) -> Result<File> {
+    paths::ensure_parent(path)?;
+    let created = !path.exists();
+    let mut options = OpenOptions::new();
+    options.create(true).write(true).append(append);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let file = options
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    #[cfg(windows)]
+    if created {
+        restrict_to_owner(path)?;
+    }
+    #[cfg(not(windows))]
+    let _ = created;
+    Ok(file)
+}
+
+/// Replace the inherited permissions of `path` with full access for its
+/// owner and the system account only.
+#[cfg(windows)]
+fn restrict_to_owner(path: &Path) -> Result<()> {
+    let mut descriptor = PSECURITY_DESCRIPTOR::default();
+    // SAFETY: the SDDL string is a valid static string; the descriptor it
+    // allocates is freed with LocalFree below, after the DACL taken from it
+    // was applied.
+    unsafe {
+        ConvertStringSecurityDescriptorToSecurityDescriptorW(
+            w!("D:P(A;;FA;;;OW)(A;;FA;;;SY)"),
+            SDDL_REVISION_1,
+            &mut descriptor,
+            None,
+        )
+        .context("Failed to build history file permissions")?;
+
+        let (mut present, mut defaulted) = (BOOL::default(), BOOL::default());
+        let mut dacl: *mut ACL = std::ptr::null_mut();
+        let result = GetSecurityDescriptorDacl(descriptor, &mut present, &mut dacl, &mut defaulted)
+            .context("Failed to build history file permissions")
+            .and_then(|()| {
+                SetNamedSecurityInfoW(
+                    &HSTRING::from(path.as_os_str()),
+                    SE_FILE_OBJECT,
+                    DACL_SECURITY_INFORMATION | PROTECTED_DACL_SECURITY_INFORMATION,
+                    None,
+                    None,
+                    Some(dacl),
+                    None,
+                )
+                .ok()
+                .with_context(|| format!("Failed to restrict access to {}", path.display()))
+            });
+        LocalFree(Some(HLOCAL(descriptor.0)));
+        result
+    }
+}
+
+/// `YYYY-MM-DD` of a day number since 1970-01-01
+fn date(day: u64) -> String {
+    let (year, month, day) = stats::civil_from_days(i64::try_from(day).unwrap_or(0));
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// ISO-8601 UTC timestamp of `secs` since the Unix epoch
+pub fn timestamp(secs: u64) -> String {
+    let time = secs % SECS_PER_DAY;
+    format!(
+        "{}T{:02}:{:02}:{:02}Z",
+        date(secs / SECS_PER_DAY),
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
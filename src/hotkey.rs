@@ -1,121 +1,977 @@
 //! Global hotkey handling
 
 use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver};
+
 use global_hotkey::{
-    GlobalHotKeyManager,
+    GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState,
     hotkey::{Code, HotKey, Modifiers},
 };
-use tracing::info;
+use tracing::{debug, info, warn};
+#[cfg(windows)]
+use windows::Win32::UI::Input::KeyboardAndMouse::GetAsyncKeyState;
+
+use crate::error::SttError;
+use crate::wake;
+
+/// Known-good alternatives probed when the configured hotkey cannot be registered,
+/// as (HOTKEY_MODIFIER, HOTKEY_KEY) pairs in config syntax.
+const HOTKEY_CANDIDATES: &[(&str, &str)] = &[
+    ("CTRL", "SPACE"),
+    ("ALT", "SPACE"),
+    ("CTRL+SHIFT", "SPACE"),
+    ("CTRL+SHIFT", "D"),
+    ("CTRL+ALT", "D"),
+    ("NONE", "F9"),
+    ("NONE", "F10"),
+    ("CTRL", "F12"),
+];
+
+/// Modifier names in the order they are written, with the flag each stands for
+const MODIFIER_NAMES: &[(&str, Modifiers)] = &[
+    ("CTRL", Modifiers::CONTROL),
+    ("ALT", Modifiers::ALT),
+    ("SHIFT", Modifiers::SHIFT),
+    ("WIN", Modifiers::SUPER),
+    ("SUPER", Modifiers::SUPER),
+];
+
+/// Key names and the keys they stand for; the first name of a key is the one shown
+const KEY_NAMES: &[(&str, Code)] = &[
+    ("WIN", Code::MetaLeft),
+    ("SUPER", Code::MetaLeft),
+    ("ALT", Code::AltLeft),
+    ("ALTRIGHT", Code::AltRight),
+    ("SPACE", Code::Space),
+    ("ENTER", Code::Enter),
+    ("RETURN", Code::Enter),
+    ("TAB", Code::Tab),
+    ("BACKSPACE", Code::Backspace),
+    ("ESC", Code::Escape),
+    ("ESCAPE", Code::Escape),
+    ("F1", Code::F1),
+    ("F2", Code::F2),
+    ("F3", Code::F3),
+    ("F4", Code::F4),
+    ("F5", Code::F5),
+    ("F6", Code::F6),
+    ("F7", Code::F7),
+    ("F8", Code::F8),
+    ("F9", Code::F9),
+    ("F10", Code::F10),
+    ("F11", Code::F11),
+    ("F12", Code::F12),
+    ("A", Code::KeyA),
+    ("B", Code::KeyB),
+    ("C", Code::KeyC),
+    ("D", Code::KeyD),
+    ("E", Code::KeyE),
+    ("F", Code::KeyF),
+    ("G", Code::KeyG),
+    ("H", Code::KeyH),
+    ("I", Code::KeyI),
+    ("J", Code::KeyJ),
+    ("K", Code::KeyK),
+    ("L", Code::KeyL),
+    ("M", Code::KeyM),
+    ("N", Code::KeyN),
+    ("O", Code::KeyO),
+    ("P", Code::KeyP),
+    ("Q", Code::KeyQ),
+    ("R", Code::KeyR),
+    ("S", Code::KeyS),
+    ("T", Code::KeyT),
+    ("U", Code::KeyU),
+    ("V", Code::KeyV),
+    ("W", Code::KeyW),
+    ("X", Code::KeyX),
+    ("Y", Code::KeyY),
+    ("Z", Code::KeyZ),
+];
+
+/// Modifier keys held with a hotkey, such as `CTRL+SHIFT`, or `NONE`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HotkeyModifier(Modifiers);
+
+impl HotkeyModifier {
+    /// No modifier, the key alone
+    pub const NONE: Self = Self(Modifiers::empty());
+
+    /// Ctrl alone
+    pub const CTRL: Self = Self(Modifiers::CONTROL);
+
+    /// These modifiers plus SHIFT, `None` when SHIFT is already among them
+    pub fn with_shift(self) -> Option<Self> {
+        (!self.0.contains(Modifiers::SHIFT)).then_some(Self(self.0 | Modifiers::SHIFT))
+    }
+
+    /// The modifiers to register, `None` for the key alone
+    fn modifiers(self) -> Option<Modifiers> {
+        (!self.0.is_empty()).then_some(self.0)
+    }
+}
+
+impl FromStr for HotkeyModifier {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s.is_empty() || s.eq_ignore_ascii_case("NONE") {
+            return Ok(Self::NONE);
+        }
+        s.split('+')
+            .try_fold(Modifiers::empty(), |mods, part| {
+                let part = part.trim().to_uppercase();
+                let Some(&(_, parsed)) = MODIFIER_NAMES.iter().find(|&&(name, _)| name == part)
+                else {
+                    anyhow::bail!(
+                        "invalid modifier {part:?}, use NONE or CTRL, ALT, SHIFT and WIN joined by +"
+                    );
+                };
+                Ok(mods | parsed)
+            })
+            .map(Self)
+    }
+}
+
+impl fmt::Display for HotkeyModifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return f.write_str("NONE");
+        }
+        // SUPER is listed twice; only its first name, WIN, is shown
+        let mut shown = Modifiers::empty();
+        let mut names = Vec::new();
+        for &(name, flag) in MODIFIER_NAMES {
+            if self.0.contains(flag) && !shown.contains(flag) {
+                shown |= flag;
+                names.push(name);
+            }
+        }
+        f.write_str(&names.join("+"))
+    }
+}
+
+/// Key of a hotkey, such as `SPACE`, `F9` or `D`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyName(Code);
+
+impl KeyName {
+    /// The space bar
+    pub const SPACE: Self = Self(Code::Space);
+}
+
+impl FromStr for KeyName {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let upper = s.trim().to_uppercase();
+        KEY_NAMES
+            .iter()
+            .find(|&&(name, _)| name == upper)
+            .map(|&(_, code)| Self(code))
+            .with_context(|| {
+                format!(
+                    "invalid key {s:?}, use A-Z, F1-F12, SPACE, ENTER, TAB, BACKSPACE, ESC, WIN, ALT or ALTRIGHT"
+                )
+            })
+    }
+}
+
+impl fmt::Display for KeyName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = KEY_NAMES
+            .iter()
+            .find(|&&(_, code)| code == self.0)
+            .map_or("?", |&(name, _)| name);
+        f.write_str(name)
+    }
+}
+
+/// Virtual-key codes for each modifier, any one of them counting as held
+const MODIFIER_KEYS: &[(Modifiers, &[u16])] = &[
+    (Modifiers::CONTROL, &[0x11]),
+    (Modifiers::ALT, &[0x12]),
+    (Modifiers::SHIFT, &[0x10]),
+    (Modifiers::SUPER, &[0x5B, 0x5C]),
+];
+
+/// What to do with a recording hotkey that is already held when the app starts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeldAtStartup {
+    /// Ignore it until it is released and pressed again
+    Ignore,
+    /// Start recording at once, as if it had just been pressed
+    Record,
+}
+
+impl FromStr for HeldAtStartup {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "ignore" => Ok(Self::Ignore),
+            "record" => Ok(Self::Record),
+            other => anyhow::bail!("unknown startup behavior {other:?}, use ignore or record"),
+        }
+    }
+}
+
+/// Which hotkeys are held, for telling state changes from keyboard auto-repeat
+#[derive(Debug, Default)]
+pub struct HotkeyStateTracker {
+    /// IDs of the hotkeys whose last event was a press
+    held: HashSet<u32>,
+    /// Recording hotkey found held at startup, until its release is seen
+    held_at_start: Option<(u32, HeldAtStartup)>,
+}
+
+impl HotkeyStateTracker {
+    /// Whether `event` changes its hotkey's state. Repeated presses of a held
+    /// key and releases of a key that is not held do not.
+    pub fn transition(&mut self, event: &GlobalHotKeyEvent) -> bool {
+        match event.state {
+            HotKeyState::Pressed => self.held.insert(event.id),
+            HotKeyState::Released => self.held.remove(&event.id),
+        }
+    }
+
+    /// Count `id` as held since before startup, dealing with its release as
+    /// `held` says
+    pub fn hold_at_startup(&mut self, id: u32, held: HeldAtStartup) {
+        self.held.insert(id);
+        self.held_at_start = Some((id, held));
+    }
+
+    /// Whether a hotkey held at startup was not seen released yet
+    pub const fn awaits_startup_release(&self) -> bool {
+        self.held_at_start.is_some()
+    }
+
+    /// Keep the `events` that change a key's state, in the order they happened.
+    ///
+    /// The release of a hotkey held at startup is dropped with `Ignore`. When
+    /// that release never comes, as no press was seen, the hotkey is released
+    /// once `key_is_up` reports its key up.
+    pub fn filter(
+        &mut self,
+        events: impl IntoIterator<Item = GlobalHotKeyEvent>,
+        key_is_up: impl FnOnce(u32) -> bool,
+    ) -> Vec<GlobalHotKeyEvent> {
+        let mut repeats = 0;
+        let mut changes: Vec<_> = events
+            .into_iter()
+            .filter(|event| {
+                if let Some((id, held)) = self.held_at_start
+                    && event.id == id
+                    && event.state == HotKeyState::Released
+                {
+                    self.held_at_start = None;
+                    if held == HeldAtStartup::Ignore {
+                        debug!("Ignored the release of the hotkey held at startup");
+                        self.forget(id);
+                        return false;
+                    }
+                }
+                let changed = self.transition(event);
+                repeats += usize::from(!changed);
+                changed
+            })
+            .collect();
+        if repeats > 0 {
+            debug!("Ignored {} repeated hotkey events", repeats);
+        }
+        if let Some((id, held)) = self.held_at_start
+            && key_is_up(id)
+        {
+            debug!("Hotkey held at startup released without an event");
+            self.held_at_start = None;
+            self.forget(id);
+            if held == HeldAtStartup::Record {
+                changes.push(GlobalHotKeyEvent {
+                    id,
+                    state: HotKeyState::Released,
+                });
+            }
+        }
+        changes
+    }
+
+    /// Count `id` as released, when its release event cannot arrive any more
+    pub fn forget(&mut self, id: u32) {
+        self.held.remove(&id);
+    }
+
+    /// Count every hotkey as released
+    pub fn clear(&mut self) {
+        self.held.clear();
+        self.held_at_start = None;
+    }
+}
 
 /// Hotkey listener
 pub struct HotkeyListener {
     /// Hotkey manager
-    _manager: GlobalHotKeyManager,
+    manager: GlobalHotKeyManager,
     /// Hotkey ID
     pub hotkey: HotKey,
+    /// Latch hotkey, registered only while a recording is in progress
+    latch: Option<HotKey>,
+    /// Escape, alone and with the hotkey's modifier, registered only while recording
+    cancel: Vec<HotKey>,
+    /// Optional secondary hotkey that records with whisper's translate task
+    translate: Option<HotKey>,
+    /// The hotkey with SHIFT added, recording in the secondary language
+    secondary: Option<HotKey>,
+    /// Hotkey events, forwarded by a handler that also wakes the event loop
+    events: Receiver<GlobalHotKeyEvent>,
+    /// Held hotkeys, so auto-repeated presses are dropped
+    tracker: HotkeyStateTracker,
 }
 
 impl HotkeyListener {
     /// Create new hotkey listener
-    pub fn new(modifier: &str, key: &str) -> Result<Self> {
-        let manager = GlobalHotKeyManager::new().context("Failed to create hotkey manager")?;
-
-        let modifiers = if modifier.is_empty() || modifier.to_uppercase() == "NONE" {
-            None
-        } else {
-            Some(Self::parse_modifier(modifier)?)
-        };
+    ///
+    /// The optional latch key shares the main hotkey's modifier and, like
+    /// Escape, is only grabbed globally while recording, see
+    /// [`Self::arm_recording_keys`]. A translate hotkey that fails to register
+    /// is disabled with a warning, as is the SHIFT variant for `secondary`.
+    pub fn new(
+        modifier: HotkeyModifier,
+        key: KeyName,
+        latch_key: Option<KeyName>,
+        translate: Option<(HotkeyModifier, KeyName)>,
+        secondary: bool,
+    ) -> Result<Self, SttError> {
+        let manager = GlobalHotKeyManager::new().map_err(SttError::HotkeyManager)?;
+        // Releases are detected on a helper thread and send no window message,
+        // so the handler wakes the loop itself; only the first handler set sticks
+        let (sender, events) = mpsc::channel();
+        GlobalHotKeyEvent::set_event_handler(Some(move |event| {
+            let _ = sender.send(event);
+            wake::wake();
+        }));
 
-        let code = Self::parse_key(key)?;
-
-        let hotkey = HotKey::new(modifiers, code);
+        let hotkey = Self::build_hotkey(modifier, key);
+        let latch = latch_key.map(|k| HotKey::new(Some(hotkey.mods), k.0));
+        let translate = translate.map(|(m, k)| Self::build_hotkey(m, k));
+        let shifted = modifier.with_shift();
+        if secondary && shifted.is_none() {
+            warn!("The hotkey already uses SHIFT, secondary language disabled");
+        }
+        let secondary = shifted
+            .filter(|_| secondary)
+            .map(|m| Self::build_hotkey(m, key));
+        // Escape is pressed with the modifier still held, or alone once latched
+        let taken: Vec<u32> = [Some(hotkey), latch, translate, secondary]
+            .into_iter()
+            .flatten()
+            .map(|hotkey| hotkey.id())
+            .collect();
+        let mut cancel = vec![HotKey::new(None, Code::Escape)];
+        if !hotkey.mods.is_empty() {
+            cancel.push(HotKey::new(Some(hotkey.mods), Code::Escape));
+        }
+        cancel.retain(|escape| !taken.contains(&escape.id()));
 
         // Try to register the hotkey
         match manager.register(hotkey) {
-            Ok(_) => {
-                let hotkey_desc = if modifier.is_empty() || modifier.to_uppercase() == "NONE" {
-                    key.to_string()
-                } else {
-                    format!("{} + {}", modifier, key)
-                };
-                info!("Registered hotkey: {}", hotkey_desc);
+            Ok(()) => {
+                info!("Registered hotkey: {}", Self::describe(modifier, key));
+                let translate = translate.filter(|&t| match manager.register(t) {
+                    Ok(()) => {
+                        info!("Registered translate hotkey");
+                        true
+                    }
+                    Err(e) => {
+                        warn!("Failed to register translate hotkey, disabling it: {}", e);
+                        false
+                    }
+                });
+                let secondary = secondary.filter(|&s| match manager.register(s) {
+                    Ok(()) => {
+                        info!("Registered secondary language hotkey: {}", s.into_string());
+                        true
+                    }
+                    Err(e) => {
+                        warn!(
+                            "Failed to register {}, secondary language disabled: {}",
+                            s.into_string(),
+                            e
+                        );
+                        false
+                    }
+                });
                 Ok(Self {
-                    _manager: manager,
+                    manager,
                     hotkey,
+                    latch,
+                    cancel,
+                    translate,
+                    secondary,
+                    events,
+                    tracker: HotkeyStateTracker::default(),
                 })
             }
-            Err(_e) => {
-                anyhow::bail!(
-                    "Failed to register hotkey {} + {}. This combination may be reserved by Windows. \
-                    Try a different combination like CTRL+SPACE or ALT+SPACE in your .env file.",
-                    modifier,
-                    key
-                )
+            Err(source) => Err(SttError::HotkeyTaken {
+                hotkey: Self::describe(modifier, key),
+                free: Self::probe_candidates(&manager, hotkey),
+                source,
+            }),
+        }
+    }
+
+    /// Find which of [`HOTKEY_CANDIDATES`] can currently be registered.
+    ///
+    /// Each successful registration is released immediately so the probe never
+    /// keeps a key away from other applications.
+    fn probe_candidates(manager: &GlobalHotKeyManager, failed: HotKey) -> Vec<String> {
+        HOTKEY_CANDIDATES
+            .iter()
+            .filter_map(|&(modifier, key)| Some((modifier.parse().ok()?, key.parse().ok()?)))
+            .filter(|&(modifier, key)| {
+                let candidate = Self::build_hotkey(modifier, key);
+                candidate != failed && Self::probe(manager, candidate)
+            })
+            .map(|(modifier, key)| Self::describe(modifier, key))
+            .collect()
+    }
+
+    /// Common hotkeys that can currently be registered, as (modifier, key) pairs in config syntax
+    pub fn free_candidates() -> Result<Vec<(&'static str, &'static str)>> {
+        let manager = GlobalHotKeyManager::new().context("Failed to create hotkey manager")?;
+        Ok(HOTKEY_CANDIDATES
+            .iter()
+            .copied()
+            .filter(|&(modifier, key)| {
+                Self::parse_hotkey(modifier, key)
+                    .is_ok_and(|candidate| Self::probe(&manager, candidate))
+            })
+            .collect())
+    }
+
+    /// Check whether a config-style hotkey parses and can currently be registered
+    pub fn is_available(modifier: &str, key: &str) -> Result<bool> {
+        let hotkey = Self::parse_hotkey(modifier, key)?;
+        let manager = GlobalHotKeyManager::new().context("Failed to create hotkey manager")?;
+        Ok(Self::probe(&manager, hotkey))
+    }
+
+    /// Register `hotkey` and release it again right away, reporting whether it was free
+    fn probe(manager: &GlobalHotKeyManager, hotkey: HotKey) -> bool {
+        if manager.register(hotkey).is_err() {
+            return false;
+        }
+        if let Err(e) = manager.unregister(hotkey) {
+            warn!(
+                "Failed to release probed hotkey {}: {}",
+                hotkey.into_string(),
+                e
+            );
+        }
+        true
+    }
+
+    /// Build a hotkey from a modifier and key
+    fn build_hotkey(modifier: HotkeyModifier, key: KeyName) -> HotKey {
+        HotKey::new(modifier.modifiers(), key.0)
+    }
+
+    /// Build a hotkey from config-style modifier and key strings
+    fn parse_hotkey(modifier: &str, key: &str) -> Result<HotKey> {
+        Ok(Self::build_hotkey(modifier.parse()?, key.parse()?))
+    }
+
+    /// Human-readable description of a hotkey
+    fn describe(modifier: HotkeyModifier, key: KeyName) -> String {
+        if modifier == HotkeyModifier::NONE {
+            key.to_string()
+        } else {
+            format!("{modifier}+{key}")
+        }
+    }
+
+    /// Unregister the recording hotkeys while paused so other applications can use them
+    ///
+    /// Releases of keys held meanwhile never arrive, so every key counts as released.
+    pub fn set_paused(&mut self, paused: bool) -> Result<()> {
+        self.tracker.clear();
+        let hotkeys: Vec<HotKey> = std::iter::once(self.hotkey)
+            .chain(self.translate)
+            .chain(self.secondary)
+            .collect();
+        if paused {
+            self.manager
+                .unregister_all(&hotkeys)
+                .context("Failed to unregister hotkey")
+        } else {
+            self.manager
+                .register_all(&hotkeys)
+                .context("Failed to re-register hotkey")
+        }
+    }
+
+    /// Whether a translate hotkey is registered
+    pub const fn has_translate(&self) -> bool {
+        self.translate.is_some()
+    }
+
+    /// Check whether an event ID belongs to the translate hotkey
+    pub fn is_translate(&self, id: u32) -> bool {
+        self.translate.is_some_and(|translate| translate.id() == id)
+    }
+
+    /// Check whether an event ID belongs to the hotkey with SHIFT for the secondary language
+    pub fn is_secondary(&self, id: u32) -> bool {
+        self.secondary.is_some_and(|secondary| secondary.id() == id)
+    }
+
+    /// Whether hotkeys `a` and `b` are one key for a recording session, as
+    /// the hotkey and its SHIFT variant are, so releasing either stops it
+    pub fn same_session(&self, a: u32, b: u32) -> bool {
+        let main = |id| id == self.hotkey.id() || self.is_secondary(id);
+        a == b || (main(a) && main(b))
+    }
+
+    /// Look for a recording hotkey already held down, as at startup, and
+    /// count it as pressed so its auto-repeat does not start a recording.
+    ///
+    /// Returns the hotkey to start recording with when `held` is `Record`.
+    /// With `Ignore` its release is dropped too, so nothing happens until
+    /// it is pressed again.
+    pub fn sample_held_keys(&mut self, held: HeldAtStartup) -> Option<u32> {
+        // The SHIFT variant first, as the main hotkey's keys are down for it too
+        let hotkey = self
+            .secondary
+            .into_iter()
+            .chain(self.translate)
+            .chain(std::iter::once(self.hotkey))
+            .find(|&hotkey| is_down(hotkey))?;
+        info!(
+            "Hotkey {} is held at startup, {}",
+            hotkey.into_string(),
+            match held {
+                HeldAtStartup::Ignore => "ignoring it until released",
+                HeldAtStartup::Record => "recording right away",
             }
+        );
+        self.tracker.hold_at_startup(hotkey.id(), held);
+        (held == HeldAtStartup::Record).then_some(hotkey.id())
+    }
+
+    /// Whether a hotkey held at startup was not seen released yet, so the
+    /// loop keeps checking the key
+    pub const fn awaits_startup_release(&self) -> bool {
+        self.tracker.awaits_startup_release()
+    }
+
+    /// Take every queued hotkey event that changes a key's state, in the
+    /// order they happened, see [`HotkeyStateTracker::filter`]
+    pub fn next_events(&mut self) -> Vec<GlobalHotKeyEvent> {
+        let hotkeys = self.recording_hotkeys();
+        self.tracker
+            .filter(self.events.try_iter(), |id| key_is_up(hotkeys, id))
+    }
+
+    /// Count the hotkey `id` as released after its release event was lost
+    pub fn forget_press(&mut self, id: u32) {
+        self.tracker.forget(id);
+    }
+
+    /// Whether the key of the recording hotkey `id` is physically up.
+    ///
+    /// False when it is down or its state cannot be read, so a missed release
+    /// is only assumed when the keyboard reports one.
+    pub fn key_is_up(&self, id: u32) -> bool {
+        key_is_up(self.recording_hotkeys(), id)
+    }
+
+    /// The main hotkey and, when registered, its translate and SHIFT variants
+    fn recording_hotkeys(&self) -> [Option<HotKey>; 3] {
+        [Some(self.hotkey), self.translate, self.secondary]
+    }
+
+    /// Check whether an event ID belongs to the latch hotkey
+    pub fn is_latch(&self, id: u32) -> bool {
+        self.latch.is_some_and(|latch| latch.id() == id)
+    }
+
+    /// Check whether an event ID belongs to one of the Escape keys
+    pub fn is_cancel(&self, id: u32) -> bool {
+        self.cancel.iter().any(|escape| escape.id() == id)
+    }
+
+    /// Start listening for the latch key and Escape for the duration of a recording
+    pub fn arm_recording_keys(&self) {
+        if let Some(latch) = self.latch
+            && let Err(e) = self.manager.register(latch)
+        {
+            warn!(
+                "Failed to register latch key, hold-to-lock unavailable: {}",
+                e
+            );
+        }
+        for &escape in &self.cancel {
+            if let Err(e) = self.manager.register(escape) {
+                warn!(
+                    "Failed to register Escape, cancelling may be unavailable: {}",
+                    e
+                );
+            }
+        }
+    }
+
+    /// Release the latch key and Escape so they are not grabbed outside of recordings
+    ///
+    /// A release after unregistering never arrives, so they count as released.
+    pub fn disarm_recording_keys(&mut self) {
+        if let Some(latch) = self.latch {
+            let _ = self.manager.unregister(latch);
+            self.tracker.forget(latch.id());
+        }
+        for &escape in &self.cancel {
+            let _ = self.manager.unregister(escape);
+            self.tracker.forget(escape.id());
+        }
+    }
+}
+
+/// Whether the key of `hotkey` and all of its modifiers are physically down
+fn is_down(hotkey: HotKey) -> bool {
+    virtual_key(hotkey.key).is_some_and(key_down)
+        && MODIFIER_KEYS
+            .iter()
+            .filter(|&&(modifier, _)| hotkey.mods.contains(modifier))
+            .all(|&(_, keys)| keys.iter().copied().any(key_down))
+}
+
+/// Whether the key of the hotkey `id` among `hotkeys` is physically up, false
+/// when it is down, unknown or its state cannot be read
+fn key_is_up(hotkeys: [Option<HotKey>; 3], id: u32) -> bool {
+    let Some(hotkey) = hotkeys
+        .into_iter()
+        .flatten()
+        .find(|hotkey| hotkey.id() == id)
+    else {
+        return false;
+    };
+    let Some(vk) = virtual_key(hotkey.key) else {
+        return false;
+    };
+    #[cfg(windows)]
+    // SAFETY: GetAsyncKeyState only reads the key state; any code is valid.
+    return unsafe { GetAsyncKeyState(i32::from(vk)) } >= 0;
+    #[cfg(not(windows))]
+    {
+        let _ = vk;
+        false
+    }
+}
+
+/// Whether the key with virtual-key code `vk` is physically down, false when
+/// its state cannot be read
+fn key_down(vk: u16) -> bool {
+    #[cfg(windows)]
+    // SAFETY: GetAsyncKeyState only reads the key state; any code is valid.
+    return unsafe { GetAsyncKeyState(i32::from(vk)) } < 0;
+    #[cfg(not(windows))]
+    {
+        let _ = vk;
+        false
+    }
+}
+
+/// Windows virtual-key code of a key [`KeyName`] accepts
+const fn virtual_key(code: Code) -> Option<u16> {
+    let vk = match code {
+        Code::MetaLeft => 0x5B,
+        Code::AltLeft => 0xA4,
+        Code::AltRight => 0xA5,
+        Code::Space => 0x20,
+        Code::Enter => 0x0D,
+        Code::Tab => 0x09,
+        Code::Backspace => 0x08,
+        Code::Escape => 0x1B,
+        Code::F1 => 0x70,
+        Code::F2 => 0x71,
+        Code::F3 => 0x72,
+        Code::F4 => 0x73,
+        Code::F5 => 0x74,
+        Code::F6 => 0x75,
+        Code::F7 => 0x76,
+        Code::F8 => 0x77,
+        Code::F9 => 0x78,
+        Code::F10 => 0x79,
+        Code::F11 => 0x7A,
+        Code::F12 => 0x7B,
+        Code::KeyA => 0x41,
+        Code::KeyB => 0x42,
+        Code::KeyC => 0x43,
+        Code::KeyD => 0x44,
+        Code::KeyE => 0x45,
+        Code::KeyF => 0x46,
+        Code::KeyG => 0x47,
+        Code::KeyH => 0x48,
+        Code::KeyI => 0x49,
+        Code::KeyJ => 0x4A,
+        Code::KeyK => 0x4B,
+        Code::KeyL => 0x4C,
+        Code::KeyM => 0x4D,
+        Code::KeyN => 0x4E,
+        Code::KeyO => 0x4F,
+        Code::KeyP => 0x50,
+        Code::KeyQ => 0x51,
+        Code::KeyR => 0x52,
+        Code::KeyS => 0x53,
+        Code::KeyT => 0x54,
+        Code::KeyU => 0x55,
+        Code::KeyV => 0x56,
+        Code::KeyW => 0x57,
+        Code::KeyX => 0x58,
+        Code::KeyY => 0x59,
+        Code::KeyZ => 0x5A,
+        _ => return None,
+    };
+    Some(vk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Id of the recording hotkey in the sequences
+    const RECORD: u32 = 1;
+    /// Id of a second hotkey, such as translate
+    const TRANSLATE: u32 = 2;
+
+    /// Press event of hotkey `id`
+    const fn press(id: u32) -> GlobalHotKeyEvent {
+        GlobalHotKeyEvent {
+            id,
+            state: HotKeyState::Pressed,
         }
     }
 
-    /// Parse modifier string to Modifiers
-    fn parse_modifier(modifier: &str) -> Result<Modifiers> {
-        match modifier.to_uppercase().as_str() {
-            "CTRL" => Ok(Modifiers::CONTROL),
-            "ALT" => Ok(Modifiers::ALT),
-            "SHIFT" => Ok(Modifiers::SHIFT),
-            "WIN" | "SUPER" => Ok(Modifiers::SUPER),
-            _ => anyhow::bail!("Invalid modifier: {}", modifier),
-        }
-    }
-
-    /// Parse key string to Code
-    fn parse_key(key: &str) -> Result<Code> {
-        match key.to_uppercase().as_str() {
-            "WIN" | "SUPER" => Ok(Code::MetaLeft),
-            "ALT" => Ok(Code::AltLeft),
-            "ALTRIGHT" => Ok(Code::AltRight),
-            "SPACE" => Ok(Code::Space),
-            "ENTER" | "RETURN" => Ok(Code::Enter),
-            "TAB" => Ok(Code::Tab),
-            "BACKSPACE" => Ok(Code::Backspace),
-            "ESC" | "ESCAPE" => Ok(Code::Escape),
-            "F1" => Ok(Code::F1),
-            "F2" => Ok(Code::F2),
-            "F3" => Ok(Code::F3),
-            "F4" => Ok(Code::F4),
-            "F5" => Ok(Code::F5),
-            "F6" => Ok(Code::F6),
-            "F7" => Ok(Code::F7),
-            "F8" => Ok(Code::F8),
-            "F9" => Ok(Code::F9),
-            "F10" => Ok(Code::F10),
-            "F11" => Ok(Code::F11),
-            "F12" => Ok(Code::F12),
-            "A" => Ok(Code::KeyA),
-            "B" => Ok(Code::KeyB),
-            "C" => Ok(Code::KeyC),
-            "D" => Ok(Code::KeyD),
-            "E" => Ok(Code::KeyE),
-            "F" => Ok(Code::KeyF),
-            "G" => Ok(Code::KeyG),
-            "H" => Ok(Code::KeyH),
-            "I" => Ok(Code::KeyI),
-            "J" => Ok(Code::KeyJ),
-            "K" => Ok(Code::KeyK),
-            "L" => Ok(Code::KeyL),
-            "M" => Ok(Code::KeyM),
-            "N" => Ok(Code::KeyN),
-            "O" => Ok(Code::KeyO),
-            "P" => Ok(Code::KeyP),
-            "Q" => Ok(Code::KeyQ),
-            "R" => Ok(Code::KeyR),
-            "S" => Ok(Code::KeyS),
-            "T" => Ok(Code::KeyT),
-            "U" => Ok(Code::KeyU),
-            "V" => Ok(Code::KeyV),
-            "W" => Ok(Code::KeyW),
-            "X" => Ok(Code::KeyX),
-            "Y" => Ok(Code::KeyY),
-            "Z" => Ok(Code::KeyZ),
-            _ => anyhow::bail!("Invalid key: {}", key),
+    /// Release event of hotkey `id`
+    const fn release(id: u32) -> GlobalHotKeyEvent {
+        GlobalHotKeyEvent {
+            id,
+            state: HotKeyState::Released,
         }
     }
+
+    /// Events of `sequence` that `tracker` lets through, in order
+    fn changes(
+        tracker: &mut HotkeyStateTracker,
+        sequence: &[GlobalHotKeyEvent],
+    ) -> Vec<GlobalHotKeyEvent> {
+        sequence
+            .iter()
+            .filter(|event| tracker.transition(event))
+            .copied()
+            .collect()
+    }
+
+    #[test]
+    fn auto_repeat_keeps_only_the_first_press() {
+        let mut tracker = HotkeyStateTracker::default();
+        let sequence = [
+            press(RECORD),
+            press(RECORD),
+            press(RECORD),
+            press(RECORD),
+            release(RECORD),
+        ];
+        assert_eq!(
+            changes(&mut tracker, &sequence),
+            [press(RECORD), release(RECORD)],
+            "repeats not dropped"
+        );
+    }
+
+    #[test]
+    fn a_release_without_a_press_is_dropped() {
+        let mut tracker = HotkeyStateTracker::default();
+        let sequence = [release(RECORD), release(RECORD), press(RECORD)];
+        assert_eq!(
+            changes(&mut tracker, &sequence),
+            [press(RECORD)],
+            "stray releases passed through"
+        );
+    }
+
+    #[test]
+    fn each_hotkey_is_tracked_on_its_own() {
+        let mut tracker = HotkeyStateTracker::default();
+        let sequence = [
+            press(RECORD),
+            press(TRANSLATE),
+            press(RECORD),
+            release(TRANSLATE),
+            press(TRANSLATE),
+            release(RECORD),
+            release(RECORD),
+        ];
+        assert_eq!(
+            changes(&mut tracker, &sequence),
+            [
+                press(RECORD),
+                press(TRANSLATE),
+                release(TRANSLATE),
+                press(TRANSLATE),
+                release(RECORD),
+            ],
+            "interleaved hotkeys mixed up"
+        );
+    }
+
+    #[test]
+    fn a_missing_release_swallows_the_next_press_until_forgotten() {
+        let mut tracker = HotkeyStateTracker::default();
+        // The release was lost, so the next press looks like a repeat
+        assert_eq!(
+            changes(&mut tracker, &[press(RECORD), press(RECORD)]),
+            [press(RECORD)],
+            "press after a lost release not taken for a repeat"
+        );
+        tracker.forget(RECORD);
+        assert_eq!(
+            changes(&mut tracker, &[press(RECORD), release(RECORD)]),
+            [press(RECORD), release(RECORD)],
+            "forgotten hotkey not pressed afresh"
+        );
+    }
+
+    #[test]
+    fn a_held_hotkey_only_passes_its_release() {
+        let mut tracker = HotkeyStateTracker::default();
+        tracker.hold_at_startup(RECORD, HeldAtStartup::Record);
+        let sequence = [press(RECORD), press(RECORD), release(RECORD)];
+        assert_eq!(
+            changes(&mut tracker, &sequence),
+            [release(RECORD)],
+            "press of a hotkey already held passed through"
+        );
+    }
+
+    #[test]
+    fn clearing_releases_every_hotkey() {
+        let mut tracker = HotkeyStateTracker::default();
+        changes(&mut tracker, &[press(RECORD), press(TRANSLATE)]);
+        tracker.clear();
+        let sequence = [release(RECORD), press(TRANSLATE), press(RECORD)];
+        assert_eq!(
+            changes(&mut tracker, &sequence),
+            [press(TRANSLATE), press(RECORD)],
+            "hotkeys still held after clearing"
+        );
+    }
+
+    /// Key state of a keyboard on which every key is still down
+    fn still_down(_: u32) -> bool {
+        false
+    }
+
+    #[test]
+    fn the_release_of_a_key_ignored_at_startup_is_dropped() {
+        let mut tracker = HotkeyStateTracker::default();
+        tracker.hold_at_startup(RECORD, HeldAtStartup::Ignore);
+        let sequence = [press(RECORD), press(RECORD), release(RECORD)];
+        assert_eq!(
+            tracker.filter(sequence, still_down),
+            [],
+            "startup hold or its release passed through"
+        );
+        assert!(!tracker.awaits_startup_release(), "release not taken");
+        assert_eq!(
+            tracker.filter([press(RECORD), release(RECORD)], still_down),
+            [press(RECORD), release(RECORD)],
+            "next real press dropped"
+        );
+    }
+
+    #[test]
+    fn the_release_of_a_key_recording_at_startup_passes() {
+        let mut tracker = HotkeyStateTracker::default();
+        tracker.hold_at_startup(RECORD, HeldAtStartup::Record);
+        assert_eq!(
+            tracker.filter([press(RECORD), release(RECORD)], still_down),
+            [release(RECORD)],
+            "release of the startup recording dropped"
+        );
+        assert!(!tracker.awaits_startup_release(), "release not taken");
+    }
+
+    #[test]
+    fn other_hotkeys_pass_while_the_startup_release_is_awaited() {
+        let mut tracker = HotkeyStateTracker::default();
+        tracker.hold_at_startup(RECORD, HeldAtStartup::Ignore);
+        assert_eq!(
+            tracker.filter([press(TRANSLATE), release(TRANSLATE)], still_down),
+            [press(TRANSLATE), release(TRANSLATE)],
+            "another hotkey's events dropped"
+        );
+        assert!(
+            tracker.awaits_startup_release(),
+            "stopped waiting too early"
+        );
+    }
+
+    #[test]
+    fn a_startup_key_released_without_an_event_is_released_once_up() {
+        for (held, expected) in [
+            (HeldAtStartup::Ignore, Vec::new()),
+            (HeldAtStartup::Record, vec![release(RECORD)]),
+        ] {
+            let mut tracker = HotkeyStateTracker::default();
+            tracker.hold_at_startup(RECORD, held);
+            assert_eq!(
+                tracker.filter([], still_down),
+                [],
+                "released while the key is down with {held:?}"
+            );
+            assert!(tracker.awaits_startup_release(), "gave up with {held:?}");
+            assert_eq!(
+                tracker.filter([], |id| id == RECORD),
+                expected,
+                "wrong release once the key is up with {held:?}"
+            );
+            assert!(
+                !tracker.awaits_startup_release(),
+                "still waiting with {held:?}"
+            );
+            assert_eq!(
+                tracker.filter([press(RECORD)], still_down),
+                [press(RECORD)],
+                "next press taken for a repeat with {held:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn a_release_event_settles_the_startup_key_before_its_state_is_read() {
+        let mut tracker = HotkeyStateTracker::default();
+        tracker.hold_at_startup(RECORD, HeldAtStartup::Record);
+        assert_eq!(
+            tracker.filter([release(RECORD)], |_| true),
+            [release(RECORD)],
+            "release reported twice"
+        );
+    }
+
+    #[test]
+    fn clearing_stops_waiting_for_the_startup_release() {
+        let mut tracker = HotkeyStateTracker::default();
+        tracker.hold_at_startup(RECORD, HeldAtStartup::Ignore);
+        tracker.clear();
+        assert!(!tracker.awaits_startup_release(), "still waiting");
+        assert_eq!(
+            tracker.filter([press(RECORD), release(RECORD)], |_| true),
+            [press(RECORD), release(RECORD)],
+            "real press or release dropped after clearing"
+        );
+    }
 }
@@ -0,0 +1,236 @@
+//! Local HTTP status and control endpoint.
+//!
+//! Serves `GET /status` and `POST /record/start`, `/record/stop` and
+//! `/record/cancel` on 127.0.0.1 for dashboards, answering with the same JSON
+//! as the control pipe. Only requests addressed to localhost are served, and
+//! a configured token must be sent as `Authorization: Bearer <token>`.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use tracing::{debug, info, warn};
+
+use crate::ipc::{self, IpcCommand, IpcRequest, IpcResponse};
+
+/// Longest a client may take to send its request
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Largest request head accepted, request line and headers together
+const MAX_HEAD: u64 = 8192;
+
+/// A parsed request head
+struct Request {
+    /// `GET`, `POST`, ...
+    method: String,
+    /// Path without the query string
+    path: String,
+    /// Value of the `Host` header
+    host: Option<String>,
+    /// Value of the `Authorization` header
+    authorization: Option<String>,
+    /// Whether a browser sent it on behalf of a web page
+    has_origin: bool,
+}
+
+/// Listen on `port` of the loopback address and serve clients on a helper thread.
+///
+/// Fails only if the port cannot be bound; the app runs on without the endpoint.
+pub fn start(port: u16, token: Option<String>, requests: Sender<IpcRequest>) -> Result<()> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+        .with_context(|| format!("Failed to listen on 127.0.0.1:{port}"))?;
+    info!("Serving status and control on http://127.0.0.1:{}", port);
+    std::thread::Builder::new()
+        .name("http-server".to_owned())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        if !serve(stream, port, token.as_deref(), &requests) {
+                            break;
+                        }
+                    }
+                    Err(e) => debug!("Failed to accept HTTP client: {}", e),
+                }
+            }
+        })
+        .context("Failed to start HTTP server thread")?;
+    Ok(())
+}
+
+/// Answer one request; false once the event loop is gone.
+fn serve(
+    mut stream: TcpStream,
+    port: u16,
+    token: Option<&str>,
+    requests: &Sender<IpcRequest>,
+) -> bool {
+    if let Err(e) = stream.set_read_timeout(Some(READ_TIMEOUT)) {
+        debug!("Failed to set HTTP read timeout: {}", e);
+    }
+    let request = match read_request(&stream) {
+        Ok(request) => request,
+        Err(e) => {
+            respond(&mut stream, 400, &IpcResponse::failed(format!("{e:#}")));
+            return true;
+        }
+    };
+
+    // A web page may reach localhost through the browser, or through a DNS
+    // name rebound to 127.0.0.1; neither is trusted without a token
+    let local_host = request.host.as_deref().is_some_and(|host| {
+        host == format!("127.0.0.1:{port}") || host == format!("localhost:{port}")
+    });
+    if !local_host {
+        respond(
+            &mut stream,
+            403,
+            &IpcResponse::failed("not a localhost request"),
+        );
+        return true;
+    }
+    let authorized = match token {
+        Some(token) => request
+            .authorization
+            .as_deref()
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|sent| tokens_match(sent.trim(), token)),
+        None => !request.has_origin,
+    };
+    if !authorized {
+        warn!("Refused unauthorized HTTP request for {}", request.path);
+        respond(
+            &mut stream,
+            401,
+            &IpcResponse::failed("missing or wrong token"),
+        );
+        return true;
+    }
+
+    let command = match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/status") => IpcCommand::Status,
+        ("POST", "/record/start") => IpcCommand::Start,
+        ("POST", "/record/stop") => IpcCommand::Stop,
+        ("POST", "/record/cancel") => IpcCommand::Cancel,
+        (_, "/status" | "/record/start" | "/record/stop" | "/record/cancel") => {
+            respond(&mut stream, 405, &IpcResponse::failed("method not allowed"));
+            return true;
+        }
+        _ => {
+            respond(&mut stream, 404, &IpcResponse::failed("not found"));
+            return true;
+        }
+    };
+    info!("HTTP command: {}", command);
+    let Some(response) = ipc::dispatch(requests, command) else {
+        return false;
+    };
+    let status = if response.ok { 200 } else { 409 };
+    respond(&mut stream, status, &response);
+    true
+}
+
+/// Whether the `sent` token equals `token`, compared in time that depends
+/// only on their length so a caller cannot guess it byte by byte
+fn tokens_match(sent: &str, token: &str) -> bool {
+    sent.len() == token.len()
+        && sent
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |difference, (a, b)| {
+                // Kept from being turned into an early exit
+                std::hint::black_box(difference | (a ^ b))
+            })
+            == 0
+}
+
+/// Read the request line and headers; a body is never needed and left unread.
+fn read_request(stream: &TcpStream) -> Result<Request> {
+    let mut reader = BufReader::new(stream).take(MAX_HEAD);
+    let mut line = String::new();
+    reader
+        .read_line(&mut line)
+        .context("Failed to read request")?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        anyhow::bail!("malformed request line {:?}", line.trim());
+    };
+    let mut request = Request {
+        method: method.to_owned(),
+        path: target.split('?').next().unwrap_or(target).to_owned(),
+        host: None,
+        authorization: None,
+        has_origin: false,
+    };
+
+    loop {
+        line.clear();
+        if reader
+            .read_line(&mut line)
+            .context("Failed to read headers")?
+            == 0
+        {
+            anyhow::bail!("request headers were cut off");
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            return Ok(request);
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            continue;
+        };
+        let value = value.trim().to_owned();
+        match name.trim().to_lowercase().as_str() {
+            "host" => request.host = Some(value.to_lowercase()),
+            "authorization" => request.authorization = Some(value),
+            "origin" => request.has_origin = true,
+            _ => {}
+        }
+    }
+}
+
+/// Write `body` as JSON with `status`, closing the connection afterwards.
+fn respond(stream: &mut TcpStream, status: u16, body: &IpcResponse) {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Conflict",
+    };
+    let json = serde_json::to_string(body).unwrap_or_default();
+    let response = format!(
+        "HTTP/1.1 {status} {reason}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{json}",
+        json.len()
+    );
+    if let Err(e) = stream.write_all(response.as_bytes()) {
+        debug!("Failed to answer HTTP client: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_exact_token_matches() {
+        assert!(tokens_match("s3cret", "s3cret"), "the right token refused");
+        assert!(
+            !tokens_match("s3creT", "s3cret"),
+            "a token one byte off accepted"
+        );
+        assert!(
+            !tokens_match("s3cre", "s3cret"),
+            "a prefix of the token accepted"
+        );
+        assert!(
+            !tokens_match("s3crets", "s3cret"),
+            "a longer token accepted"
+        );
+        assert!(!tokens_match("", "s3cret"), "an empty token accepted");
+    }
+}
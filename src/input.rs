@@ -3,8 +3,10 @@
 use std::thread;
 use std::time::Duration;
 
-use anyhow::Result;
-use enigo::{Enigo, Keyboard, Settings};
+use anyhow::{Context, Result};
+use enigo::{Direction, Enigo, Key, Keyboard, Settings};
+
+use crate::error::SttError;
 
 /// Text injector
 pub struct TextInjector {
@@ -20,12 +22,35 @@ impl TextInjector {
         }
     }
 
+    /// Check that the keyboard simulation backend can be created
+    pub fn check() -> Result<()> {
+        Enigo::new(&Settings::default())
+            .map(drop)
+            .context("Keyboard simulation is unavailable")
+    }
+
     /// Type text into active window
-    pub fn inject(&mut self, text: &str) -> Result<()> {
+    pub fn inject(&mut self, text: &str) -> Result<(), SttError> {
         thread::sleep(Duration::from_millis(100));
 
         // Use text() method which is more reliable for Unicode on Windows
-        self.enigo.text(text)?;
+        self.enigo.text(text).map_err(SttError::Injection)?;
+
+        Ok(())
+    }
+
+    /// Press Backspace `backspaces` times in the active window, then type `text`
+    pub fn erase_and_type(&mut self, backspaces: usize, text: &str) -> Result<(), SttError> {
+        thread::sleep(Duration::from_millis(100));
+
+        for _ in 0..backspaces {
+            self.enigo
+                .key(Key::Backspace, Direction::Click)
+                .map_err(SttError::Injection)?;
+        }
+        if !text.is_empty() {
+            self.enigo.text(text).map_err(SttError::Injection)?;
+        }
 
         Ok(())
     }
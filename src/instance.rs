@@ -0,0 +1,174 @@
+//! Single-instance enforcement.
+//!
+//! The first instance holds a named mutex for its lifetime. A second launch
+//! finds the mutex taken, signals a named event so the running instance can
+//! say it is already running, and exits before registering any hotkey.
+
+use std::sync::mpsc::{self, Receiver};
+
+#[cfg(windows)]
+use anyhow::Context;
+use anyhow::Result;
+#[cfg(windows)]
+use tracing::{info, warn};
+
+#[cfg(windows)]
+use windows::Win32::Foundation::{
+    CloseHandle, HANDLE, WAIT_ABANDONED, WAIT_OBJECT_0, WAIT_TIMEOUT,
+};
+#[cfg(windows)]
+use windows::Win32::System::Threading::{
+    CreateEventW, CreateMutexW, EVENT_MODIFY_STATE, INFINITE, OpenEventW, ReleaseMutex,
+    SYNCHRONIZATION_SYNCHRONIZE, SetEvent, WaitForSingleObject,
+};
+#[cfg(windows)]
+use windows::core::{PCWSTR, w};
+
+#[cfg(windows)]
+use crate::wake;
+
+/// Mutex held by the running instance, per user session
+#[cfg(windows)]
+const MUTEX_NAME: PCWSTR = w!("Local\\SpeedyStt.Instance");
+
+/// Event a second launch signals to reach the running instance
+#[cfg(windows)]
+const ACTIVATION_EVENT_NAME: PCWSTR = w!("Local\\SpeedyStt.Activate");
+
+/// Ownership of the single-instance mutex, released on drop
+pub struct InstanceLock {
+    /// Owned named mutex
+    #[cfg(windows)]
+    mutex: HANDLE,
+    /// Activation event, kept open so later launches find it
+    #[cfg(windows)]
+    activation: HANDLE,
+    /// Receives a message for each later launch
+    activations: Receiver<()>,
+}
+
+impl InstanceLock {
+    /// Become the running instance, or `None` after notifying the one already running.
+    ///
+    /// A mutex abandoned by a crashed instance is taken over.
+    #[cfg(windows)]
+    pub fn acquire() -> Result<Option<Self>> {
+        // SAFETY: the names are valid static strings; every handle created
+        // here is either closed on failure or owned by the returned lock.
+        unsafe {
+            let mutex = CreateMutexW(None, false, MUTEX_NAME)
+                .context("Failed to create single-instance mutex")?;
+            match WaitForSingleObject(mutex, 0) {
+                WAIT_OBJECT_0 => {}
+                WAIT_ABANDONED => {
+                    info!("Previous instance exited without releasing its lock; taking over");
+                }
+                WAIT_TIMEOUT => {
+                    let _ = CloseHandle(mutex);
+                    signal_running_instance();
+                    return Ok(None);
+                }
+                other => {
+                    let _ = CloseHandle(mutex);
+                    anyhow::bail!("Failed to take single-instance mutex ({:?})", other);
+                }
+            }
+
+            // Auto-reset, so each later launch wakes the watcher once
+            let activation = match CreateEventW(None, false, false, ACTIVATION_EVENT_NAME) {
+                Ok(event) => event,
+                Err(e) => {
+                    let _ = ReleaseMutex(mutex);
+                    let _ = CloseHandle(mutex);
+                    return Err(e).context("Failed to create activation event");
+                }
+            };
+            Ok(Some(Self {
+                mutex,
+                activation,
+                activations: watch_activations(),
+            }))
+        }
+    }
+
+    /// Every launch is the first without a way to find other instances.
+    #[cfg(not(windows))]
+    pub fn acquire() -> Result<Option<Self>> {
+        let (_, activations) = mpsc::channel();
+        Ok(Some(Self { activations }))
+    }
+
+    /// Whether another launch was attempted since the last call
+    pub fn activation_requested(&self) -> bool {
+        let mut requested = false;
+        while self.activations.try_recv().is_ok() {
+            requested = true;
+        }
+        requested
+    }
+}
+
+#[cfg(windows)]
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        // SAFETY: both handles were created in `acquire`, the mutex is owned
+        // by this thread, and each is closed exactly once.
+        unsafe {
+            let _ = ReleaseMutex(self.mutex);
+            let _ = CloseHandle(self.mutex);
+            let _ = CloseHandle(self.activation);
+        }
+    }
+}
+
+/// Tell the running instance that it was launched again, logging failures.
+#[cfg(windows)]
+fn signal_running_instance() {
+    // SAFETY: the event is opened by its static name and closed after use.
+    unsafe {
+        match OpenEventW(EVENT_MODIFY_STATE, false, ACTIVATION_EVENT_NAME) {
+            Ok(event) => {
+                if let Err(e) = SetEvent(event) {
+                    warn!("Failed to signal the running instance: {}", e);
+                }
+                let _ = CloseHandle(event);
+            }
+            Err(e) => warn!("Failed to reach the running instance: {}", e),
+        }
+    }
+}
+
+/// Wait for launch signals on a helper thread, forwarding each and waking the loop.
+///
+/// The thread opens its own handle to the event and runs until the process exits.
+#[cfg(windows)]
+fn watch_activations() -> Receiver<()> {
+    let (sender, receiver) = mpsc::channel();
+    let spawned = std::thread::Builder::new()
+        .name("instance-activation".to_owned())
+        .spawn(move || {
+            // SAFETY: the event is opened by its static name; the handle is
+            // only waited on and closed when the receiver is gone.
+            unsafe {
+                let event =
+                    match OpenEventW(SYNCHRONIZATION_SYNCHRONIZE, false, ACTIVATION_EVENT_NAME) {
+                        Ok(event) => event,
+                        Err(e) => {
+                            warn!("Failed to watch for other launches: {}", e);
+                            return;
+                        }
+                    };
+                while WaitForSingleObject(event, INFINITE) == WAIT_OBJECT_0 {
+                    if sender.send(()).is_err() {
+                        break;
+                    }
+                    wake::wake();
+                }
+                let _ = CloseHandle(event);
+            }
+        });
+    if let Err(e) = spawned {
+        warn!("Failed to start instance activation thread: {}", e);
+    }
+    receiver
+}
@@ -0,0 +1,548 @@
+//! Control from scripts over a named pipe.
+//!
+//! The running instance serves `\\.\pipe\speedy-stt`, reading one JSON
+//! command per line and answering each with one JSON line. Commands are
+//! handed to the event loop, which runs them like the matching hotkey or tray
+//! action. Only clients in the same Windows session are answered, and one
+//! that stalls mid-conversation is disconnected so the next can connect.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+#[cfg(windows)]
+use anyhow::Context;
+#[cfg(windows)]
+use tracing::{debug, info, warn};
+
+#[cfg(windows)]
+use windows::Win32::Foundation::{
+    CloseHandle, ERROR_BROKEN_PIPE, ERROR_IO_PENDING, ERROR_PIPE_CONNECTED, HANDLE,
+    INVALID_HANDLE_VALUE, WAIT_TIMEOUT,
+};
+#[cfg(windows)]
+use windows::Win32::Storage::FileSystem::{
+    FILE_FLAG_FIRST_PIPE_INSTANCE, FILE_FLAG_OVERLAPPED, PIPE_ACCESS_DUPLEX, ReadFile, WriteFile,
+};
+#[cfg(windows)]
+use windows::Win32::System::IO::{CancelIoEx, GetOverlappedResult, OVERLAPPED};
+#[cfg(windows)]
+use windows::Win32::System::Pipes::{
+    ConnectNamedPipe, CreateNamedPipeW, DisconnectNamedPipe, GetNamedPipeClientSessionId,
+    PIPE_READMODE_BYTE, PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_BYTE, PIPE_WAIT,
+};
+#[cfg(windows)]
+use windows::Win32::System::RemoteDesktop::ProcessIdToSessionId;
+#[cfg(windows)]
+use windows::Win32::System::Threading::{
+    CreateEventW, GetCurrentProcessId, INFINITE, WaitForSingleObject,
+};
+#[cfg(windows)]
+use windows::core::HSTRING;
+
+use crate::wake;
+
+/// Path clients open to reach the running instance
+#[cfg(windows)]
+const PIPE_PATH: &str = r"\\.\pipe\speedy-stt";
+
+/// Longest a command line may be, so a misbehaving client cannot exhaust memory
+#[cfg(windows)]
+const MAX_LINE: usize = 4096;
+
+/// Longest a connected client may go without sending a command or taking
+/// its answer before it is disconnected
+#[cfg(windows)]
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Longest a server waits for the event loop to answer a command
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Longest a client waits for the pipe while another client is connected
+#[cfg(windows)]
+const BUSY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Windows error returned while another client holds the only pipe instance
+#[cfg(windows)]
+const ERROR_PIPE_BUSY: i32 = 231;
+
+/// Windows error returned when no instance serves the pipe
+#[cfg(windows)]
+const ERROR_FILE_NOT_FOUND: i32 = 2;
+
+/// A command sent over the pipe, e.g. `{"command": "set-language", "language": "de"}`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum IpcCommand {
+    /// Start a recording that runs until `stop`, like a latched hotkey press
+    Start,
+    /// Stop the recording and type its transcription
+    Stop,
+    /// Discard the recording without typing anything
+    Cancel,
+    /// Report what the app is doing
+    Status,
+    /// Transcribe in this language from now on
+    SetLanguage {
+        /// Whisper language code or `auto`
+        language: String,
+    },
+    /// Pause the hotkeys, or resume them when paused
+    TogglePause,
+}
+
+impl FromStr for IpcCommand {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim().to_lowercase();
+        match s.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["start"] => Ok(Self::Start),
+            ["stop"] => Ok(Self::Stop),
+            ["cancel"] => Ok(Self::Cancel),
+            ["status"] => Ok(Self::Status),
+            ["set-language", language] => Ok(Self::SetLanguage {
+                language: (*language).to_owned(),
+            }),
+            ["toggle-pause"] => Ok(Self::TogglePause),
+            _ => anyhow::bail!(
+                "unknown command {s:?}, use start, stop, cancel, status, set-language <code> or toggle-pause"
+            ),
+        }
+    }
+}
+
+impl fmt::Display for IpcCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Start => write!(f, "start"),
+            Self::Stop => write!(f, "stop"),
+            Self::Cancel => write!(f, "cancel"),
+            Self::Status => write!(f, "status"),
+            Self::SetLanguage { ref language } => write!(f, "set-language {language}"),
+            Self::TogglePause => write!(f, "toggle-pause"),
+        }
+    }
+}
+
+/// What the app is doing, returned by every successful command
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppStatus {
+    /// `idle`, `paused`, `recording`, `latched` or `transcribing`
+    pub state: String,
+    /// File name of the configured Whisper model
+    pub model: String,
+    /// Whether the Whisper model is in memory
+    pub model_loaded: bool,
+    /// Language used for the next transcription
+    pub language: String,
+    /// Whether the hotkeys are paused
+    pub paused: bool,
+    /// Seconds since the app started
+    pub uptime_secs: u64,
+    /// Dictations completed since the app started
+    pub dictations: u64,
+    /// Most recent error shown to the user, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+/// Answer to one command
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IpcResponse {
+    /// Whether the command was carried out
+    pub ok: bool,
+    /// Why it was not
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// State after the command
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<AppStatus>,
+}
+
+impl IpcResponse {
+    /// Successful answer reporting `status`
+    pub const fn done(status: AppStatus) -> Self {
+        Self {
+            ok: true,
+            error: None,
+            status: Some(status),
+        }
+    }
+
+    /// Failed answer explaining `error`
+    pub fn failed(error: impl Into<String>) -> Self {
+        Self {
+            ok: false,
+            error: Some(error.into()),
+            status: None,
+        }
+    }
+}
+
+/// A command waiting for the event loop, answered through `reply`
+pub struct IpcRequest {
+    /// Command to run
+    pub command: IpcCommand,
+    /// Where the answer goes; dropped unanswered if the client gave up
+    pub reply: Sender<IpcResponse>,
+}
+
+/// Receives commands from the pipe server thread and other front ends
+pub struct IpcServer {
+    /// Commands in arrival order
+    requests: Receiver<IpcRequest>,
+    /// Handed to other front ends so their commands arrive here too
+    sender: Sender<IpcRequest>,
+}
+
+impl IpcServer {
+    /// Serve the pipe on a helper thread, or receive nothing when `enabled` is false.
+    pub fn start(enabled: bool) -> Self {
+        let (sender, requests) = mpsc::channel();
+        #[cfg(windows)]
+        if enabled {
+            let pipe_sender = sender.clone();
+            let spawned = std::thread::Builder::new()
+                .name("ipc-server".to_owned())
+                .spawn(move || {
+                    if let Err(e) = serve(&pipe_sender) {
+                        warn!("Control pipe stopped: {:#}", e);
+                    }
+                });
+            if let Err(e) = spawned {
+                warn!("Failed to start control pipe thread: {}", e);
+            }
+        }
+        #[cfg(not(windows))]
+        let _ = enabled;
+        Self { requests, sender }
+    }
+
+    /// Next command from a client, without blocking
+    pub fn poll(&self) -> Option<IpcRequest> {
+        self.requests.try_recv().ok()
+    }
+
+    /// Channel another front end sends its commands through
+    pub fn requester(&self) -> Sender<IpcRequest> {
+        self.sender.clone()
+    }
+}
+
+/// Hand `command` to the event loop and wait for its answer; `None` once the loop is gone.
+pub fn dispatch(sender: &Sender<IpcRequest>, command: IpcCommand) -> Option<IpcResponse> {
+    let (reply, answer) = mpsc::channel();
+    sender.send(IpcRequest { command, reply }).ok()?;
+    wake::wake();
+    Some(
+        answer
+            .recv_timeout(REPLY_TIMEOUT)
+            .unwrap_or_else(|_| IpcResponse::failed("Speedy STT did not respond in time")),
+    )
+}
+
+/// Accept clients one at a time until the event loop is gone.
+///
+/// The pipe has a single instance, created first so no other process can
+/// serve the name, and rejects remote clients. A client in another session
+/// is disconnected unanswered, as is one that stays silent for
+/// [`CLIENT_TIMEOUT`].
+#[cfg(windows)]
+fn serve(sender: &Sender<IpcRequest>) -> Result<()> {
+    let pipe = Pipe::create()?;
+    info!("Listening for commands on {}", PIPE_PATH);
+
+    let own_session = session_of_process();
+    loop {
+        pipe.connect()
+            .context("Failed to accept control pipe client")?;
+        let client_session = client_session(pipe.handle);
+        let gone = if own_session.is_some() && client_session == own_session {
+            serve_client(&pipe, sender)
+        } else {
+            warn!("Rejected control pipe client from another session");
+            false
+        };
+        // SAFETY: the pipe handle is valid; disconnecting discards unread data.
+        let _ = unsafe { DisconnectNamedPipe(pipe.handle) };
+        if gone {
+            return Ok(());
+        }
+    }
+}
+
+/// Answer the commands of one connected client; true once the event loop is gone.
+#[cfg(windows)]
+fn serve_client(pipe: &Pipe, sender: &Sender<IpcRequest>) -> bool {
+    let mut pending = Vec::new();
+    let mut chunk = [0; 512];
+    loop {
+        let line = loop {
+            if let Some(end) = pending.iter().position(|&byte| byte == b'\n') {
+                break pending.drain(..=end).collect::<Vec<u8>>();
+            }
+            if pending.len() >= MAX_LINE {
+                break pending.drain(..MAX_LINE).collect();
+            }
+            match pipe.read(&mut chunk) {
+                Ok(Some(0)) => return false,
+                Ok(Some(read)) => pending.extend_from_slice(chunk.get(..read).unwrap_or_default()),
+                Ok(None) => {
+                    info!(
+                        "Disconnected a control pipe client silent for {}s",
+                        CLIENT_TIMEOUT.as_secs()
+                    );
+                    return false;
+                }
+                Err(e) => {
+                    debug!("Control pipe client read failed: {}", e);
+                    return false;
+                }
+            }
+        };
+        let line = String::from_utf8_lossy(&line);
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<IpcCommand>(&line) {
+            Ok(command) => {
+                info!("Control pipe command: {}", command);
+                let Some(response) = dispatch(sender, command) else {
+                    return true;
+                };
+                response
+            }
+            Err(e) => IpcResponse::failed(format!("invalid command: {e}")),
+        };
+        let mut json = serde_json::to_string(&response).unwrap_or_default();
+        json.push('\n');
+        match pipe.write_all(json.as_bytes()) {
+            Ok(true) => {}
+            Ok(false) => {
+                info!("Disconnected a control pipe client that did not take its answer");
+                return false;
+            }
+            Err(e) => {
+                debug!("Control pipe client write failed: {}", e);
+                return false;
+            }
+        }
+    }
+}
+
+/// The server end of the control pipe, opened for overlapped I/O so reads
+/// and writes can give up on a stalled client
+#[cfg(windows)]
+struct Pipe {
+    /// The one pipe instance
+    handle: HANDLE,
+    /// Signalled when an operation on the pipe completes
+    event: HANDLE,
+}
+
+#[cfg(windows)]
+impl Pipe {
+    /// Create the pipe instance, failing if another process serves the name
+    fn create() -> Result<Self> {
+        // SAFETY: the name is a valid string that outlives the call and no
+        // security attributes are passed; the handle is checked below and
+        // closed on drop.
+        let handle = unsafe {
+            CreateNamedPipeW(
+                &HSTRING::from(PIPE_PATH),
+                PIPE_ACCESS_DUPLEX | FILE_FLAG_FIRST_PIPE_INSTANCE | FILE_FLAG_OVERLAPPED,
+                PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+                1,
+                4096,
+                4096,
+                0,
+                None,
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(windows::core::Error::from_thread())
+                .context("Failed to create control pipe");
+        }
+        // SAFETY: an unnamed manual-reset event without security attributes;
+        // it is closed on drop.
+        let event = match unsafe { CreateEventW(None, true, false, None) } {
+            Ok(event) => event,
+            Err(e) => {
+                // SAFETY: the pipe handle was created above and is closed once.
+                let _ = unsafe { CloseHandle(handle) };
+                return Err(e).context("Failed to create control pipe event");
+            }
+        };
+        Ok(Self { handle, event })
+    }
+
+    /// Wait as long as it takes for the next client
+    fn connect(&self) -> windows::core::Result<()> {
+        let mut overlapped = self.overlapped();
+        // SAFETY: the pipe handle is valid and `overlapped` outlives the
+        // operation, which `complete` waits for.
+        match unsafe { ConnectNamedPipe(self.handle, Some(&raw mut overlapped)) } {
+            // A client connected between creation and this call
+            Err(e) if e.code() == ERROR_PIPE_CONNECTED.to_hresult() => Ok(()),
+            started => self.complete(started, &overlapped, INFINITE).map(drop),
+        }
+    }
+
+    /// Read what the client sent into `buffer`, `Some(0)` once it closed its
+    /// end and `None` when it sent nothing for [`CLIENT_TIMEOUT`]
+    fn read(&self, buffer: &mut [u8]) -> windows::core::Result<Option<usize>> {
+        let mut overlapped = self.overlapped();
+        // SAFETY: the pipe handle is valid, and `buffer` and `overlapped`
+        // outlive the operation, which `complete` waits for.
+        let started =
+            unsafe { ReadFile(self.handle, Some(buffer), None, Some(&raw mut overlapped)) };
+        match self.complete(started, &overlapped, client_timeout_ms()) {
+            Err(e) if e.code() == ERROR_BROKEN_PIPE.to_hresult() => Ok(Some(0)),
+            read => read.map(|read| read.map(|bytes| usize::try_from(bytes).unwrap_or_default())),
+        }
+    }
+
+    /// Write all of `bytes`, false when the client did not take them
+    /// within [`CLIENT_TIMEOUT`]
+    fn write_all(&self, mut bytes: &[u8]) -> windows::core::Result<bool> {
+        while !bytes.is_empty() {
+            let mut overlapped = self.overlapped();
+            // SAFETY: the pipe handle is valid, and `bytes` and `overlapped`
+            // outlive the operation, which `complete` waits for.
+            let started =
+                unsafe { WriteFile(self.handle, Some(bytes), None, Some(&raw mut overlapped)) };
+            let Some(written) = self.complete(started, &overlapped, client_timeout_ms())? else {
+                return Ok(false);
+            };
+            let written = usize::try_from(written).unwrap_or_default();
+            bytes = bytes.get(written..).unwrap_or_default();
+        }
+        Ok(true)
+    }
+
+    /// Fresh overlapped state for one operation, signalling the event
+    fn overlapped(&self) -> OVERLAPPED {
+        OVERLAPPED {
+            hEvent: self.event,
+            ..Default::default()
+        }
+    }
+
+    /// Bytes moved by the operation `started` with `overlapped`, or `None`
+    /// when it did not finish within `timeout_ms` and was cancelled
+    fn complete(
+        &self,
+        started: windows::core::Result<()>,
+        overlapped: &OVERLAPPED,
+        timeout_ms: u32,
+    ) -> windows::core::Result<Option<u32>> {
+        if let Err(e) = started
+            && e.code() != ERROR_IO_PENDING.to_hresult()
+        {
+            return Err(e);
+        }
+        let mut transferred = 0;
+        // SAFETY: the event is valid for the life of the pipe.
+        if unsafe { WaitForSingleObject(self.event, timeout_ms) } == WAIT_TIMEOUT {
+            // SAFETY: the operation was started on this handle with
+            // `overlapped`; waiting for it afterwards ensures it no longer
+            // uses `overlapped` or its buffer once this returns.
+            unsafe {
+                let _ = CancelIoEx(self.handle, Some(overlapped));
+                let _ = GetOverlappedResult(self.handle, overlapped, &raw mut transferred, true);
+            }
+            return Ok(None);
+        }
+        // SAFETY: the operation has completed, so its result can be read.
+        unsafe { GetOverlappedResult(self.handle, overlapped, &raw mut transferred, false) }?;
+        Ok(Some(transferred))
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        // SAFETY: both handles were created in `create` and are closed once.
+        unsafe {
+            let _ = CloseHandle(self.event);
+            let _ = CloseHandle(self.handle);
+        }
+    }
+}
+
+/// [`CLIENT_TIMEOUT`] in the milliseconds Windows waits take
+#[cfg(windows)]
+fn client_timeout_ms() -> u32 {
+    u32::try_from(CLIENT_TIMEOUT.as_millis()).unwrap_or(INFINITE)
+}
+
+/// Windows session of this process
+#[cfg(windows)]
+fn session_of_process() -> Option<u32> {
+    let mut session = 0;
+    // SAFETY: the output pointer is valid for the call.
+    unsafe { ProcessIdToSessionId(GetCurrentProcessId(), &raw mut session) }
+        .map_err(|e| warn!("Failed to look up own session: {}", e))
+        .ok()
+        .map(|()| session)
+}
+
+/// Windows session of the client connected to `pipe`
+#[cfg(windows)]
+fn client_session(pipe: HANDLE) -> Option<u32> {
+    let mut session = 0;
+    // SAFETY: the pipe handle is valid and the output pointer is valid for the call.
+    unsafe { GetNamedPipeClientSessionId(pipe, &raw mut session) }
+        .map_err(|e| warn!("Failed to look up control pipe client session: {}", e))
+        .ok()
+        .map(|()| session)
+}
+
+/// Send `command` to the running instance and wait for its answer.
+#[cfg(windows)]
+pub fn send(command: &IpcCommand) -> Result<IpcResponse> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::time::Instant;
+
+    let started = Instant::now();
+    let mut pipe = loop {
+        match std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(PIPE_PATH)
+        {
+            Ok(pipe) => break pipe,
+            Err(e) if e.raw_os_error() == Some(ERROR_PIPE_BUSY) => {
+                if started.elapsed() >= BUSY_TIMEOUT {
+                    anyhow::bail!("Speedy STT is busy with another client");
+                }
+                std::thread::sleep(Duration::from_millis(50));
+            }
+            Err(e) if e.raw_os_error() == Some(ERROR_FILE_NOT_FOUND) => {
+                anyhow::bail!("Speedy STT is not running, or its control pipe is disabled")
+            }
+            Err(e) => return Err(e).context("Failed to open the control pipe"),
+        }
+    };
+
+    let mut json = serde_json::to_string(command).context("Failed to encode command")?;
+    json.push('\n');
+    pipe.write_all(json.as_bytes())
+        .context("Failed to send command")?;
+    let mut line = String::new();
+    BufReader::new(&pipe)
+        .read_line(&mut line)
+        .context("Failed to read answer")?;
+    serde_json::from_str(&line).with_context(|| format!("Invalid answer {line:?}"))
+}
+
+/// The control pipe only exists on Windows.
+#[cfg(not(windows))]
+pub fn send(_command: &IpcCommand) -> Result<IpcResponse> {
+    anyhow::bail!("Controlling a running instance is only supported on Windows")
+}
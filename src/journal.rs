@@ -0,0 +1,70 @@
+//! Record of ducked volumes that survives a crash.
+//!
+//! The original volume of every ducked session is written to disk before it is
+//! faded out and removed once it has been restored. A journal left behind by
+//! a killed process is replayed on the next start so no app stays muted.
+
+#![cfg_attr(
+    not(windows),
+    allow(
+        dead_code,
+        reason = "volumes are only ducked, and journaled, on Windows"
+    )
+)]
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::paths;
+
+/// Identity and volumes of one ducked session, without any COM pointers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// Session instance identifier, stable for the lifetime of the session
+    pub id: Option<String>,
+    /// Executable name of the owning process, if it could be resolved
+    pub process: Option<String>,
+    /// Owning process ID, 0 for sandboxed sessions
+    pub pid: u32,
+    /// Volume before ducking
+    pub original_volume: f32,
+    /// Volume while ducked
+    pub ducked_volume: f32,
+}
+
+/// Replace the journal with `entries`.
+pub fn write(entries: &[JournalEntry]) -> Result<()> {
+    let path = paths::ducking_journal();
+    let json = serde_json::to_string_pretty(entries).context("Failed to encode ducking journal")?;
+    paths::ensure_parent(&path)?;
+    // Written under a temporary name so a crash mid-write keeps the previous journal
+    let temporary = path.with_extension("json.tmp");
+    std::fs::write(&temporary, json)
+        .with_context(|| format!("Failed to write {}", temporary.display()))?;
+    std::fs::rename(&temporary, &path)
+        .with_context(|| format!("Failed to replace {}", path.display()))
+}
+
+/// Entries left behind by a previous run, `None` when there is no journal.
+pub fn read() -> Result<Option<Vec<JournalEntry>>> {
+    let path = paths::ducking_journal();
+    if !path.exists() {
+        return Ok(None);
+    }
+    let contents = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&contents)
+        .map(Some)
+        .with_context(|| format!("Invalid JSON in {}", path.display()))
+}
+
+/// Delete the journal after every session was restored, logging failures.
+pub fn clear() {
+    let path = paths::ducking_journal();
+    if path.exists()
+        && let Err(e) = std::fs::remove_file(&path)
+    {
+        warn!("Failed to remove {}: {}", path.display(), e);
+    }
+}
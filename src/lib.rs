@@ -0,0 +1,44 @@
+//! Speedy-STT's dictation pipeline, for embedding in other apps.
+//!
+//! [`AudioRecorder`] captures the microphone, [`WhisperEngine`] transcribes
+//! it, and [`Punctuation`], [`Spacing`] and a [`TextPipeline`] of stages
+//! such as the [`WordFilter`] turn the segments into the text to use.
+//! [`DictationSession`] runs the whole pipeline behind start and stop.
+//!
+//! ```no_run
+//! use std::path::Path;
+//! use std::sync::Arc;
+//!
+//! use speedy_stt::{DictationEvent, DictationSession, WhisperEngine};
+//!
+//! let engine = Arc::new(WhisperEngine::load(Path::new("ggml-base.en.bin"), 4)?);
+//! let session = DictationSession::new(engine, |event| {
+//!     if let DictationEvent::Transcribed(dictation) = event {
+//!         tracing::info!("Dictated: {}", dictation.text);
+//!     }
+//! });
+//! # drop(session);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+pub mod audio;
+pub mod dictation;
+pub mod filter;
+#[cfg(test)]
+mod fixtures;
+pub mod pipeline;
+pub mod punctuation;
+pub mod spacing;
+pub mod spill;
+pub mod whisper;
+
+pub use audio::{AudioError, AudioLevels, AudioRecorder, CapturedAudio};
+pub use dictation::{Dictation, DictationError, DictationEvent, DictationSession};
+pub use filter::{FilterAction, Filtered, WordFilter};
+pub use pipeline::{StageKind, TextPipeline, TextStage};
+pub use punctuation::Punctuation;
+pub use spacing::Spacing;
+pub use whisper::{
+    EngineError, LanguageCode, Segment, Transcript, TranscriptionTask, WhisperEngine,
+    WhisperOptions,
+};
@@ -0,0 +1,328 @@
+//! Tracing setup.
+//!
+//! Installs the global subscriber with a reloadable level filter so a changed
+//! log level applies without restarting. The output destination is fixed at
+//! startup. Log files are named after the UTC date, start afresh each day and
+//! at a size cap, and only the newest few are kept.
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, PoisonError};
+
+use anyhow::{Context, Result};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Registry, reload};
+
+use crate::config::Config;
+use crate::paths;
+use crate::stats;
+
+/// Handle for swapping the level filter after initialization
+static FILTER_HANDLE: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// The log file writer, once logging to a file is set up
+static ROLLING: OnceLock<RollingLog> = OnceLock::new();
+
+/// Log files kept before the configuration is loaded
+const FALLBACK_KEEP_FILES: usize = 7;
+
+/// Least severe events written to the log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogLevel {
+    /// Everything, including per-buffer detail
+    Trace,
+    /// Diagnostic detail
+    Debug,
+    /// Normal operation
+    Info,
+    /// Problems the app recovers from
+    Warn,
+    /// Failures only
+    Error,
+}
+
+impl LogLevel {
+    /// Name as written in the configuration
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Trace => "trace",
+            Self::Debug => "debug",
+            Self::Info => "info",
+            Self::Warn => "warn",
+            Self::Error => "error",
+        }
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "trace" => Ok(Self::Trace),
+            "debug" => Ok(Self::Debug),
+            "info" => Ok(Self::Info),
+            "warn" | "warning" => Ok(Self::Warn),
+            "error" => Ok(Self::Error),
+            other => {
+                anyhow::bail!("unknown log level {other:?}, use trace, debug, info, warn or error")
+            }
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Configure tracing based on the log level and output destination in config.
+pub fn init(config: &Config) -> Result<()> {
+    let (filter, handle) = reload::Layer::new(build_filter(config.log_level));
+    let writer = if config.log_to_file {
+        let log = RollingLog::open(
+            paths::log_file(),
+            config.log_max_size_mb.saturating_mul(1_000_000),
+            config.log_keep_files,
+        )?;
+        let _ = ROLLING.set(log.clone());
+        BoxMakeWriter::new(log)
+    } else {
+        BoxMakeWriter::new(std::io::stdout)
+    };
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer().with_writer(writer))
+        .init();
+    let _ = FILTER_HANDLE.set(handle);
+
+    Ok(())
+}
+
+/// Log to the default log file at `info`, for errors that occur before the config is loaded.
+pub fn init_fallback() {
+    let Ok(log) = RollingLog::open(paths::log_file(), 0, FALLBACK_KEEP_FILES) else {
+        return;
+    };
+    let _ = ROLLING.set(log.clone());
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(build_filter(LogLevel::Info))
+        .with_writer(log)
+        .try_init();
+}
+
+/// Log file currently written to, `None` when logging to the console
+pub fn current_file() -> Option<PathBuf> {
+    ROLLING.get().map(RollingLog::current_file)
+}
+
+/// Apply a new log level to the running subscriber.
+pub fn set_level(level: LogLevel) -> Result<()> {
+    FILTER_HANDLE
+        .get()
+        .context("Logging is not initialized")?
+        .reload(build_filter(level))
+        .context("Failed to change log level")
+}
+
+/// Level filter for a configured level.
+fn build_filter(level: LogLevel) -> EnvFilter {
+    // enigo is suppressed to error-only to prevent transcribed text from leaking into the log file
+    EnvFilter::new(format!("{level},enigo=error"))
+}
+
+/// Shared handle of the dated log files
+#[derive(Clone)]
+struct RollingLog {
+    /// Open file and rotation state, locked for each event so a rotation
+    /// never splits or interleaves lines
+    state: Arc<Mutex<RollingState>>,
+}
+
+/// The open log file and when to move on from it
+struct RollingState {
+    /// Configured log path; dated files share its directory, stem and extension
+    base: PathBuf,
+    /// Size at which a new file is started, 0 for one file per day
+    max_bytes: u64,
+    /// Dated files to keep, the open one included
+    keep: usize,
+    /// Open file, `None` after it could not be opened
+    file: Option<File>,
+    /// Path of the open file
+    path: PathBuf,
+    /// UTC date of the open file
+    day: String,
+    /// Number of the open file within its day, 0 for the first
+    part: u32,
+    /// Bytes in the open file
+    size: u64,
+}
+
+impl RollingLog {
+    /// Open today's file next to `base`, pruning older ones beyond `keep`
+    fn open(base: PathBuf, max_bytes: u64, keep: usize) -> Result<Self> {
+        paths::ensure_parent(&base)?;
+        let mut state = RollingState {
+            path: base.clone(),
+            base,
+            max_bytes,
+            keep: keep.max(1),
+            file: None,
+            day: String::new(),
+            part: 0,
+            size: 0,
+        };
+        state.roll_if_due().context("Failed to open log file")?;
+        Ok(Self {
+            state: Arc::new(Mutex::new(state)),
+        })
+    }
+
+    /// Lock the state; a panic while writing a line leaves it usable
+    fn lock(&self) -> MutexGuard<'_, RollingState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Path of the open file
+    fn current_file(&self) -> PathBuf {
+        self.lock().path.clone()
+    }
+}
+
+impl<'a> MakeWriter<'a> for RollingLog {
+    type Writer = RollingWriter<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        let mut state = self.lock();
+        // A failed rotation keeps writing to the previous file
+        let _ = state.roll_if_due();
+        RollingWriter(state)
+    }
+}
+
+/// Writer for one event, holding the lock until the event is written
+struct RollingWriter<'a>(MutexGuard<'a, RollingState>);
+
+impl Write for RollingWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let state = &mut *self.0;
+        let Some(ref mut file) = state.file else {
+            return Ok(buf.len());
+        };
+        let written = file.write(buf)?;
+        state.size += u64::try_from(written).unwrap_or(u64::MAX);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.file.as_mut().map_or(Ok(()), Write::flush)
+    }
+}
+
+impl RollingState {
+    /// Move to a new file when the day changed or the open one is full.
+    ///
+    /// Files of today left full by an earlier run are skipped.
+    fn roll_if_due(&mut self) -> io::Result<()> {
+        let day = stats::today();
+        let full = self.max_bytes > 0 && self.size >= self.max_bytes;
+        if self.file.is_some() && day == self.day && !full {
+            return Ok(());
+        }
+        if day == self.day {
+            self.part += 1;
+        } else {
+            self.day = day;
+            self.part = 0;
+        }
+        loop {
+            let path = self.dated_file(self.part);
+            let size = std::fs::metadata(&path).map_or(0, |meta| meta.len());
+            if self.max_bytes == 0 || size < self.max_bytes {
+                let file = OpenOptions::new().create(true).append(true).open(&path)?;
+                self.file = Some(file);
+                self.path = path;
+                self.size = size;
+                break;
+            }
+            self.part += 1;
+        }
+        self.prune();
+        Ok(())
+    }
+
+    /// `speedy-stt.2024-05-01.log` for the first file of the day, then `speedy-stt.2024-05-01.1.log`
+    fn dated_file(&self, part: u32) -> PathBuf {
+        let (stem, extension) = name_parts(&self.base);
+        let name = match part {
+            0 => format!("{stem}.{}.{extension}", self.day),
+            part => format!("{stem}.{}.{part}.{extension}", self.day),
+        };
+        self.base.with_file_name(name)
+    }
+
+    /// Delete the oldest dated files beyond `keep`; failures are ignored, as
+    /// there is no log to report them to.
+    fn prune(&self) {
+        let (stem, extension) = name_parts(&self.base);
+        let (prefix, suffix) = (format!("{stem}."), format!(".{extension}"));
+        let Some(entries) = self
+            .base
+            .parent()
+            .and_then(|dir| std::fs::read_dir(dir).ok())
+        else {
+            return;
+        };
+        let mut files: Vec<((String, u32), PathBuf)> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let dated = name.strip_prefix(&prefix)?.strip_suffix(&suffix)?;
+                let (day, part) = match dated.split_once('.') {
+                    Some((day, part)) => (day, part.parse().ok()?),
+                    None => (dated, 0),
+                };
+                is_date(day).then(|| ((day.to_owned(), part), entry.path()))
+            })
+            .collect();
+        files.sort();
+        let excess = files.len().saturating_sub(self.keep);
+        for (_, path) in files.into_iter().take(excess) {
+            if path != self.path {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+}
+
+/// Stem and extension of the configured log file name
+fn name_parts(base: &Path) -> (String, String) {
+    let part = |part: Option<&std::ffi::OsStr>, default: &str| {
+        part.map_or_else(|| default.to_owned(), |p| p.to_string_lossy().into_owned())
+    };
+    (
+        part(base.file_stem(), "speedy-stt"),
+        part(base.extension(), "log"),
+    )
+}
+
+/// Whether `text` looks like `YYYY-MM-DD`
+fn is_date(text: &str) -> bool {
+    text.len() == 10
+        && text.char_indices().all(|(i, c)| {
+            if i == 4 || i == 7 {
+                c == '-'
+            } else {
+                c.is_ascii_digit()
+            }
+        })
+}
@@ -3,51 +3,103 @@
 #![cfg_attr(windows, windows_subsystem = "windows")]
 
 mod app;
-mod audio;
+mod captions;
+mod cli;
+mod clipboard;
+#[cfg(windows)]
+mod com;
+mod commands;
 mod config;
+mod cooldown;
+mod correction;
+mod crash;
+mod devices;
+mod diagnostics;
+mod dialog;
+mod duplicate;
+mod error;
 mod feedback;
+mod foreground;
+mod history;
 mod hotkey;
+mod http;
 mod input;
+mod instance;
+mod ipc;
+mod journal;
+mod logging;
+mod media;
+mod memory;
+mod models;
+mod notify;
+mod output;
+mod overlay;
+mod paths;
+mod privacy;
+mod quiet;
+mod recovery;
+mod resend;
+#[cfg(test)]
+mod scratch;
+mod session;
+mod setup;
+mod speech;
+mod state;
+mod stats;
+mod template;
+mod tokens;
+mod transcribe;
 mod tray;
 mod volume;
-mod whisper;
+mod wake;
+mod wav;
+mod webhook;
 
 use anyhow::{Context, Result};
-use tracing_subscriber::EnvFilter;
+use speedy_stt::{audio, filter, pipeline, punctuation, spacing, spill, whisper};
 
 use app::App;
+use cli::{Cli, Command};
 use config::Config;
+use instance::InstanceLock;
 
-/// Main entry point: load configuration, set up logging, and run the app.
+/// Main entry point: parse arguments, load configuration, set up logging, and run the app.
 fn main() -> Result<()> {
-    let config = Config::load().context("Failed to load configuration")?;
-    setup_logging(&config)?;
-    App::new(config)?.run()
-}
-
-/// Configure tracing based on the log level and output destination in config.
-fn setup_logging(config: &Config) -> Result<()> {
-    let level = match config.log_level.as_str() {
-        "trace" => "trace",
-        "debug" => "debug",
-        "warn" => "warn",
-        "error" => "error",
-        _ => "info",
+    crash::install_hook();
+    let cli = match Cli::parse(std::env::args().skip(1)) {
+        Ok(cli) => cli,
+        Err(e) => {
+            cli::attach_parent_console();
+            return Err(e);
+        }
     };
-    // enigo is suppressed to error-only to prevent transcribed text from leaking into the log file
-    let filter = EnvFilter::new(format!("{level},enigo=error"));
-    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+    if cli.command != Command::Run {
+        return cli.run_console_command();
+    }
 
-    if config.log_to_file {
-        let file = std::fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open("speedy-stt.log")
-            .context("Failed to open log file")?;
-        subscriber.with_writer(file).init();
-    } else {
-        subscriber.init();
+    // A failed or cancelled setup falls through to the default config, whose
+    // problems are reported below
+    if cli.setup && !Config::file_exists() {
+        let _ = setup::run();
     }
 
-    Ok(())
+    let config = match Config::load(cli.overrides) {
+        Ok(config) => config,
+        Err(e) => {
+            // Logging is not configured yet, so record the problems in the default log file
+            logging::init_fallback();
+            dialog::show_error_blocking("Speedy STT configuration", &format!("{e:#}"));
+            return Err(e).context("Failed to load configuration");
+        }
+    };
+    logging::init(&config)?;
+    // Checked before the journal, which belongs to the running instance while it records
+    let Some(instance) = InstanceLock::acquire()? else {
+        tracing::info!("Speedy STT is already running, exiting");
+        return Ok(());
+    };
+    crash::own_ducked_audio();
+    volume::restore_from_journal();
+    spill::sweep(&paths::spill_dir());
+    App::new(config, instance)?.run()
 }
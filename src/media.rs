@@ -0,0 +1,164 @@
+//! Pausing media players through the system media transport controls.
+//!
+//! Players that integrate with the Windows media overlay (SMTC) can be paused
+//! and resumed instead of ducked, so spoken content is not missed. Only the
+//! sessions paused here are resumed, and only if they are still paused.
+
+#![cfg_attr(
+    not(windows),
+    allow(dead_code, reason = "media sessions can only be paused on Windows")
+)]
+
+#[cfg(windows)]
+use anyhow::Context;
+use anyhow::Result;
+use tracing::info;
+#[cfg(windows)]
+use tracing::{debug, warn};
+
+#[cfg(windows)]
+use windows::Media::Control::{
+    GlobalSystemMediaTransportControlsSession, GlobalSystemMediaTransportControlsSessionManager,
+    GlobalSystemMediaTransportControlsSessionPlaybackStatus,
+};
+
+/// Media session paused when recording started.
+struct PausedSession {
+    /// Transport controls of the session
+    #[cfg(windows)]
+    session: GlobalSystemMediaTransportControlsSession,
+    /// App user model ID of the player, for log messages and ducking exclusions
+    app: String,
+}
+
+/// Players paused for the length of one recording.
+///
+/// Created via [`MediaPauser::pause`]; [`MediaPauser::resume`] plays them
+/// again. Sessions the user resumed or changed in the meantime are left alone.
+pub struct MediaPauser {
+    /// Sessions that were playing and accepted the pause request
+    paused: Vec<PausedSession>,
+}
+
+impl MediaPauser {
+    /// Pause every media session that is currently playing.
+    ///
+    /// COM (or WinRT) must be initialized on the calling thread.
+    #[cfg(windows)]
+    pub fn pause() -> Result<Self> {
+        let manager = GlobalSystemMediaTransportControlsSessionManager::RequestAsync()
+            .and_then(|request| request.join())
+            .context("Failed to open the media transport controls")?;
+        let sessions = manager
+            .GetSessions()
+            .context("Failed to list media sessions")?;
+
+        let mut paused = Vec::new();
+        for session in sessions {
+            let app = session
+                .SourceAppUserModelId()
+                .map(|id| id.to_string())
+                .unwrap_or_default();
+            if status(&session)
+                != Some(GlobalSystemMediaTransportControlsSessionPlaybackStatus::Playing)
+            {
+                debug!("Media session {}: not playing, leaving it", app);
+                continue;
+            }
+            match session.TryPauseAsync().and_then(|request| request.join()) {
+                Ok(true) => {
+                    info!("Media session {}: paused", app);
+                    paused.push(PausedSession { session, app });
+                }
+                Ok(false) => info!("Media session {}: refused to pause", app),
+                Err(e) => warn!("Media session {}: failed to pause: {}", app, e),
+            }
+        }
+        Ok(Self { paused })
+    }
+
+    /// Nothing can be paused without the Windows media transport controls.
+    #[cfg(not(windows))]
+    pub fn pause() -> Result<Self> {
+        Ok(Self { paused: Vec::new() })
+    }
+
+    /// Executable names of the paused players, so ducking can skip them.
+    ///
+    /// Desktop apps report their executable as app ID (e.g. `Spotify.exe`);
+    /// packaged apps report `Family!App`, of which the app name is used.
+    pub fn paused_apps(&self) -> Vec<String> {
+        self.paused
+            .iter()
+            .filter_map(|paused| {
+                let name = paused.app.rsplit('!').next()?.to_lowercase();
+                (!name.is_empty()).then_some(name)
+            })
+            .collect()
+    }
+
+    /// Resume the paused sessions that are still paused.
+    ///
+    /// A session the user played or stopped during the recording is not
+    /// touched; sessions that went away are skipped.
+    pub fn resume(&mut self) {
+        #[cfg_attr(not(windows), allow(unused_mut, reason = "only counted on Windows"))]
+        let (mut resumed, mut skipped) = (0, 0);
+        for paused in std::mem::take(&mut self.paused) {
+            #[cfg(windows)]
+            {
+                if status(&paused.session)
+                    != Some(GlobalSystemMediaTransportControlsSessionPlaybackStatus::Paused)
+                {
+                    info!(
+                        "Media session {}: changed during recording, leaving it",
+                        paused.app
+                    );
+                    skipped += 1;
+                    continue;
+                }
+                match paused
+                    .session
+                    .TryPlayAsync()
+                    .and_then(|request| request.join())
+                {
+                    Ok(true) => resumed += 1,
+                    Ok(false) => {
+                        info!("Media session {}: refused to resume", paused.app);
+                        skipped += 1;
+                    }
+                    Err(e) => {
+                        warn!("Media session {}: failed to resume: {}", paused.app, e);
+                        skipped += 1;
+                    }
+                }
+            }
+            #[cfg(not(windows))]
+            drop(paused);
+        }
+        if resumed + skipped > 0 {
+            info!(
+                "Media playback: resumed {}, left {} unchanged",
+                resumed, skipped
+            );
+        }
+    }
+}
+
+impl Drop for MediaPauser {
+    /// Resume on abnormal exit so players are not left paused.
+    fn drop(&mut self) {
+        self.resume();
+    }
+}
+
+/// Current playback status of `session`, if it can be read.
+#[cfg(windows)]
+fn status(
+    session: &GlobalSystemMediaTransportControlsSession,
+) -> Option<GlobalSystemMediaTransportControlsSessionPlaybackStatus> {
+    session
+        .GetPlaybackInfo()
+        .and_then(|info| info.PlaybackStatus())
+        .ok()
+}
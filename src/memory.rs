@@ -0,0 +1,119 @@
+//! Whether the Whisper model fits into memory.
+//!
+//! Loading a model that does not fit drives Windows into swap until the
+//! whole desktop stalls. Before a load, the memory the model needs is
+//! estimated from its file size and compared with the physical memory that
+//! is available; after the load, the working set it actually took is logged.
+
+use std::path::Path;
+
+#[cfg(windows)]
+use windows::Win32::System::ProcessStatus::{K32GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS};
+#[cfg(windows)]
+use windows::Win32::System::SystemInformation::{GlobalMemoryStatusEx, MEMORYSTATUSEX};
+#[cfg(windows)]
+use windows::Win32::System::Threading::GetCurrentProcess;
+
+/// Bytes in a mebibyte
+const MIB: u64 = 1024 * 1024;
+
+/// Memory Whisper needs on top of the model file, by largest file size:
+/// buffers for the encoder, decoder and KV cache, from whisper.cpp's model table
+const OVERHEAD: &[(u64, u64)] = &[
+    (100 * MIB, 200 * MIB),  // tiny
+    (200 * MIB, 250 * MIB),  // base
+    (600 * MIB, 400 * MIB),  // small
+    (2000 * MIB, 650 * MIB), // medium
+    (u64::MAX, 1000 * MIB),  // large
+];
+
+/// Physical memory left over after a load for the rest of the system to be comfortable
+const HEADROOM: u64 = 512 * MIB;
+
+/// A model that needs more memory than is available
+#[derive(Debug, Clone, Copy)]
+pub struct Shortfall {
+    /// Estimated bytes the model needs once loaded
+    pub required: u64,
+    /// Physical bytes available before the load
+    pub available: u64,
+}
+
+impl Shortfall {
+    /// Estimate in whole mebibytes, for messages
+    pub const fn required_mb(&self) -> u64 {
+        mb(self.required)
+    }
+
+    /// Available memory in whole mebibytes, for messages
+    pub const fn available_mb(&self) -> u64 {
+        mb(self.available)
+    }
+}
+
+/// `bytes` in whole mebibytes, for messages
+pub const fn mb(bytes: u64) -> u64 {
+    bytes / MIB
+}
+
+/// Estimated bytes the model in a file of `file_size` bytes needs once loaded
+pub fn estimate(file_size: u64) -> u64 {
+    let overhead = OVERHEAD
+        .iter()
+        .find(|&&(largest, _)| file_size <= largest)
+        .map_or(0, |&(_, overhead)| overhead);
+    file_size.saturating_add(overhead)
+}
+
+/// How far the model at `path` does not fit, `None` when it fits comfortably
+/// or either side cannot be measured
+pub fn check_model(path: &Path) -> Option<Shortfall> {
+    let required = estimate(std::fs::metadata(path).ok()?.len());
+    let available = available()?;
+    (required.saturating_add(HEADROOM) > available).then_some(Shortfall {
+        required,
+        available,
+    })
+}
+
+/// Physical memory available to new allocations without paging
+#[cfg(windows)]
+pub fn available() -> Option<u64> {
+    let mut status = MEMORYSTATUSEX {
+        dwLength: u32::try_from(size_of::<MEMORYSTATUSEX>()).ok()?,
+        ..Default::default()
+    };
+    // SAFETY: `status` is a valid MEMORYSTATUSEX with its length set, as required.
+    unsafe { GlobalMemoryStatusEx(&raw mut status) }.ok()?;
+    Some(status.ullAvailPhys)
+}
+
+/// Physical memory available to new allocations without paging
+#[cfg(not(windows))]
+pub const fn available() -> Option<u64> {
+    None
+}
+
+/// Bytes of physical memory the process uses right now
+#[cfg(windows)]
+pub fn working_set() -> Option<u64> {
+    let size = u32::try_from(size_of::<PROCESS_MEMORY_COUNTERS>()).ok()?;
+    let mut counters = PROCESS_MEMORY_COUNTERS {
+        cb: size,
+        ..Default::default()
+    };
+    // SAFETY: GetCurrentProcess has no preconditions; its pseudo handle needs no closing.
+    let process = unsafe { GetCurrentProcess() };
+    // SAFETY: `process` is valid for the calling process, and `counters` is
+    // writable for the `size` bytes passed.
+    let ok = unsafe { K32GetProcessMemoryInfo(process, &raw mut counters, size) };
+    ok.as_bool()
+        .then_some(counters.WorkingSetSize)
+        .and_then(|bytes| u64::try_from(bytes).ok())
+}
+
+/// Bytes of physical memory the process uses right now
+#[cfg(not(windows))]
+pub const fn working_set() -> Option<u64> {
+    None
+}
@@ -0,0 +1,265 @@
+//! The Whisper models kept loaded, one slot per model file.
+//!
+//! Normally only `whisper_model` is used. With `auto_model` rules, a short
+//! recording is transcribed with a smaller model instead, picked by its
+//! length. Every model loads in the background when first needed and is
+//! unloaded after its own cooldown.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+
+use crate::cooldown::ModelCooldown;
+use crate::error::SttError;
+use crate::whisper::WhisperEngine;
+
+/// Background load of one model
+type ModelLoad = JoinHandle<Result<WhisperEngine, SttError>>;
+
+/// Use `model` for recordings shorter than `under`, as `"seconds = model"`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelRule {
+    /// Recording length from which the rule no longer applies
+    pub under: Duration,
+    /// Model file name, as for `whisper_model`
+    pub model: String,
+}
+
+impl FromStr for ModelRule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (seconds, model) = s.split_once('=').context("expected \"seconds = model\"")?;
+        let seconds: f64 = seconds
+            .trim()
+            .parse()
+            .with_context(|| format!("{:?} is not a number of seconds", seconds.trim()))?;
+        let under = Duration::try_from_secs_f64(seconds)
+            .ok()
+            .filter(|under| !under.is_zero())
+            .with_context(|| format!("{seconds} is not a positive number of seconds"))?;
+        let model = model.trim();
+        if model.is_empty() {
+            anyhow::bail!("the model is empty");
+        }
+        Ok(Self {
+            under,
+            model: model.to_owned(),
+        })
+    }
+}
+
+/// Engine of one model file
+struct ModelSlot {
+    /// Loaded engine; running transcriptions hold their own reference
+    engine: Option<Arc<WhisperEngine>>,
+    /// Background load in progress
+    load: Option<ModelLoad>,
+    /// When the load in progress started, for its timeout
+    load_started: Instant,
+    /// Unloads the engine once it has been idle long enough
+    cooldown: ModelCooldown,
+}
+
+/// Loaded and loading models, and which one a recording is transcribed with
+pub struct ModelPool {
+    /// One slot per model file used so far
+    slots: HashMap<PathBuf, ModelSlot>,
+    /// Model for recordings no rule matches
+    default: PathBuf,
+    /// Shorter recordings' models, shortest threshold first
+    rules: Vec<(Duration, PathBuf)>,
+    /// Model of the latest dictation, loaded first when a recording starts
+    likely: PathBuf,
+    /// Idle time after which a model is unloaded
+    delay: Duration,
+}
+
+impl ModelPool {
+    /// Pool using `default` unless one of `rules` matches, unloading after `delay`
+    pub fn new(default: PathBuf, mut rules: Vec<(Duration, PathBuf)>, delay: Duration) -> Self {
+        rules.sort_by_key(|&(under, _)| under);
+        // Short dictations are the common case, so their model goes first
+        let likely = rules
+            .first()
+            .map_or_else(|| default.clone(), |(_, path)| path.clone());
+        Self {
+            slots: HashMap::new(),
+            default,
+            rules,
+            likely,
+            delay,
+        }
+    }
+
+    /// Whether recordings pick their model by length
+    pub const fn is_auto(&self) -> bool {
+        !self.rules.is_empty()
+    }
+
+    /// Model for a recording of `length`
+    pub fn select(&self, length: Duration) -> &Path {
+        self.rules
+            .iter()
+            .find(|&&(under, _)| length < under)
+            .map_or(&self.default, |(_, path)| path)
+    }
+
+    /// Model a recording that just started most likely needs
+    pub fn likely(&self) -> &Path {
+        &self.likely
+    }
+
+    /// Remember `path` as the model of the latest dictation
+    pub fn set_likely(&mut self, path: &Path) {
+        path.clone_into(&mut self.likely);
+    }
+
+    /// Change the idle time for every model
+    pub fn set_delay(&mut self, delay: Duration) {
+        self.delay = delay;
+        for slot in self.slots.values_mut() {
+            slot.cooldown.set_delay(delay);
+        }
+    }
+
+    /// Engine of `path`, if it is loaded
+    pub fn engine(&self, path: &Path) -> Option<Arc<WhisperEngine>> {
+        self.slots.get(path).and_then(|slot| slot.engine.clone())
+    }
+
+    /// Whether `path` is loaded or loading
+    pub fn is_available(&self, path: &Path) -> bool {
+        self.slots
+            .get(path)
+            .is_some_and(|slot| slot.engine.is_some() || slot.load.is_some())
+    }
+
+    /// Whether any model is loaded
+    pub fn any_loaded(&self) -> bool {
+        self.slots.values().any(|slot| slot.engine.is_some())
+    }
+
+    /// Track `load` as the background load of `path`
+    pub fn start_load(&mut self, path: &Path, load: ModelLoad) {
+        let slot = self.slot_mut(path);
+        slot.load = Some(load);
+        slot.load_started = Instant::now();
+    }
+
+    /// Take a load that finished, with the model it was loading
+    pub fn finished_load(&mut self) -> Option<(PathBuf, ModelLoad)> {
+        self.slots.iter_mut().find_map(|(path, slot)| {
+            let load = slot.load.take_if(|load| load.is_finished())?;
+            Some((path.clone(), load))
+        })
+    }
+
+    /// Models whose load has run for `timeout` or longer
+    pub fn overdue_loads(&self, timeout: Duration) -> Vec<PathBuf> {
+        self.slots
+            .iter()
+            .filter(|&(_, slot)| slot.load.is_some() && slot.load_started.elapsed() >= timeout)
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+
+    /// Stop tracking the load of `path`, leaving its thread detached
+    pub fn abandon_load(&mut self, path: &Path) {
+        self.slot_mut(path).load = None;
+    }
+
+    /// When the earliest load in progress times out after `timeout`
+    pub fn load_deadline(&self, timeout: Duration) -> Option<Instant> {
+        self.slots
+            .values()
+            .filter(|slot| slot.load.is_some())
+            .map(|slot| slot.load_started + timeout)
+            .min()
+    }
+
+    /// Take over `engine` as the loaded `path`, starting its cooldown
+    pub fn loaded(&mut self, path: &Path, engine: WhisperEngine) {
+        let slot = self.slot_mut(path);
+        slot.engine = Some(Arc::new(engine));
+        slot.cooldown.start(Instant::now());
+    }
+
+    /// Restart the cooldown of `path`, which was just needed
+    pub fn touch(&mut self, path: &Path) {
+        self.slot_mut(path).cooldown.start(Instant::now());
+    }
+
+    /// When the earliest cooldown of a loaded model expires
+    pub fn cooldown_deadline(&self) -> Option<Instant> {
+        self.slots
+            .values()
+            .filter(|slot| slot.engine.is_some())
+            .filter_map(|slot| slot.cooldown.deadline())
+            .min()
+    }
+
+    /// Unload every model whose cooldown expired at `now`, returning their
+    /// files; see [`ModelCooldown::should_unload`] for `handled_event`
+    pub fn unload_idle(&mut self, now: Instant, handled_event: bool) -> Vec<PathBuf> {
+        let mut unloaded = Vec::new();
+        for (path, slot) in &mut self.slots {
+            if slot
+                .engine
+                .as_ref()
+                .is_some_and(|engine| slot.cooldown.should_unload(now, engine, handled_event))
+            {
+                slot.engine = None;
+                slot.cooldown.reset();
+                unloaded.push(path.clone());
+            }
+        }
+        unloaded
+    }
+
+    /// Unload every model no transcription is using, except the `needed`
+    /// ones, to make room for them; returns the files unloaded
+    pub fn evict_except(&mut self, needed: &[PathBuf]) -> Vec<PathBuf> {
+        let mut evicted = Vec::new();
+        for (path, slot) in &mut self.slots {
+            if !needed.contains(path)
+                && slot
+                    .engine
+                    .as_ref()
+                    .is_some_and(|engine| Arc::strong_count(engine) == 1)
+            {
+                slot.engine = None;
+                slot.cooldown.reset();
+                evicted.push(path.clone());
+            }
+        }
+        evicted
+    }
+
+    /// Drop every engine the app holds, returning whether any was loaded
+    pub fn unload_all(&mut self) -> bool {
+        let mut any = false;
+        for slot in self.slots.values_mut() {
+            any |= slot.engine.take().is_some();
+            slot.cooldown.reset();
+        }
+        any
+    }
+
+    /// Slot of `path`, added on first use
+    fn slot_mut(&mut self, path: &Path) -> &mut ModelSlot {
+        self.slots
+            .entry(path.to_owned())
+            .or_insert_with(|| ModelSlot {
+                engine: None,
+                load: None,
+                load_started: Instant::now(),
+                cooldown: ModelCooldown::new(self.delay),
+            })
+    }
+}
@@ -0,0 +1,94 @@
+//! Tray balloon notifications.
+//!
+//! Shows Windows balloon tips on the existing tray icon so errors that would
+//! otherwise only reach the log file are visible to the user.
+
+#[cfg(windows)]
+use anyhow::Result;
+#[cfg(windows)]
+use windows::Win32::Foundation::HWND;
+#[cfg(windows)]
+use windows::Win32::UI::Shell::{
+    NIF_INFO, NIIF_ERROR, NIIF_INFO, NIIF_WARNING, NIM_MODIFY, NOTIFY_ICON_DATA_FLAGS,
+    NOTIFYICONDATAW, Shell_NotifyIconW,
+};
+
+/// Highest tray icon ID probed when looking up our icon's shell identifier.
+#[cfg(windows)]
+const MAX_PROBED_ICON_ID: u32 = 32;
+
+/// How prominent a notification is, mapped to the balloon's icon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// Nothing went wrong; the user is only told something
+    Info,
+    /// Something degraded but the app keeps working
+    Warning,
+    /// An operation failed
+    Error,
+}
+
+/// Find the shell ID under which the tray icon owned by `hwnd` was registered.
+///
+/// tray-icon does not expose the ID, so each candidate is probed with a
+/// no-op modify, which only succeeds for an icon that exists.
+#[cfg(windows)]
+pub fn find_icon_id(hwnd: *mut core::ffi::c_void) -> Option<u32> {
+    (1..=MAX_PROBED_ICON_ID).find(|&id| {
+        let data = NOTIFYICONDATAW {
+            cbSize: notify_data_size(),
+            hWnd: HWND(hwnd),
+            uID: id,
+            uFlags: NOTIFY_ICON_DATA_FLAGS(0),
+            ..Default::default()
+        };
+        // SAFETY: data is a fully initialized NOTIFYICONDATAW that outlives the call.
+        unsafe { Shell_NotifyIconW(NIM_MODIFY, &raw const data) }.as_bool()
+    })
+}
+
+/// Show a balloon tip on the tray icon identified by `hwnd` and `id`.
+#[cfg(windows)]
+pub fn show_balloon(
+    hwnd: *mut core::ffi::c_void,
+    id: u32,
+    title: &str,
+    body: &str,
+    severity: Severity,
+) -> Result<()> {
+    let mut data = NOTIFYICONDATAW {
+        cbSize: notify_data_size(),
+        hWnd: HWND(hwnd),
+        uID: id,
+        uFlags: NIF_INFO,
+        dwInfoFlags: match severity {
+            Severity::Info => NIIF_INFO,
+            Severity::Warning => NIIF_WARNING,
+            Severity::Error => NIIF_ERROR,
+        },
+        ..Default::default()
+    };
+    copy_wide(&mut data.szInfoTitle, title);
+    copy_wide(&mut data.szInfo, body);
+
+    // SAFETY: data is a fully initialized NOTIFYICONDATAW that outlives the call.
+    if !unsafe { Shell_NotifyIconW(NIM_MODIFY, &raw const data) }.as_bool() {
+        anyhow::bail!("Shell_NotifyIconW rejected the notification");
+    }
+    Ok(())
+}
+
+/// Size of the notification struct as the shell expects it in `cbSize`.
+#[cfg(windows)]
+fn notify_data_size() -> u32 {
+    u32::try_from(std::mem::size_of::<NOTIFYICONDATAW>()).unwrap_or(u32::MAX)
+}
+
+/// Copy text into a fixed UTF-16 buffer, truncating and keeping the null terminator.
+#[cfg(windows)]
+fn copy_wide(target: &mut [u16], text: &str) {
+    let capacity = target.len().saturating_sub(1);
+    for (slot, unit) in target.iter_mut().zip(text.encode_utf16().take(capacity)) {
+        *slot = unit;
+    }
+}
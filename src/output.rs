@@ -0,0 +1,94 @@
+//! Where transcriptions are delivered.
+//!
+//! Text is typed into the target window, appended to an output file watched
+//! by another tool, or both, in which case the file doubles as a journal.
+//! Each entry is formatted from a template and written with a single append
+//! so a watcher never sees half of it.
+
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+use crate::history;
+use crate::paths;
+use crate::stats;
+
+/// Attempts to open an output file that another program has locked
+const LOCKED_ATTEMPTS: u32 = 5;
+
+/// Pause between those attempts
+const LOCKED_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// What happens to a finished transcription
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputMode {
+    /// Type it into the target window
+    Inject,
+    /// Append it to the output file only
+    File,
+    /// Append it to the output file, then type it
+    Both,
+}
+
+impl OutputMode {
+    /// Whether the text is typed
+    pub const fn types(self) -> bool {
+        matches!(self, Self::Inject | Self::Both)
+    }
+
+    /// Whether the text is appended to the output file
+    pub const fn writes_file(self) -> bool {
+        matches!(self, Self::File | Self::Both)
+    }
+}
+
+impl FromStr for OutputMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "inject" => Ok(Self::Inject),
+            "file" => Ok(Self::File),
+            "both" => Ok(Self::Both),
+            other => anyhow::bail!("unknown output mode {other:?}, use inject, file or both"),
+        }
+    }
+}
+
+/// Append `text` to `path`, formatted by `template`.
+///
+/// A file locked by the program watching it is retried briefly before
+/// giving up.
+pub fn append(path: &Path, template: &str, text: &str, language: &str) -> Result<()> {
+    let entry = template
+        .replace("{time}", &history::timestamp(history::now_secs()))
+        .replace("{date}", &stats::today())
+        .replace("{words}", &text.split_whitespace().count().to_string())
+        .replace("{language}", language)
+        .replace("{text}", text);
+    paths::ensure_parent(path)?;
+
+    let mut attempt = 1;
+    let mut file = loop {
+        match OpenOptions::new().create(true).append(true).open(path) {
+            Ok(file) => break file,
+            Err(e) if is_locked(&e) && attempt < LOCKED_ATTEMPTS => {
+                attempt += 1;
+                std::thread::sleep(LOCKED_RETRY_DELAY);
+            }
+            Err(e) => return Err(e).with_context(|| format!("Failed to open {}", path.display())),
+        }
+    };
+    file.write_all(entry.as_bytes())
+        .with_context(|| format!("Failed to append to {}", path.display()))
+}
+
+/// Whether opening failed because another process holds the file
+fn is_locked(error: &io::Error) -> bool {
+    // ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION
+    cfg!(windows) && matches!(error.raw_os_error(), Some(32 | 33))
+}
@@ -0,0 +1,820 @@
+//! On-screen recording indicator.
+//!
+//! A small always-on-top window near a screen corner shows a red dot with an
+//! input level meter while recording and a spinner while transcribing, for
+//! when the tray is out of sight (e.g. behind a fullscreen app). The window is
+//! click-through and never takes focus, so typing stays in the target app.
+//! It is drawn per-monitor DPI aware, so it keeps its size on every screen.
+
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::sync::Arc;
+#[cfg(windows)]
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+
+#[cfg(windows)]
+use anyhow::Context;
+use anyhow::Result;
+use tracing::warn;
+
+#[cfg(windows)]
+use windows::Win32::Foundation::{COLORREF, HWND, LPARAM, LRESULT, POINT, RECT, SIZE, WPARAM};
+#[cfg(windows)]
+use windows::Win32::Graphics::Gdi::{
+    AC_SRC_ALPHA, AC_SRC_OVER, BI_RGB, BITMAPINFO, BITMAPINFOHEADER, BLENDFUNCTION,
+    CreateCompatibleDC, CreateDIBSection, DIB_RGB_COLORS, DeleteDC, DeleteObject,
+    EnumDisplayMonitors, GetMonitorInfoW, HBITMAP, HDC, HGDIOBJ, HMONITOR,
+    MONITOR_DEFAULTTOPRIMARY, MONITORINFO, MonitorFromPoint, MonitorFromWindow, SelectObject,
+};
+#[cfg(windows)]
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+#[cfg(windows)]
+use windows::Win32::UI::HiDpi::{
+    DPI_AWARENESS_CONTEXT, DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2, GetDpiForMonitor,
+    MDT_EFFECTIVE_DPI, SetThreadDpiAwarenessContext,
+};
+#[cfg(windows)]
+use windows::Win32::UI::WindowsAndMessaging::{
+    CreateWindowExW, DefWindowProcW, DestroyWindow, GetForegroundWindow, HWND_TOPMOST,
+    RegisterClassW, SW_HIDE, SW_SHOWNOACTIVATE, SWP_NOACTIVATE, SWP_NOMOVE, SWP_NOSIZE,
+    SetWindowPos, ShowWindow, ULW_ALPHA, UpdateLayeredWindow, WM_DISPLAYCHANGE, WM_DPICHANGED,
+    WNDCLASSW, WS_EX_LAYERED, WS_EX_NOACTIVATE, WS_EX_TOOLWINDOW, WS_EX_TOPMOST, WS_EX_TRANSPARENT,
+    WS_POPUP,
+};
+#[cfg(windows)]
+use windows::core::{BOOL, w};
+
+use crate::config::Config;
+use crate::tray::TrayState;
+
+/// Indicator width in pixels at 100% scaling
+const WIDTH: i32 = 72;
+
+/// Indicator height in pixels at 100% scaling
+const HEIGHT: i32 = 24;
+
+/// Screen DPI at 100% scaling, which the sizes above are given for
+const BASE_DPI: i32 = 96;
+
+/// Default gap between the indicator and the edges of the work area
+pub const DEFAULT_MARGIN: u32 = 16;
+
+/// Set by the window procedure when monitors or their scaling change
+#[cfg(windows)]
+static DISPLAY_CHANGED: AtomicBool = AtomicBool::new(false);
+
+/// Time between redraws while the indicator is shown
+const FRAME_INTERVAL: Duration = Duration::from_millis(40);
+
+/// Number of level bars, each showing the peak of one frame
+const LEVEL_BARS: usize = 12;
+
+/// Number of dots around the transcribing spinner
+const SPINNER_DOTS: usize = 8;
+
+/// Screen corner the indicator is placed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverlayCorner {
+    /// Upper left corner
+    TopLeft,
+    /// Upper right corner
+    TopRight,
+    /// Lower left corner, above the taskbar
+    BottomLeft,
+    /// Lower right corner, above the taskbar
+    BottomRight,
+}
+
+impl FromStr for OverlayCorner {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().replace('_', "-").as_str() {
+            "top-left" => Ok(Self::TopLeft),
+            "top-right" => Ok(Self::TopRight),
+            "bottom-left" => Ok(Self::BottomLeft),
+            "bottom-right" => Ok(Self::BottomRight),
+            other => anyhow::bail!(
+                "unknown corner {other:?}, use top-left, top-right, bottom-left or bottom-right"
+            ),
+        }
+    }
+}
+
+/// Monitor the indicator is placed on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverlayMonitor {
+    /// The main display
+    Primary,
+    /// The display showing the foreground window, followed as it changes
+    Active,
+    /// The display at this position, counted from 1 left to right
+    Number(usize),
+}
+
+impl FromStr for OverlayMonitor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "primary" => Ok(Self::Primary),
+            "active" => Ok(Self::Active),
+            other => match other.parse::<usize>() {
+                Ok(number) if number > 0 => Ok(Self::Number(number)),
+                _ => anyhow::bail!(
+                    "unknown monitor {other:?}, use primary, active or a number from 1"
+                ),
+            },
+        }
+    }
+}
+
+/// Where on screen the indicator goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OverlayPlacement {
+    /// Monitor the indicator is shown on
+    pub monitor: OverlayMonitor,
+    /// Corner of that monitor's work area
+    pub corner: OverlayCorner,
+    /// Gap to the edges of the work area, in pixels at 100% scaling
+    pub margin: u32,
+}
+
+impl OverlayPlacement {
+    /// Placement set in `config`
+    pub const fn from_config(config: &Config) -> Self {
+        Self {
+            monitor: config.overlay_monitor,
+            corner: config.overlay_corner,
+            margin: config.overlay_margin,
+        }
+    }
+}
+
+/// What the indicator currently shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Indicator {
+    /// Red dot and input level meter
+    Recording,
+    /// Rotating spinner
+    Transcribing,
+}
+
+/// Recording indicator window, created the first time it is shown
+pub struct Overlay {
+    /// Whether the indicator is shown at all
+    enabled: bool,
+    /// Where the indicator is placed
+    placement: OverlayPlacement,
+    /// Peak input level since the last frame, as `f32` bits, written by the recorder
+    level: Arc<AtomicU32>,
+    /// Levels of the most recent frames, oldest first
+    levels: VecDeque<f32>,
+    /// What is shown, `None` while hidden
+    shown: Option<Indicator>,
+    /// Spinner position, advanced every frame
+    step: usize,
+    /// When the last frame was drawn
+    drawn_at: Instant,
+    /// Window showing the indicator
+    #[cfg(windows)]
+    window: Option<Window>,
+}
+
+impl Overlay {
+    /// Create the indicator; no window exists until it is first shown
+    pub fn new(enabled: bool, placement: OverlayPlacement) -> Self {
+        Self {
+            enabled,
+            placement,
+            level: Arc::new(AtomicU32::new(0)),
+            levels: VecDeque::with_capacity(LEVEL_BARS),
+            shown: None,
+            step: 0,
+            drawn_at: Instant::now(),
+            #[cfg(windows)]
+            window: None,
+        }
+    }
+
+    /// Peak level shared with the recorder, which raises it for each audio buffer
+    pub fn level(&self) -> Arc<AtomicU32> {
+        Arc::clone(&self.level)
+    }
+
+    /// Turn the indicator on or off; it appears with the next state change
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.hide();
+        }
+    }
+
+    /// Place the indicator at `placement` from the next time it is shown
+    pub const fn set_placement(&mut self, placement: OverlayPlacement) {
+        self.placement = placement;
+    }
+
+    /// Mirror the tray state: shown while recording or transcribing, hidden otherwise
+    pub fn show(&mut self, state: TrayState) {
+        let indicator = match state {
+            TrayState::Recording | TrayState::Translating | TrayState::Latched => {
+                Some(Indicator::Recording)
+            }
+            TrayState::Transcribing => Some(Indicator::Transcribing),
+            TrayState::Idle | TrayState::Paused => None,
+        };
+        let Some(indicator) = indicator.filter(|_| self.enabled) else {
+            self.hide();
+            return;
+        };
+        if self.shown == Some(indicator) {
+            return;
+        }
+        if self.shown.is_none() {
+            self.levels.clear();
+            self.level.store(0, Ordering::Relaxed);
+        }
+        self.shown = Some(indicator);
+        self.step = 0;
+        if let Err(e) = self.present(true) {
+            warn!("Recording indicator disabled: {:#}", e);
+            self.enabled = false;
+            self.hide();
+        }
+    }
+
+    /// Redraw the indicator when its next frame is due
+    ///
+    /// Cheap to call on every loop iteration; does nothing while hidden.
+    pub fn tick(&mut self) {
+        if self.shown.is_none() || self.drawn_at.elapsed() < FRAME_INTERVAL {
+            return;
+        }
+        if let Err(e) = self.present(false) {
+            warn!("Failed to draw recording indicator: {:#}", e);
+        }
+    }
+
+    /// When the next frame is due, `None` while hidden
+    pub fn next_frame(&self) -> Option<Instant> {
+        self.shown.map(|_| self.drawn_at + FRAME_INTERVAL)
+    }
+
+    /// Advance the animation and draw a frame, moving the window if `reposition`
+    fn present(&mut self, reposition: bool) -> Result<()> {
+        // Positions and sizes are in physical pixels for every call below
+        #[cfg(windows)]
+        let _dpi_aware = PerMonitorDpi::enter();
+        self.drawn_at = Instant::now();
+        self.step = (self.step + 1) % SPINNER_DOTS;
+        let peak = f32::from_bits(self.level.swap(0, Ordering::Relaxed));
+        if self.levels.len() == LEVEL_BARS {
+            self.levels.pop_front();
+        }
+        // Square root so quiet speech still moves the bars visibly
+        self.levels.push_back(peak.clamp(0.0, 1.0).sqrt());
+
+        let Some(indicator) = self.shown else {
+            return Ok(());
+        };
+        let dpi = self.place_window(reposition)?;
+        let pixels = match indicator {
+            Indicator::Recording => draw_recording(&self.levels, dpi),
+            Indicator::Transcribing => draw_spinner(self.step, dpi),
+        };
+        self.draw_window(&pixels)
+    }
+
+    /// Create and place the window as needed, returning the DPI it is drawn at.
+    ///
+    /// The monitor is looked up again when `reposition`, after the displays
+    /// changed, and on every frame for the active monitor so the indicator
+    /// follows the foreground window from screen to screen.
+    #[cfg(windows)]
+    fn place_window(&mut self, reposition: bool) -> Result<i32> {
+        let display_changed = DISPLAY_CHANGED.swap(false, Ordering::Relaxed);
+        let refresh = reposition || display_changed || self.window.is_none();
+        if !refresh && self.placement.monitor != OverlayMonitor::Active {
+            return Ok(self.window.as_ref().map_or(BASE_DPI, |window| window.dpi));
+        }
+        let monitor = find_monitor(self.placement.monitor);
+        if let Some(ref window) = self.window
+            && !refresh
+            && window.monitor == monitor
+        {
+            return Ok(window.dpi);
+        }
+        let (position, dpi) = corner_position(monitor, self.placement);
+        // A window drawn for another DPI is replaced, as its bitmap has the wrong size
+        if self.window.as_ref().is_some_and(|window| window.dpi != dpi) {
+            self.window = None;
+        }
+        let window = match self.window {
+            Some(ref mut window) => window,
+            None => self.window.insert(Window::create(dpi)?),
+        };
+        window.position = position;
+        window.monitor = monitor;
+        Ok(dpi)
+    }
+
+    /// The indicator needs a layered window, which only Windows provides.
+    #[cfg(not(windows))]
+    fn place_window(&mut self, _reposition: bool) -> Result<i32> {
+        anyhow::bail!("the recording indicator is only available on Windows")
+    }
+
+    /// Copy `pixels` to the window placed by [`Self::place_window`] and show it
+    #[cfg(windows)]
+    fn draw_window(&self, pixels: &[u32]) -> Result<()> {
+        let Some(ref window) = self.window else {
+            return Ok(());
+        };
+        window.draw(pixels)?;
+        window.set_visible(true);
+        Ok(())
+    }
+
+    /// The indicator needs a layered window, which only Windows provides.
+    #[cfg(not(windows))]
+    fn draw_window(&self, _pixels: &[u32]) -> Result<()> {
+        anyhow::bail!("the recording indicator is only available on Windows")
+    }
+
+    /// Hide the window, keeping it for the next recording
+    fn hide(&mut self) {
+        self.shown = None;
+        #[cfg(windows)]
+        if let Some(ref window) = self.window {
+            window.set_visible(false);
+        }
+    }
+}
+
+/// Layered popup window and the bitmap its contents are drawn into.
+#[cfg(windows)]
+struct Window {
+    /// Window handle
+    hwnd: HWND,
+    /// Memory device context holding the bitmap
+    dc: HDC,
+    /// 32-bit top-down DIB section of `size` pixels
+    bitmap: HBITMAP,
+    /// Object the bitmap replaced in the device context, restored before deletion
+    previous: HGDIOBJ,
+    /// Premultiplied BGRA pixels of the bitmap
+    bits: *mut u32,
+    /// Top-left corner of the window on screen
+    position: POINT,
+    /// Window and bitmap size in physical pixels
+    size: SIZE,
+    /// DPI the window is drawn at
+    dpi: i32,
+    /// Monitor the window was placed on
+    monitor: HMONITOR,
+}
+
+#[cfg(windows)]
+impl Window {
+    /// Create the hidden window and its drawing surface, sized for `dpi`.
+    ///
+    /// Must be called with the thread per-monitor DPI aware, which the
+    /// window keeps for its lifetime.
+    fn create(dpi: i32) -> Result<Self> {
+        let size = SIZE {
+            cx: scaled(WIDTH, dpi),
+            cy: scaled(HEIGHT, dpi),
+        };
+        let class_name = w!("SpeedySttRecordingIndicator");
+        // SAFETY: the class and window are registered and created with valid
+        // static strings and this module's own window procedure; the DIB
+        // section is created with a correctly sized header, and every handle
+        // is released in Drop.
+        unsafe {
+            let instance = GetModuleHandleW(None).context("Failed to get module handle")?;
+            let class = WNDCLASSW {
+                lpfnWndProc: Some(window_proc),
+                hInstance: instance.into(),
+                lpszClassName: class_name,
+                ..Default::default()
+            };
+            // Fails harmlessly when an earlier indicator registered the class
+            RegisterClassW(&class);
+            let hwnd = CreateWindowExW(
+                WS_EX_LAYERED
+                    | WS_EX_TRANSPARENT
+                    | WS_EX_TOPMOST
+                    | WS_EX_NOACTIVATE
+                    | WS_EX_TOOLWINDOW,
+                class_name,
+                w!("Speedy STT recording indicator"),
+                WS_POPUP,
+                0,
+                0,
+                size.cx,
+                size.cy,
+                None,
+                None,
+                Some(instance.into()),
+                None,
+            )
+            .context("Failed to create indicator window")?;
+
+            let info = BITMAPINFO {
+                bmiHeader: BITMAPINFOHEADER {
+                    biSize: u32::try_from(size_of::<BITMAPINFOHEADER>()).unwrap_or_default(),
+                    biWidth: size.cx,
+                    // Negative height makes the rows top-down
+                    biHeight: -size.cy,
+                    biPlanes: 1,
+                    biBitCount: 32,
+                    biCompression: BI_RGB.0,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let dc = CreateCompatibleDC(None);
+            let mut bits = std::ptr::null_mut();
+            let bitmap = match CreateDIBSection(Some(dc), &info, DIB_RGB_COLORS, &mut bits, None, 0)
+            {
+                Ok(bitmap) => bitmap,
+                Err(e) => {
+                    let _ = DeleteDC(dc);
+                    let _ = DestroyWindow(hwnd);
+                    return Err(e).context("Failed to create indicator bitmap");
+                }
+            };
+            let previous = SelectObject(dc, bitmap.into());
+            Ok(Self {
+                hwnd,
+                dc,
+                bitmap,
+                previous,
+                bits: bits.cast(),
+                position: POINT::default(),
+                size,
+                dpi,
+                monitor: HMONITOR::default(),
+            })
+        }
+    }
+
+    /// Replace the window contents with `pixels`, at the current position.
+    fn draw(&self, pixels: &[u32]) -> Result<()> {
+        let source = POINT::default();
+        let blend = BLENDFUNCTION {
+            BlendOp: u8::try_from(AC_SRC_OVER).unwrap_or_default(),
+            BlendFlags: 0,
+            SourceConstantAlpha: 255,
+            AlphaFormat: u8::try_from(AC_SRC_ALPHA).unwrap_or_default(),
+        };
+        let len = usize::try_from(self.size.cx * self.size.cy).unwrap_or_default();
+        // SAFETY: `bits` points to the DIB section of `size` 32-bit pixels
+        // owned by this window, which lives until Drop and is only touched
+        // from this thread.
+        unsafe {
+            std::slice::from_raw_parts_mut(self.bits, len).copy_from_slice(&pixels[..len]);
+            UpdateLayeredWindow(
+                self.hwnd,
+                None,
+                Some(&self.position),
+                Some(&self.size),
+                Some(self.dc),
+                Some(&source),
+                COLORREF(0),
+                Some(&blend),
+                ULW_ALPHA,
+            )
+            .context("Failed to update indicator window")
+        }
+    }
+
+    /// Show the window without activating it, or hide it.
+    fn set_visible(&self, visible: bool) {
+        // SAFETY: `hwnd` is a window created by this struct and not yet destroyed.
+        unsafe {
+            if visible {
+                let _ = ShowWindow(self.hwnd, SW_SHOWNOACTIVATE);
+                // Re-assert topmost, as other topmost windows may have been raised since
+                let _ = SetWindowPos(
+                    self.hwnd,
+                    Some(HWND_TOPMOST),
+                    0,
+                    0,
+                    0,
+                    0,
+                    SWP_NOMOVE | SWP_NOSIZE | SWP_NOACTIVATE,
+                );
+            } else {
+                let _ = ShowWindow(self.hwnd, SW_HIDE);
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Window {
+    fn drop(&mut self) {
+        // SAFETY: the handles were created in `create` and are released once,
+        // the bitmap after it has been deselected from the device context.
+        unsafe {
+            SelectObject(self.dc, self.previous);
+            let _ = DeleteObject(self.bitmap.into());
+            let _ = DeleteDC(self.dc);
+            let _ = DestroyWindow(self.hwnd);
+        }
+    }
+}
+
+/// Window procedure of the indicator, which notes display changes so the
+/// next frame places it again.
+#[cfg(windows)]
+unsafe extern "system" fn window_proc(
+    hwnd: HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    if msg == WM_DISPLAYCHANGE || msg == WM_DPICHANGED {
+        DISPLAY_CHANGED.store(true, Ordering::Relaxed);
+        return LRESULT(0);
+    }
+    // SAFETY: forwards the arguments Windows passed to this window procedure.
+    unsafe { DefWindowProcW(hwnd, msg, wparam, lparam) }
+}
+
+/// Per-monitor DPI awareness of the calling thread, restored on drop.
+///
+/// The rest of the app stays DPI unaware and is scaled by Windows.
+#[cfg(windows)]
+struct PerMonitorDpi(DPI_AWARENESS_CONTEXT);
+
+#[cfg(windows)]
+impl PerMonitorDpi {
+    /// Make the thread per-monitor DPI aware; does nothing before Windows 10 1703
+    fn enter() -> Self {
+        // SAFETY: changes only the calling thread's awareness, which Drop restores.
+        Self(unsafe { SetThreadDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2) })
+    }
+}
+
+#[cfg(windows)]
+impl Drop for PerMonitorDpi {
+    fn drop(&mut self) {
+        if !self.0.0.is_null() {
+            // SAFETY: restores the awareness `enter` replaced on this thread.
+            unsafe { SetThreadDpiAwarenessContext(self.0) };
+        }
+    }
+}
+
+/// Monitor `choice` names right now, the primary one when it names none
+#[cfg(windows)]
+fn find_monitor(choice: OverlayMonitor) -> HMONITOR {
+    // SAFETY: the primary monitor always contains the origin.
+    let primary = || unsafe { MonitorFromPoint(POINT::default(), MONITOR_DEFAULTTOPRIMARY) };
+    match choice {
+        OverlayMonitor::Primary => primary(),
+        // SAFETY: MonitorFromWindow accepts any window handle, including
+        // none, and falls back to the primary monitor.
+        OverlayMonitor::Active => unsafe {
+            MonitorFromWindow(GetForegroundWindow(), MONITOR_DEFAULTTOPRIMARY)
+        },
+        OverlayMonitor::Number(number) => {
+            let mut monitors: Vec<(HMONITOR, RECT)> = Vec::new();
+            // SAFETY: the callback only runs during the call and casts the
+            // parameter back to the vector, which outlives it.
+            let _ = unsafe {
+                EnumDisplayMonitors(
+                    None,
+                    None,
+                    Some(collect_monitor),
+                    LPARAM(
+                        std::ptr::from_mut(&mut monitors)
+                            .expose_provenance()
+                            .cast_signed(),
+                    ),
+                )
+            };
+            monitors.sort_by_key(|&(_, area)| (area.left, area.top));
+            monitors
+                .get(number - 1)
+                .map(|&(monitor, _)| monitor)
+                .unwrap_or_else(|| {
+                    warn!(
+                        "Monitor {} not found among {}, showing the indicator on the primary one",
+                        number,
+                        monitors.len()
+                    );
+                    primary()
+                })
+        }
+    }
+}
+
+/// Collect each monitor and its area into the `Vec<(HMONITOR, RECT)>` that `monitors` points to
+#[cfg(windows)]
+unsafe extern "system" fn collect_monitor(
+    monitor: HMONITOR,
+    _dc: HDC,
+    area: *mut RECT,
+    monitors: LPARAM,
+) -> BOOL {
+    // SAFETY: `find_monitor` passes a pointer to its vector, which is not
+    // otherwise used while EnumDisplayMonitors runs.
+    let monitors = unsafe {
+        &mut *std::ptr::with_exposed_provenance_mut::<Vec<(HMONITOR, RECT)>>(
+            monitors.0.cast_unsigned(),
+        )
+    };
+    // SAFETY: EnumDisplayMonitors passes the monitor's area, valid for the call.
+    if let Some(&area) = unsafe { area.as_ref() } {
+        monitors.push((monitor, area));
+    }
+    BOOL::from(true)
+}
+
+/// Top-left position for the indicator at `placement` in the work area of
+/// `monitor`, in physical pixels, and the DPI of that monitor.
+#[cfg(windows)]
+fn corner_position(monitor: HMONITOR, placement: OverlayPlacement) -> (POINT, i32) {
+    let (mut dpi_x, mut dpi_y) = (0, 0);
+    // SAFETY: GetDpiForMonitor accepts any monitor handle and writes both DPIs.
+    let dpi = match unsafe {
+        GetDpiForMonitor(monitor, MDT_EFFECTIVE_DPI, &raw mut dpi_x, &raw mut dpi_y)
+    } {
+        Ok(()) => i32::try_from(dpi_x).unwrap_or(BASE_DPI),
+        Err(_) => BASE_DPI,
+    };
+    let margin = scaled(i32::try_from(placement.margin).unwrap_or(i32::MAX), dpi);
+    let mut info = MONITORINFO {
+        cbSize: u32::try_from(size_of::<MONITORINFO>()).unwrap_or_default(),
+        ..Default::default()
+    };
+    // SAFETY: `info` is a correctly sized MONITORINFO; an invalid monitor
+    // handle only makes the call fail.
+    if !unsafe { GetMonitorInfoW(monitor, &raw mut info) }.as_bool() {
+        return (
+            POINT {
+                x: margin,
+                y: margin,
+            },
+            dpi,
+        );
+    }
+    let area = info.rcWork;
+    let (width, height) = (scaled(WIDTH, dpi), scaled(HEIGHT, dpi));
+    let left = area.left.saturating_add(margin);
+    let right = area.right.saturating_sub(margin).saturating_sub(width);
+    let top = area.top.saturating_add(margin);
+    let bottom = area.bottom.saturating_sub(margin).saturating_sub(height);
+    let position = match placement.corner {
+        OverlayCorner::TopLeft => POINT { x: left, y: top },
+        OverlayCorner::TopRight => POINT { x: right, y: top },
+        OverlayCorner::BottomLeft => POINT { x: left, y: bottom },
+        OverlayCorner::BottomRight => POINT {
+            x: right,
+            y: bottom,
+        },
+    };
+    (position, dpi)
+}
+
+/// `length` pixels at 100% scaling in physical pixels at `dpi`
+fn scaled(length: i32, dpi: i32) -> i32 {
+    length.saturating_mul(dpi) / BASE_DPI
+}
+
+/// Premultiplied RGBA pixels being composed, converted to BGRA once complete.
+///
+/// Shapes are given in pixels at 100% scaling and drawn at the canvas DPI.
+struct Canvas {
+    /// Pixels in row-major order, each channel in `0.0..=1.0`
+    pixels: Vec<[f32; 4]>,
+    /// Pixels per row
+    row_len: usize,
+    /// Physical pixels per pixel at 100% scaling
+    scale: f32,
+}
+
+impl Canvas {
+    /// Canvas at `dpi` with the translucent dark pill every frame is drawn on
+    fn background(dpi: i32) -> Self {
+        let row_len = usize::try_from(scaled(WIDTH, dpi)).unwrap_or_default();
+        let rows = usize::try_from(scaled(HEIGHT, dpi)).unwrap_or_default();
+        let mut canvas = Self {
+            pixels: vec![[0.0; 4]; row_len * rows],
+            row_len: row_len.max(1),
+            scale: f32::from(u16::try_from(dpi).unwrap_or(96)) / 96.0,
+        };
+        let radius = f32::from(u8::try_from(HEIGHT / 2).unwrap_or_default());
+        canvas.capsule(
+            (radius, radius),
+            (width() - radius, radius),
+            radius,
+            [0.08, 0.08, 0.08, 0.78],
+        );
+        canvas
+    }
+
+    /// Draw a capsule of `radius` around the segment from `a` to `b`, antialiased.
+    ///
+    /// A circle is a capsule whose ends coincide.
+    fn capsule(&mut self, a: (f32, f32), b: (f32, f32), radius: f32, rgba: [f32; 4]) {
+        let scale = self.scale;
+        let (a, b, radius) = (
+            (a.0 * scale, a.1 * scale),
+            (b.0 * scale, b.1 * scale),
+            radius * scale,
+        );
+        let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+        let length_sq = dx.mul_add(dx, dy * dy);
+        let row_len = self.row_len;
+        for (index, pixel) in self.pixels.iter_mut().enumerate() {
+            let x = f32::from(u16::try_from(index % row_len).unwrap_or_default()) + 0.5;
+            let y = f32::from(u16::try_from(index / row_len).unwrap_or_default()) + 0.5;
+            let t = if length_sq > 0.0 {
+                ((x - a.0).mul_add(dx, (y - a.1) * dy) / length_sq).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let (nearest_x, nearest_y) = (t.mul_add(dx, a.0), t.mul_add(dy, a.1));
+            let distance = (x - nearest_x).hypot(y - nearest_y);
+            let coverage = (radius - distance + 0.5).clamp(0.0, 1.0);
+            if coverage <= 0.0 {
+                continue;
+            }
+            let alpha = rgba[3] * coverage;
+            for channel in 0..3 {
+                pixel[channel] = rgba[channel].mul_add(alpha, pixel[channel] * (1.0 - alpha));
+            }
+            pixel[3] = alpha + pixel[3] * (1.0 - alpha);
+        }
+    }
+
+    /// Pixels as premultiplied BGRA, the layout `UpdateLayeredWindow` expects
+    fn into_bgra(self) -> Vec<u32> {
+        #[allow(
+            clippy::cast_possible_truncation,
+            clippy::cast_sign_loss,
+            reason = "channels are clamped to 0..=1 before scaling to a byte"
+        )]
+        let byte = |value: f32| u32::from((value.clamp(0.0, 1.0) * 255.0).round() as u8);
+        self.pixels
+            .into_iter()
+            .map(|[r, g, b, a]| byte(a) << 24 | byte(r) << 16 | byte(g) << 8 | byte(b))
+            .collect()
+    }
+}
+
+/// Indicator width at 100% scaling as a drawing coordinate
+fn width() -> f32 {
+    f32::from(u8::try_from(WIDTH).unwrap_or_default())
+}
+
+/// Red dot followed by one bar per recent level, newest on the right, at `dpi`.
+fn draw_recording(levels: &VecDeque<f32>, dpi: i32) -> Vec<u32> {
+    let mut canvas = Canvas::background(dpi);
+    let middle = f32::from(u8::try_from(HEIGHT / 2).unwrap_or_default());
+    canvas.capsule((12.0, middle), (12.0, middle), 6.0, [0.92, 0.16, 0.16, 1.0]);
+
+    let (first, last) = (26.0, width() - 10.0);
+    let spacing = (last - first) / f32::from(u8::try_from(LEVEL_BARS - 1).unwrap_or(1));
+    // Bars fill from the right so the newest level is always next to the edge
+    let offset = LEVEL_BARS - levels.len();
+    for (slot, level) in levels.iter().enumerate() {
+        let x = f32::from(u8::try_from(slot + offset).unwrap_or_default()).mul_add(spacing, first);
+        let half = level.mul_add(middle - 6.0, 1.0);
+        canvas.capsule(
+            (x, middle - half),
+            (x, middle + half),
+            1.0,
+            [1.0, 1.0, 1.0, 0.9],
+        );
+    }
+    canvas.into_bgra()
+}
+
+/// Ring of dots with a bright head at `step` and a fading tail behind it, at `dpi`.
+fn draw_spinner(step: usize, dpi: i32) -> Vec<u32> {
+    let mut canvas = Canvas::background(dpi);
+    let center = (
+        width() / 2.0,
+        f32::from(u8::try_from(HEIGHT / 2).unwrap_or_default()),
+    );
+    let dots = f32::from(u8::try_from(SPINNER_DOTS).unwrap_or(1));
+    for dot in 0..SPINNER_DOTS {
+        let angle = f32::from(u8::try_from(dot).unwrap_or_default()) / dots * std::f32::consts::TAU;
+        let behind = (step + SPINNER_DOTS - dot) % SPINNER_DOTS;
+        let alpha = 1.0 - f32::from(u8::try_from(behind).unwrap_or_default()) / dots;
+        let position = (
+            angle.sin().mul_add(7.0, center.0),
+            (-angle.cos()).mul_add(7.0, center.1),
+        );
+        canvas.capsule(position, position, 1.6, [1.0, 1.0, 1.0, alpha.max(0.15)]);
+    }
+    canvas.into_bgra()
+}
@@ -0,0 +1,194 @@
+//! Resolution of file locations.
+//!
+//! Bundled assets are looked up next to the executable, with the working
+//! directory as a fallback for running from the repo root. User files (config,
+//! logs, statistics) live under `%APPDATA%\speedy-stt` (`~/.config/speedy-stt`
+//! on Linux) unless a portable copy exists next to the executable, and models
+//! may also come from a shared data directory under `%LOCALAPPDATA%`.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use tracing::info;
+
+/// Directory of the bundled sounds and icons, next to the executable.
+const ASSETS_DIR_NAME: &str = "assets";
+
+/// Directory name used under the per-user config and data directories.
+const APP_DIR_NAME: &str = "speedy-stt";
+
+/// File name of the log file.
+const LOG_FILE_NAME: &str = "speedy-stt.log";
+
+/// File name of the daily statistics file.
+const STATS_FILE_NAME: &str = "speedy-stt-stats.json";
+
+/// File name of the settings changed from the tray.
+const STATE_FILE_NAME: &str = "state.toml";
+
+/// File name of the original volumes of ducked sessions.
+const DUCKING_JOURNAL_FILE_NAME: &str = "ducking-journal.json";
+
+/// Directory containing the running executable, if it can be determined.
+pub fn exe_dir() -> Option<PathBuf> {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+}
+
+/// Resolve a bundled relative path against the executable directory.
+///
+/// Prefers an existing file next to the executable, then an existing file in
+/// the working directory, and otherwise returns the executable-relative path
+/// so newly created files land next to the binary.
+pub fn resolve(relative: &str) -> PathBuf {
+    let relative = Path::new(relative);
+    if relative.is_absolute() {
+        return relative.to_path_buf();
+    }
+
+    let beside_exe = exe_dir().map(|dir| dir.join(relative));
+    if let Some(ref path) = beside_exe
+        && path.exists()
+    {
+        return path.clone();
+    }
+    if relative.exists() {
+        return relative.to_path_buf();
+    }
+    beside_exe.unwrap_or_else(|| relative.to_path_buf())
+}
+
+/// Per-user configuration directory (`%APPDATA%\speedy-stt` on Windows), if known.
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(APP_DIR_NAME))
+}
+
+/// Per-user local data directory (`%LOCALAPPDATA%\speedy-stt` on Windows), if known.
+pub fn data_dir() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|dir| dir.join(APP_DIR_NAME))
+}
+
+/// A user file next to the executable if one already exists there (portable
+/// use), otherwise `name` inside `subdir` of the per-user config directory.
+fn user_file(subdir: &str, name: &str) -> PathBuf {
+    let portable = exe_dir().map(|dir| dir.join(name));
+    if let Some(ref path) = portable
+        && path.exists()
+    {
+        return path.clone();
+    }
+    config_dir()
+        .map(|dir| dir.join(subdir).join(name))
+        .or(portable)
+        .unwrap_or_else(|| PathBuf::from(name))
+}
+
+/// Configured log file; the dated files actually written share its folder and name.
+pub fn log_file() -> PathBuf {
+    user_file("logs", LOG_FILE_NAME)
+}
+
+/// Location of the JSON file holding daily dictation statistics.
+pub fn stats_file() -> PathBuf {
+    user_file("", STATS_FILE_NAME)
+}
+
+/// Location of the settings saved from the tray.
+pub fn state_file() -> PathBuf {
+    user_file("", STATE_FILE_NAME)
+}
+
+/// Location of the journal of ducked sessions, in the local data directory.
+pub fn ducking_journal() -> PathBuf {
+    data_dir().map_or_else(
+        || user_file("", DUCKING_JOURNAL_FILE_NAME),
+        |dir| dir.join(DUCKING_JOURNAL_FILE_NAME),
+    )
+}
+
+/// Directory for temporary audio files, such as long recordings spilled to disk.
+pub fn spill_dir() -> PathBuf {
+    std::env::temp_dir().join(APP_DIR_NAME)
+}
+
+/// Location of the audio of the dictation in flight, kept for crash recovery.
+pub fn recovery_file() -> PathBuf {
+    spill_dir().join("unfinished-dictation.wav")
+}
+
+/// Location of a sound or icon: the configured `file`, or `default` when none is set.
+///
+/// Both are relative to `assets_dir`, itself resolved like [`resolve`] and
+/// `assets` by default. A relative `file` missing there is also looked up
+/// like [`resolve`], where it was relative to before the directory could be set.
+pub fn asset(assets_dir: Option<&str>, file: Option<&str>, default: &str) -> PathBuf {
+    let dir = resolve(assets_dir.unwrap_or(ASSETS_DIR_NAME));
+    let Some(file) = file else {
+        return dir.join(default);
+    };
+    let in_assets = dir.join(file);
+    if in_assets.exists() {
+        return in_assets;
+    }
+    Some(resolve(file))
+        .filter(|legacy| legacy.exists())
+        .unwrap_or(in_assets)
+}
+
+/// Location of a Whisper model file.
+///
+/// Checks the bundled `assets/models` first, then the shared data directory,
+/// and returns the bundled location when neither has the file.
+pub fn model_file(name: &str) -> PathBuf {
+    let bundled = resolve(&format!("assets/models/{name}"));
+    if bundled.exists() {
+        return bundled;
+    }
+    data_dir()
+        .map(|dir| dir.join("models").join(name))
+        .filter(|path| path.exists())
+        .unwrap_or(bundled)
+}
+
+/// Create the parent directory of `path` if it does not exist yet.
+pub fn ensure_parent(path: &Path) -> Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display())),
+        _ => Ok(()),
+    }
+}
+
+/// Log where configuration, logs, statistics and models are read from.
+pub fn log_locations(config_file: Option<&Path>, model: &Path) {
+    match config_file {
+        Some(file) => info!("Configuration file: {}", file.display()),
+        None => info!("Configuration file: none, using defaults"),
+    }
+    match crate::logging::current_file() {
+        Some(file) => info!("Log file: {}", file.display()),
+        None => info!("Log file: none, logging to the console"),
+    }
+    info!("Statistics file: {}", stats_file().display());
+    info!("Saved settings file: {}", state_file().display());
+    info!("Ducking journal: {}", ducking_journal().display());
+    info!("Model file: {}", model.display());
+    if let Some(dir) = data_dir() {
+        info!("Shared models directory: {}", dir.join("models").display());
+    }
+}
+
+/// Open a file with the application associated with its type.
+pub fn open_in_default_app(path: &Path) -> Result<()> {
+    let opener = if cfg!(windows) {
+        "explorer"
+    } else {
+        "xdg-open"
+    };
+    std::process::Command::new(opener)
+        .arg(path)
+        .spawn()
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    Ok(())
+}
@@ -0,0 +1,298 @@
+//! Post-processing of transcribed text in a configurable order.
+//!
+//! Each step is a [`TextStage`]; a [`TextPipeline`] runs them in the order
+//! `text_pipeline` lists them, after the segments have been joined. A stage
+//! may withhold the text, which ends the pipeline. Sentence breaks need the
+//! segment timings and so always run before the join, outside the pipeline.
+
+use std::fmt;
+use std::str::FromStr;
+
+use tracing::debug;
+
+use crate::filter::{Filtered, WordFilter};
+
+/// One step of post-processing on the joined text
+pub trait TextStage: Send {
+    /// Name the stage is listed by in `text_pipeline`
+    fn name(&self) -> &'static str;
+
+    /// Process `text`, or withhold it from being typed
+    fn process(&mut self, text: String) -> Filtered;
+}
+
+/// Stages that can be listed in `text_pipeline`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum StageKind {
+    /// Collapse runs of whitespace and trim the ends
+    Normalize,
+    /// The word filter, when a list file is set
+    Filter,
+}
+
+impl StageKind {
+    /// Order used when `text_pipeline` is not set, as before it existed
+    pub const DEFAULT_ORDER: &[Self] = &[Self::Filter];
+
+    /// Name in `text_pipeline`
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Normalize => "normalize",
+            Self::Filter => "filter",
+        }
+    }
+}
+
+impl fmt::Display for StageKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A stage name that no stage has
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("unknown text stage {0:?}, use normalize or filter")]
+pub struct UnknownStage(pub String);
+
+impl FromStr for StageKind {
+    type Err = UnknownStage;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "normalize" => Ok(Self::Normalize),
+            "filter" => Ok(Self::Filter),
+            other => Err(UnknownStage(other.to_owned())),
+        }
+    }
+}
+
+/// Whitespace clean-up
+struct Normalize;
+
+impl TextStage for Normalize {
+    fn name(&self) -> &'static str {
+        StageKind::Normalize.as_str()
+    }
+
+    fn process(&mut self, text: String) -> Filtered {
+        Filtered::Text(text.split_whitespace().collect::<Vec<_>>().join(" "))
+    }
+}
+
+impl TextStage for WordFilter {
+    fn name(&self) -> &'static str {
+        StageKind::Filter.as_str()
+    }
+
+    fn process(&mut self, text: String) -> Filtered {
+        self.reload_if_changed();
+        self.apply(&text)
+    }
+}
+
+/// Stages run one after the other on each transcription
+#[derive(Default)]
+pub struct TextPipeline {
+    /// Stages in the order they run
+    stages: Vec<Box<dyn TextStage>>,
+}
+
+impl TextPipeline {
+    /// Pipeline running `order`; `filter` is left out without a `word_filter`
+    pub fn new(order: &[StageKind], mut word_filter: Option<WordFilter>) -> Self {
+        let mut stages: Vec<Box<dyn TextStage>> = Vec::new();
+        for &kind in order {
+            match kind {
+                StageKind::Normalize => stages.push(Box::new(Normalize)),
+                StageKind::Filter => {
+                    if let Some(filter) = word_filter.take() {
+                        stages.push(Box::new(filter));
+                    }
+                }
+            }
+        }
+        Self { stages }
+    }
+
+    /// Append `stage`, to run after the others
+    pub fn push<S: TextStage + 'static>(&mut self, stage: S) {
+        self.stages.push(Box::new(stage));
+    }
+
+    /// Run every stage on `text` in order; `log_text` adds the text to the
+    /// debug log line of each stage, which otherwise only has its length
+    pub fn run(&mut self, mut text: String, log_text: bool) -> Filtered {
+        for stage in &mut self.stages {
+            let before = text.chars().count();
+            match stage.process(text) {
+                Filtered::Text(processed) => {
+                    let after = processed.chars().count();
+                    if log_text {
+                        debug!(
+                            "Text stage {}: {} -> {} characters, {:?}",
+                            stage.name(),
+                            before,
+                            after,
+                            processed
+                        );
+                    } else {
+                        debug!(
+                            "Text stage {}: {} -> {} characters",
+                            stage.name(),
+                            before,
+                            after
+                        );
+                    }
+                    text = processed;
+                }
+                Filtered::Withheld => {
+                    debug!("Text stage {} withheld the text", stage.name());
+                    return Filtered::Withheld;
+                }
+            }
+        }
+        Filtered::Text(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stage replacing every `from` with `to`
+    struct Replace {
+        /// Text replaced
+        from: &'static str,
+        /// Replacement
+        to: &'static str,
+    }
+
+    impl TextStage for Replace {
+        fn name(&self) -> &'static str {
+            "replace"
+        }
+
+        fn process(&mut self, text: String) -> Filtered {
+            Filtered::Text(text.replace(self.from, self.to))
+        }
+    }
+
+    /// Stage withholding text that contains `word`
+    struct Withhold(&'static str);
+
+    impl TextStage for Withhold {
+        fn name(&self) -> &'static str {
+            "withhold"
+        }
+
+        fn process(&mut self, text: String) -> Filtered {
+            if text.contains(self.0) {
+                Filtered::Withheld
+            } else {
+                Filtered::Text(text)
+            }
+        }
+    }
+
+    /// Pipeline of `stages` in the order given
+    fn pipeline(stages: Vec<Box<dyn TextStage>>) -> TextPipeline {
+        TextPipeline { stages }
+    }
+
+    #[test]
+    fn normalizing_first_leaves_a_double_space_nothing_to_match() {
+        let gap = || Replace {
+            from: "  ",
+            to: " / ",
+        };
+        let mut normalized_first = pipeline(vec![Box::new(Normalize), Box::new(gap())]);
+        assert_eq!(
+            normalized_first.run(" one  two ".to_owned(), false),
+            Filtered::Text("one two".to_owned()),
+            "double space survived normalizing"
+        );
+        let mut normalized_last = pipeline(vec![Box::new(gap()), Box::new(Normalize)]);
+        assert_eq!(
+            normalized_last.run(" one  two ".to_owned(), false),
+            Filtered::Text("one / two".to_owned()),
+            "double space not replaced before normalizing"
+        );
+    }
+
+    #[test]
+    fn each_stage_sees_the_text_the_previous_one_left() {
+        let cat = || Replace {
+            from: "cat",
+            to: "dog",
+        };
+        let dog = || Replace {
+            from: "dog",
+            to: "fox",
+        };
+        let mut chained = pipeline(vec![Box::new(cat()), Box::new(dog())]);
+        assert_eq!(
+            chained.run("cat and dog".to_owned(), false),
+            Filtered::Text("fox and fox".to_owned()),
+            "second stage did not see the first one's output"
+        );
+        let mut reversed = pipeline(vec![Box::new(dog()), Box::new(cat())]);
+        assert_eq!(
+            reversed.run("cat and dog".to_owned(), false),
+            Filtered::Text("dog and fox".to_owned()),
+            "stages did not run in the listed order"
+        );
+    }
+
+    #[test]
+    fn a_withholding_stage_ends_the_pipeline() {
+        let secret = || Replace {
+            from: "secret",
+            to: "s*****",
+        };
+        let mut masked_first = pipeline(vec![Box::new(secret()), Box::new(Withhold("secret"))]);
+        assert_eq!(
+            masked_first.run("the secret".to_owned(), false),
+            Filtered::Text("the s*****".to_owned()),
+            "masked text still withheld"
+        );
+        let mut withheld_first = pipeline(vec![Box::new(Withhold("secret")), Box::new(secret())]);
+        assert_eq!(
+            withheld_first.run("the secret".to_owned(), false),
+            Filtered::Withheld,
+            "later stage ran after the text was withheld"
+        );
+    }
+
+    #[test]
+    fn pushed_stages_run_after_the_configured_ones() {
+        let mut pipeline = TextPipeline::new(&[StageKind::Normalize], None);
+        pipeline.push(Replace { from: " ", to: "_" });
+        assert_eq!(
+            pipeline.run("a   b".to_owned(), true),
+            Filtered::Text("a_b".to_owned()),
+            "pushed stage ran before normalizing"
+        );
+    }
+
+    #[test]
+    fn the_filter_stage_is_left_out_without_a_word_filter() {
+        let mut pipeline = TextPipeline::new(StageKind::DEFAULT_ORDER, None);
+        assert_eq!(
+            pipeline.run("  kept as is ".to_owned(), false),
+            Filtered::Text("  kept as is ".to_owned()),
+            "text changed without stages"
+        );
+    }
+
+    #[test]
+    fn stage_names_parse_case_insensitively() {
+        assert_eq!(" Normalize ".parse(), Ok(StageKind::Normalize), "normalize");
+        assert_eq!("FILTER".parse(), Ok(StageKind::Filter), "filter");
+        assert_eq!(
+            "spell".parse::<StageKind>(),
+            Err(UnknownStage("spell".to_owned())),
+            "unknown stage accepted"
+        );
+    }
+}
@@ -0,0 +1,119 @@
+//! Where transcribed text may go.
+//!
+//! One policy decides which sinks see the text of a dictation besides the
+//! window it is typed into: the tray preview, copy and re-send actions,
+//! read-back, the history file, the webhook, live captions, the log, the
+//! audio kept for crash recovery and the token dump.
+//! Strict mode keeps it out of all of them; normal mode follows
+//! `keep_last_transcription` and `webhook_include_text`.
+
+use std::str::FromStr;
+
+use anyhow::Result;
+
+use crate::config::Config;
+use crate::history::HistoryDetail;
+
+/// How strictly transcribed text is kept out of everything but its target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrivacyMode {
+    /// Text is kept as the other settings allow
+    Normal,
+    /// Text is only ever typed; the history keeps metadata and the log sizes
+    Strict,
+}
+
+impl FromStr for PrivacyMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "normal" => Ok(Self::Normal),
+            "strict" => Ok(Self::Strict),
+            other => anyhow::bail!("unknown privacy mode {other:?}, use normal or strict"),
+        }
+    }
+}
+
+/// What may be done with transcribed text under the current settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrivacyPolicy {
+    /// Configured mode
+    mode: PrivacyMode,
+    /// Whether the latest transcription may be kept, in normal mode
+    keep_last: bool,
+    /// History detail configured for normal mode
+    history_detail: HistoryDetail,
+    /// Whether the webhook payload may carry the text, in normal mode
+    webhook_text: bool,
+}
+
+impl PrivacyPolicy {
+    /// Policy of `config`
+    pub const fn new(config: &Config) -> Self {
+        Self {
+            mode: config.privacy_mode,
+            keep_last: config.keep_last_transcription,
+            history_detail: config.history_detail,
+            webhook_text: config.webhook_include_text,
+        }
+    }
+
+    /// Whether the latest transcription may be kept for the tray preview,
+    /// copy and re-send actions, and read aloud
+    pub const fn keeps_text(self) -> bool {
+        matches!(self.mode, PrivacyMode::Normal) && self.keep_last
+    }
+
+    /// Whether the text is posted to the webhook along with its metadata
+    pub const fn webhook_text(self) -> bool {
+        matches!(self.mode, PrivacyMode::Normal) && self.webhook_text
+    }
+
+    /// Whether the text itself may appear in debug log lines
+    pub const fn logs_text(self) -> bool {
+        matches!(self.mode, PrivacyMode::Normal)
+    }
+
+    /// Whether transcriptions are broadcast to caption clients
+    pub const fn captions(self) -> bool {
+        matches!(self.mode, PrivacyMode::Normal)
+    }
+
+    /// What the history records, `None` for nothing at all
+    pub const fn history_detail(self) -> Option<HistoryDetail> {
+        match self.mode {
+            PrivacyMode::Strict => Some(HistoryDetail::MetadataOnly),
+            PrivacyMode::Normal if self.keep_last => Some(self.history_detail),
+            PrivacyMode::Normal => None,
+        }
+    }
+
+    /// Whether the previous dictation's text may be kept in memory to spot a
+    /// repeat, rather than only hashes of it
+    pub const fn compares_text(self) -> bool {
+        matches!(self.mode, PrivacyMode::Normal)
+    }
+
+    /// Whether a dictation's audio may be kept on disk until it was typed
+    pub const fn keeps_audio(self) -> bool {
+        matches!(self.mode, PrivacyMode::Normal)
+    }
+
+    /// Whether Whisper's tokens and the text may be written out for analysis
+    pub const fn dumps_tokens(self) -> bool {
+        matches!(self.mode, PrivacyMode::Normal)
+    }
+
+    /// `text` for a log line, replaced by its length and word count in strict mode
+    pub fn loggable(self, text: &str) -> String {
+        match self.mode {
+            PrivacyMode::Normal => format!("{text:?}"),
+            PrivacyMode::Strict => format!(
+                "<{} characters, {} words>",
+                text.chars().count(),
+                text.split_whitespace().count()
+            ),
+        }
+    }
+}
@@ -0,0 +1,307 @@
+//! Sentence breaks for transcriptions that came back unpunctuated.
+//!
+//! Small models sometimes return a long run of words without a single full
+//! stop. When a transcription has too few sentence marks for its length, a
+//! long enough pause between two segments is taken as the end of a sentence:
+//! the first segment gets a period and the next one a capital letter.
+
+use std::time::Duration;
+
+use tracing::debug;
+
+use crate::whisper::Transcript;
+
+/// Words per sentence mark from which a transcription counts as unpunctuated
+const WORDS_PER_MARK: usize = 25;
+
+/// Characters that end a sentence
+const SENTENCE_MARKS: &[char] = &['.', '!', '?', '…'];
+
+/// Where sentence breaks are added to unpunctuated transcriptions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Punctuation {
+    /// Shortest pause between segments taken as a sentence break, `None` when off
+    pause: Option<Duration>,
+}
+
+impl Punctuation {
+    /// Break sentences at pauses of at least `pause`, or never for `None`
+    pub const fn new(pause: Option<Duration>) -> Self {
+        Self { pause }
+    }
+
+    /// Break sentences at pauses of at least `millis`, or never for 0
+    pub fn from_pause_ms(millis: u64) -> Self {
+        Self::new((millis > 0).then(|| Duration::from_millis(millis)))
+    }
+
+    /// Add sentence breaks to `transcript` if it needs them.
+    ///
+    /// Transcriptions that are punctuated already, or written in scripts
+    /// without capital letters such as Chinese or Arabic, are left alone, as
+    /// are segments that end in punctuation of their own.
+    pub fn apply(self, transcript: &mut Transcript) {
+        let Some(pause) = self.pause else {
+            return;
+        };
+        let segments = &mut transcript.segments;
+        let words: usize = segments
+            .iter()
+            .map(|segment| segment.text.split_whitespace().count())
+            .sum();
+        let marks = segments
+            .iter()
+            .flat_map(|segment| segment.text.chars())
+            .filter(|c| SENTENCE_MARKS.contains(c))
+            .count();
+        if words / (marks + 1) < WORDS_PER_MARK
+            || !segments.iter().all(|segment| is_cased(&segment.text))
+        {
+            return;
+        }
+
+        let mut breaks = 0;
+        for i in 1..segments.len() {
+            let (before, after) = segments.split_at_mut(i);
+            let (Some(previous), Some(next)) = (before.last_mut(), after.first_mut()) else {
+                continue;
+            };
+            let ends_in_word = previous
+                .text
+                .trim_end()
+                .chars()
+                .next_back()
+                .is_some_and(char::is_alphanumeric);
+            let starts_with_letter = next
+                .text
+                .trim_start()
+                .chars()
+                .next()
+                .is_some_and(char::is_alphabetic);
+            if next.start.saturating_sub(previous.end) < pause
+                || !ends_in_word
+                || !starts_with_letter
+            {
+                continue;
+            }
+            previous.text.truncate(previous.text.trim_end().len());
+            previous.text.push('.');
+            capitalize(&mut next.text);
+            breaks += 1;
+        }
+        if breaks > 0 {
+            debug!("Added {} sentence break(s) at pauses", breaks);
+        }
+    }
+}
+
+/// Whether every letter of `text` has an upper and lower case, as in Latin,
+/// Greek or Cyrillic writing, whose sentences end in a period
+fn is_cased(text: &str) -> bool {
+    text.chars()
+        .filter(|c| c.is_alphabetic())
+        .all(|c| c.is_lowercase() || c.is_uppercase())
+}
+
+/// Upper-case the first letter of `text`, after any leading whitespace
+fn capitalize(text: &mut String) {
+    let start = text.len() - text.trim_start().len();
+    if let Some(first) = text.get(start..).and_then(|rest| rest.chars().next()) {
+        let upper: String = first.to_uppercase().collect();
+        text.replace_range(start..start + first.len_utf8(), &upper);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::whisper::Segment;
+
+    /// Twelve words without a sentence mark
+    const RUN_ON: &str = " we walked down to the river and then we sat there for";
+
+    /// Transcript of `(start, end, text)` segments, times in milliseconds
+    fn transcript(segments: &[(u64, u64, &str)]) -> Transcript {
+        Transcript {
+            segments: segments
+                .iter()
+                .map(|&(start, end, text)| Segment {
+                    start: Duration::from_millis(start),
+                    end: Duration::from_millis(end),
+                    text: text.to_owned(),
+                    tokens: Vec::new(),
+                })
+                .collect(),
+            language: "en".to_owned(),
+        }
+    }
+
+    /// Texts of the segments of `transcript`
+    fn texts(transcript: Transcript) -> Vec<String> {
+        transcript
+            .segments
+            .into_iter()
+            .map(|segment| segment.text)
+            .collect()
+    }
+
+    /// Texts of the segments of `transcript` after `punctuation` ran
+    fn punctuated(punctuation: Punctuation, segments: &[(u64, u64, &str)]) -> Vec<String> {
+        let mut transcript = transcript(segments);
+        punctuation.apply(&mut transcript);
+        texts(transcript)
+    }
+
+    /// Texts of the segments of the fixture `name` after `punctuation` ran
+    fn punctuated_fixture(punctuation: Punctuation, name: &str) -> Vec<String> {
+        let mut transcript = fixtures::transcript(name);
+        punctuation.apply(&mut transcript);
+        texts(transcript)
+    }
+
+    /// Breaking sentences at pauses of 700 ms
+    fn at_pauses() -> Punctuation {
+        Punctuation::from_pause_ms(700)
+    }
+
+    #[test]
+    fn a_long_pause_ends_the_sentence() {
+        let texts = punctuated(
+            at_pauses(),
+            &[
+                (0, 4000, RUN_ON),
+                (5000, 9000, RUN_ON),
+                (9100, 13000, RUN_ON),
+            ],
+        );
+        assert_eq!(
+            texts,
+            [
+                " we walked down to the river and then we sat there for.",
+                " We walked down to the river and then we sat there for",
+                RUN_ON,
+            ],
+            "break not added at the long pause only"
+        );
+    }
+
+    #[test]
+    fn turned_off_leaves_run_on_text_alone() {
+        let segments = [
+            (0, 4000, RUN_ON),
+            (6000, 9000, RUN_ON),
+            (11_000, 13_000, RUN_ON),
+        ];
+        for punctuation in [Punctuation::from_pause_ms(0), Punctuation::default()] {
+            assert_eq!(
+                punctuated(punctuation, &segments),
+                [RUN_ON, RUN_ON, RUN_ON],
+                "text changed while turned off"
+            );
+        }
+    }
+
+    #[test]
+    fn punctuated_text_is_left_alone() {
+        let sentence = " We walked down to the river. Then we sat there for a while!";
+        let segments = [
+            (0, 4000, sentence),
+            (6000, 9000, RUN_ON),
+            (11_000, 13_000, sentence),
+        ];
+        assert_eq!(
+            punctuated(at_pauses(), &segments),
+            [sentence, RUN_ON, sentence],
+            "punctuated text changed"
+        );
+    }
+
+    #[test]
+    fn a_segment_ending_in_its_own_punctuation_keeps_it() {
+        let clause = " we walked down to the river and then we sat there,";
+        let texts = punctuated(
+            at_pauses(),
+            &[
+                (0, 4000, clause),
+                (6000, 9000, RUN_ON),
+                (11_000, 13_000, RUN_ON),
+            ],
+        );
+        assert_eq!(
+            texts.first().map(String::as_str),
+            Some(clause),
+            "period added after a comma"
+        );
+        assert_eq!(
+            texts.get(1).map(String::as_str),
+            Some(" we walked down to the river and then we sat there for."),
+            "no break at the second pause"
+        );
+    }
+
+    #[test]
+    fn a_segment_starting_with_a_number_gets_no_break() {
+        let counted = " 12 people walked down to the river and then we sat there";
+        let texts = punctuated(
+            at_pauses(),
+            &[
+                (0, 4000, RUN_ON),
+                (6000, 9000, counted),
+                (9100, 13000, RUN_ON),
+            ],
+        );
+        assert_eq!(
+            texts,
+            [RUN_ON, counted, RUN_ON],
+            "break added before a number"
+        );
+    }
+
+    #[test]
+    fn scripts_without_capitals_are_left_alone() {
+        let chinese = " 我们 走 到 河边 然后 坐 在 那里 很久 很久 很久 很久 很久";
+        let segments = [
+            (0, 4000, chinese),
+            (6000, 9000, chinese),
+            (11_000, 13_000, chinese),
+        ];
+        assert_eq!(
+            punctuated(at_pauses(), &segments),
+            [chinese, chinese, chinese],
+            "Chinese text punctuated"
+        );
+    }
+
+    #[test]
+    fn a_run_on_dictation_breaks_only_at_its_long_pause() {
+        assert_eq!(
+            punctuated_fixture(at_pauses(), "run-on-en"),
+            [
+                " so the first thing we need to do is open the settings page and then",
+                " scroll down until you see the section about audio devices.",
+                " After that pick the microphone you want to use and press save",
+                " it should take effect right away",
+            ],
+            "break not added at the 1240 ms pause only"
+        );
+    }
+
+    #[test]
+    fn a_longer_threshold_skips_a_shorter_pause() {
+        assert_eq!(
+            punctuated_fixture(Punctuation::from_pause_ms(1300), "run-on-en"),
+            texts(fixtures::transcript("run-on-en")),
+            "break added at a pause shorter than the threshold"
+        );
+    }
+
+    #[test]
+    fn a_punctuated_dictation_is_left_alone() {
+        assert_eq!(
+            punctuated_fixture(at_pauses(), "punctuated-en"),
+            texts(fixtures::transcript("punctuated-en")),
+            "punctuated dictation changed despite its pauses"
+        );
+    }
+}
@@ -0,0 +1,56 @@
+//! Whether the user does not want to be disturbed right now.
+//!
+//! Windows reports when the user is presenting, runs a full-screen app or is
+//! in quiet time. Notifications and non-essential sounds are held back then.
+//! The state is cached briefly, as every notification and sound asks, and a
+//! query that fails counts as not quiet so nothing is lost silently.
+
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+#[cfg(windows)]
+use windows::Win32::UI::Shell::{
+    QUNS_APP, QUNS_BUSY, QUNS_PRESENTATION_MODE, QUNS_QUIET_TIME, QUNS_RUNNING_D3D_FULL_SCREEN,
+    SHQueryUserNotificationState,
+};
+
+/// How long a queried state is reused
+const CACHE_TTL: Duration = Duration::from_secs(2);
+
+/// Last queried state and when it was queried
+static CACHE: Mutex<Option<(Instant, bool)>> = Mutex::new(None);
+
+/// Whether notifications and non-essential sounds should be held back
+pub fn is_quiet() -> bool {
+    let mut cache = CACHE.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some((at, quiet)) = *cache
+        && at.elapsed() < CACHE_TTL
+    {
+        return quiet;
+    }
+    let quiet = query().unwrap_or(false);
+    *cache = Some((Instant::now(), quiet));
+    quiet
+}
+
+/// Whether the shell says the user is busy, `None` when it cannot tell
+#[cfg(windows)]
+fn query() -> Option<bool> {
+    // SAFETY: SHQueryUserNotificationState has no preconditions and only
+    // writes the state it returns.
+    let state = unsafe { SHQueryUserNotificationState() }.ok()?;
+    Some(matches!(
+        state,
+        QUNS_BUSY
+            | QUNS_RUNNING_D3D_FULL_SCREEN
+            | QUNS_PRESENTATION_MODE
+            | QUNS_QUIET_TIME
+            | QUNS_APP
+    ))
+}
+
+/// Whether the shell says the user is busy, `None` when it cannot tell
+#[cfg(not(windows))]
+const fn query() -> Option<bool> {
+    None
+}
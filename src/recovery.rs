@@ -0,0 +1,243 @@
+//! The audio of the dictation in flight, kept on disk until it is typed.
+//!
+//! Before a recording is transcribed its Whisper input is written to a WAV
+//! file, which is deleted once the text was delivered; text that could not
+//! be typed or copied leaves it in place. A file still there at
+//! the next start belongs to a dictation lost to a crash and is offered from
+//! the tray, to be transcribed again and copied to the clipboard. New
+//! dictations are not saved over it until its text was handled.
+
+use std::path::PathBuf;
+
+use anyhow::Result;
+use tracing::{debug, info, warn};
+
+use crate::wav;
+
+/// The one recovery file and which dictation it holds
+pub struct Recovery {
+    /// Location of the file
+    path: PathBuf,
+    /// Whether dictations are saved, off when disabled or in strict privacy mode
+    enabled: bool,
+    /// Whether the file is from an earlier run and waits to be recovered
+    leftover: bool,
+    /// Ticket of the leftover while it is transcribed again
+    recovering: Option<u64>,
+    /// Ticket of the dictation the file holds
+    current: u64,
+    /// Ticket handed to the next saved dictation
+    next: u64,
+}
+
+impl Recovery {
+    /// Recovery in `path`, noting a file left by an earlier run
+    ///
+    /// A leftover file is deleted instead when recovery is off.
+    pub fn new(path: PathBuf, enabled: bool) -> Self {
+        let leftover = path.exists();
+        let mut recovery = Self {
+            path,
+            enabled,
+            leftover,
+            recovering: None,
+            current: 0,
+            next: 1,
+        };
+        if leftover {
+            if enabled {
+                info!("Found the audio of an unfinished dictation");
+            } else {
+                recovery.discard();
+            }
+        }
+        recovery
+    }
+
+    /// Turn saving on or off, deleting the file when it is turned off
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.discard();
+        }
+    }
+
+    /// Whether a dictation from an earlier run waits to be recovered
+    pub const fn has_leftover(&self) -> bool {
+        self.leftover
+    }
+
+    /// Save the Whisper input of a dictation about to be transcribed,
+    /// returning its ticket; a leftover waiting to be recovered is kept
+    pub fn save(&mut self, samples: &[f32]) -> Option<u64> {
+        if !self.enabled {
+            return None;
+        }
+        if self.leftover || self.recovering.is_some() {
+            debug!("Not saving the dictation while an unfinished one waits to be recovered");
+            return None;
+        }
+        if let Err(e) = wav::write(&self.path, samples) {
+            warn!("Failed to save the dictation for recovery: {:#}", e);
+            return None;
+        }
+        self.current = self.next;
+        self.next += 1;
+        Some(self.current)
+    }
+
+    /// Read the leftover dictation for transcription, returning its ticket
+    pub fn take_leftover(&mut self) -> Result<(Vec<f32>, u64)> {
+        let samples = wav::read(&self.path)?;
+        self.leftover = false;
+        self.current = self.next;
+        self.next += 1;
+        self.recovering = Some(self.current);
+        Ok((samples, self.current))
+    }
+
+    /// Settle the dictation of `ticket` once its text was handled, deleting
+    /// the file if the text was `delivered` and no later one was saved over it.
+    ///
+    /// A recovered dictation whose text was not delivered waits to be
+    /// recovered again.
+    pub fn settle(&mut self, ticket: u64, delivered: bool) {
+        if ticket != self.current {
+            return;
+        }
+        if self.recovering == Some(ticket) {
+            self.recovering = None;
+            self.leftover = !delivered;
+        }
+        if delivered {
+            self.discard();
+        } else {
+            info!("Keeping the audio of a dictation that was not delivered");
+        }
+    }
+
+    /// Delete the file, whichever dictation it holds
+    pub fn discard(&mut self) {
+        self.leftover = false;
+        self.recovering = None;
+        self.current = 0;
+        match std::fs::remove_file(&self.path) {
+            Ok(()) => debug!("Deleted the recovery file"),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!(
+                "Failed to delete recovery file {}: {}",
+                self.path.display(),
+                e
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch::ScratchDir;
+
+    /// A second of quiet Whisper input
+    fn dictation() -> Vec<f32> {
+        vec![0.0; 16_000]
+    }
+
+    #[test]
+    fn undelivered_text_keeps_the_audio_for_the_next_start() {
+        let dir = ScratchDir::new("recovery-undelivered");
+        let path = dir.path().join("recovery.wav");
+        let mut recovery = Recovery::new(path.clone(), true);
+        let ticket = recovery.save(&dictation()).expect("saved");
+
+        // Transcribed, but typing it failed
+        recovery.settle(ticket, false);
+        assert!(path.exists(), "audio kept after a failed injection");
+
+        let mut restarted = Recovery::new(path.clone(), true);
+        assert!(restarted.has_leftover(), "offered at the next start");
+        let (samples, _) = restarted.take_leftover().expect("leftover read");
+        assert_eq!(
+            samples.len(),
+            dictation().len(),
+            "whole dictation recovered"
+        );
+    }
+
+    #[test]
+    fn delivered_text_deletes_the_audio() {
+        let dir = ScratchDir::new("recovery-delivered");
+        let path = dir.path().join("recovery.wav");
+        let mut recovery = Recovery::new(path.clone(), true);
+        let ticket = recovery.save(&dictation()).expect("saved");
+        recovery.settle(ticket, true);
+        assert!(!path.exists(), "audio deleted once typed");
+        assert!(!Recovery::new(path, true).has_leftover(), "nothing offered");
+    }
+
+    #[test]
+    fn an_earlier_ticket_leaves_a_later_dictation_alone() {
+        let dir = ScratchDir::new("recovery-later");
+        let path = dir.path().join("recovery.wav");
+        let mut recovery = Recovery::new(path.clone(), true);
+        let first = recovery.save(&dictation()).expect("first saved");
+        let second = recovery.save(&dictation()).expect("second saved");
+        recovery.settle(first, true);
+        assert!(path.exists(), "the later dictation is still in flight");
+        recovery.settle(second, true);
+        assert!(!path.exists(), "deleted once the later one is typed");
+    }
+
+    #[test]
+    fn a_recovered_dictation_is_not_saved_over_until_settled() {
+        let dir = ScratchDir::new("recovery-recovering");
+        let path = dir.path().join("recovery.wav");
+        let mut recovery = Recovery::new(path.clone(), true);
+        let lost = recovery.save(&dictation()).expect("saved");
+        recovery.settle(lost, false);
+
+        let mut restarted = Recovery::new(path.clone(), true);
+        let (_, ticket) = restarted.take_leftover().expect("leftover read");
+        assert!(
+            restarted.save(&[0.5; 800]).is_none(),
+            "a new dictation was saved over the one being recovered"
+        );
+        let (samples, _) = Recovery::new(path.clone(), true)
+            .take_leftover()
+            .expect("file still readable");
+        assert_eq!(
+            samples.len(),
+            dictation().len(),
+            "the recovered dictation was replaced"
+        );
+
+        restarted.settle(ticket, true);
+        assert!(
+            !path.exists(),
+            "audio deleted once the recovered text is copied"
+        );
+        assert!(
+            restarted.save(&dictation()).is_some(),
+            "new dictations not saved again after the recovery settled"
+        );
+    }
+
+    #[test]
+    fn an_undelivered_recovery_waits_to_be_recovered_again() {
+        let dir = ScratchDir::new("recovery-again");
+        let path = dir.path().join("recovery.wav");
+        let mut recovery = Recovery::new(path.clone(), true);
+        let lost = recovery.save(&dictation()).expect("saved");
+        recovery.settle(lost, false);
+
+        let mut restarted = Recovery::new(path.clone(), true);
+        let (_, ticket) = restarted.take_leftover().expect("leftover read");
+        restarted.settle(ticket, false);
+        assert!(path.exists(), "audio deleted although the text was lost");
+        assert!(restarted.has_leftover(), "not offered again");
+        assert!(
+            restarted.save(&dictation()).is_none(),
+            "a new dictation was saved over the one still waiting"
+        );
+    }
+}
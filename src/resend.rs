@@ -0,0 +1,101 @@
+//! Recent transcriptions that can be typed again into another application.
+//!
+//! When text went to the wrong window or could not be typed at all, the tray
+//! menu offers to send it again to one of the applications dictated into
+//! lately. Only the newest text per application is kept, for a limited time,
+//! and only while the privacy policy allows keeping text.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Applications offered in the tray menu at most
+pub const MAX_TARGETS: usize = 5;
+
+/// A transcription and the application it was typed into
+#[derive(Debug, Clone)]
+struct ResendEntry {
+    /// Text as it was delivered
+    text: String,
+    /// Executable name of the application, e.g. `notepad.exe`
+    app: String,
+    /// When the text was delivered
+    at: Instant,
+}
+
+/// Latest transcription per application, newest first
+#[derive(Debug)]
+pub struct ResendHistory {
+    /// Entries, newest first, one per application
+    entries: VecDeque<ResendEntry>,
+    /// Age after which an entry is forgotten, `None` to keep nothing
+    max_age: Option<Duration>,
+}
+
+impl ResendHistory {
+    /// History forgetting entries after `max_age`, or keeping nothing for `None`
+    pub const fn new(max_age: Option<Duration>) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            max_age,
+        }
+    }
+
+    /// Change how long entries are kept, dropping everything when turned off
+    pub fn set_max_age(&mut self, max_age: Option<Duration>) {
+        self.max_age = max_age;
+        if max_age.is_none() {
+            self.entries.clear();
+        }
+    }
+
+    /// Remember `text` as delivered to `app` at `now`, replacing that app's older text
+    pub fn record(&mut self, text: &str, app: &str, now: Instant) {
+        if self.max_age.is_none() {
+            return;
+        }
+        self.entries
+            .retain(|entry| !entry.app.eq_ignore_ascii_case(app));
+        self.entries.push_front(ResendEntry {
+            text: text.to_owned(),
+            app: app.to_owned(),
+            at: now,
+        });
+        self.entries.truncate(MAX_TARGETS);
+    }
+
+    /// Forget entries older than the maximum age, returning whether any were
+    pub fn expire(&mut self, now: Instant) -> bool {
+        let before = self.entries.len();
+        match self.max_age {
+            Some(max_age) => self
+                .entries
+                .retain(|entry| now.saturating_duration_since(entry.at) < max_age),
+            None => self.entries.clear(),
+        }
+        self.entries.len() != before
+    }
+
+    /// Forget every entry
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// When the oldest entry expires, `None` while empty
+    pub fn next_expiry(&self) -> Option<Instant> {
+        let max_age = self.max_age?;
+        self.entries.back().map(|entry| entry.at + max_age)
+    }
+
+    /// Applications with a stored text, newest first
+    pub fn apps(&self) -> Vec<String> {
+        self.entries.iter().map(|entry| entry.app.clone()).collect()
+    }
+
+    /// Latest text delivered to `app`
+    pub fn text_for(&self, app: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|entry| entry.app == app)
+            .map(|entry| entry.text.as_str())
+    }
+}
@@ -0,0 +1,30 @@
+//! Throwaway directories for tests that touch the file system.
+//!
+//! Each directory is unique to the test process and name, and is deleted
+//! again when the guard goes out of scope, whether the test passed or not.
+
+use std::path::{Path, PathBuf};
+
+/// A fresh directory under the system temp folder, removed on drop
+pub struct ScratchDir(PathBuf);
+
+impl ScratchDir {
+    /// Empty directory for the test `name`
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("speedy-stt-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).expect("create scratch directory");
+        Self(path)
+    }
+
+    /// Location of the directory
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
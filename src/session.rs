@@ -0,0 +1,268 @@
+//! Windows logoff, shutdown and sleep handling.
+//!
+//! A hidden top-level window receives `WM_QUERYENDSESSION`, upon which the
+//! event loop stops any recording and restores ducked audio before Windows
+//! asks again with `WM_ENDSESSION`. Console close, logoff and shutdown events
+//! are handled the same way when a console is attached. The same window is
+//! told when the system suspends and resumes.
+
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+#[cfg(windows)]
+use anyhow::Context;
+use anyhow::Result;
+#[cfg(windows)]
+use std::time::{Duration, Instant};
+#[cfg(windows)]
+use tracing::{info, warn};
+
+#[cfg(windows)]
+use windows::Win32::Foundation::HANDLE;
+#[cfg(windows)]
+use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+#[cfg(windows)]
+use windows::Win32::System::Console::{
+    CTRL_BREAK_EVENT, CTRL_C_EVENT, CTRL_CLOSE_EVENT, CTRL_LOGOFF_EVENT, CTRL_SHUTDOWN_EVENT,
+    SetConsoleCtrlHandler,
+};
+#[cfg(windows)]
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+#[cfg(windows)]
+use windows::Win32::System::Power::{
+    HPOWERNOTIFY, RegisterSuspendResumeNotification, UnregisterSuspendResumeNotification,
+};
+#[cfg(windows)]
+use windows::Win32::UI::WindowsAndMessaging::{
+    CreateWindowExW, DEVICE_NOTIFY_WINDOW_HANDLE, DefWindowProcW, DestroyWindow,
+    PBT_APMRESUMEAUTOMATIC, PBT_APMRESUMESUSPEND, PBT_APMSUSPEND, RegisterClassW, WINDOW_EX_STYLE,
+    WM_ENDSESSION, WM_POWERBROADCAST, WM_QUERYENDSESSION, WNDCLASSW, WS_OVERLAPPED,
+};
+#[cfg(windows)]
+use windows::core::{BOOL, w};
+
+#[cfg(windows)]
+use crate::volume;
+#[cfg(windows)]
+use crate::wake;
+
+/// No end of the session is known
+const RUNNING: u8 = 0;
+/// Windows asked whether the session may end; the loop has not prepared yet
+const QUERIED: u8 = 1;
+/// The loop stopped recording and restored audio, waiting for the outcome
+const PREPARED: u8 = 2;
+/// The session is ending or the console was closed; the loop must exit
+const EXITING: u8 = 3;
+
+/// Shutdown progress, written by the window procedure and console handler
+static STATE: AtomicU8 = AtomicU8::new(RUNNING);
+
+/// Set once the loop has cleaned up, so the console handler can return
+static FINISHED: AtomicBool = AtomicBool::new(false);
+
+/// The system is about to sleep; cleared by [`take_suspend`]
+static SUSPEND_PENDING: AtomicBool = AtomicBool::new(false);
+
+/// The system woke up; cleared by [`take_resume`], so repeated resume messages count once
+static RESUME_PENDING: AtomicBool = AtomicBool::new(false);
+
+/// Longest the console handler waits for cleanup; Windows kills the process after 5 s
+#[cfg(windows)]
+const CONSOLE_GRACE: Duration = Duration::from_millis(4500);
+
+/// What the event loop has to do about the end of the session
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shutdown {
+    /// Nothing, keep running
+    None,
+    /// Stop recording and restore audio, then call [`prepared`]; the end may still be cancelled
+    Prepare,
+    /// Clean up and leave the loop
+    Exit,
+}
+
+/// Current request from Windows, checked after each message pump
+pub fn requested() -> Shutdown {
+    match STATE.load(Ordering::Acquire) {
+        QUERIED => Shutdown::Prepare,
+        EXITING => Shutdown::Exit,
+        _ => Shutdown::None,
+    }
+}
+
+/// Whether the message pump must return so the loop handles a session end
+/// or sleep before further messages
+#[cfg(windows)]
+pub fn pump_interrupted() -> bool {
+    STATE.load(Ordering::Acquire) == QUERIED || SUSPEND_PENDING.load(Ordering::Acquire)
+}
+
+/// Whether the system is about to sleep, once per notification
+pub fn take_suspend() -> bool {
+    SUSPEND_PENDING.swap(false, Ordering::AcqRel)
+}
+
+/// Whether the system woke up since the last call
+pub fn take_resume() -> bool {
+    RESUME_PENDING.swap(false, Ordering::AcqRel)
+}
+
+/// Record that nothing is left to lose if the process is killed now
+pub fn prepared() {
+    let _ = STATE.compare_exchange(QUERIED, PREPARED, Ordering::AcqRel, Ordering::Acquire);
+}
+
+/// Record that the loop has exited and cleaned up
+pub fn finished() {
+    FINISHED.store(true, Ordering::Release);
+}
+
+/// Hidden window and console handler receiving end-of-session and power notifications
+pub struct SessionWatcher {
+    /// Hidden top-level window; message-only windows get no session messages
+    #[cfg(windows)]
+    hwnd: HWND,
+    /// Suspend and resume registration, which modern standby requires
+    #[cfg(windows)]
+    power: Option<HPOWERNOTIFY>,
+}
+
+impl SessionWatcher {
+    /// Create the hidden window on the calling thread, which must pump its messages.
+    #[cfg(windows)]
+    pub fn create() -> Result<Self> {
+        let class_name = w!("SpeedySttSession");
+        // SAFETY: the class and window are registered and created with valid
+        // static strings and this module's own window procedure; the window
+        // is destroyed in Drop. The console handler is a plain function that
+        // stays valid for the life of the process.
+        unsafe {
+            let instance = GetModuleHandleW(None).context("Failed to get module handle")?;
+            let class = WNDCLASSW {
+                lpfnWndProc: Some(window_proc),
+                hInstance: instance.into(),
+                lpszClassName: class_name,
+                ..Default::default()
+            };
+            RegisterClassW(&class);
+            // Never shown, so it has no taskbar button
+            let hwnd = CreateWindowExW(
+                WINDOW_EX_STYLE::default(),
+                class_name,
+                w!("Speedy STT"),
+                WS_OVERLAPPED,
+                0,
+                0,
+                0,
+                0,
+                None,
+                None,
+                Some(instance.into()),
+                None,
+            )
+            .context("Failed to create session window")?;
+            // Only called when a console is attached, otherwise harmless
+            if let Err(e) = SetConsoleCtrlHandler(Some(console_handler), true) {
+                warn!("Failed to install console handler: {}", e);
+            }
+            let power =
+                RegisterSuspendResumeNotification(HANDLE(hwnd.0), DEVICE_NOTIFY_WINDOW_HANDLE)
+                    .map_err(|e| warn!("Failed to register for sleep notifications: {}", e))
+                    .ok();
+            Ok(Self { hwnd, power })
+        }
+    }
+
+    /// Ending the session kills the process without notice off Windows.
+    #[cfg(not(windows))]
+    pub fn create() -> Result<Self> {
+        Ok(Self {})
+    }
+}
+
+#[cfg(windows)]
+impl Drop for SessionWatcher {
+    fn drop(&mut self) {
+        // SAFETY: the window and registration were created in `create` and
+        // are released once; removing a handler that was never added fails
+        // harmlessly.
+        unsafe {
+            if let Some(power) = self.power.take() {
+                let _ = UnregisterSuspendResumeNotification(power);
+            }
+            let _ = SetConsoleCtrlHandler(Some(console_handler), false);
+            let _ = DestroyWindow(self.hwnd);
+        }
+    }
+}
+
+/// Window procedure answering the end-of-session messages.
+///
+/// `WM_QUERYENDSESSION` is always allowed and only flags the loop, which
+/// prepares before the pump delivers `WM_ENDSESSION`. Once that returns the
+/// process may be killed at any time, so a session ending before the loop
+/// prepared restores ducked audio from the journal right here.
+#[cfg(windows)]
+unsafe extern "system" fn window_proc(
+    hwnd: HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    match msg {
+        WM_QUERYENDSESSION => {
+            info!("Windows session is ending, stopping recording and restoring audio");
+            let _ = STATE.compare_exchange(RUNNING, QUERIED, Ordering::AcqRel, Ordering::Acquire);
+            LRESULT(1)
+        }
+        WM_ENDSESSION if wparam.0 != 0 => {
+            if STATE.swap(EXITING, Ordering::AcqRel) != PREPARED {
+                volume::restore_from_journal();
+            }
+            info!("Windows session ended");
+            LRESULT(0)
+        }
+        WM_ENDSESSION => {
+            info!("Windows session end was cancelled");
+            let _ = STATE.compare_exchange(QUERIED, RUNNING, Ordering::AcqRel, Ordering::Acquire);
+            let _ = STATE.compare_exchange(PREPARED, RUNNING, Ordering::AcqRel, Ordering::Acquire);
+            LRESULT(0)
+        }
+        WM_POWERBROADCAST => {
+            match u32::try_from(wparam.0) {
+                Ok(PBT_APMSUSPEND) => {
+                    info!("System is going to sleep");
+                    SUSPEND_PENDING.store(true, Ordering::Release);
+                }
+                Ok(PBT_APMRESUMEAUTOMATIC | PBT_APMRESUMESUSPEND) => {
+                    RESUME_PENDING.store(true, Ordering::Release);
+                }
+                _ => {}
+            }
+            LRESULT(1)
+        }
+        // SAFETY: forwards the arguments Windows passed to this window procedure.
+        _ => unsafe { DefWindowProcW(hwnd, msg, wparam, lparam) },
+    }
+}
+
+/// Console control handler, run by Windows on a thread of its own.
+///
+/// Asks the loop to exit and waits for its cleanup, since the process is
+/// terminated as soon as this returns for close, logoff and shutdown.
+#[cfg(windows)]
+unsafe extern "system" fn console_handler(ctrl_type: u32) -> BOOL {
+    match ctrl_type {
+        CTRL_C_EVENT | CTRL_BREAK_EVENT | CTRL_CLOSE_EVENT | CTRL_LOGOFF_EVENT
+        | CTRL_SHUTDOWN_EVENT => {
+            STATE.store(EXITING, Ordering::Release);
+            wake::wake();
+            let deadline = Instant::now() + CONSOLE_GRACE;
+            while !FINISHED.load(Ordering::Acquire) && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            BOOL::from(true)
+        }
+        _ => BOOL::from(false),
+    }
+}
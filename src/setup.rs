@@ -0,0 +1,264 @@
+//! Interactive first-run setup.
+//!
+//! Runs in a console window when no config file exists yet: picks a model
+//! (downloading it if needed), a free hotkey and a language, then writes
+//! `config.toml` so startup continues normally. Skipped with `--no-setup`.
+
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use tracing::info;
+
+use crate::config::Config;
+use crate::hotkey::HotkeyListener;
+use crate::paths;
+use crate::whisper::WhisperEngine;
+
+/// Models offered during setup, with their approximate download size
+const MODEL_CHOICES: &[(&str, &str)] = &[
+    ("tiny", "75 MB, fastest, lowest accuracy"),
+    ("base", "140 MB, recommended for CPU"),
+    ("small", "460 MB, better accuracy"),
+    ("medium", "1.5 GB, high accuracy"),
+    ("large-v3", "3 GB, best accuracy"),
+];
+
+/// Model picked when the user just presses Enter
+const DEFAULT_MODEL: &str = "base";
+
+/// Download location of the ggml Whisper models
+const MODEL_BASE_URL: &str = "https://huggingface.co/ggerganov/whisper.cpp/resolve/main";
+
+/// Ask for the essential settings and write the config file, returning its path.
+pub fn run() -> Result<PathBuf> {
+    open_console();
+    let result = run_steps();
+    if let Err(ref e) = result {
+        println!("\nSetup failed: {e:#}");
+        println!("Press Enter to close this window.");
+        let _ = read_line();
+    }
+    close_console();
+    result
+}
+
+/// Prompt for each setting in turn and write the answers.
+fn run_steps() -> Result<PathBuf> {
+    println!("Speedy STT first-run setup");
+    println!("No configuration file was found. Answer a few questions to create one;");
+    println!("press Enter to accept the suggested value.\n");
+
+    let model = choose_model()?;
+    let (modifier, key) = choose_hotkey()?;
+    let language = choose_language()?;
+
+    let path = Config::create_file(&[
+        ("whisper_model", &model),
+        ("hotkey_modifier", &modifier),
+        ("hotkey_key", &key),
+        ("whisper_language", &language),
+    ])?;
+    info!("First-run setup wrote {}", path.display());
+    println!("\nSaved {}", path.display());
+    println!(
+        "Hold {} to dictate. Press Enter to start Speedy STT.",
+        describe_hotkeys(&[(&modifier, &key)])
+    );
+    let _ = read_line();
+    Ok(path)
+}
+
+/// Pick a model by name, downloading it when it is not installed yet.
+fn choose_model() -> Result<String> {
+    println!("Whisper model:");
+    for (index, &(name, description)) in MODEL_CHOICES.iter().enumerate() {
+        let file = model_file_name(name);
+        let status = if paths::model_file(&file).exists() {
+            "installed"
+        } else {
+            "not installed"
+        };
+        println!("  {}. {name:<9} {description} ({status})", index + 1);
+    }
+
+    loop {
+        let answer = prompt("Model", DEFAULT_MODEL)?;
+        let name = answer
+            .parse::<usize>()
+            .ok()
+            .and_then(|number| MODEL_CHOICES.get(number.checked_sub(1)?))
+            .map_or(answer.as_str(), |&(name, _)| name);
+        let file = if name.ends_with(".bin") {
+            name.to_owned()
+        } else {
+            model_file_name(name)
+        };
+
+        let path = paths::model_file(&file);
+        if !path.exists() {
+            if !confirm(&format!("{file} is not installed. Download it now?"), true)? {
+                continue;
+            }
+            if let Err(e) = download_model(&file) {
+                println!("Download failed: {e:#}");
+                continue;
+            }
+        }
+        match WhisperEngine::check_model(&paths::model_file(&file)) {
+            Ok(()) => return Ok(file),
+            Err(e) => println!("{e:#}"),
+        }
+    }
+}
+
+/// Pick a hotkey that can currently be registered.
+fn choose_hotkey() -> Result<(String, String)> {
+    let free = HotkeyListener::free_candidates()?;
+    println!("\nPush-to-talk hotkey (hold to record):");
+    if free.is_empty() {
+        println!("  None of the common combinations are free; enter your own.");
+    } else {
+        println!("  Free right now: {}", describe_hotkeys(&free));
+    }
+    let suggestion = free.first().map_or_else(
+        || "CTRL+SPACE".to_owned(),
+        |&hotkey| describe_hotkeys(&[hotkey]),
+    );
+
+    loop {
+        let answer = prompt("Hotkey", &suggestion)?.to_uppercase();
+        let (modifier, key) = answer
+            .rsplit_once('+')
+            .map_or(("NONE", answer.as_str()), |(m, k)| (m, k));
+        match HotkeyListener::is_available(modifier, key) {
+            Ok(true) => return Ok((modifier.to_owned(), key.to_owned())),
+            Ok(false) => println!("{answer} is taken by Windows or another application."),
+            Err(e) => println!("{e:#}"),
+        }
+    }
+}
+
+/// Pick a transcription language code.
+fn choose_language() -> Result<String> {
+    println!("\nLanguage you will dictate in (en, de, fr, ... or auto to detect):");
+    loop {
+        let language = prompt("Language", "en")?.to_lowercase();
+        if WhisperEngine::is_known_language(&language) {
+            return Ok(language);
+        }
+        println!("Unknown language code {language:?}.");
+    }
+}
+
+/// Download a ggml model into the shared models directory, returning its path.
+///
+/// Uses the `curl` bundled with Windows so no HTTP client is linked in; the
+/// file is written under a temporary name and renamed once complete.
+pub fn download_model(file: &str) -> Result<PathBuf> {
+    let dir = paths::data_dir()
+        .map(|dir| dir.join("models"))
+        .unwrap_or_else(|| paths::resolve("assets/models"));
+    let path = dir.join(file);
+    let partial = dir.join(format!("{file}.part"));
+    paths::ensure_parent(&path)?;
+
+    let url = format!("{MODEL_BASE_URL}/{file}");
+    println!("Downloading {url}");
+    let status = std::process::Command::new("curl")
+        .args(["--location", "--fail", "--progress-bar", "--output"])
+        .arg(&partial)
+        .arg(&url)
+        .status()
+        .context("Failed to run curl")?;
+    if !status.success() {
+        let _ = std::fs::remove_file(&partial);
+        anyhow::bail!("curl exited with {status}");
+    }
+
+    WhisperEngine::check_model(&partial)?;
+    std::fs::rename(&partial, &path)
+        .with_context(|| format!("Failed to move download to {}", path.display()))?;
+    info!("Downloaded model to {}", path.display());
+    Ok(path)
+}
+
+/// File name of a ggml model by its short name.
+fn model_file_name(name: &str) -> String {
+    format!("ggml-{name}.bin")
+}
+
+/// Comma-separated hotkeys in the `MODIFIER+KEY` form accepted by the prompt.
+fn describe_hotkeys(hotkeys: &[(&str, &str)]) -> String {
+    hotkeys
+        .iter()
+        .map(|&(modifier, key)| {
+            if modifier == "NONE" {
+                key.to_owned()
+            } else {
+                format!("{modifier}+{key}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Ask a yes/no question.
+fn confirm(question: &str, default: bool) -> Result<bool> {
+    let hint = if default { "Y/n" } else { "y/N" };
+    print!("{question} [{hint}] ");
+    std::io::stdout()
+        .flush()
+        .context("Failed to write prompt")?;
+    let answer = read_line()?.to_lowercase();
+    Ok(match answer.as_str() {
+        "" => default,
+        _ => answer.starts_with('y'),
+    })
+}
+
+/// Ask for a value, returning `default` for an empty answer.
+fn prompt(label: &str, default: &str) -> Result<String> {
+    print!("{label} [{default}]: ");
+    std::io::stdout()
+        .flush()
+        .context("Failed to write prompt")?;
+    let answer = read_line()?;
+    Ok(if answer.is_empty() {
+        default.to_owned()
+    } else {
+        answer
+    })
+}
+
+/// Read one trimmed line from the console; a closed input cancels setup.
+fn read_line() -> Result<String> {
+    let mut line = String::new();
+    let read = std::io::stdin()
+        .lock()
+        .read_line(&mut line)
+        .context("Failed to read from console")?;
+    if read == 0 {
+        anyhow::bail!("Setup cancelled");
+    }
+    Ok(line.trim().to_owned())
+}
+
+/// Give the GUI-subsystem process a console window for the prompts.
+fn open_console() {
+    #[cfg(windows)]
+    // SAFETY: AllocConsole has no preconditions; it fails harmlessly when the
+    // process already has a console, which is then used instead.
+    unsafe {
+        let _ = windows::Win32::System::Console::AllocConsole();
+    }
+}
+
+/// Close the setup console so only the tray icon remains.
+fn close_console() {
+    #[cfg(windows)]
+    // SAFETY: FreeConsole has no preconditions; failure is harmless.
+    unsafe {
+        let _ = windows::Win32::System::Console::FreeConsole();
+    }
+}
@@ -0,0 +1,306 @@
+//! Joining Whisper segments into one text.
+//!
+//! Segments arrive with a leading space and sometimes stray whitespace of
+//! their own. They are trimmed and joined with single spaces, except next to
+//! brackets, quotes and punctuation that hug their neighbour, and between
+//! words of languages written without spaces, such as Japanese. Japanese
+//! quoted in a spaced language keeps the spaces around it.
+
+/// Languages written without spaces between words unless configured otherwise
+pub const DEFAULT_NO_SPACE_LANGUAGES: &[&str] = &["zh", "ja", "yue", "th", "lo", "km", "my", "bo"];
+
+/// Characters after which the next segment follows without a space
+const OPENING: &[char] = &[
+    '(', '[', '{', '“', '‘', '«', '„', '¿', '¡', '「', '『', '（', '【',
+];
+
+/// Characters before which the previous segment ends without a space
+const CLOSING: &[char] = &[
+    '.', ',', ';', ':', '!', '?', ')', ']', '}', '”', '’', '»', '…', '%', '、', '。', '，', '！',
+    '？', '」', '』', '）', '】',
+];
+
+/// How segments are joined, per language of the transcription
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Spacing {
+    /// Languages whose words are not separated by spaces
+    no_space_languages: Vec<String>,
+}
+
+impl Spacing {
+    /// Spacing that joins `no_space_languages` without spaces
+    pub fn new(no_space_languages: &[String]) -> Self {
+        Self {
+            no_space_languages: no_space_languages.to_vec(),
+        }
+    }
+
+    /// Join `segments` written in `language` into trimmed text.
+    ///
+    /// In a language without spaces, a boundary between two Latin words,
+    /// such as an English name inside Japanese, still gets one. In a spaced
+    /// language, words next to Chinese or Japanese text keep their space,
+    /// which is only left out inside such text and next to full-width
+    /// punctuation.
+    pub fn join<'a>(&self, segments: impl IntoIterator<Item = &'a str>, language: &str) -> String {
+        let spaced = !self.no_space_languages.iter().any(|l| l == language);
+        let mut text = String::new();
+        for segment in segments {
+            let segment = segment.split_whitespace().collect::<Vec<_>>().join(" ");
+            let (Some(last), Some(first)) = (text.chars().next_back(), segment.chars().next())
+            else {
+                text.push_str(&segment);
+                continue;
+            };
+            let separated = if spaced {
+                let inside_cjk = is_unspaced_script(last) && is_unspaced_script(first);
+                !(inside_cjk || is_full_width_punctuation(last) || is_full_width_punctuation(first))
+            } else {
+                is_latin(last) && is_latin(first)
+            };
+            if separated && !OPENING.contains(&last) && !CLOSING.contains(&first) {
+                text.push(' ');
+            }
+            text.push_str(&segment);
+        }
+        text
+    }
+}
+
+/// Whether `c` belongs to a script written without spaces, Chinese and
+/// Japanese characters or full-width punctuation, whatever the language
+fn is_unspaced_script(c: char) -> bool {
+    matches!(
+        c,
+        '\u{3000}'..='\u{30FF}'
+            | '\u{3400}'..='\u{4DBF}'
+            | '\u{4E00}'..='\u{9FFF}'
+            | '\u{F900}'..='\u{FAFF}'
+            | '\u{FF00}'..='\u{FFEF}'
+    )
+}
+
+/// Whether `c` is a CJK or full-width punctuation mark, such as `。` or `」`
+fn is_full_width_punctuation(c: char) -> bool {
+    matches!(c, '\u{3000}'..='\u{303F}' | '\u{FF00}'..='\u{FFEF}') && !c.is_alphanumeric()
+}
+
+/// Whether `c` is a Latin letter or digit
+fn is_latin(c: char) -> bool {
+    c.is_alphanumeric() && c < '\u{0250}'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    /// Spacing with the default languages written without spaces
+    fn default_spacing() -> Spacing {
+        let languages: Vec<String> = DEFAULT_NO_SPACE_LANGUAGES
+            .iter()
+            .map(|&language| language.to_owned())
+            .collect();
+        Spacing::new(&languages)
+    }
+
+    /// Segments of the fixture `name` joined in its language with default spacing
+    fn joined_fixture(name: &str) -> String {
+        let transcript = fixtures::transcript(name);
+        default_spacing().join(
+            transcript
+                .segments
+                .iter()
+                .map(|segment| segment.text.as_str()),
+            &transcript.language,
+        )
+    }
+
+    #[test]
+    fn segments_of_a_spaced_language_get_single_spaces() {
+        assert_eq!(
+            default_spacing().join([" Hello  there", "  general ", " Kenobi"], "en"),
+            "Hello there general Kenobi",
+            "stray whitespace kept"
+        );
+    }
+
+    #[test]
+    fn japanese_segments_join_without_spaces() {
+        assert_eq!(
+            default_spacing().join([" こんにちは", " 世界"], "ja"),
+            "こんにちは世界",
+            "space between Japanese words"
+        );
+        assert_eq!(
+            default_spacing().join([" 我们", " 使用", " 中文"], "zh"),
+            "我们使用中文",
+            "space between Chinese words"
+        );
+    }
+
+    #[test]
+    fn latin_words_inside_japanese_keep_their_space() {
+        assert_eq!(
+            default_spacing().join([" 東京で", " Tokyo", " Tower", " を見た"], "ja"),
+            "東京でTokyo Towerを見た",
+            "Latin words merged or spaced from the Japanese"
+        );
+        assert_eq!(
+            default_spacing().join([" 価格は", " 100", " 円"], "ja"),
+            "価格は100円",
+            "number spaced from the Japanese"
+        );
+    }
+
+    #[test]
+    fn cjk_text_in_a_spaced_language_keeps_the_spaces_around_it() {
+        assert_eq!(
+            default_spacing().join([" I said", " ありがとう", " to her"], "en"),
+            "I said ありがとう to her",
+            "space next to Japanese lost in English"
+        );
+        assert_eq!(
+            default_spacing().join([" She wrote", " ありがとう", " ございます"], "en"),
+            "She wrote ありがとうございます",
+            "space inside the Japanese in English"
+        );
+        assert_eq!(
+            default_spacing().join([" He said", " 「", " はい", " 」", " and left"], "en"),
+            "He said「はい」and left",
+            "space next to full-width brackets in English"
+        );
+    }
+
+    #[test]
+    fn only_configured_languages_join_their_words_without_spaces() {
+        let spacing = Spacing::new(&["ja".to_owned()]);
+        assert_eq!(
+            spacing.join([" 日本", " 語"], "zh"),
+            "日本語",
+            "space between Chinese characters of an unlisted language"
+        );
+        assert_eq!(
+            spacing.join([" ສະບາຍ", " ດີ"], "lo"),
+            "ສະບາຍ ດີ",
+            "unlisted language joined without a space"
+        );
+        assert_eq!(
+            Spacing::new(&["lo".to_owned()]).join([" ສະບາຍ", " ດີ"], "lo"),
+            "ສະບາຍດີ",
+            "listed language joined with a space"
+        );
+    }
+
+    #[test]
+    fn closing_punctuation_follows_without_a_space() {
+        assert_eq!(
+            default_spacing().join([" Hello", ",", " world", " !"], "en"),
+            "Hello, world!",
+            "space before closing punctuation"
+        );
+        assert_eq!(
+            default_spacing().join([" fifty", " %", " of it", " …"], "en"),
+            "fifty% of it…",
+            "space before a percent sign or ellipsis"
+        );
+    }
+
+    #[test]
+    fn opening_brackets_and_quotes_hug_the_next_word() {
+        assert_eq!(
+            default_spacing().join([" He said", " (", " quietly", " )", " yes"], "en"),
+            "He said (quietly) yes",
+            "brackets spaced wrongly"
+        );
+        assert_eq!(
+            default_spacing().join([" “", " Hi", " ”", " she said"], "en"),
+            "“Hi” she said",
+            "curly quotes spaced wrongly"
+        );
+        assert_eq!(
+            default_spacing().join([" Dijo", " ¿", " qué", " ?"], "es"),
+            "Dijo ¿qué?",
+            "Spanish question marks spaced wrongly"
+        );
+    }
+
+    #[test]
+    fn straight_quotes_are_spaced_like_words() {
+        assert_eq!(
+            default_spacing().join([" he said", " \"hi\""], "en"),
+            "he said \"hi\"",
+            "straight quote taken for opening or closing"
+        );
+    }
+
+    #[test]
+    fn punctuation_inside_segments_is_left_alone() {
+        assert_eq!(
+            default_spacing().join([" Hello,", " world.", " Again?"], "en"),
+            "Hello, world. Again?",
+            "segments carrying their own punctuation joined wrongly"
+        );
+    }
+
+    #[test]
+    fn full_width_punctuation_hugs_its_neighbours() {
+        assert_eq!(
+            default_spacing().join([" はい", " 。", " そうです", " 」"], "ja"),
+            "はい。そうです」",
+            "space next to Japanese punctuation"
+        );
+        assert_eq!(
+            default_spacing().join([" OK", " 。", " Next"], "en"),
+            "OK。Next",
+            "space next to full-width punctuation in English"
+        );
+    }
+
+    #[test]
+    fn empty_segments_add_no_space() {
+        assert_eq!(
+            default_spacing().join([" Hello", "", "   ", " world", " ."], "en"),
+            "Hello world.",
+            "empty segments left a double space"
+        );
+    }
+
+    #[test]
+    fn a_dictation_in_english_is_joined_with_single_spaces() {
+        assert_eq!(
+            joined_fixture("punctuated-en"),
+            "Okay, let's try this again with the new microphone. It sounds a lot clearer \
+             than the old one did so I think we're good.",
+            "English dictation joined wrongly"
+        );
+    }
+
+    #[test]
+    fn a_dictation_in_japanese_is_joined_without_spaces() {
+        assert_eq!(
+            joined_fixture("ja"),
+            "今日は天気がいいので、公園まで散歩に行きました。帰りにコーヒーを買いました。",
+            "Japanese dictation joined with spaces"
+        );
+    }
+
+    #[test]
+    fn english_names_in_a_japanese_dictation_stay_spaced() {
+        assert_eq!(
+            joined_fixture("mixed-ja-en"),
+            "明日の会議ではGitHubのプルリクエストを確認して、Visual Studio Codeで開いてください。",
+            "English names in Japanese joined wrongly"
+        );
+    }
+
+    #[test]
+    fn japanese_in_an_english_dictation_keeps_its_spaces() {
+        assert_eq!(
+            joined_fixture("mixed-en-ja"),
+            "My favourite word is 木漏れ日 which means sunlight through the trees. \
+             My teacher wrote 「木漏れ日」on the board.",
+            "Japanese in English joined wrongly"
+        );
+    }
+}
@@ -0,0 +1,72 @@
+//! Spoken read-back of transcriptions.
+//!
+//! Text is rendered to a WAV clip with the Windows speech synthesizer and
+//! played like any other feedback sound, so it uses the feedback output
+//! device and volume and is cut off by the next sound.
+
+#[cfg(windows)]
+use anyhow::Context;
+use anyhow::Result;
+#[cfg(windows)]
+use windows::Media::SpeechSynthesis::SpeechSynthesizer;
+#[cfg(windows)]
+use windows::Storage::Streams::DataReader;
+#[cfg(windows)]
+use windows::core::HSTRING;
+
+#[cfg(windows)]
+use crate::com::ComApartment;
+
+/// Slowest and fastest speaking rates accepted by the synthesizer
+pub const RATE_RANGE: std::ops::RangeInclusive<f64> = 0.5..=6.0;
+
+/// Render `text` as WAV audio at `rate` times the normal speaking speed.
+#[cfg(windows)]
+pub fn synthesize(text: &str, rate: f64) -> Result<Vec<u8>> {
+    // Declared first so COM outlives the WinRT objects below
+    let _com = ComApartment::enter()?;
+    let synthesizer = SpeechSynthesizer::new().context("Failed to create speech synthesizer")?;
+    synthesizer
+        .Options()
+        .and_then(|options| options.SetSpeakingRate(rate))
+        .context("Failed to set speaking rate")?;
+    let stream = synthesizer
+        .SynthesizeTextToStreamAsync(&HSTRING::from(text))
+        .and_then(|request| request.join())
+        .context("Failed to synthesize speech")?;
+
+    let size = stream.Size().context("Failed to read speech length")?;
+    let size = u32::try_from(size).context("Synthesized speech is too long")?;
+    let reader = stream
+        .GetInputStreamAt(0)
+        .and_then(|input| DataReader::CreateDataReader(&input))
+        .context("Failed to read synthesized speech")?;
+    reader
+        .LoadAsync(size)
+        .and_then(|request| request.join())
+        .context("Failed to read synthesized speech")?;
+    let mut wav = vec![0; usize::try_from(size).unwrap_or_default()];
+    reader
+        .ReadBytes(&mut wav)
+        .context("Failed to read synthesized speech")?;
+    Ok(wav)
+}
+
+/// Speech synthesis needs the Windows speech APIs.
+#[cfg(not(windows))]
+pub fn synthesize(_text: &str, _rate: f64) -> Result<Vec<u8>> {
+    anyhow::bail!("Spoken read-back is only available on Windows")
+}
+
+/// `text` cut to at most `max_chars` characters at a word boundary.
+pub fn truncate(text: &str, max_chars: usize) -> &str {
+    match text.char_indices().nth(max_chars) {
+        None => text,
+        Some((end, _)) => {
+            let cut = &text[..end];
+            cut.rfind(char::is_whitespace)
+                .map_or(cut, |space| &cut[..space])
+                .trim_end()
+        }
+    }
+}
@@ -0,0 +1,183 @@
+//! Older audio of a long recording, moved out of memory to a temporary file.
+//!
+//! Once a recording's buffer grows past a limit, [`AudioRecorder`] converts
+//! the buffered samples to what Whisper takes, 16 kHz mono, and appends them
+//! to a file, which shrinks them several times over. They are read back once
+//! for transcription, and the file is deleted when the recording is dropped.
+//!
+//! [`AudioRecorder`]: crate::audio::AudioRecorder
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use tracing::{info, warn};
+
+/// Prefix of every spill file's name
+const FILE_PREFIX: &str = "recording-";
+
+/// Extension of every spill file's name
+const FILE_EXTENSION: &str = "f32";
+
+/// Numbers the spill files of this process
+static NEXT_FILE: AtomicU32 = AtomicU32::new(0);
+
+/// Whisper input of a recording that no longer fits into memory
+///
+/// Samples are kept in memory instead once the file cannot be written.
+#[derive(Debug)]
+pub struct Spill {
+    /// Directory the file is created in on the first append
+    dir: PathBuf,
+    /// File the samples went to, once created
+    path: Option<PathBuf>,
+    /// Open file, `None` before the first append or after a write failed
+    file: Option<File>,
+    /// Samples fully written to the file
+    written: usize,
+    /// Whether writing failed, so further samples stay in memory
+    failed: bool,
+    /// Samples after the ones in the file, when writing failed
+    kept: Vec<f32>,
+}
+
+impl Spill {
+    /// Spill going to a new file in `dir`, created only once something is appended
+    pub const fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            path: None,
+            file: None,
+            written: 0,
+            failed: false,
+            kept: Vec::new(),
+        }
+    }
+
+    /// Samples spilled so far
+    pub const fn len(&self) -> usize {
+        self.written + self.kept.len()
+    }
+
+    /// Whether nothing was spilled
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Append 16 kHz mono `samples`, keeping them in memory if the file fails
+    pub fn append(&mut self, samples: &[f32]) {
+        if !self.failed
+            && let Err(e) = self.write(samples)
+        {
+            warn!(
+                "Failed to spill the recording to disk, keeping it in memory: {}",
+                e
+            );
+            self.failed = true;
+            self.file = None;
+        }
+        if self.failed {
+            self.kept.extend_from_slice(samples);
+        }
+    }
+
+    /// Every spilled sample in order
+    pub fn read_back(&self) -> io::Result<Vec<f32>> {
+        let mut samples = Vec::with_capacity(self.len());
+        if let Some(ref path) = self.path {
+            let bytes = fs::read(path)?;
+            samples.extend(
+                bytes
+                    .chunks_exact(size_of::<f32>())
+                    .take(self.written)
+                    .filter_map(|sample| sample.try_into().ok())
+                    .map(f32::from_le_bytes),
+            );
+            if samples.len() < self.written {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!(
+                        "{} holds {} of {} samples",
+                        path.display(),
+                        samples.len(),
+                        self.written
+                    ),
+                ));
+            }
+        }
+        samples.extend_from_slice(&self.kept);
+        Ok(samples)
+    }
+
+    /// Write `samples` to the file, creating it first if needed
+    fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+        let file = match self.file {
+            Some(ref mut file) => file,
+            None => {
+                fs::create_dir_all(&self.dir)?;
+                let path = self.dir.join(format!(
+                    "{FILE_PREFIX}{}-{}.{FILE_EXTENSION}",
+                    std::process::id(),
+                    NEXT_FILE.fetch_add(1, Ordering::Relaxed)
+                ));
+                let file = File::create(&path)?;
+                info!("Spilling the recording to {}", path.display());
+                self.path = Some(path);
+                self.file.insert(file)
+            }
+        };
+        let bytes: Vec<u8> = samples
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect();
+        file.write_all(&bytes)?;
+        self.written += samples.len();
+        Ok(())
+    }
+}
+
+impl Drop for Spill {
+    fn drop(&mut self) {
+        // Closed first, as Windows cannot delete a file that is still open
+        self.file = None;
+        if let Some(path) = self.path.take()
+            && let Err(e) = fs::remove_file(&path)
+            && e.kind() != io::ErrorKind::NotFound
+        {
+            warn!("Failed to delete spill file {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Delete spill files in `dir` left behind by a process that crashed
+///
+/// Files of the calling process are kept, as it may be recording already.
+pub fn sweep(dir: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let own = format!("{FILE_PREFIX}{}-", std::process::id());
+    let mut removed = 0_usize;
+    for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+        let is_spill = path.extension().is_some_and(|ext| ext == FILE_EXTENSION)
+            && path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(FILE_PREFIX) && !name.starts_with(&own));
+        if !is_spill {
+            continue;
+        }
+        match fs::remove_file(&path) {
+            Ok(()) => removed += 1,
+            Err(e) => warn!(
+                "Failed to delete stale spill file {}: {}",
+                path.display(),
+                e
+            ),
+        }
+    }
+    if removed > 0 {
+        info!("Deleted {} spill files left by an earlier run", removed);
+    }
+}
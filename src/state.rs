@@ -0,0 +1,427 @@
+//! Settings changed from the tray, persisted across restarts.
+//!
+//! Choices are written to `state.toml` rather than the hand-edited config so
+//! its comments survive. Each entry remembers the config file value it
+//! replaced; if the config file was edited since, the edit wins.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{Context, Result};
+use tracing::{info, warn};
+
+use crate::config;
+use crate::paths;
+
+/// Quiet period after the last change before the state file is written
+const SAVE_DELAY: Duration = Duration::from_secs(2);
+
+/// Table holding the config file value each entry replaced
+const REPLACED_TABLE: &str = "replaced";
+
+/// Runtime choices waiting to be written to the state file
+pub struct StateStore {
+    /// Location of the state file
+    path: PathBuf,
+    /// Changed settings, `None` to forget a saved choice
+    pending: BTreeMap<String, Option<String>>,
+    /// When the most recent change was made
+    changed_at: Option<Instant>,
+}
+
+impl StateStore {
+    /// Create a store writing to `path`
+    pub const fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            pending: BTreeMap::new(),
+            changed_at: None,
+        }
+    }
+
+    /// Remember `value` for `key`, or forget the saved choice with `None`
+    pub fn set(&mut self, key: &str, value: Option<&str>) {
+        self.pending
+            .insert(key.to_owned(), value.map(str::to_owned));
+        self.changed_at = Some(Instant::now());
+    }
+
+    /// Write pending changes once no further change arrived for [`SAVE_DELAY`]
+    pub fn save_if_due(&mut self, config_file: Option<&Path>) {
+        if self.changed_at.is_some_and(|at| at.elapsed() >= SAVE_DELAY) {
+            self.save(config_file);
+        }
+    }
+
+    /// When pending changes become due for saving, if there are any
+    pub fn save_due(&self) -> Option<Instant> {
+        self.changed_at.map(|at| at + SAVE_DELAY)
+    }
+
+    /// Write pending changes now, logging failures
+    pub fn save(&mut self, config_file: Option<&Path>) {
+        if self.pending.is_empty() {
+            return;
+        }
+        self.changed_at = None;
+        let pending = std::mem::take(&mut self.pending);
+        if let Err(e) = self.write(&pending, config_file) {
+            warn!(
+                "Failed to save settings to {}: {:#}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+
+    /// Merge `pending` into the state file, recording the config values they replace
+    fn write(
+        &self,
+        pending: &BTreeMap<String, Option<String>>,
+        config_file: Option<&Path>,
+    ) -> Result<()> {
+        let mut state = read_table(&self.path).unwrap_or_else(|e| {
+            warn!("Discarding unreadable {}: {:#}", self.path.display(), e);
+            toml::Table::new()
+        });
+        let config = config_file
+            .and_then(|path| read_table(path).ok())
+            .unwrap_or_default();
+
+        let mut replaced = match state.remove(REPLACED_TABLE) {
+            Some(toml::Value::Table(table)) => table,
+            _ => toml::Table::new(),
+        };
+        for (key, value) in pending {
+            match *value {
+                Some(ref value) => {
+                    state.insert(key.clone(), toml::Value::String(value.clone()));
+                    replaced.insert(
+                        key.clone(),
+                        toml::Value::String(assigned_value(&config, key)),
+                    );
+                }
+                None => {
+                    state.remove(key);
+                    replaced.remove(key);
+                }
+            }
+        }
+        state.insert(REPLACED_TABLE.to_owned(), toml::Value::Table(replaced));
+
+        let contents = toml::to_string(&state).context("Failed to serialize settings")?;
+        write_atomically(&self.path, &contents)?;
+        info!("Saved settings to {}", self.path.display());
+        Ok(())
+    }
+}
+
+/// Saved choices that still apply on top of the config file at `config_file`.
+///
+/// When the config file is newer than the state file and a setting there no
+/// longer matches the value the saved choice replaced, the hand edit wins:
+/// the choice is reported in `warnings` and removed from the state file.
+pub fn load_overlay(
+    path: &Path,
+    config_file: Option<(&Path, &toml::Table)>,
+    warnings: &mut Vec<String>,
+) -> toml::Table {
+    if !path.exists() {
+        return toml::Table::new();
+    }
+    let mut state = match read_table(path) {
+        Ok(state) => state,
+        Err(e) => {
+            warnings.push(format!("Ignoring saved settings: {e:#}"));
+            return toml::Table::new();
+        }
+    };
+    let mut replaced = match state.remove(REPLACED_TABLE) {
+        Some(toml::Value::Table(table)) => table,
+        _ => toml::Table::new(),
+    };
+
+    let Some((config_path, config)) = config_file else {
+        return state;
+    };
+    if modified(config_path) <= modified(path) {
+        return state;
+    }
+    let mut dropped = Vec::new();
+    state.retain(|key, value| {
+        let current = assigned_value(config, key);
+        let edited = replaced
+            .get(key)
+            .and_then(toml::Value::as_str)
+            .is_some_and(|original| original != current);
+        if edited {
+            dropped.push(key.to_owned());
+            warnings.push(format!(
+                "{key} was edited in {} after it was changed from the tray; \
+                using {current:?} instead of {value}",
+                config_path.display()
+            ));
+        }
+        !edited
+    });
+
+    if !dropped.is_empty() {
+        replaced.retain(|key, _| !dropped.iter().any(|name| name == key));
+        let mut kept = state.clone();
+        kept.insert(REPLACED_TABLE.to_owned(), toml::Value::Table(replaced));
+        if let Err(e) = toml::to_string(&kept)
+            .context("Failed to serialize settings")
+            .and_then(|contents| write_atomically(path, &contents))
+        {
+            warnings.push(format!("Failed to update saved settings: {e:#}"));
+        }
+    }
+    state
+}
+
+/// Value assigned to `key` outside any profile, as text; empty when unset.
+fn assigned_value(config: &toml::Table, key: &str) -> String {
+    config
+        .get("default")
+        .and_then(toml::Value::as_table)
+        .and_then(|defaults| defaults.get(key))
+        .or_else(|| config.get(key))
+        .map(config::raw_value)
+        .unwrap_or_default()
+}
+
+/// Parse a TOML file; a missing file is an empty table.
+fn read_table(path: &Path) -> Result<toml::Table> {
+    if !path.exists() {
+        return Ok(toml::Table::new());
+    }
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    toml::from_str(&contents).with_context(|| format!("Invalid TOML in {}", path.display()))
+}
+
+/// Last modification time of `path`, if known.
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
+
+/// Replace `path` with `contents` via a temporary file, so a crash never leaves it half written.
+fn write_atomically(path: &Path, contents: &str) -> Result<()> {
+    paths::ensure_parent(path)?;
+    let temporary = path.with_extension("toml.tmp");
+    std::fs::write(&temporary, contents)
+        .with_context(|| format!("Failed to write {}", temporary.display()))?;
+    std::fs::rename(&temporary, path)
+        .with_context(|| format!("Failed to replace {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch::ScratchDir;
+
+    /// Write `contents` to `path`, dated `age` before now
+    fn write_dated(path: &Path, contents: &str, age: Duration) {
+        std::fs::write(path, contents).expect("file written");
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .and_then(|file| file.set_modified(SystemTime::now() - age))
+            .expect("modification time set");
+    }
+
+    /// Date the existing file `path` `age` before now
+    fn redate(path: &Path, age: Duration) {
+        let contents = std::fs::read_to_string(path).expect("file read");
+        write_dated(path, &contents, age);
+    }
+
+    /// TOML file at `path`, parsed
+    fn parsed(path: &Path) -> toml::Table {
+        read_table(path).expect("config parsed")
+    }
+
+    /// Save `key = value` from the tray over the config file at `config`
+    fn save_choice(state: &Path, config: &Path, key: &str, value: &str) {
+        let mut store = StateStore::new(state.to_owned());
+        store.set(key, Some(value));
+        store.save(Some(config));
+    }
+
+    #[test]
+    fn a_saved_choice_records_the_config_value_it_replaced() {
+        let dir = ScratchDir::new("state-replaced");
+        let (state, config) = (
+            dir.path().join("state.toml"),
+            dir.path().join("config.toml"),
+        );
+        write_dated(
+            &config,
+            "[default]\nwhisper_language = \"en\"\n",
+            Duration::ZERO,
+        );
+        save_choice(&state, &config, "whisper_language", "de");
+
+        let saved = parsed(&state);
+        assert_eq!(
+            saved.get("whisper_language").and_then(toml::Value::as_str),
+            Some("de"),
+            "choice not saved"
+        );
+        assert_eq!(
+            saved
+                .get(REPLACED_TABLE)
+                .and_then(|replaced| replaced.get("whisper_language"))
+                .and_then(toml::Value::as_str),
+            Some("en"),
+            "replaced value not recorded"
+        );
+    }
+
+    #[test]
+    fn a_later_edit_of_the_config_file_wins() {
+        let dir = ScratchDir::new("state-edited");
+        let (state, config) = (
+            dir.path().join("state.toml"),
+            dir.path().join("config.toml"),
+        );
+        write_dated(&config, "whisper_language = \"en\"\n", Duration::ZERO);
+        save_choice(&state, &config, "whisper_language", "de");
+        redate(&state, Duration::from_secs(60));
+        write_dated(&config, "whisper_language = \"fr\"\n", Duration::ZERO);
+
+        let mut warnings = Vec::new();
+        let overlay = load_overlay(&state, Some((&config, &parsed(&config))), &mut warnings);
+        assert!(
+            !overlay.contains_key("whisper_language"),
+            "tray choice beat the edit"
+        );
+        assert_eq!(warnings.len(), 1, "edit not reported: {warnings:?}");
+        let rewritten = parsed(&state);
+        assert!(
+            !rewritten.contains_key("whisper_language"),
+            "dropped choice left in the state file"
+        );
+        assert!(
+            rewritten
+                .get(REPLACED_TABLE)
+                .and_then(|replaced| replaced.get("whisper_language"))
+                .is_none(),
+            "replaced value of the dropped choice left in the state file"
+        );
+    }
+
+    #[test]
+    fn a_choice_survives_edits_to_other_settings() {
+        let dir = ScratchDir::new("state-unedited");
+        let (state, config) = (
+            dir.path().join("state.toml"),
+            dir.path().join("config.toml"),
+        );
+        write_dated(&config, "whisper_language = \"en\"\n", Duration::ZERO);
+        save_choice(&state, &config, "whisper_language", "de");
+        redate(&state, Duration::from_secs(60));
+        write_dated(
+            &config,
+            "whisper_language = \"en\"\nvolume_boost = 2.0\n",
+            Duration::ZERO,
+        );
+
+        let mut warnings = Vec::new();
+        let overlay = load_overlay(&state, Some((&config, &parsed(&config))), &mut warnings);
+        assert_eq!(
+            overlay
+                .get("whisper_language")
+                .and_then(toml::Value::as_str),
+            Some("de"),
+            "unedited choice dropped"
+        );
+        assert!(warnings.is_empty(), "unexpected warnings: {warnings:?}");
+        assert!(
+            parsed(&state).contains_key("whisper_language"),
+            "unedited choice removed from the state file"
+        );
+    }
+
+    #[test]
+    fn an_older_config_file_does_not_override_a_choice() {
+        let dir = ScratchDir::new("state-older");
+        let (state, config) = (
+            dir.path().join("state.toml"),
+            dir.path().join("config.toml"),
+        );
+        write_dated(&config, "whisper_language = \"en\"\n", Duration::ZERO);
+        save_choice(&state, &config, "whisper_language", "de");
+        write_dated(
+            &config,
+            "whisper_language = \"fr\"\n",
+            Duration::from_secs(60),
+        );
+
+        let mut warnings = Vec::new();
+        let overlay = load_overlay(&state, Some((&config, &parsed(&config))), &mut warnings);
+        assert_eq!(
+            overlay
+                .get("whisper_language")
+                .and_then(toml::Value::as_str),
+            Some("de"),
+            "choice dropped for an older config file"
+        );
+        assert!(warnings.is_empty(), "unexpected warnings: {warnings:?}");
+    }
+
+    #[test]
+    fn forgetting_a_choice_removes_it_and_its_replaced_value() {
+        let dir = ScratchDir::new("state-forget");
+        let (state, config) = (
+            dir.path().join("state.toml"),
+            dir.path().join("config.toml"),
+        );
+        write_dated(&config, "whisper_language = \"en\"\n", Duration::ZERO);
+        save_choice(&state, &config, "whisper_language", "de");
+        let mut store = StateStore::new(state.clone());
+        store.set("whisper_language", None);
+        store.save(Some(&config));
+
+        let saved = parsed(&state);
+        assert!(!saved.contains_key("whisper_language"), "choice kept");
+        assert!(
+            saved
+                .get(REPLACED_TABLE)
+                .and_then(|replaced| replaced.get("whisper_language"))
+                .is_none(),
+            "replaced value kept"
+        );
+    }
+
+    #[test]
+    fn changes_are_written_once_they_settled() {
+        let dir = ScratchDir::new("state-debounce");
+        let state = dir.path().join("state.toml");
+        let mut store = StateStore::new(state.clone());
+        assert_eq!(store.save_due(), None, "due without changes");
+        store.set("whisper_language", Some("de"));
+        let due = store.save_due().expect("due after a change");
+
+        store.save_if_due(None);
+        assert!(!state.exists(), "written before the changes settled");
+        assert_eq!(store.save_due(), Some(due), "change forgotten");
+
+        store.set("volume_boost", Some("2"));
+        assert!(
+            store.save_due().is_some_and(|later| later >= due),
+            "a newer change did not push the save back"
+        );
+        store.changed_at = Instant::now().checked_sub(SAVE_DELAY);
+        store.save_if_due(None);
+        let saved = parsed(&state);
+        assert!(
+            saved.contains_key("whisper_language") && saved.contains_key("volume_boost"),
+            "settled changes not all written: {saved:?}"
+        );
+        assert_eq!(store.save_due(), None, "still due after writing");
+    }
+}
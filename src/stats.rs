@@ -0,0 +1,301 @@
+//! Dictation statistics.
+//!
+//! Counts dictations, recorded audio, typed words and transcription speed for
+//! the current session, optionally rolled up per day into a JSON file, and
+//! where the time of recent dictations went. Only counts are kept, never
+//! transcribed text.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::paths;
+
+/// Dictations the latency averages are taken over
+const RECENT_TIMINGS: usize = 20;
+
+/// Where the time of one dictation went, from key release to typed text.
+///
+/// Each stage is measured from the end of the previous one, so no span is
+/// counted twice and the stages add up to [`Self::total`].
+#[derive(Debug, Clone, Copy)]
+pub struct Timings {
+    /// Length of the recording
+    pub audio: Duration,
+    /// Downmixing and resampling the audio, overlapping the model wait
+    pub prepare: Duration,
+    /// Waiting for the model to load or earlier dictations to finish
+    pub model_wait: Duration,
+    /// Running Whisper on the audio
+    pub transcribe: Duration,
+    /// Preparing the text for typing
+    pub post_process: Duration,
+    /// Typing the text into the target application
+    pub inject: Duration,
+    /// When the hotkey was released
+    released: Instant,
+    /// End of the last measured stage
+    mark: Instant,
+}
+
+impl Timings {
+    /// Start measuring a dictation of `audio` whose hotkey was just released
+    pub fn released(audio: Duration) -> Self {
+        let now = Instant::now();
+        Self {
+            audio,
+            prepare: Duration::ZERO,
+            model_wait: Duration::ZERO,
+            transcribe: Duration::ZERO,
+            post_process: Duration::ZERO,
+            inject: Duration::ZERO,
+            released: now,
+            mark: now,
+        }
+    }
+
+    /// Time since the previous stage ended, starting the next one
+    fn lap(&mut self) -> Duration {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.mark);
+        self.mark = now;
+        elapsed
+    }
+
+    /// The audio was prepared for Whisper, which took `took` on its own worker
+    pub const fn prepared(&mut self, took: Duration) {
+        self.prepare = took;
+    }
+
+    /// Transcription is starting
+    pub fn model_ready(&mut self) {
+        self.model_wait = self.lap();
+    }
+
+    /// Whisper returned its text
+    pub fn transcribed(&mut self) {
+        self.transcribe = self.lap();
+    }
+
+    /// The text is ready to type
+    pub fn post_processed(&mut self) {
+        self.post_process = self.lap();
+    }
+
+    /// The text was typed, or typing failed
+    pub fn injected(&mut self) {
+        self.inject = self.lap();
+    }
+
+    /// From key release to the end of the last measured stage
+    pub fn total(&self) -> Duration {
+        self.mark.duration_since(self.released)
+    }
+
+    /// Transcription time per second of audio
+    pub fn realtime_factor(&self) -> Option<f64> {
+        let audio = self.audio.as_secs_f64();
+        (audio > 0.0).then(|| self.transcribe.as_secs_f64() / audio)
+    }
+}
+
+impl fmt::Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let factor = self
+            .realtime_factor()
+            .map_or_else(|| "n/a".to_owned(), |factor| format!("{factor:.2}"));
+        write!(
+            f,
+            "audio {:.1}s, prepare {:.1}s, model wait {:.1}s, transcribe {:.1}s (RTF {}), post-process {:.1}s, inject {:.1}s, total {:.1}s",
+            self.audio.as_secs_f64(),
+            self.prepare.as_secs_f64(),
+            self.model_wait.as_secs_f64(),
+            self.transcribe.as_secs_f64(),
+            factor,
+            self.post_process.as_secs_f64(),
+            self.inject.as_secs_f64(),
+            self.total().as_secs_f64()
+        )
+    }
+}
+
+/// Accumulated counters for a session or a day
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Totals {
+    /// Completed dictations
+    pub dictations: u64,
+    /// Seconds of audio recorded
+    pub recorded_secs: f64,
+    /// Words typed into other applications
+    pub words: u64,
+    /// Seconds spent transcribing
+    pub transcription_secs: f64,
+}
+
+impl Totals {
+    /// Fold another set of counters into this one
+    fn add(&mut self, other: &Self) {
+        self.dictations += other.dictations;
+        self.recorded_secs += other.recorded_secs;
+        self.words += other.words;
+        self.transcription_secs += other.transcription_secs;
+    }
+
+    /// Transcription time per second of audio; below 1.0 is faster than real time
+    pub fn realtime_factor(&self) -> Option<f64> {
+        (self.recorded_secs > 0.0).then(|| self.transcription_secs / self.recorded_secs)
+    }
+
+    /// Human-readable multi-line summary
+    pub fn summary(&self) -> String {
+        let factor = self
+            .realtime_factor()
+            .map_or_else(|| "n/a".to_owned(), |factor| format!("{factor:.2}x"));
+        format!(
+            "Dictations: {}\nRecorded: {:.0} s\nWords typed: {}\nAverage real-time factor: {}",
+            self.dictations, self.recorded_secs, self.words, factor
+        )
+    }
+}
+
+/// Session counters with an optional daily rollup file
+pub struct SessionStats {
+    /// Totals since the app started
+    session: Totals,
+    /// JSON file holding per-day totals, `None` when the rollup is disabled
+    daily_file: Option<PathBuf>,
+    /// Timings of the latest dictations, oldest first
+    recent: VecDeque<Timings>,
+}
+
+impl SessionStats {
+    /// Start an empty session, rolling up into `daily_file` if given
+    pub const fn new(daily_file: Option<PathBuf>) -> Self {
+        Self {
+            session: Totals {
+                dictations: 0,
+                recorded_secs: 0.0,
+                words: 0,
+                transcription_secs: 0.0,
+            },
+            daily_file,
+            recent: VecDeque::new(),
+        }
+    }
+
+    /// Enable or disable the daily rollup file
+    pub fn set_daily_file(&mut self, daily_file: Option<PathBuf>) {
+        self.daily_file = daily_file;
+    }
+
+    /// Count one dictation; rollup failures are logged and never propagated
+    pub fn record(&mut self, timings: &Timings, words: usize) {
+        let entry = Totals {
+            dictations: 1,
+            recorded_secs: timings.audio.as_secs_f64(),
+            words: u64::try_from(words).unwrap_or(u64::MAX),
+            transcription_secs: timings.transcribe.as_secs_f64(),
+        };
+        self.session.add(&entry);
+        if self.recent.len() == RECENT_TIMINGS {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(*timings);
+
+        if let Some(ref path) = self.daily_file
+            && let Err(e) = add_to_daily(path, &today(), &entry)
+        {
+            warn!("Failed to update daily statistics: {:#}", e);
+        }
+    }
+
+    /// Dictations completed since the app started
+    pub const fn dictations(&self) -> u64 {
+        self.session.dictations
+    }
+
+    /// Summary of this session and, when the rollup is enabled, of today
+    pub fn summary(&self) -> String {
+        let mut text = format!("This session\n{}", self.session.summary());
+        if let Some(latency) = self.latency_summary() {
+            text.push_str(&format!("\n\n{latency}"));
+        }
+        if let Some(ref path) = self.daily_file {
+            match load_daily(path) {
+                Ok(days) => {
+                    let day = today();
+                    let totals = days.get(&day).copied().unwrap_or_default();
+                    text.push_str(&format!("\n\nToday ({day})\n{}", totals.summary()));
+                }
+                Err(e) => warn!("Failed to read daily statistics: {:#}", e),
+            }
+        }
+        text
+    }
+
+    /// Average time per stage over the latest dictations, `None` before the first
+    fn latency_summary(&self) -> Option<String> {
+        let count = u32::try_from(self.recent.len()).ok().filter(|&n| n > 0)?;
+        let average = |stage: fn(&Timings) -> Duration| {
+            (self.recent.iter().map(stage).sum::<Duration>() / count).as_secs_f64()
+        };
+        Some(format!(
+            "Last {count} dictation(s), average\nModel wait: {:.1} s\nTranscription: {:.1} s\nPost-processing: {:.1} s\nTyping: {:.1} s\nRelease to text: {:.1} s",
+            average(|t| t.model_wait),
+            average(|t| t.transcribe),
+            average(|t| t.post_process),
+            average(|t| t.inject),
+            average(Timings::total)
+        ))
+    }
+}
+
+/// Read the per-day totals, treating a missing file as empty
+fn load_daily(path: &Path) -> Result<BTreeMap<String, Totals>> {
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&contents).with_context(|| format!("Invalid JSON in {}", path.display()))
+}
+
+/// Add `entry` to the totals stored for `day`
+fn add_to_daily(path: &Path, day: &str, entry: &Totals) -> Result<()> {
+    let mut days = load_daily(path)?;
+    days.entry(day.to_owned()).or_default().add(entry);
+    let json = serde_json::to_string_pretty(&days).context("Failed to encode statistics")?;
+    paths::ensure_parent(path)?;
+    std::fs::write(path, json).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Current UTC date as `YYYY-MM-DD`
+pub fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let days = i64::try_from(secs / 86_400).unwrap_or(0);
+    let (year, month, day) = civil_from_days(days);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Convert days since 1970-01-01 to a proleptic Gregorian `(year, month, day)`.
+///
+/// Uses Howard Hinnant's `civil_from_days` algorithm.
+pub const fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
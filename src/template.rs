@@ -0,0 +1,327 @@
+//! Wrapping of typed text in a template, such as `> {text}` for a journal.
+//!
+//! The template is parsed when the config loads, so a mistake is reported
+//! then instead of at the first dictation. Placeholders are written in
+//! braces, optionally followed by modifiers such as `{text|lower|noperiod}`,
+//! and `{{` or `}}` stand for a literal brace. It runs after the text
+//! pipeline, as the last step before the text is typed.
+
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::Result;
+
+use crate::history;
+
+/// One piece of a parsed template
+#[derive(Clone, PartialEq, Eq)]
+enum Part {
+    /// Text copied as written
+    Literal(String),
+    /// A placeholder and the modifiers applied to it, in order
+    Field(Field, Vec<Modifier>),
+}
+
+/// What a placeholder stands for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    /// The dictated text
+    Text,
+    /// UTC time the text is typed, ISO-8601
+    Time,
+    /// Language code the dictation was transcribed in
+    Language,
+}
+
+/// Change made to a placeholder's value, written after a `|`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Modifier {
+    /// All lowercase
+    Lower,
+    /// All uppercase
+    Upper,
+    /// Without one closing full stop; an ellipsis is kept
+    NoPeriod,
+}
+
+impl Modifier {
+    /// `value` with the modifier applied
+    fn apply(self, value: String) -> String {
+        match self {
+            Self::Lower => value.to_lowercase(),
+            Self::Upper => value.to_uppercase(),
+            Self::NoPeriod => match value.strip_suffix(['.', '。']) {
+                Some(rest) if !rest.ends_with('.') => rest.to_owned(),
+                _ => value,
+            },
+        }
+    }
+}
+
+impl FromStr for Modifier {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "lower" => Ok(Self::Lower),
+            "upper" => Ok(Self::Upper),
+            "noperiod" => Ok(Self::NoPeriod),
+            other => anyhow::bail!("unknown modifier {other:?}, use lower, upper or noperiod"),
+        }
+    }
+}
+
+/// Template typed instead of the bare text, `{text}` marking where it goes
+#[derive(Clone, PartialEq, Eq)]
+pub struct InjectTemplate {
+    /// Template as written in the config, for logs
+    source: String,
+    /// Parsed pieces in order
+    parts: Vec<Part>,
+}
+
+impl InjectTemplate {
+    /// `text` in `language` wrapped in the template
+    pub fn render(&self, text: &str, language: &str) -> String {
+        let mut rendered = String::with_capacity(self.source.len() + text.len());
+        for part in &self.parts {
+            match *part {
+                Part::Literal(ref literal) => rendered.push_str(literal),
+                Part::Field(field, ref modifiers) => {
+                    let value = match field {
+                        Field::Text => text.to_owned(),
+                        Field::Time => history::timestamp(history::now_secs()),
+                        Field::Language => language.to_owned(),
+                    };
+                    let value = modifiers
+                        .iter()
+                        .fold(value, |value, modifier| modifier.apply(value));
+                    rendered.push_str(&value);
+                }
+            }
+        }
+        rendered
+    }
+}
+
+impl FromStr for InjectTemplate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let rest = chars.as_str();
+                    let Some(end) = rest.find('}') else {
+                        anyhow::bail!("unclosed {{ in template, write {{{{ for a literal brace");
+                    };
+                    let mut pieces = rest.get(..end).unwrap_or_default().split('|');
+                    let field = match pieces.next().unwrap_or_default().trim() {
+                        "text" => Field::Text,
+                        "time" => Field::Time,
+                        "lang" | "language" => Field::Language,
+                        other => anyhow::bail!(
+                            "unknown placeholder {{{other}}}, use {{text}}, {{time}} or {{lang}}"
+                        ),
+                    };
+                    let modifiers = pieces.map(str::parse).collect::<Result<Vec<_>>>()?;
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Field(field, modifiers));
+                    chars = rest.get(end + 1..).unwrap_or_default().chars();
+                }
+                '}' => anyhow::bail!("unmatched }} in template, write }}}} for a literal brace"),
+                other => literal.push(other),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        if !parts
+            .iter()
+            .any(|part| matches!(*part, Part::Field(Field::Text, _)))
+        {
+            anyhow::bail!("template has no {{text}} placeholder, so the dictation would be lost");
+        }
+        Ok(Self {
+            source: s.to_owned(),
+            parts,
+        })
+    }
+}
+
+impl fmt::Debug for InjectTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `template` parsed and rendered for `text` in English
+    fn render(template: &str, text: &str) -> String {
+        template
+            .parse::<InjectTemplate>()
+            .expect("template should parse")
+            .render(text, "en")
+    }
+
+    /// Error message for `template`, which must not parse
+    fn error(template: &str) -> String {
+        let err = template
+            .parse::<InjectTemplate>()
+            .expect_err("template should be rejected");
+        format!("{err:#}")
+    }
+
+    #[test]
+    fn doubled_braces_are_typed_as_literal_braces() {
+        assert_eq!(
+            render("{{{text}}}", "note"),
+            "{note}",
+            "{{ and }} should type single braces around the text"
+        );
+        assert_eq!(
+            render("{{text}} {text}", "note"),
+            "{text} note",
+            "an escaped placeholder should be typed as written"
+        );
+    }
+
+    #[test]
+    fn placeholders_are_filled_in() {
+        assert_eq!(
+            render("> {text} ({lang})", "hello"),
+            "> hello (en)",
+            "text and language should be filled in"
+        );
+        assert_eq!(
+            render("{ language }: {text}", "hello"),
+            "en: hello",
+            "space inside the braces and the long name should be accepted"
+        );
+    }
+
+    #[test]
+    fn unknown_placeholder_is_rejected() {
+        let message = error("{text} {date}");
+        assert!(
+            message.contains("unknown placeholder {date}"),
+            "the error should name the placeholder: {message}"
+        );
+    }
+
+    #[test]
+    fn template_without_text_is_rejected() {
+        let message = error("[{time}]");
+        assert!(
+            message.contains("no {text} placeholder"),
+            "a template that drops the dictation should be refused: {message}"
+        );
+        assert!(
+            "{{text}}".parse::<InjectTemplate>().is_err(),
+            "an escaped {{text}} should not count as the placeholder"
+        );
+    }
+
+    #[test]
+    fn stray_braces_are_rejected() {
+        assert!(
+            error("{text").contains("unclosed"),
+            "an open brace without a close should be refused"
+        );
+        assert!(
+            error("{text} }").contains("unmatched }"),
+            "a lone closing brace should be refused"
+        );
+    }
+
+    #[test]
+    fn case_modifiers_change_the_text() {
+        assert_eq!(
+            render("{text|lower}", "Hello World"),
+            "hello world",
+            "lower should lowercase the text"
+        );
+        assert_eq!(
+            render("{text|upper}", "Hello World"),
+            "HELLO WORLD",
+            "upper should uppercase the text"
+        );
+        assert_eq!(
+            render("{lang|upper}: {text}", "hi"),
+            "EN: hi",
+            "modifiers should work on every placeholder and only on it"
+        );
+    }
+
+    #[test]
+    fn noperiod_drops_one_closing_full_stop() {
+        assert_eq!(
+            render("{text|noperiod}", "Search this."),
+            "Search this",
+            "the closing full stop should be dropped"
+        );
+        assert_eq!(
+            render("{text|noperiod}", "検索。"),
+            "検索",
+            "an ideographic full stop should be dropped too"
+        );
+        assert_eq!(
+            render("{text|noperiod}", "Wait..."),
+            "Wait...",
+            "an ellipsis should be kept"
+        );
+        assert_eq!(
+            render("{text|noperiod}", "Really?"),
+            "Really?",
+            "other closing punctuation should be kept"
+        );
+    }
+
+    #[test]
+    fn modifiers_chain_left_to_right() {
+        assert_eq!(
+            render("{ text | lower | noperiod }", "Find The Docs."),
+            "find the docs",
+            "both modifiers should apply, with spaces around them allowed"
+        );
+        assert_eq!(
+            render("{text|lower|upper}", "Mixed"),
+            "MIXED",
+            "the last case modifier should win"
+        );
+        assert_eq!(
+            render("{text|upper|lower}", "Mixed"),
+            "mixed",
+            "the last case modifier should win"
+        );
+    }
+
+    #[test]
+    fn unknown_or_empty_modifier_is_rejected() {
+        let message = error("{text|title}");
+        assert!(
+            message.contains("unknown modifier \"title\""),
+            "the error should name the modifier: {message}"
+        );
+        assert!(
+            error("{text|}").contains("unknown modifier"),
+            "an empty modifier should be refused"
+        );
+    }
+}
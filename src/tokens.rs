@@ -0,0 +1,199 @@
+//! Whisper's raw tokens of each dictation, for tooling that analyses recognition.
+//!
+//! With `whisper_dump_tokens_dir` set, every full transcription is written to
+//! one JSON file there: the tokens of each segment with their ids,
+//! probabilities and timestamps as Whisper decoded them, the text as it was
+//! delivered and the parameters of the pass. Nothing is written in strict
+//! privacy mode, and a failed write only logs a warning.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use tracing::{debug, warn};
+
+use crate::config::Config;
+use crate::history;
+use crate::paths;
+use crate::privacy::PrivacyPolicy;
+use crate::whisper::{LanguageCode, Transcript, TranscriptionTask, WhisperOptions};
+
+/// Where token files go and the engine settings they record
+#[derive(Debug)]
+pub struct TokenDump {
+    /// Folder the files are written to
+    dir: PathBuf,
+    /// Inference threads of the pass
+    threads: usize,
+    /// Context options the model was loaded with
+    options: WhisperOptions,
+}
+
+/// Contents of one token file
+#[derive(Serialize)]
+struct Record<'a> {
+    /// When the file was written, in UTC
+    time: String,
+    /// Text after filtering and any inject template, null when nothing was delivered
+    text: Option<&'a str>,
+    /// Settings of the pass
+    parameters: Parameters<'a>,
+    /// Segments in the order Whisper produced them
+    segments: Vec<SegmentRecord<'a>>,
+}
+
+/// Settings the pass ran with
+#[derive(Serialize)]
+struct Parameters<'a> {
+    /// File name of the model
+    model: String,
+    /// Language asked for, `auto` to detect it
+    language: &'a str,
+    /// Language Whisper recognised
+    detected_language: &'a str,
+    /// `transcribe` or `translate`
+    task: &'static str,
+    /// Decoding strategy
+    sampling: &'static str,
+    /// Inference threads
+    threads: usize,
+    /// Whether the model was loaded on the GPU
+    use_gpu: bool,
+    /// GPU the model was loaded on
+    gpu_device: u32,
+    /// Whether flash attention was on
+    flash_attn: bool,
+}
+
+/// One segment and the tokens it was decoded from
+#[derive(Serialize)]
+struct SegmentRecord<'a> {
+    /// Start in the audio, in milliseconds
+    start_ms: u128,
+    /// End in the audio, in milliseconds
+    end_ms: u128,
+    /// Raw text of the segment
+    text: &'a str,
+    /// Tokens of the segment, special tokens included
+    tokens: Vec<TokenRecord<'a>>,
+}
+
+/// One token as Whisper decoded it
+#[derive(Serialize)]
+struct TokenRecord<'a> {
+    /// Id in the model's vocabulary
+    id: i32,
+    /// Text of the token
+    text: &'a str,
+    /// Probability Whisper gave the token
+    probability: f32,
+    /// Start in the audio, in milliseconds
+    start_ms: u128,
+    /// End in the audio, in milliseconds
+    end_ms: u128,
+}
+
+impl TokenDump {
+    /// Dump configured in `config` for passes on `threads` threads, `None`
+    /// when it is off or strict privacy mode rules it out
+    pub fn new(config: &Config, threads: usize) -> Option<Self> {
+        let dir = config.whisper_dump_tokens_dir.as_deref()?;
+        if !PrivacyPolicy::new(config).dumps_tokens() {
+            return None;
+        }
+        Some(Self {
+            dir: paths::resolve(dir),
+            threads,
+            options: config.whisper_options(),
+        })
+    }
+
+    /// Write the tokens of `transcript` by `model`, asked for in `language`
+    /// and delivered as `text`, if at all, logging instead of failing
+    pub fn write(
+        &self,
+        model: &Path,
+        language: &LanguageCode,
+        task: TranscriptionTask,
+        transcript: &Transcript,
+        text: Option<&str>,
+    ) {
+        match self.try_write(model, language, task, transcript, text) {
+            Ok(path) => debug!("Wrote Whisper tokens to {}", path.display()),
+            Err(e) => warn!("Failed to write Whisper tokens: {:#}", e),
+        }
+    }
+
+    /// Write the token file, returning where it went
+    fn try_write(
+        &self,
+        model: &Path,
+        language: &LanguageCode,
+        task: TranscriptionTask,
+        transcript: &Transcript,
+        text: Option<&str>,
+    ) -> Result<PathBuf> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let record = Record {
+            time: history::timestamp(now.as_secs()),
+            text,
+            parameters: Parameters {
+                model: model
+                    .file_name()
+                    .unwrap_or(model.as_os_str())
+                    .to_string_lossy()
+                    .into_owned(),
+                language: language.as_str(),
+                detected_language: &transcript.language,
+                task: match task {
+                    TranscriptionTask::Transcribe => "transcribe",
+                    TranscriptionTask::Translate => "translate",
+                },
+                sampling: "greedy",
+                threads: self.threads,
+                use_gpu: self.options.use_gpu,
+                gpu_device: self.options.gpu_device,
+                flash_attn: self.options.flash_attn,
+            },
+            segments: transcript
+                .segments
+                .iter()
+                .map(|segment| SegmentRecord {
+                    start_ms: segment.start.as_millis(),
+                    end_ms: segment.end.as_millis(),
+                    text: &segment.text,
+                    tokens: segment
+                        .tokens
+                        .iter()
+                        .map(|token| TokenRecord {
+                            id: token.id,
+                            text: &token.text,
+                            probability: token.probability,
+                            start_ms: token.start.as_millis(),
+                            end_ms: token.end.as_millis(),
+                        })
+                        .collect(),
+                })
+                .collect(),
+        };
+        let json = serde_json::to_string_pretty(&record).context("Failed to encode tokens")?;
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+        let path = self.dir.join(file_name(now));
+        std::fs::write(&path, json)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+/// File name for a dump written `since_epoch`, e.g. `tokens-20240501T093000-123.json`
+fn file_name(since_epoch: Duration) -> String {
+    let stamp: String = history::timestamp(since_epoch.as_secs())
+        .chars()
+        .filter(|&c| c != '-' && c != ':' && c != 'Z')
+        .collect();
+    format!("tokens-{stamp}-{:03}.json", since_epoch.subsec_millis())
+}
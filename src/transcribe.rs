@@ -0,0 +1,160 @@
+//! Transcribing audio files from the command line.
+//!
+//! Decodes a recording such as a voice memo, converts it to the 16 kHz mono
+//! input Whisper expects and runs the configured model over it. The text is
+//! printed or written as plain text or SRT subtitles; nothing is typed.
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use rodio::decoder::DecoderError;
+use rodio::{Decoder, Source};
+
+use crate::audio::{self, WHISPER_SAMPLE_RATE};
+use crate::config::Config;
+use crate::paths;
+use crate::punctuation::Punctuation;
+use crate::spacing::Spacing;
+use crate::tokens::TokenDump;
+use crate::whisper::{Segment, TranscriptionTask, WhisperEngine};
+
+/// Containers and codecs the decoder understands, for the unsupported-format error
+const SUPPORTED_FORMATS: &str = "WAV, MP3, FLAC, Ogg Vorbis and M4A/AAC";
+
+/// Shortest audio for which transcription progress is printed
+const PROGRESS_MIN_AUDIO: Duration = Duration::from_secs(30);
+
+/// Where the transcription of a file goes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Output {
+    /// Print the text to the console
+    Stdout,
+    /// Write the text to this file
+    Text(PathBuf),
+    /// Write SRT subtitles with one cue per segment to this file
+    Subtitles(PathBuf),
+}
+
+/// Transcribe `input` with the model and language of `config`.
+pub fn run(config: &Config, input: &Path, output: &Output) -> Result<()> {
+    let samples = decode(input)?;
+    let audio_length = Duration::from_millis(
+        u64::try_from(samples.len()).unwrap_or(u64::MAX) * 1000 / u64::from(WHISPER_SAMPLE_RATE),
+    );
+    eprintln!(
+        "Decoded {} ({:.1}s of audio)",
+        input.display(),
+        audio_length.as_secs_f64()
+    );
+
+    // whisper.cpp's own log lines would bury the progress on the console
+    whisper_rs::install_logging_hooks();
+    let model_path = paths::model_file(&config.whisper_model);
+    WhisperEngine::check_model(&model_path)?;
+    eprintln!("Loading model {}", model_path.display());
+    let threads = WhisperEngine::effective_threads(config.whisper_threads);
+    let engine = WhisperEngine::load_with_options(&model_path, threads, config.whisper_options())?;
+    let dump = TokenDump::new(config, threads);
+
+    let progress = (audio_length >= PROGRESS_MIN_AUDIO).then(print_progress);
+    let mut transcript = engine.transcribe_with_progress(
+        &samples,
+        &config.whisper_language,
+        TranscriptionTask::Transcribe,
+        dump.is_some(),
+        progress,
+    )?;
+
+    Punctuation::from_pause_ms(config.punctuation_pause_ms).apply(&mut transcript);
+    let spacing = Spacing::new(&config.no_space_languages);
+    let text = transcript.text(&spacing);
+    if let Some(ref dump) = dump {
+        dump.write(
+            &model_path,
+            &config.whisper_language,
+            TranscriptionTask::Transcribe,
+            &transcript,
+            Some(&text),
+        );
+    }
+    match *output {
+        Output::Stdout => println!("{text}"),
+        Output::Text(ref path) => write(path, &format!("{text}\n"))?,
+        Output::Subtitles(ref path) => write(path, &subtitles(&transcript.segments))?,
+    }
+    Ok(())
+}
+
+/// Decode a whole audio file into Whisper input.
+fn decode(path: &Path) -> Result<Vec<f32>> {
+    let file = File::open(path).with_context(|| format!("Cannot open {}", path.display()))?;
+    let decoder = Decoder::try_from(file).map_err(|e| match e {
+        DecoderError::UnrecognizedFormat => anyhow::anyhow!(
+            "{} is not in a supported format; supported are {SUPPORTED_FORMATS}",
+            path.display()
+        ),
+        other => anyhow::anyhow!("Cannot decode {}: {other}", path.display()),
+    })?;
+    let channels = decoder.channels();
+    let sample_rate = decoder.sample_rate();
+    let samples: Vec<f32> = decoder.collect();
+    if samples.is_empty() {
+        anyhow::bail!("{} contains no audio", path.display());
+    }
+    Ok(audio::to_whisper_input(&samples, channels, sample_rate))
+}
+
+/// Progress callback printing the percentage done in steps of 5, on one line
+fn print_progress() -> Box<dyn FnMut(i32)> {
+    let mut shown = 0;
+    Box::new(move |percent| {
+        if percent >= shown + 5 || (percent == 100 && shown < 100) {
+            shown = percent;
+            eprint!("\rTranscribing... {percent}%");
+            if percent == 100 {
+                eprintln!();
+            }
+        }
+    })
+}
+
+/// Numbered SRT cues, leaving out segments without text
+fn subtitles(segments: &[Segment]) -> String {
+    segments
+        .iter()
+        .filter(|segment| !segment.text.trim().is_empty())
+        .enumerate()
+        .map(|(i, segment)| {
+            format!(
+                "{}\n{} --> {}\n{}\n",
+                i + 1,
+                srt_timestamp(segment.start),
+                srt_timestamp(segment.end),
+                segment.text.trim()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Offset formatted as SRT writes it, `HH:MM:SS,mmm`
+fn srt_timestamp(offset: Duration) -> String {
+    let millis = offset.as_millis();
+    format!(
+        "{:02}:{:02}:{:02},{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// Write `contents` to `path` and say so on the console.
+fn write(path: &Path, contents: &str) -> Result<()> {
+    std::fs::write(path, contents)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    eprintln!("Wrote {}", path.display());
+    Ok(())
+}
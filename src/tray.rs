@@ -1,12 +1,72 @@
 //! System tray icon management
 
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
 use anyhow::{Context, Result};
-use tracing::info;
+#[cfg(windows)]
+use tracing::warn;
+use tracing::{debug, info};
 use tray_icon::{
     Icon, TrayIcon, TrayIconBuilder,
-    menu::{Menu, MenuEvent, MenuItem},
+    menu::{CheckMenuItem, Menu, MenuEvent, MenuItem, PredefinedMenuItem, Submenu},
 };
 
+use crate::config::Config;
+#[cfg(windows)]
+use crate::notify;
+use crate::notify::Severity;
+use crate::wake;
+
+/// Default idle icon embedded in the binary
+const EMBEDDED_IDLE_ICON: &[u8] = include_bytes!("../assets/icons/microphone.ico");
+
+/// Default recording icon embedded in the binary
+const EMBEDDED_RECORDING_ICON: &[u8] = include_bytes!("../assets/icons/microphone-recording.ico");
+
+/// Default transcribing icon embedded in the binary
+const EMBEDDED_BUSY_ICON: &[u8] = include_bytes!("../assets/icons/microphone-busy.ico");
+
+/// Icon files of the tray states, each replaced by its embedded default when unusable
+pub struct IconFiles {
+    /// Shown while idle
+    idle: PathBuf,
+    /// Shown while recording
+    recording: PathBuf,
+    /// Shown and animated while transcribing
+    busy: PathBuf,
+}
+
+impl IconFiles {
+    /// Icons set in `config`, or the bundled ones in its assets directory
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            idle: config.asset_file(config.icon_idle.as_deref(), "icons/microphone.ico"),
+            recording: config.asset_file(
+                config.icon_recording.as_deref(),
+                "icons/microphone-recording.ico",
+            ),
+            busy: config.asset_file(None, "icons/microphone-busy.ico"),
+        }
+    }
+}
+
+/// Time each frame of the transcribing animation stays on screen
+const BUSY_FRAME_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Number of progress dots drawn on the transcribing icon, one frame per dot
+const BUSY_FRAME_COUNT: usize = 3;
+
+/// Identical notifications within this window are suppressed
+const NOTIFY_REPEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Maximum number of characters shown in the last-transcription preview
+const PREVIEW_MAX_CHARS: usize = 40;
+
+/// Maximum number of characters shown for a microphone name
+const DEVICE_NAME_MAX_CHARS: usize = 48;
+
 /// System tray icon states
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TrayState {
@@ -14,6 +74,69 @@ pub enum TrayState {
     Idle,
     /// Recording state
     Recording,
+    /// Recording for translation to English
+    Translating,
+    /// Recording latched by hold-to-lock, continues until the hotkey is pressed again
+    Latched,
+    /// Hotkey paused from the tray menu
+    Paused,
+    /// Whisper is transcribing the last recording
+    Transcribing,
+}
+
+impl TrayState {
+    /// Tooltip text shown for this state
+    pub const fn tooltip(self) -> &'static str {
+        match self {
+            Self::Idle => "Speedy STT - Idle",
+            Self::Recording => "Speedy STT - Recording",
+            Self::Translating => "Speedy STT - Recording (translate to English)",
+            Self::Latched => "Speedy STT - Recording (locked, press hotkey to stop)",
+            Self::Paused => "Speedy STT - Paused",
+            Self::Transcribing => "Speedy STT - Transcribing...",
+        }
+    }
+}
+
+/// Actions requested from the tray menu
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrayCommand {
+    /// Quit the application
+    Quit,
+    /// Pause (`true`) or resume (`false`) dictation
+    Pause(bool),
+    /// Re-read the configuration file
+    ReloadConfig,
+    /// Open the log file in the default viewer
+    OpenLog,
+    /// Show version and model information
+    About,
+    /// Show dictation statistics
+    Statistics,
+    /// Run the self-check and show its report
+    Diagnostics,
+    /// Copy the last transcription to the clipboard
+    CopyLast,
+    /// Transcribe subsequent recordings in this language
+    SetLanguage(String),
+    /// Record from the named input device, or the system default with `None`
+    SelectDevice(Option<String>),
+    /// Re-enumerate input devices for the Microphone menu
+    RescanDevices,
+    /// Switch to the named settings profile, or the defaults with `None`
+    SetProfile(Option<String>),
+    /// Show (`true`) or hide (`false`) the on-screen recording indicator
+    ShowOverlay(bool),
+    /// Open the transcription history file in the default editor
+    OpenHistory,
+    /// Type the text last delivered to the named application into it again
+    Resend(String),
+    /// Drop the last transcription and every text kept for re-sending
+    ForgetTranscriptions,
+    /// Transcribe the dictation left by a crash and copy its text
+    RecoverDictation,
+    /// Type the repeated dictation held back for confirmation
+    TypeRepeat,
 }
 
 /// System tray manager
@@ -22,22 +145,176 @@ pub struct TrayManager {
     tray: TrayIcon,
     /// Quit menu item
     quit_item: MenuItem,
+    /// Pause dictation toggle
+    pause_item: CheckMenuItem,
+    /// On-screen recording indicator toggle
+    overlay_item: CheckMenuItem,
+    /// Reload configuration menu item
+    reload_item: MenuItem,
+    /// Open log file menu item
+    log_item: MenuItem,
+    /// About menu item
+    about_item: MenuItem,
+    /// Statistics menu item
+    stats_item: MenuItem,
+    /// Run diagnostics menu item
+    diagnostics_item: MenuItem,
+    /// Disabled item previewing the last transcription
+    last_item: MenuItem,
+    /// Copy last transcription menu item
+    copy_item: MenuItem,
+    /// Open transcription history menu item, enabled when a history file is set
+    history_item: MenuItem,
+    /// Re-send submenu, rebuilt when the kept texts change
+    resend_menu: Submenu,
+    /// Re-send choices with their application names
+    resend_items: Vec<(String, MenuItem)>,
+    /// Forget recent transcriptions menu item
+    forget_item: MenuItem,
+    /// Transcribe the dictation left by a crash, enabled while there is one
+    recover_item: MenuItem,
+    /// Type the repeat held back for confirmation, enabled while there is one
+    repeat_item: MenuItem,
+    /// Language choices with their codes, empty when the submenu is hidden
+    language_items: Vec<(String, CheckMenuItem)>,
+    /// Active transcription language, shown in the idle tooltip
+    language: String,
+    /// Microphone submenu, rebuilt when devices are rescanned
+    device_menu: Submenu,
+    /// Device choices with their names, `None` for the system default
+    device_items: Vec<(Option<String>, CheckMenuItem)>,
+    /// Rescan input devices menu item
+    rescan_item: MenuItem,
+    /// Whether a microphone is connected; the idle tooltip says so when not
+    microphone_present: bool,
+    /// Profile submenu, rebuilt when the config file changes
+    profile_menu: Submenu,
+    /// Profile choices with their names, `None` for the defaults
+    profile_items: Vec<(Option<String>, CheckMenuItem)>,
+    /// Active settings profile, shown in the idle tooltip
+    profile: Option<String>,
     /// Idle icon
     idle_icon: Option<Icon>,
     /// Recording icon
     recording_icon: Option<Icon>,
+    /// Transcribing icon
+    busy_icon: Option<Icon>,
+    /// Animation frames shown while transcribing, built once so no icon handles are recreated
+    busy_frames: Vec<Icon>,
+    /// Index of the animation frame currently shown
+    busy_frame: usize,
+    /// When the current animation frame was shown
+    busy_frame_at: Instant,
+    /// State currently displayed
+    state: TrayState,
+    /// Whether balloon notifications are shown
+    notifications_enabled: bool,
+    /// Last notification shown and when, used for rate limiting
+    last_notification: Option<(String, Instant)>,
+    /// Shell ID of the tray icon, looked up on first notification
+    #[cfg(windows)]
+    icon_id: Option<u32>,
+    /// Menu clicks, forwarded by a handler that also wakes the event loop
+    menu_events: Receiver<MenuEvent>,
 }
 
 impl TrayManager {
-    /// Create new tray manager
-    pub fn new() -> Result<Self> {
+    /// Create new tray manager, offering `language_choices` in a Language submenu
+    pub fn new(
+        notifications_enabled: bool,
+        overlay_enabled: bool,
+        language: &str,
+        language_choices: &[String],
+        icons: &IconFiles,
+    ) -> Result<Self> {
+        let last_item = MenuItem::new("Last: (none)", false, None);
+        let copy_item = MenuItem::new("Copy last transcription", false, None);
+        let history_item = MenuItem::new("Open transcription history", false, None);
+        // Application entries are filled in by `set_resend_targets`
+        let resend_menu = Submenu::new("Re-send last transcription to", false);
+        let forget_item = MenuItem::new("Forget recent transcriptions", true, None);
+        let recover_item = MenuItem::new("Transcribe recovered dictation", false, None);
+        let repeat_item = MenuItem::new("Type held-back repeat", false, None);
+        let pause_item = CheckMenuItem::new("Pause dictation", true, false, None);
+        let overlay_item =
+            CheckMenuItem::new("Show recording indicator", true, overlay_enabled, None);
+        let reload_item = MenuItem::new("Reload configuration", true, None);
+        let log_item = MenuItem::new("Open log file", true, None);
+        let about_item = MenuItem::new("About", true, None);
+        let stats_item = MenuItem::new("Statistics", true, None);
+        let diagnostics_item = MenuItem::new("Run diagnostics", true, None);
         let quit_item = MenuItem::new("Quit", true, None);
         let menu = Menu::new();
-        menu.append(&quit_item).context("Failed to add quit item")?;
+        menu.append_items(&[
+            &last_item,
+            &copy_item,
+            &resend_menu,
+            &history_item,
+            &forget_item,
+            &recover_item,
+            &repeat_item,
+            &PredefinedMenuItem::separator(),
+            &pause_item,
+            &overlay_item,
+        ])
+        .context("Failed to add tray menu items")?;
+
+        // The active language is always offered, even when missing from the list
+        let mut codes = language_choices.to_vec();
+        if !codes.is_empty() && !codes.iter().any(|code| code == language) {
+            codes.insert(0, language.to_owned());
+        }
+        let language_items: Vec<(String, CheckMenuItem)> = codes
+            .into_iter()
+            .map(|code| {
+                let item = CheckMenuItem::new(language_label(&code), true, code == language, None);
+                (code, item)
+            })
+            .collect();
+        if !language_items.is_empty() {
+            let submenu = Submenu::new("Language", true);
+            for (_, item) in &language_items {
+                submenu
+                    .append(item)
+                    .context("Failed to add language menu item")?;
+            }
+            menu.append(&submenu)
+                .context("Failed to add language menu")?;
+        }
+
+        // Device entries are filled in by `set_input_devices` above this separator
+        let device_menu = Submenu::new("Microphone", true);
+        let rescan_item = MenuItem::new("Rescan devices", true, None);
+        device_menu
+            .append_items(&[&PredefinedMenuItem::separator(), &rescan_item])
+            .context("Failed to add microphone menu items")?;
+        menu.append(&device_menu)
+            .context("Failed to add microphone menu")?;
 
-        // Try to load icons (optional - will use default if not found)
-        let idle_icon = Self::load_icon("./assets/icons/microphone.ico");
-        let recording_icon = Self::load_icon("./assets/icons/microphone.ico");
+        // Profile entries are filled in by `set_profiles`
+        let profile_menu = Submenu::new("Profile", false);
+        menu.append(&profile_menu)
+            .context("Failed to add profile menu")?;
+
+        menu.append_items(&[
+            &reload_item,
+            &log_item,
+            &stats_item,
+            &diagnostics_item,
+            &about_item,
+            &PredefinedMenuItem::separator(),
+            &quit_item,
+        ])
+        .context("Failed to add tray menu items")?;
+
+        // On-disk icons override the embedded defaults; a state without any
+        // usable icon falls back to the idle icon
+        let idle_icon = Self::load_icon(&icons.idle, EMBEDDED_IDLE_ICON);
+        let recording_icon = Self::load_icon(&icons.recording, EMBEDDED_RECORDING_ICON)
+            .or_else(|| idle_icon.clone());
+        let busy_icon =
+            Self::load_icon(&icons.busy, EMBEDDED_BUSY_ICON).or_else(|| idle_icon.clone());
+        let busy_frames = Self::build_busy_frames(&icons.busy, EMBEDDED_BUSY_ICON);
 
         let mut builder = TrayIconBuilder::new()
             .with_tooltip("Speedy STT")
@@ -49,46 +326,139 @@ impl TrayManager {
         }
 
         let tray = builder.build().context("Failed to create tray icon")?;
+        let (sender, menu_events) = mpsc::channel();
+        MenuEvent::set_event_handler(Some(move |event| {
+            let _ = sender.send(event);
+            wake::wake();
+        }));
 
         info!("System tray icon created");
 
         Ok(Self {
             tray,
             quit_item,
+            pause_item,
+            overlay_item,
+            reload_item,
+            log_item,
+            about_item,
+            stats_item,
+            diagnostics_item,
+            last_item,
+            copy_item,
+            history_item,
+            resend_menu,
+            resend_items: Vec::new(),
+            forget_item,
+            recover_item,
+            repeat_item,
+            language_items,
+            language: language.to_owned(),
+            device_menu,
+            device_items: Vec::new(),
+            rescan_item,
+            microphone_present: true,
+            profile_menu,
+            profile_items: Vec::new(),
+            profile: None,
             idle_icon,
             recording_icon,
+            busy_icon,
+            busy_frames,
+            busy_frame: 0,
+            busy_frame_at: Instant::now(),
+            state: TrayState::Idle,
+            notifications_enabled,
+            last_notification: None,
+            #[cfg(windows)]
+            icon_id: None,
+            menu_events,
         })
     }
 
-    /// Load icon from file
-    fn load_icon(path: &str) -> Option<Icon> {
-        match Icon::from_path(path, None) {
+    /// Load icon from file, falling back to the embedded default
+    fn load_icon(path: &Path, embedded: &[u8]) -> Option<Icon> {
+        #[cfg(windows)]
+        let loaded = Icon::from_path(path, None).map_err(|e| e.to_string());
+        // Only Windows loads icon files itself; elsewhere the ICO is decoded here
+        #[cfg(not(windows))]
+        let loaded = std::fs::read(path)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| decode_ico(&bytes).ok_or_else(|| "not a valid ICO file".to_owned()))
+            .and_then(|(rgba, width, height)| {
+                Icon::from_rgba(rgba, width, height).map_err(|e| e.to_string())
+            });
+        match loaded {
             Ok(icon) => {
-                info!("Loaded icon: {}", path);
-                Some(icon)
-            }
-            Err(e) => {
-                info!("Could not load icon {}: {} (using default)", path, e);
-                None
+                info!("Loaded icon: {}", path.display());
+                return Some(icon);
             }
+            Err(e) => info!(
+                "Could not load icon {}: {} (using embedded default)",
+                path.display(),
+                e
+            ),
+        }
+
+        let icon = decode_ico(embedded)
+            .and_then(|(rgba, width, height)| Icon::from_rgba(rgba, width, height).ok());
+        if icon.is_none() {
+            info!("Embedded icon for {} could not be decoded", path.display());
         }
+        icon
     }
 
-    /// Update tray icon state
-    pub fn set_state(&mut self, state: TrayState) -> Result<()> {
-        let tooltip = match state {
-            TrayState::Idle => "Speedy STT - Idle",
-            TrayState::Recording => "Speedy STT - Recording",
+    /// Build the transcribing animation by drawing progress dots onto the busy icon
+    ///
+    /// Returns no frames when the icon cannot be decoded, which disables the animation.
+    fn build_busy_frames(path: &Path, embedded: &[u8]) -> Vec<Icon> {
+        let decoded = std::fs::read(path)
+            .ok()
+            .and_then(|bytes| decode_ico(&bytes))
+            .or_else(|| decode_ico(embedded));
+        let Some((rgba, width, height)) = decoded else {
+            info!("Transcribing animation disabled: icon could not be decoded");
+            return Vec::new();
         };
 
-        self.tray
-            .set_tooltip(Some(tooltip))
-            .context("Failed to set tooltip")?;
+        (0..BUSY_FRAME_COUNT)
+            .filter_map(|lit| {
+                let frame = draw_progress_dots(&rgba, width, lit);
+                Icon::from_rgba(frame, width, height).ok()
+            })
+            .collect()
+    }
 
-        // Update icon if available
+    /// Update tray icon state
+    pub fn set_state(&mut self, state: TrayState) -> Result<()> {
+        if state == TrayState::Idle {
+            let details = match self.profile {
+                Some(ref profile) => format!("{}, {profile}", self.language),
+                None => self.language.clone(),
+            };
+            let status = if self.microphone_present {
+                state.tooltip()
+            } else {
+                "Speedy STT - No microphone"
+            };
+            self.set_tooltip(&format!("{status} ({details})"))?;
+        } else {
+            self.set_tooltip(state.tooltip())?;
+        }
+        self.state = state;
+
+        // Update icon if available; the animation restarts from its first frame
+        self.busy_frame = 0;
+        self.busy_frame_at = Instant::now();
         let icon = match state {
-            TrayState::Idle => &self.idle_icon,
-            TrayState::Recording => &self.recording_icon,
+            TrayState::Idle | TrayState::Paused => &self.idle_icon,
+            TrayState::Transcribing => match self.busy_frames.first() {
+                Some(frame) => &Some(frame.clone()),
+                None => &self.busy_icon,
+            },
+            TrayState::Recording | TrayState::Translating | TrayState::Latched => {
+                &self.recording_icon
+            }
         };
 
         if let Some(icon) = icon {
@@ -100,11 +470,417 @@ impl TrayManager {
         Ok(())
     }
 
-    /// Check if quit was clicked
-    pub fn should_quit(&self) -> bool {
-        if let Ok(event) = MenuEvent::receiver().try_recv() {
-            return event.id == self.quit_item.id();
+    /// Advance the transcribing animation when its frame is due
+    ///
+    /// Cheap to call on every loop iteration; does nothing in any other state.
+    pub fn tick(&mut self) -> Result<()> {
+        if self.state != TrayState::Transcribing
+            || self.busy_frames.len() < 2
+            || self.busy_frame_at.elapsed() < BUSY_FRAME_INTERVAL
+        {
+            return Ok(());
+        }
+        self.busy_frame = (self.busy_frame + 1) % self.busy_frames.len();
+        self.busy_frame_at = Instant::now();
+        if let Some(frame) = self.busy_frames.get(self.busy_frame) {
+            self.tray.set_icon(Some(frame.clone()))?;
+        }
+        Ok(())
+    }
+
+    /// When the next animation frame is due, `None` unless animating
+    pub fn next_frame(&self) -> Option<Instant> {
+        (self.state == TrayState::Transcribing && self.busy_frames.len() >= 2)
+            .then(|| self.busy_frame_at + BUSY_FRAME_INTERVAL)
+    }
+
+    /// Take the next menu action, if any item was clicked
+    pub fn poll_command(&self) -> Option<TrayCommand> {
+        let event = self.menu_events.try_recv().ok()?;
+        if event.id == self.quit_item.id() {
+            Some(TrayCommand::Quit)
+        } else if event.id == self.pause_item.id() {
+            Some(TrayCommand::Pause(self.pause_item.is_checked()))
+        } else if event.id == self.overlay_item.id() {
+            Some(TrayCommand::ShowOverlay(self.overlay_item.is_checked()))
+        } else if event.id == self.reload_item.id() {
+            Some(TrayCommand::ReloadConfig)
+        } else if event.id == self.log_item.id() {
+            Some(TrayCommand::OpenLog)
+        } else if event.id == self.about_item.id() {
+            Some(TrayCommand::About)
+        } else if event.id == self.stats_item.id() {
+            Some(TrayCommand::Statistics)
+        } else if event.id == self.diagnostics_item.id() {
+            Some(TrayCommand::Diagnostics)
+        } else if event.id == self.copy_item.id() {
+            Some(TrayCommand::CopyLast)
+        } else if event.id == self.history_item.id() {
+            Some(TrayCommand::OpenHistory)
+        } else if event.id == self.forget_item.id() {
+            Some(TrayCommand::ForgetTranscriptions)
+        } else if event.id == self.recover_item.id() {
+            Some(TrayCommand::RecoverDictation)
+        } else if event.id == self.repeat_item.id() {
+            Some(TrayCommand::TypeRepeat)
+        } else if event.id == self.rescan_item.id() {
+            Some(TrayCommand::RescanDevices)
+        } else if let Some((name, _)) = self
+            .device_items
+            .iter()
+            .find(|(_, item)| event.id == item.id())
+        {
+            Some(TrayCommand::SelectDevice(name.clone()))
+        } else if let Some((app, _)) = self
+            .resend_items
+            .iter()
+            .find(|(_, item)| event.id == item.id())
+        {
+            Some(TrayCommand::Resend(app.clone()))
+        } else if let Some((name, _)) = self
+            .profile_items
+            .iter()
+            .find(|(_, item)| event.id == item.id())
+        {
+            Some(TrayCommand::SetProfile(name.clone()))
+        } else {
+            self.language_items
+                .iter()
+                .find(|(_, item)| event.id == item.id())
+                .map(|(code, _)| TrayCommand::SetLanguage(code.clone()))
+        }
+    }
+
+    /// State currently displayed
+    pub const fn state(&self) -> TrayState {
+        self.state
+    }
+
+    /// Replace the tooltip text without changing the icon or state
+    pub fn set_tooltip(&self, tooltip: &str) -> Result<()> {
+        self.tray
+            .set_tooltip(Some(tooltip))
+            .context("Failed to set tooltip")
+    }
+
+    /// Enable or disable balloon notifications
+    pub const fn set_notifications_enabled(&mut self, enabled: bool) {
+        self.notifications_enabled = enabled;
+    }
+
+    /// Show a balloon notification, skipping repeats of the same message within a short window
+    pub fn notify(&mut self, title: &str, body: &str, severity: Severity) {
+        if !self.notifications_enabled {
+            return;
+        }
+        let key = format!("{title}\n{body}");
+        if let Some((ref last, at)) = self.last_notification
+            && *last == key
+            && at.elapsed() < NOTIFY_REPEAT_INTERVAL
+        {
+            debug!("Suppressed repeated notification: {}", title);
+            return;
+        }
+        self.last_notification = Some((key, Instant::now()));
+
+        #[cfg(windows)]
+        {
+            let hwnd = self.tray.window_handle();
+            if self.icon_id.is_none() {
+                self.icon_id = notify::find_icon_id(hwnd);
+            }
+            let Some(id) = self.icon_id else {
+                warn!("Tray icon not found, cannot show notification: {}", title);
+                return;
+            };
+            if let Err(e) = notify::show_balloon(hwnd, id, title, body, severity) {
+                warn!("Failed to show notification: {}", e);
+            }
+        }
+        #[cfg(not(windows))]
+        info!("Notification ({:?}): {} - {}", severity, title, body);
+    }
+
+    /// Show a truncated preview of the last transcription, or clear it with `None`
+    pub fn set_last_transcription(&self, text: Option<&str>) {
+        match text {
+            Some(text) => {
+                self.last_item
+                    .set_text(format!("Last: {}", truncate_preview(text)));
+                self.copy_item.set_enabled(true);
+            }
+            None => {
+                self.last_item.set_text("Last: (none)");
+                self.copy_item.set_enabled(false);
+            }
+        }
+    }
+
+    /// Rebuild the re-send menu from `apps`, newest first
+    ///
+    /// The menu is disabled while no text is kept for re-sending.
+    pub fn set_resend_targets(&mut self, apps: &[String]) -> Result<()> {
+        for (_, item) in self.resend_items.drain(..) {
+            self.resend_menu
+                .remove(&item)
+                .context("Failed to remove re-send menu item")?;
+        }
+        self.resend_menu.set_enabled(!apps.is_empty());
+        for app in apps {
+            let item = MenuItem::new(truncate_label(app, PREVIEW_MAX_CHARS), true, None);
+            self.resend_menu
+                .append(&item)
+                .context("Failed to add re-send menu item")?;
+            self.resend_items.push((app.clone(), item));
+        }
+        Ok(())
+    }
+
+    /// Check the active language in the submenu and refresh the idle tooltip
+    pub fn set_language(&mut self, language: &str) -> Result<()> {
+        language.clone_into(&mut self.language);
+        for (code, item) in &self.language_items {
+            item.set_checked(code == language);
+        }
+        if self.state == TrayState::Idle {
+            self.set_state(TrayState::Idle)?;
+        }
+        Ok(())
+    }
+
+    /// Rebuild the Microphone menu from `names`, checking `selected`
+    ///
+    /// A selected device that is not currently available stays listed so the
+    /// choice is visible, but is marked as missing.
+    pub fn set_input_devices(&mut self, names: &[String], selected: Option<&str>) -> Result<()> {
+        for (_, item) in self.device_items.drain(..) {
+            self.device_menu
+                .remove(&item)
+                .context("Failed to remove microphone menu item")?;
+        }
+
+        let mut choices: Vec<(Option<String>, String)> = vec![(None, "Default device".to_owned())];
+        choices.extend(names.iter().map(|name| {
+            (
+                Some(name.clone()),
+                truncate_label(name, DEVICE_NAME_MAX_CHARS),
+            )
+        }));
+        if let Some(selected) = selected
+            && !names.iter().any(|name| name == selected)
+        {
+            let label = format!(
+                "{} (not found)",
+                truncate_label(selected, DEVICE_NAME_MAX_CHARS)
+            );
+            choices.push((Some(selected.to_owned()), label));
+        }
+
+        for (position, (name, label)) in choices.into_iter().enumerate() {
+            let item = CheckMenuItem::new(label, true, name.as_deref() == selected, None);
+            self.device_menu
+                .insert(&item, position)
+                .context("Failed to add microphone menu item")?;
+            self.device_items.push((name, item));
+        }
+        Ok(())
+    }
+
+    /// Show in the idle tooltip whether a microphone is connected
+    pub fn set_microphone_present(&mut self, present: bool) -> Result<()> {
+        self.microphone_present = present;
+        if self.state == TrayState::Idle {
+            self.set_state(TrayState::Idle)?;
+        }
+        Ok(())
+    }
+
+    /// Check the selected device in the Microphone menu, `None` for the default
+    pub fn set_selected_device(&self, selected: Option<&str>) {
+        for (name, item) in &self.device_items {
+            item.set_checked(name.as_deref() == selected);
+        }
+    }
+
+    /// Rebuild the Profile menu from `names`, checking `active`
+    ///
+    /// The menu is disabled when the config file defines no profiles.
+    pub fn set_profiles(&mut self, names: &[String], active: Option<&str>) -> Result<()> {
+        for (_, item) in self.profile_items.drain(..) {
+            self.profile_menu
+                .remove(&item)
+                .context("Failed to remove profile menu item")?;
+        }
+
+        self.profile_menu.set_enabled(!names.is_empty());
+        if !names.is_empty() {
+            let choices = std::iter::once((None, "Default".to_owned())).chain(
+                names
+                    .iter()
+                    .map(|name| (Some(name.clone()), truncate_label(name, PREVIEW_MAX_CHARS))),
+            );
+            for (name, label) in choices {
+                let item = CheckMenuItem::new(label, true, name.as_deref() == active, None);
+                self.profile_menu
+                    .append(&item)
+                    .context("Failed to add profile menu item")?;
+                self.profile_items.push((name, item));
+            }
+        }
+        self.set_profile(active)
+    }
+
+    /// Check the active profile and refresh the idle tooltip, `None` for the defaults
+    pub fn set_profile(&mut self, active: Option<&str>) -> Result<()> {
+        self.profile = active.map(str::to_owned);
+        for (name, item) in &self.profile_items {
+            item.set_checked(name.as_deref() == active);
+        }
+        if self.state == TrayState::Idle {
+            self.set_state(TrayState::Idle)?;
+        }
+        Ok(())
+    }
+
+    /// Sync the pause toggle's check mark with the actual hotkey state
+    pub fn set_paused_checked(&self, paused: bool) {
+        self.pause_item.set_checked(paused);
+    }
+
+    /// Enable the recovered dictation item while one waits to be transcribed
+    pub fn set_recovery_available(&self, available: bool) {
+        self.recover_item.set_enabled(available);
+    }
+
+    /// Enable the held-back repeat item while one waits for confirmation
+    pub fn set_repeat_pending(&self, pending: bool) {
+        self.repeat_item.set_enabled(pending);
+    }
+
+    /// Enable the history item when a transcription history file is configured,
+    /// naming today's file when there is one per day
+    pub fn set_history_available(&self, available: bool, daily: bool) {
+        self.history_item.set_enabled(available);
+        self.history_item.set_text(if daily {
+            "Open today's transcription history"
+        } else {
+            "Open transcription history"
+        });
+    }
+
+    /// Sync the indicator toggle's check mark with the configured setting
+    pub fn set_overlay_checked(&self, enabled: bool) {
+        self.overlay_item.set_checked(enabled);
+    }
+}
+
+/// Menu label for a Whisper language code.
+fn language_label(code: &str) -> String {
+    if code == "auto" {
+        "Auto-detect".to_owned()
+    } else {
+        code.to_owned()
+    }
+}
+
+/// Shorten text to [`PREVIEW_MAX_CHARS`] for the last-transcription preview.
+fn truncate_preview(text: &str) -> String {
+    truncate_label(text, PREVIEW_MAX_CHARS)
+}
+
+/// Shorten text to `max_chars` on a character boundary, adding an ellipsis.
+///
+/// Menu item text treats `&` as an accelerator prefix, so it is escaped.
+fn truncate_label(text: &str, max_chars: usize) -> String {
+    let single_line = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut preview: String = single_line.chars().take(max_chars).collect();
+    if single_line.chars().count() > max_chars {
+        preview.push('\u{2026}');
+    }
+    preview.replace('&', "&&")
+}
+
+/// Copy a square RGBA icon and draw a row of progress dots along its bottom edge.
+///
+/// The dot at index `lit` is opaque white and the others are translucent grey.
+fn draw_progress_dots(rgba: &[u8], size: u32, lit: usize) -> Vec<u8> {
+    let mut frame = rgba.to_vec();
+    let size = i64::from(size);
+    let radius = (size / 12).max(1);
+    let spacing = radius * 3;
+    let center_y = size - radius - 1;
+
+    for dot in 0..BUSY_FRAME_COUNT {
+        let offset = i64::try_from(dot).unwrap_or(0) - 1;
+        let center_x = size / 2 + offset * spacing;
+        let color = if dot == lit {
+            [255, 255, 255, 255]
+        } else {
+            [160, 160, 160, 160]
+        };
+        for y in (center_y - radius)..=(center_y + radius) {
+            for x in (center_x - radius)..=(center_x + radius) {
+                let (dx, dy) = (x - center_x, y - center_y);
+                if dx * dx + dy * dy > radius * radius || x < 0 || y < 0 || x >= size {
+                    continue;
+                }
+                let Ok(index) = usize::try_from((y * size + x) * 4) else {
+                    continue;
+                };
+                if let Some(pixel) = frame.get_mut(index..index + 4) {
+                    pixel.copy_from_slice(&color);
+                }
+            }
         }
-        false
     }
+    frame
+}
+
+/// Decode the largest 32-bit bitmap entry of an ICO file into RGBA pixels.
+///
+/// Returns `(rgba, width, height)`, or `None` for malformed data and for
+/// PNG-compressed or paletted entries, which the embedded icons do not use.
+fn decode_ico(bytes: &[u8]) -> Option<(Vec<u8>, u32, u32)> {
+    let read_u16 = |at: usize| -> Option<u16> {
+        Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+    };
+    let read_u32 = |at: usize| -> Option<u32> {
+        Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+    };
+
+    // ICONDIR is 6 bytes, followed by one 16-byte ICONDIRENTRY per image
+    let count = usize::from(read_u16(4)?);
+    let (offset, size) = (0..count)
+        .filter_map(|i| {
+            let entry = 6 + i * 16;
+            let size = u32::from(*bytes.get(entry)?);
+            let bpp = read_u16(entry + 6)?;
+            let offset = usize::try_from(read_u32(entry + 12)?).ok()?;
+            (bpp == 32).then_some((offset, if size == 0 { 256 } else { size }))
+        })
+        .max_by_key(|&(_, size)| size)?;
+
+    // BITMAPINFOHEADER: the stored height covers both the color and mask bitmaps
+    let header_len = usize::try_from(read_u32(offset)?).ok()?;
+    let bit_count = read_u16(offset + 14)?;
+    if header_len < 40 || bit_count != 32 {
+        return None;
+    }
+    let row_len = usize::try_from(size).ok()? * 4;
+    let pixels_start = offset + header_len;
+    let pixels = bytes.get(pixels_start..pixels_start + row_len * row_len / 4)?;
+
+    // Rows are stored bottom-up in BGRA order
+    let rgba = pixels
+        .chunks_exact(row_len)
+        .rev()
+        .flat_map(|row| {
+            row.chunks_exact(4).flat_map(|px| match *px {
+                [b, g, r, a] => [r, g, b, a],
+                _ => [0; 4],
+            })
+        })
+        .collect();
+
+    Some((rgba, size, size))
 }
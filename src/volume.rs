@@ -1,150 +1,634 @@
 //! Audio ducking via the Windows Audio Session API (WASAPI).
 //!
 //! Enumerates all active audio sessions on the default render endpoint,
-//! fades them to silence when recording starts, and restores them when
-//! recording stops. A 500ms linear fade is applied in both directions.
+//! fades them to a fraction of their volume when recording starts, and
+//! restores them when recording stops. Fade durations and curves are
+//! configurable per direction. A long-lived worker thread owns COM and the
+//! cached render endpoints, refreshed when devices change, so fades never
+//! delay recording; it also rescans for sessions that start playing
+//! mid-recording.
 
+#![cfg_attr(
+    not(windows),
+    allow(
+        dead_code,
+        reason = "only the worker's no-op fallback is used off Windows"
+    )
+)]
+
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(windows)]
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::JoinHandle;
 use std::time::Duration;
 
-use anyhow::{Context, Result};
-use tracing::{debug, error, info, warn};
+#[cfg(windows)]
+use anyhow::Context;
+use anyhow::Result;
+#[cfg(windows)]
+use tracing::{debug, info};
+use tracing::{error, warn};
 
+#[cfg(windows)]
+use windows::Win32::Foundation::PROPERTYKEY;
+#[cfg(windows)]
+use windows::Win32::Media::Audio::Endpoints::IAudioMeterInformation;
 #[cfg(windows)]
 use windows::Win32::Media::Audio::{
-    AudioSessionStateExpired, DEVICE_STATE_ACTIVE, IAudioSessionControl, IAudioSessionControl2,
-    IAudioSessionEnumerator, IAudioSessionManager2, IMMDeviceCollection, IMMDeviceEnumerator,
+    AudioSessionStateExpired, DEVICE_STATE, DEVICE_STATE_ACTIVE, EDataFlow, ERole,
+    IAudioSessionControl, IAudioSessionControl2, IAudioSessionEnumerator, IAudioSessionManager2,
+    IMMDeviceCollection, IMMDeviceEnumerator, IMMNotificationClient, IMMNotificationClient_Impl,
     ISimpleAudioVolume, MMDeviceEnumerator, eRender,
 };
 #[cfg(windows)]
-use windows::Win32::System::Com::{
-    CLSCTX_ALL, COINIT_APARTMENTTHREADED, CoCreateInstance, CoInitializeEx, CoUninitialize,
-};
+use windows::Win32::System::Com::{CLSCTX_ALL, CoCreateInstance, CoTaskMemFree};
+#[cfg(windows)]
+use windows::core::{GUID, HRESULT, PCWSTR, PWSTR, implement};
+
+#[cfg(windows)]
+use crate::com::ComApartment;
+use crate::error::SttError;
+#[cfg(windows)]
+use crate::foreground;
+use crate::journal;
 #[cfg(windows)]
-use windows::core::HRESULT;
+use crate::journal::JournalEntry;
+#[cfg(windows)]
+use crate::media::MediaPauser;
 #[cfg(windows)]
 use windows::core::Interface;
 
-/// Duration of the fade-out and fade-in transitions.
-const FADE_DURATION: Duration = Duration::from_millis(500);
-
 /// Interval between volume steps during a fade.
 const FADE_STEP_INTERVAL: Duration = Duration::from_millis(10);
 
+/// Interval at which sessions started during a recording are looked for.
+const RESCAN_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Peak level below which a session counts as silent.
+const SILENCE_PEAK: f32 = 1e-4;
+
+/// Number of peak meter readings taken before deciding a session is silent.
+const PEAK_SAMPLES: u32 = 3;
+
+/// Interval between peak meter readings, so a short pause in music is not mistaken for silence.
+const PEAK_SAMPLE_INTERVAL: Duration = Duration::from_millis(25);
+
+/// Quietest volume used as the reference point of exponential fades (-60 dB).
+const EXPONENTIAL_FLOOR: f32 = 0.001;
+
+/// Shape of the volume change over the course of a fade.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FadeCurve {
+    /// Constant change in volume per step
+    Linear,
+    /// Constant change in loudness (decibels) per step, which sounds even
+    Exponential,
+    /// Slow start and end with a faster middle
+    SCurve,
+}
+
+impl FadeCurve {
+    /// Volume between `from` and `to` after `progress` (0..=1) of a fade.
+    fn volume(self, from: f32, to: f32, progress: f32) -> f32 {
+        match self {
+            Self::Linear => (to - from).mul_add(progress, from),
+            Self::Exponential => {
+                let start = from.max(EXPONENTIAL_FLOOR);
+                let end = to.max(EXPONENTIAL_FLOOR);
+                let volume = start * (end / start).powf(progress);
+                // Land exactly on the target, which may be below the floor
+                if progress >= 1.0 { to } else { volume }
+            }
+            Self::SCurve => {
+                let eased = progress * progress * 2.0f32.mul_add(-progress, 3.0);
+                (to - from).mul_add(eased, from)
+            }
+        }
+    }
+}
+
+impl FromStr for FadeCurve {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "linear" => Ok(Self::Linear),
+            "exponential" => Ok(Self::Exponential),
+            "s-curve" | "scurve" => Ok(Self::SCurve),
+            other => anyhow::bail!("unknown curve {other:?}, use linear, exponential or s-curve"),
+        }
+    }
+}
+
+/// How other applications' audio is kept out of the recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuckMode {
+    /// Lower the volume of other sessions
+    Duck,
+    /// Pause media players through the media transport controls
+    Pause,
+    /// Pause media players and duck the sessions of everything else
+    Both,
+}
+
+impl DuckMode {
+    /// Whether media players are paused.
+    const fn pauses(self) -> bool {
+        matches!(self, Self::Pause | Self::Both)
+    }
+
+    /// Whether session volumes are lowered.
+    const fn ducks(self) -> bool {
+        matches!(self, Self::Duck | Self::Both)
+    }
+}
+
+impl FromStr for DuckMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "duck" => Ok(Self::Duck),
+            "pause" => Ok(Self::Pause),
+            "both" => Ok(Self::Both),
+            other => anyhow::bail!("unknown mode {other:?}, use duck, pause or both"),
+        }
+    }
+}
+
+/// How far and how quickly other applications are ducked.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuckSettings {
+    /// Whether players are paused, ducked or both
+    pub mode: DuckMode,
+    /// Fraction of their volume sessions keep while ducked
+    pub level: f32,
+    /// Length of the fade when recording starts; zero is instant
+    pub fade_out: Duration,
+    /// Length of the fade when recording stops; zero is instant
+    pub fade_in: Duration,
+    /// Shape of both fades
+    pub curve: FadeCurve,
+    /// Lowercased executable names whose sessions are never ducked
+    pub excluded: Vec<String>,
+    /// Skip sessions that are not currently producing sound
+    pub only_playing: bool,
+}
+
 /// Direction of a volume fade.
 #[non_exhaustive]
 enum FadeDirection {
-    /// Fade volume from original level down to silence.
+    /// Fade volume from original level down to the ducked level.
     Out,
-    /// Fade volume from silence back to the original level.
+    /// Fade volume from the ducked level back to the original level.
     In,
 }
 
+/// Event context passed with every volume change made by this app.
+///
+/// Volume notifications carry it back, so handlers can tell our own changes
+/// from those made by the user or other software.
+#[cfg(windows)]
+pub const VOLUME_EVENT_CONTEXT: GUID = GUID::from_u128(0x41b0_7c3a_891b_409b_97ac_6217_eb5b_9691);
+
+/// Largest difference from the ducked volume still treated as untouched.
+const VOLUME_TOLERANCE: f32 = 0.005;
+
+/// Whether a ducked session should be faded back in.
+#[cfg(windows)]
+enum RestoreCheck {
+    /// Still at the ducked level
+    Restore,
+    /// Volume was changed to this level while ducked
+    Adjusted(f32),
+    /// The session ended or its volume can no longer be read
+    Expired,
+}
+
 /// Stored state of a single ducked audio session.
 struct DuckedSession {
     /// COM interface used to get and set the session's master volume.
     #[cfg(windows)]
     volume_control: ISimpleAudioVolume,
+    /// Session control, used to check whether the session expired.
+    #[cfg(windows)]
+    control: IAudioSessionControl2,
     /// Volume level recorded before ducking began.
     original_volume: f32,
+    /// Volume level held while ducked.
+    ducked_volume: f32,
+    /// Peak meter of the session, used to tell whether it is playing.
+    #[cfg(windows)]
+    meter: Option<IAudioMeterInformation>,
+    /// Session description for log messages.
+    label: String,
+    /// Session instance identifier, used to recognize the session on a rescan.
+    id: Option<String>,
+    /// Owning process ID, 0 for sandboxed sessions.
+    pid: u32,
+    /// Executable name of the owning process, recorded in the journal.
+    process: Option<String>,
 }
 
-/// `RPC_E_CHANGED_MODE`: COM is already initialized on this thread with a
-/// different apartment model. The thread is usable; we must not uninitialize.
-#[cfg(windows)]
-const RPC_E_CHANGED_MODE: HRESULT = HRESULT(0x80010106_u32 as i32);
-
 /// Manages fading other applications' audio during recording.
 ///
 /// Created via [`VolumeDucker::duck`], which enumerates all active sessions
 /// and fades them out. Restoring is done with [`VolumeDucker::restore`].
 /// Implements [`Drop`] for best-effort restore on panic or early return.
+/// COM must be initialized on the calling thread for its whole lifetime.
 pub struct VolumeDucker {
     /// Sessions that were ducked and need to be restored.
     sessions: Vec<DuckedSession>,
-    /// Whether this instance initialized COM and must call CoUninitialize on drop.
-    #[cfg(windows)]
-    com_initialized: bool,
+    /// Identifiers of sessions already ducked or skipped, not evaluated again.
+    seen: HashSet<String>,
+    /// Whether the sessions were already restored, so drop does not fade again.
+    restored: bool,
+    /// Fade durations and curve.
+    settings: DuckSettings,
 }
 
 impl VolumeDucker {
-    /// Enumerate all other audio sessions and fade them to silence.
+    /// Enumerate all other audio sessions on `managers` and fade them to the
+    /// configured level.
     ///
     /// Returns a `VolumeDucker` that holds the original volumes for restoration.
     /// Skips the current process, system sound sessions, and dead sessions.
     #[cfg(windows)]
-    pub fn duck() -> Result<Self> {
-        // SAFETY: CoInitializeEx initializes COM for this thread. S_OK means we
-        // initialized it fresh; S_FALSE means already initialized with the same
-        // apartment (both require a matching CoUninitialize). RPC_E_CHANGED_MODE
-        // means the thread already has COM in a different apartment — we can still
-        // use COM but must not call CoUninitialize since we did not initialize it.
-        let com_hr = unsafe { CoInitializeEx(None, COINIT_APARTMENTTHREADED) };
-        info!("CoInitializeEx HRESULT: {:?}", com_hr);
-        let com_initialized = if com_hr == RPC_E_CHANGED_MODE {
-            info!("COM already initialized with different apartment; proceeding without re-init");
-            false
-        } else {
-            com_hr.ok().context("Failed to initialize COM")?;
-            true
-        };
-
-        let sessions = collect_sessions()?;
+    pub fn duck(managers: &[IAudioSessionManager2], settings: DuckSettings) -> Self {
+        let mut seen = HashSet::new();
+        let sessions = collect_sessions(managers, &settings, &mut seen);
         info!("Audio ducking: found {} session(s) to duck", sessions.len());
 
         let ducker = Self {
             sessions,
-            com_initialized,
+            seen,
+            restored: false,
+            settings,
         };
-        ducker.fade(FadeDirection::Out)?;
-        Ok(ducker)
+        ducker.record(&[]);
+        ducker.fade(&ducker.sessions, FadeDirection::Out);
+        ducker
     }
 
-    /// Restore all ducked sessions to their original volume.
+    /// Duck sessions that appeared, or started playing, since the last scan.
     ///
-    /// Per-session errors are logged but do not abort the restore pass.
+    /// New sessions get the same fade-out and are restored with the rest.
+    /// Sessions without an instance identifier are only considered at the start.
     #[cfg(windows)]
-    pub fn restore(&self) -> Result<()> {
-        self.fade(FadeDirection::In)
+    pub fn duck_new_sessions(&mut self, managers: &[IAudioSessionManager2]) {
+        if self.restored {
+            return;
+        }
+        let new = collect_sessions(managers, &self.settings, &mut self.seen);
+        if new.is_empty() {
+            return;
+        }
+        info!(
+            "Audio ducking: {} session(s) started during recording",
+            new.len()
+        );
+        self.record(&new);
+        self.fade(&new, FadeDirection::Out);
+        self.sessions.extend(new);
     }
 
-    /// Apply a linear fade in the given direction across all ducked sessions.
+    /// Restore ducked sessions to their original volume.
     ///
+    /// Sessions whose volume no longer matches the ducked level were adjusted
+    /// during the recording and are left as they are; expired sessions are
+    /// skipped. Per-session errors are logged but do not abort the restore pass.
+    #[cfg(windows)]
+    pub fn restore(&mut self) -> Result<()> {
+        if self.restored {
+            return Ok(());
+        }
+        self.restored = true;
+
+        let (mut adjusted, mut expired) = (0, 0);
+        let sessions: Vec<DuckedSession> = std::mem::take(&mut self.sessions)
+            .into_iter()
+            .filter(|session| match restore_check(session) {
+                RestoreCheck::Restore => true,
+                RestoreCheck::Adjusted(volume) => {
+                    info!(
+                        "{}: volume changed to {:.3} during recording, leaving it",
+                        session.label, volume
+                    );
+                    adjusted += 1;
+                    false
+                }
+                RestoreCheck::Expired => {
+                    debug!("{}: expired during recording", session.label);
+                    expired += 1;
+                    false
+                }
+            })
+            .collect();
+
+        self.fade(&sessions, FadeDirection::In);
+        info!(
+            "Audio ducking: restored {}, skipped {} user-adjusted, {} expired",
+            sessions.len(),
+            adjusted,
+            expired
+        );
+        self.sessions = sessions;
+        journal::clear();
+        Ok(())
+    }
+
+    /// Write the journal for the ducked sessions plus `new`, before they are faded.
+    ///
+    /// A failure only means volumes cannot be recovered after a crash, so it
+    /// is logged and ducking goes ahead.
+    #[cfg(windows)]
+    fn record(&self, new: &[DuckedSession]) {
+        let entries: Vec<JournalEntry> = self
+            .sessions
+            .iter()
+            .chain(new)
+            .map(|session| JournalEntry {
+                id: session.id.clone(),
+                process: session.process.clone(),
+                pid: session.pid,
+                original_volume: session.original_volume,
+                ducked_volume: session.ducked_volume,
+            })
+            .collect();
+        if entries.is_empty() {
+            return;
+        }
+        if let Err(e) = journal::write(&entries) {
+            warn!(
+                "Failed to write ducking journal, volumes cannot be recovered after a crash: {:#}",
+                e
+            );
+        }
+    }
+
+    /// Fade `sessions` in the given direction with the configured curve.
+    ///
+    /// A duration shorter than one step applies the final volume at once.
     /// Per-session volume errors are logged and skipped; the session's app
     /// may have exited during recording.
     #[cfg(windows)]
-    fn fade(&self, direction: FadeDirection) -> Result<()> {
-        let steps = (FADE_DURATION.as_millis() / FADE_STEP_INTERVAL.as_millis()).max(1);
+    fn fade(&self, sessions: &[DuckedSession], direction: FadeDirection) {
+        let duration = match direction {
+            FadeDirection::Out => self.settings.fade_out,
+            FadeDirection::In => self.settings.fade_in,
+        };
+        let steps = (duration.as_millis() / FADE_STEP_INTERVAL.as_millis()).max(1);
 
-        #[allow(
-            clippy::as_conversions,
-            reason = "controlled cast within known range for linear interpolation"
-        )]
+        let mut failed = vec![false; sessions.len()];
         for step in 1..=steps {
+            #[allow(
+                clippy::as_conversions,
+                reason = "controlled cast within known range for interpolation"
+            )]
             let t = step as f32 / steps as f32;
-            for session in &self.sessions {
-                let vol = match direction {
-                    FadeDirection::Out => session.original_volume * (1.0 - t),
-                    FadeDirection::In => session.original_volume * t,
+            for (session, failed) in sessions.iter().zip(failed.iter_mut()) {
+                if *failed {
+                    continue;
+                }
+                let (from, to) = match direction {
+                    FadeDirection::Out => (session.original_volume, session.ducked_volume),
+                    FadeDirection::In => (session.ducked_volume, session.original_volume),
                 };
+                let vol = self.settings.curve.volume(from, to, t).clamp(0.0, 1.0);
                 // SAFETY: SetMasterVolume is a straightforward COM setter. We
-                // pass a valid f32 in [0.0, 1.0] and a null event context (no
-                // notification needed). Errors mean the session's app exited.
+                // pass a valid f32 in [0.0, 1.0] and a pointer to a static
+                // event context GUID. Errors mean the session's app exited.
                 match unsafe {
                     session
                         .volume_control
-                        .SetMasterVolume(vol, std::ptr::null())
+                        .SetMasterVolume(vol, &VOLUME_EVENT_CONTEXT)
                 } {
                     Ok(()) => debug!("SetMasterVolume({:.3}) ok", vol),
-                    Err(e) => warn!("SetMasterVolume({:.3}) failed: {:?}", vol, e),
+                    Err(e) => {
+                        // A session that failed once has usually gone away; skip it for the rest of the fade
+                        warn!(
+                            "{}: SetMasterVolume({:.3}) failed, skipping it for this fade: {:?}",
+                            session.label, vol, e
+                        );
+                        *failed = true;
+                    }
                 }
             }
-            std::thread::sleep(FADE_STEP_INTERVAL);
+            if failed.iter().all(|&failed| failed) {
+                break;
+            }
+            if step < steps {
+                std::thread::sleep(FADE_STEP_INTERVAL);
+            }
         }
+    }
+}
 
-        Ok(())
+/// Request handled by the ducking worker.
+enum DuckCommand {
+    /// Pause players and fade other sessions out, restoring any still ducked from before
+    Duck(DuckSettings),
+    /// Fade ducked sessions back in and resume paused players
+    Restore,
+}
+
+/// Ducking worker kept alive across recordings.
+///
+/// The thread initializes COM once and caches the render endpoints, so
+/// [`Self::duck`] and [`Self::restore`] only send it a message. Dropping the
+/// handle, including while unwinding from a panic, restores and waits.
+pub struct DuckingWorker {
+    /// Requests to the worker, `None` once it is told to exit
+    commands: Option<Sender<DuckCommand>>,
+    /// Receives errors that kept the worker from ducking
+    errors: Receiver<SttError>,
+    /// Worker thread, joined on drop
+    handle: Option<JoinHandle<()>>,
+    /// Set from a duck request until the worker finished restoring
+    ducked: Arc<AtomicBool>,
+}
+
+impl DuckingWorker {
+    /// Start the worker thread; it stays idle until asked to duck
+    ///
+    /// `ducked` is raised for every duck request and cleared once the audio is restored.
+    pub fn start(ducked: Arc<AtomicBool>) -> Self {
+        let (command_tx, command_rx) = mpsc::channel();
+        let (error_tx, error_rx) = mpsc::channel();
+        let worker_ducked = Arc::clone(&ducked);
+        let handle = std::thread::Builder::new()
+            .name("audio-ducking".to_owned())
+            .spawn(move || {
+                #[cfg(windows)]
+                run_worker(&command_rx, &error_tx, &worker_ducked);
+                // Nothing to duck elsewhere; requests are taken until the handle is dropped
+                #[cfg(not(windows))]
+                {
+                    drop(error_tx);
+                    for _ in command_rx {}
+                }
+                worker_ducked.store(false, Ordering::Relaxed);
+            })
+            .map_err(|e| warn!("Failed to start audio ducking thread: {}", e))
+            .ok();
+
+        Self {
+            commands: Some(command_tx),
+            errors: error_rx,
+            handle,
+            ducked,
+        }
+    }
+
+    /// Fade other applications' audio out in the background
+    pub fn duck(&self, settings: DuckSettings) {
+        // Raised here rather than on the worker so sounds right after this call see it
+        self.ducked.store(true, Ordering::Relaxed);
+        self.send(DuckCommand::Duck(settings));
+    }
+
+    /// Fade the ducked audio back in without waiting for the fade to finish
+    pub fn restore(&self) {
+        self.send(DuckCommand::Restore);
+    }
+
+    /// Why the last request to duck failed, reported once
+    pub fn take_error(&self) -> Option<SttError> {
+        self.errors.try_recv().ok()
+    }
+
+    /// Queue `command`; the worker only stops once the handle is dropped
+    fn send(&self, command: DuckCommand) {
+        if let Some(ref commands) = self.commands
+            && commands.send(command).is_err()
+        {
+            warn!("Audio ducking thread is not running");
+        }
+    }
+}
+
+impl Drop for DuckingWorker {
+    /// Restore and wait, so audio is never left ducked when the app exits.
+    fn drop(&mut self) {
+        // Closing the channel tells the worker to restore and exit
+        self.commands = None;
+        if let Some(handle) = self.handle.take()
+            && handle.join().is_err()
+        {
+            error!("Audio ducking thread panicked");
+        }
     }
 }
 
+/// Serve requests until the channel closes, then restore and exit.
+///
+/// While sessions are ducked the worker wakes every [`RESCAN_INTERVAL`] to
+/// duck sessions that started playing since. A panic unwinds through the
+/// [`VolumeDucker`], whose drop restores the volumes.
+#[cfg(windows)]
+fn run_worker(commands: &Receiver<DuckCommand>, errors: &Sender<SttError>, ducked: &AtomicBool) {
+    // Declared first so COM outlives the endpoints and sessions below. The
+    // worker has no message loop, so it joins the MTA, where WASAPI objects
+    // and the device listener's callbacks need no marshaling
+    let _com = match ComApartment::enter_multithreaded() {
+        Ok(com) => com,
+        Err(e) => {
+            error!("Failed to duck audio: {:#}", e);
+            let _ = errors.send(SttError::Ducking(format!("{e:#}")));
+            return;
+        }
+    };
+    let mut endpoints: Option<Endpoints> = None;
+    let mut ducker: Option<VolumeDucker> = None;
+    let mut pauser: Option<MediaPauser> = None;
+
+    loop {
+        let command = if ducker.is_some() {
+            match commands.recv_timeout(RESCAN_INTERVAL) {
+                Ok(command) => Some(command),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        } else {
+            match commands.recv() {
+                Ok(command) => Some(command),
+                Err(_) => break,
+            }
+        };
+
+        match command {
+            Some(DuckCommand::Duck(mut settings)) => {
+                restore(ducker.take(), pauser.take());
+                if settings.mode.pauses() {
+                    match MediaPauser::pause() {
+                        Ok(paused) => {
+                            // Paused players may still sound briefly; never duck them as well
+                            settings.excluded.extend(paused.paused_apps());
+                            pauser = Some(paused);
+                        }
+                        Err(e) => {
+                            error!("Failed to pause media: {:#}", e);
+                            let _ = errors.send(SttError::Ducking(format!("{e:#}")));
+                        }
+                    }
+                }
+                if !settings.mode.ducks() {
+                    ducked.store(pauser.is_some(), Ordering::Relaxed);
+                    continue;
+                }
+                match session_managers(&mut endpoints) {
+                    Ok(managers) => ducker = Some(VolumeDucker::duck(&managers, settings)),
+                    Err(e) => {
+                        error!("Failed to duck audio: {:#}", e);
+                        let _ = errors.send(SttError::Ducking(format!("{e:#}")));
+                    }
+                }
+                ducked.store(ducker.is_some() || pauser.is_some(), Ordering::Relaxed);
+            }
+            Some(DuckCommand::Restore) => {
+                restore(ducker.take(), pauser.take());
+                ducked.store(false, Ordering::Relaxed);
+            }
+            None => {
+                if let Some(ref mut ducker) = ducker {
+                    match session_managers(&mut endpoints) {
+                        Ok(managers) => ducker.duck_new_sessions(&managers),
+                        Err(e) => warn!("Failed to rescan audio sessions: {:#}", e),
+                    }
+                }
+            }
+        }
+    }
+    restore(ducker.take(), pauser.take());
+}
+
+/// Fade the sessions held by `ducker` back in and resume the players held by `pauser`.
+#[cfg(windows)]
+fn restore(ducker: Option<VolumeDucker>, pauser: Option<MediaPauser>) {
+    if let Some(mut ducker) = ducker
+        && let Err(e) = ducker.restore()
+    {
+        error!("Failed to restore audio: {}", e);
+    }
+    if let Some(mut pauser) = pauser {
+        pauser.resume();
+    }
+}
+
+/// Session managers of the active render endpoints, creating the cache on first use.
+#[cfg(windows)]
+fn session_managers(endpoints: &mut Option<Endpoints>) -> Result<Vec<IAudioSessionManager2>> {
+    let endpoints = match *endpoints {
+        Some(ref mut endpoints) => endpoints,
+        None => endpoints.insert(Endpoints::new()?),
+    };
+    endpoints.managers()
+}
+
 #[cfg(windows)]
 impl Drop for VolumeDucker {
     /// Best-effort restore on abnormal exit (e.g. panic).
@@ -154,28 +638,145 @@ impl Drop for VolumeDucker {
         if let Err(e) = self.restore() {
             error!("Failed to restore audio volumes on drop: {}", e);
         }
-        // SAFETY: Balances the CoInitializeEx call in duck(), but only when we
-        // actually initialized COM (not when RPC_E_CHANGED_MODE was returned).
-        if self.com_initialized {
-            unsafe { CoUninitialize() };
+    }
+}
+
+/// Render endpoints and their session managers, cached between recordings.
+///
+/// A registered notification client flags device changes; the cache is
+/// rebuilt the next time it is used after one.
+#[cfg(windows)]
+struct Endpoints {
+    /// Device enumerator the notification client is registered with
+    enumerator: IMMDeviceEnumerator,
+    /// Registered notification client, unregistered on drop
+    listener: IMMNotificationClient,
+    /// Set by the notification client when the cache is out of date
+    changed: Arc<AtomicBool>,
+    /// Session manager of each active render endpoint
+    managers: Vec<IAudioSessionManager2>,
+}
+
+#[cfg(windows)]
+impl Endpoints {
+    /// Create the device enumerator and listen for device changes.
+    fn new() -> Result<Self> {
+        let enumerator = device_enumerator()?;
+        let changed = Arc::new(AtomicBool::new(true));
+        let listener: IMMNotificationClient = DeviceListener {
+            changed: Arc::clone(&changed),
+        }
+        .into();
+        // SAFETY: the listener is a valid COM object; it is unregistered in
+        // drop before the enumerator is released.
+        unsafe { enumerator.RegisterEndpointNotificationCallback(&listener) }
+            .context("Failed to register for audio device notifications")?;
+
+        Ok(Self {
+            enumerator,
+            listener,
+            changed,
+            managers: Vec::new(),
+        })
+    }
+
+    /// Session managers of the active render endpoints, reloaded after a device change.
+    fn managers(&mut self) -> Result<Vec<IAudioSessionManager2>> {
+        if self.changed.swap(false, Ordering::AcqRel) {
+            match load_session_managers(&self.enumerator) {
+                Ok(managers) => self.managers = managers,
+                Err(e) => {
+                    self.changed.store(true, Ordering::Release);
+                    return Err(e);
+                }
+            }
         }
+        Ok(self.managers.clone())
     }
 }
 
-/// Enumerate audio sessions across all active render endpoints.
+#[cfg(windows)]
+impl Drop for Endpoints {
+    fn drop(&mut self) {
+        // SAFETY: the listener was registered with this enumerator in new().
+        if let Err(e) = unsafe {
+            self.enumerator
+                .UnregisterEndpointNotificationCallback(&self.listener)
+        } {
+            warn!("Failed to unregister audio device notifications: {}", e);
+        }
+    }
+}
+
+/// Notification client that marks the endpoint cache as out of date.
 ///
-/// Returns a list of [`DuckedSession`] values ready to be faded, one per
-/// active session that passes the filter in [`try_duck_session`].
-#[cfg(windows)]
-fn collect_sessions() -> Result<Vec<DuckedSession>> {
-    // SAFETY: CoCreateInstance requires COM to be initialized (done in duck()).
-    // MMDeviceEnumerator is a well-known CLSID with no additional invariants.
-    let enumerator: IMMDeviceEnumerator =
-        unsafe { CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL) }
-            .context("Failed to create IMMDeviceEnumerator")?;
-
-    // Enumerate all active render endpoints so we catch apps (e.g. Chrome) that
-    // output to a non-default device.
+/// Called on system threads, so it only sets a flag.
+#[cfg(windows)]
+#[implement(IMMNotificationClient)]
+struct DeviceListener {
+    /// Shared with [`Endpoints`]
+    changed: Arc<AtomicBool>,
+}
+
+#[cfg(windows)]
+impl DeviceListener {
+    /// Flag the cached endpoints for a reload.
+    fn mark_changed(&self) {
+        self.changed.store(true, Ordering::Release);
+    }
+}
+
+#[cfg(windows)]
+#[allow(
+    non_snake_case,
+    reason = "method names are defined by the COM interface"
+)]
+impl IMMNotificationClient_Impl for DeviceListener_Impl {
+    fn OnDeviceStateChanged(&self, _: &PCWSTR, _: DEVICE_STATE) -> windows::core::Result<()> {
+        self.mark_changed();
+        Ok(())
+    }
+
+    fn OnDeviceAdded(&self, _: &PCWSTR) -> windows::core::Result<()> {
+        self.mark_changed();
+        Ok(())
+    }
+
+    fn OnDeviceRemoved(&self, _: &PCWSTR) -> windows::core::Result<()> {
+        self.mark_changed();
+        Ok(())
+    }
+
+    fn OnDefaultDeviceChanged(
+        &self,
+        _: EDataFlow,
+        _: ERole,
+        _: &PCWSTR,
+    ) -> windows::core::Result<()> {
+        self.mark_changed();
+        Ok(())
+    }
+
+    fn OnPropertyValueChanged(&self, _: &PCWSTR, _: &PROPERTYKEY) -> windows::core::Result<()> {
+        Ok(())
+    }
+}
+
+/// Create the endpoint enumerator; COM must be initialized on the calling thread.
+#[cfg(windows)]
+fn device_enumerator() -> Result<IMMDeviceEnumerator> {
+    // SAFETY: CoCreateInstance requires COM to be initialized, which callers
+    // guarantee. MMDeviceEnumerator is a well-known CLSID with no additional invariants.
+    unsafe { CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL) }
+        .context("Failed to create IMMDeviceEnumerator")
+}
+
+/// Activate the session manager of every active render endpoint.
+///
+/// All endpoints are included so apps (e.g. Chrome) that output to a
+/// non-default device are ducked too; endpoints that fail are logged and skipped.
+#[cfg(windows)]
+fn load_session_managers(enumerator: &IMMDeviceEnumerator) -> Result<Vec<IAudioSessionManager2>> {
     // SAFETY: EnumAudioEndpoints is a standard COM query with no extra invariants.
     let devices: IMMDeviceCollection =
         unsafe { enumerator.EnumAudioEndpoints(eRender, DEVICE_STATE_ACTIVE) }
@@ -185,9 +786,7 @@ fn collect_sessions() -> Result<Vec<DuckedSession>> {
     let device_count = unsafe { devices.GetCount() }.context("Failed to get device count")?;
     info!("Active render endpoint count: {}", device_count);
 
-    let own_pid = std::process::id();
-    let mut sessions = Vec::new();
-
+    let mut managers = Vec::new();
     for d in 0..device_count {
         // SAFETY: index d is within [0, device_count) as returned by GetCount.
         let device = match unsafe { devices.Item(d) } {
@@ -199,15 +798,194 @@ fn collect_sessions() -> Result<Vec<DuckedSession>> {
         };
 
         // SAFETY: Activate is a standard COM interface activation call.
-        let session_manager: IAudioSessionManager2 =
-            match unsafe { device.Activate(CLSCTX_ALL, None) } {
-                Ok(sm) => sm,
-                Err(e) => {
-                    warn!("Failed to activate session manager for device {}: {}", d, e);
-                    continue;
-                }
+        match unsafe { device.Activate::<IAudioSessionManager2>(CLSCTX_ALL, None) } {
+            Ok(sm) => managers.push(sm),
+            Err(e) => warn!("Failed to activate session manager for device {}: {}", d, e),
+        }
+    }
+    Ok(managers)
+}
+
+/// Restore volumes left ducked by a previous run that was killed.
+///
+/// Each journal entry is matched to a live session by its instance
+/// identifier, then by process ID and executable name, then by executable
+/// name alone, as Windows keeps the volume of an app across restarts. A
+/// volume is only raised while it is still between the ducked and original
+/// level; the journal is removed afterwards either way.
+#[cfg(windows)]
+pub fn restore_from_journal() {
+    let entries = match journal::read() {
+        Ok(Some(entries)) => entries,
+        Ok(None) => return,
+        Err(e) => {
+            warn!("Discarding ducking journal: {:#}", e);
+            journal::clear();
+            return;
+        }
+    };
+    warn!(
+        "Previous run left {} session(s) ducked, restoring",
+        entries.len()
+    );
+    match restore_entries(entries) {
+        Ok(unmatched) => {
+            for entry in unmatched {
+                warn!(
+                    "{}: session not found, could not restore volume {:.3}",
+                    journal_label(&entry),
+                    entry.original_volume
+                );
+            }
+        }
+        Err(e) => error!(
+            "Failed to restore volumes from the ducking journal: {:#}",
+            e
+        ),
+    }
+    journal::clear();
+}
+
+/// The ducking journal can only be written on Windows.
+#[cfg(not(windows))]
+pub fn restore_from_journal() {
+    journal::clear();
+}
+
+/// Restore every live session matching an entry, returning entries without a match.
+#[cfg(windows)]
+fn restore_entries(mut entries: Vec<JournalEntry>) -> Result<Vec<JournalEntry>> {
+    // Declared first so COM outlives the interfaces below
+    let _com = ComApartment::enter()?;
+    let managers = load_session_managers(&device_enumerator()?)?;
+
+    for manager in &managers {
+        // SAFETY: GetSessionEnumerator is a standard COM query.
+        let Ok(session_enum) = (unsafe { manager.GetSessionEnumerator() }) else {
+            continue;
+        };
+        // SAFETY: GetCount is a simple getter with no invariants.
+        let count = unsafe { session_enum.GetCount() }.unwrap_or(0);
+        for i in 0..count {
+            if entries.is_empty() {
+                return Ok(entries);
+            }
+            // SAFETY: index i is within [0, count) as returned by GetCount.
+            let Ok(control) = (unsafe { session_enum.GetSession(i) }) else {
+                continue;
+            };
+            let Ok(control2) = control.cast::<IAudioSessionControl2>() else {
+                continue;
+            };
+            // SAFETY: GetSessionInstanceIdentifier is a simple COM getter
+            // returning a CoTaskMemAlloc'd string, freed by session_string.
+            let id = session_string(unsafe { control2.GetSessionInstanceIdentifier() });
+            // SAFETY: GetProcessId is a simple getter. Returns 0 for sandboxed processes.
+            let pid = unsafe { control2.GetProcessId() }.unwrap_or(0);
+            let process = if pid == 0 {
+                None
+            } else {
+                foreground::process_name(pid)
+            };
+            let Some(index) = journal_match(&entries, id.as_deref(), pid, process.as_deref())
+            else {
+                continue;
             };
+            let entry = entries.swap_remove(index);
+            if let Ok(volume_control) = control2.cast::<ISimpleAudioVolume>() {
+                restore_entry(&entry, &volume_control);
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// Index of the entry describing a live session, by the strongest identity that matches.
+#[cfg(windows)]
+fn journal_match(
+    entries: &[JournalEntry],
+    id: Option<&str>,
+    pid: u32,
+    process: Option<&str>,
+) -> Option<usize> {
+    let same_process = |entry: &JournalEntry| {
+        process.is_some_and(|name| {
+            entry
+                .process
+                .as_deref()
+                .is_some_and(|recorded| recorded.eq_ignore_ascii_case(name))
+        })
+    };
+    id.and_then(|id| {
+        entries
+            .iter()
+            .position(|entry| entry.id.as_deref() == Some(id))
+    })
+    .or_else(|| {
+        entries
+            .iter()
+            .position(|entry| pid != 0 && entry.pid == pid && same_process(entry))
+    })
+    .or_else(|| entries.iter().position(same_process))
+}
+
+/// Raise a matched session back to its recorded volume unless it was changed since.
+#[cfg(windows)]
+fn restore_entry(entry: &JournalEntry, volume_control: &ISimpleAudioVolume) {
+    let label = journal_label(entry);
+    // SAFETY: GetMasterVolume is a simple getter.
+    let Ok(volume) = (unsafe { volume_control.GetMasterVolume() }) else {
+        warn!("{}: could not read volume", label);
+        return;
+    };
+    if volume >= entry.original_volume - VOLUME_TOLERANCE {
+        info!("{}: already back at {:.3}", label, volume);
+        return;
+    }
+    if volume < entry.ducked_volume - VOLUME_TOLERANCE {
+        info!(
+            "{}: volume changed to {:.3} since, leaving it",
+            label, volume
+        );
+        return;
+    }
+    // SAFETY: SetMasterVolume is a simple COM setter; the recorded volume was
+    // read from the same API and lies in [0.0, 1.0].
+    match unsafe {
+        volume_control.SetMasterVolume(entry.original_volume.clamp(0.0, 1.0), &VOLUME_EVENT_CONTEXT)
+    } {
+        Ok(()) => info!(
+            "{}: restored volume from {:.3} to {:.3}",
+            label, volume, entry.original_volume
+        ),
+        Err(e) => warn!("{}: failed to restore volume: {}", label, e),
+    }
+}
 
+/// Description of a journal entry for log messages.
+#[cfg(windows)]
+fn journal_label(entry: &JournalEntry) -> String {
+    let name = entry.process.as_deref().unwrap_or("Sandboxed session");
+    format!("{name} (PID={})", entry.pid)
+}
+
+/// Enumerate audio sessions across the given render endpoints.
+///
+/// Returns a list of [`DuckedSession`] values ready to be faded to the
+/// configured level, one per active session that passes the filter in
+/// [`try_duck_session`]. Sessions whose identifier is in `seen` are skipped
+/// silently; every other identifier is added, except for sessions skipped
+/// only because they were not playing, which are checked again next time.
+#[cfg(windows)]
+fn collect_sessions(
+    managers: &[IAudioSessionManager2],
+    settings: &DuckSettings,
+    seen: &mut HashSet<String>,
+) -> Vec<DuckedSession> {
+    let own_pid = std::process::id();
+    let mut sessions = Vec::new();
+
+    for (d, session_manager) in managers.iter().enumerate() {
         // SAFETY: GetSessionEnumerator is a standard COM query.
         let session_enum: IAudioSessionEnumerator =
             match unsafe { session_manager.GetSessionEnumerator() } {
@@ -226,7 +1004,7 @@ fn collect_sessions() -> Result<Vec<DuckedSession>> {
                 continue;
             }
         };
-        info!("Device {}: {} session(s)", d, count);
+        debug!("Device {}: {} session(s)", d, count);
 
         for i in 0..count {
             // SAFETY: index i is within [0, count) as returned by GetCount.
@@ -237,19 +1015,93 @@ fn collect_sessions() -> Result<Vec<DuckedSession>> {
                     continue;
                 }
             };
-            if let Some(session) = try_duck_session(control, own_pid, i) {
+            let id = control
+                .cast::<IAudioSessionControl2>()
+                .ok()
+                // SAFETY: GetSessionInstanceIdentifier is a simple COM getter
+                // returning a CoTaskMemAlloc'd string, freed by session_string.
+                .and_then(|control2| {
+                    session_string(unsafe { control2.GetSessionInstanceIdentifier() })
+                });
+            if let Some(ref id) = id
+                && !seen.insert(id.clone())
+            {
+                continue;
+            }
+            if let Some(mut session) = try_duck_session(control, own_pid, i, settings) {
+                session.id = id;
                 sessions.push(session);
             }
         }
     }
 
-    Ok(sessions)
+    if settings.only_playing {
+        sessions = keep_playing(sessions, seen);
+    }
+    for session in &sessions {
+        info!(
+            "{}: ducking from {:.3} to {:.3}",
+            session.label, session.original_volume, session.ducked_volume
+        );
+    }
+    sessions
+}
+
+/// Drop sessions whose peak meter stayed silent over a few readings, and
+/// forget them in `seen` so a later scan notices when they start playing.
+///
+/// All sessions are sampled together, so the check takes about
+/// [`PEAK_SAMPLES`] × [`PEAK_SAMPLE_INTERVAL`] regardless of their number.
+/// Sessions without a meter are kept.
+#[cfg(windows)]
+fn keep_playing(sessions: Vec<DuckedSession>, seen: &mut HashSet<String>) -> Vec<DuckedSession> {
+    let mut peaks = vec![0.0f32; sessions.len()];
+    for sample in 0..PEAK_SAMPLES {
+        if sample > 0 {
+            std::thread::sleep(PEAK_SAMPLE_INTERVAL);
+        }
+        for (peak, session) in peaks.iter_mut().zip(&sessions) {
+            let Some(ref meter) = session.meter else {
+                *peak = f32::INFINITY;
+                continue;
+            };
+            // SAFETY: GetPeakValue is a simple COM getter.
+            match unsafe { meter.GetPeakValue() } {
+                Ok(value) => *peak = peak.max(value),
+                Err(e) => {
+                    warn!("Failed to read peak for {}: {}", session.label, e);
+                    *peak = f32::INFINITY;
+                }
+            }
+        }
+    }
+
+    sessions
+        .into_iter()
+        .zip(peaks)
+        .filter(|&(ref session, peak)| {
+            let playing = peak >= SILENCE_PEAK;
+            if !playing {
+                // Logged at debug level, as silent sessions are checked on every rescan
+                debug!(
+                    "{}: skipping, not playing (peak={:.5})",
+                    session.label, peak
+                );
+                if let Some(ref id) = session.id {
+                    seen.remove(id);
+                }
+            }
+            playing
+        })
+        .map(|(session, _)| session)
+        .collect()
 }
 
 /// Attempt to build a [`DuckedSession`] from a raw session control.
 ///
 /// Returns `None` if the session should be skipped (own process, system
-/// sounds, expired session, already silent, or missing COM interfaces).
+/// sounds, excluded executable, expired session, already at or below the
+/// ducked level, or missing COM interfaces).
 /// PID=0 is NOT treated as expired: sandboxed processes (e.g. Chrome's audio
 /// renderer) legitimately report PID=0 due to security restrictions.
 #[cfg(windows)]
@@ -257,6 +1109,7 @@ fn try_duck_session(
     control: IAudioSessionControl,
     own_pid: u32,
     idx: i32,
+    settings: &DuckSettings,
 ) -> Option<DuckedSession> {
     // Skip expired sessions by state, not PID.
     // SAFETY: GetState is a simple COM getter with no invariants.
@@ -293,7 +1146,9 @@ fn try_duck_session(
         }
     };
 
-    // Skip our own process.
+    // Skip our own process, which includes the feedback sounds; rescans come
+    // through here too, so a feedback stream opened after the first scan is
+    // never ducked either.
     if pid == own_pid {
         info!("Session {}: skipping own process (PID={})", idx, pid);
         return None;
@@ -309,6 +1164,16 @@ fn try_duck_session(
         return None;
     }
 
+    if !settings.excluded.is_empty()
+        && let Some(name) = excluded_match(&control2, pid, &settings.excluded)
+    {
+        info!(
+            "Session {}: skipping excluded process {} (PID={})",
+            idx, name, pid
+        );
+        return None;
+    }
+
     // SAFETY: cast is a standard COM QI call.
     let volume_control: ISimpleAudioVolume = match control2.cast() {
         Ok(v) => v,
@@ -327,19 +1192,217 @@ fn try_duck_session(
         }
     };
 
-    // Skip sessions already at zero to avoid a fade from 0 -> 0 -> 0.
-    if original_volume == 0.0 {
-        info!("Session {}: already silent, skipping", idx);
+    // Skip sessions that would not get any quieter, including silent ones
+    let ducked_volume = original_volume * settings.level.clamp(0.0, 1.0);
+    if original_volume <= ducked_volume {
+        info!(
+            "Session {}: already at or below ducked level, skipping",
+            idx
+        );
         return None;
     }
 
-    info!(
-        "Session {}: ducking (PID={}, original_volume={:.3}, state={:?})",
+    debug!(
+        "Session {}: eligible for ducking (PID={}, original_volume={:.3}, state={:?})",
         idx, pid, original_volume, state
     );
 
+    // SAFETY: cast is a standard COM QI call.
+    let meter = control2
+        .cast::<IAudioMeterInformation>()
+        .map_err(|e| debug!("Session {} has no peak meter: {}", idx, e))
+        .ok();
+    let process = if pid == 0 {
+        None
+    } else {
+        foreground::process_name(pid)
+    };
+
     Some(DuckedSession {
         volume_control,
+        control: control2,
         original_volume,
+        ducked_volume,
+        meter,
+        label: format!("Session {idx} (PID={pid})"),
+        id: None,
+        pid,
+        process,
     })
 }
+
+/// Decide whether `session` is restored, by its state and current volume.
+#[cfg(windows)]
+fn restore_check(session: &DuckedSession) -> RestoreCheck {
+    // SAFETY: GetState is a simple COM getter with no invariants.
+    match unsafe { session.control.GetState() } {
+        Ok(state) if state != AudioSessionStateExpired => {}
+        _ => return RestoreCheck::Expired,
+    }
+    // SAFETY: GetMasterVolume is a simple getter.
+    match unsafe { session.volume_control.GetMasterVolume() } {
+        Ok(volume) if (volume - session.ducked_volume).abs() <= VOLUME_TOLERANCE => {
+            RestoreCheck::Restore
+        }
+        Ok(volume) => RestoreCheck::Adjusted(volume),
+        Err(_) => RestoreCheck::Expired,
+    }
+}
+
+/// Name under which a session matches the exclusion list, if it does.
+///
+/// Sessions are identified by their process's executable name. Sandboxed
+/// sessions report PID 0 and cannot be opened, so their display name and the
+/// executable in their icon path are compared instead.
+#[cfg(windows)]
+fn excluded_match(
+    control: &IAudioSessionControl2,
+    pid: u32,
+    excluded: &[String],
+) -> Option<String> {
+    let is_excluded = |name: &str| {
+        let name = name.to_lowercase();
+        let stem = name.strip_suffix(".exe").unwrap_or(&name);
+        excluded
+            .iter()
+            .any(|entry| *entry == name || entry.strip_suffix(".exe").unwrap_or(entry) == stem)
+    };
+
+    if pid != 0 {
+        let name = foreground::process_name(pid)?;
+        debug!("Session PID {} resolved to {}", pid, name);
+        return is_excluded(&name).then_some(name);
+    }
+
+    // SAFETY: GetDisplayName and GetIconPath are simple COM getters returning
+    // CoTaskMemAlloc'd strings, which session_string copies and frees.
+    let display_name = session_string(unsafe { control.GetDisplayName() });
+    // SAFETY: as above.
+    let icon_path = session_string(unsafe { control.GetIconPath() });
+    // Icon paths look like `C:\path\app.exe,-101`
+    let icon_file = icon_path.as_deref().and_then(|path| {
+        let file = path.split(',').next()?.rsplit(['\\', '/']).next()?;
+        (!file.is_empty()).then(|| file.to_owned())
+    });
+    debug!(
+        "Sandboxed session: display name {:?}, icon {:?}",
+        display_name, icon_file
+    );
+    [display_name, icon_file]
+        .into_iter()
+        .flatten()
+        .find(|name| is_excluded(name))
+}
+
+/// Copy and free a string returned by an audio session getter; empty strings are `None`.
+#[cfg(windows)]
+fn session_string(result: windows::core::Result<PWSTR>) -> Option<String> {
+    let value = result.ok()?;
+    if value.is_null() {
+        return None;
+    }
+    // SAFETY: the getter returned a valid null-terminated string, read once
+    // before it is freed with the allocator that created it.
+    let text = unsafe { value.to_string() }.ok();
+    // SAFETY: the string was allocated with CoTaskMemAlloc by the getter.
+    unsafe { CoTaskMemFree(Some(value.0.cast_const().cast())) };
+    text.filter(|text| !text.trim().is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Duck and restore requests sent by each stress test
+    const CYCLES: usize = 500;
+
+    /// Working set the stress test may gain, for allocator and page noise
+    #[cfg(windows)]
+    const MEMORY_GROWTH_LIMIT: u64 = 16 * 1024 * 1024;
+
+    /// Ducking that leaves volumes as they are and pauses nothing
+    fn inaudible() -> DuckSettings {
+        DuckSettings {
+            mode: DuckMode::Duck,
+            level: 1.0,
+            fade_out: Duration::ZERO,
+            fade_in: Duration::ZERO,
+            curve: FadeCurve::Linear,
+            excluded: Vec::new(),
+            only_playing: false,
+        }
+    }
+
+    /// Start a worker, duck and restore `CYCLES` times and drop it
+    fn duck_in_a_tight_loop(ducked: &Arc<AtomicBool>) {
+        let worker = DuckingWorker::start(Arc::clone(ducked));
+        for _ in 0..CYCLES {
+            worker.duck(inaudible());
+            worker.restore();
+        }
+    }
+
+    #[test]
+    fn a_tight_duck_and_restore_loop_ends_restored() {
+        let ducked = Arc::new(AtomicBool::new(false));
+        let done = Arc::new(AtomicBool::new(false));
+        // The feedback player reads the flag while the main thread changes it
+        let reader = {
+            let ducked = Arc::clone(&ducked);
+            let done = Arc::clone(&done);
+            std::thread::spawn(move || {
+                while !done.load(Ordering::Relaxed) {
+                    std::hint::black_box(ducked.load(Ordering::Relaxed));
+                    std::thread::yield_now();
+                }
+            })
+        };
+        duck_in_a_tight_loop(&ducked);
+        done.store(true, Ordering::Relaxed);
+        reader.join().expect("reader finished");
+        assert!(
+            !ducked.load(Ordering::Relaxed),
+            "audio still counted as ducked after the worker stopped"
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn a_tight_duck_and_restore_loop_does_not_leak_handles_or_memory() {
+        use windows::Win32::System::Threading::{GetCurrentProcess, GetProcessHandleCount};
+
+        use crate::memory;
+
+        /// Handles the process holds
+        fn handles() -> u32 {
+            let mut count = 0;
+            // SAFETY: the pseudo handle of the current process needs no closing
+            // and `count` outlives the call.
+            unsafe { GetProcessHandleCount(GetCurrentProcess(), &raw mut count) }
+                .expect("handle count read");
+            count
+        }
+
+        let ducked = Arc::new(AtomicBool::new(false));
+        // COM, the endpoints and the thread pool set themselves up on first use
+        duck_in_a_tight_loop(&ducked);
+        let before = handles();
+        let memory_before = memory::working_set().expect("working set read");
+        duck_in_a_tight_loop(&ducked);
+        duck_in_a_tight_loop(&ducked);
+        let after = handles();
+        let memory_after = memory::working_set().expect("working set read");
+        assert!(
+            after <= before + 32,
+            "handles grew from {before} to {after} over {} cycles",
+            CYCLES * 2
+        );
+        assert!(
+            memory_after <= memory_before + MEMORY_GROWTH_LIMIT,
+            "working set grew from {} MiB to {} MiB over {} cycles",
+            memory::mb(memory_before),
+            memory::mb(memory_after),
+            CYCLES * 2
+        );
+    }
+}
@@ -0,0 +1,58 @@
+//! Sleeping and waking the main event loop.
+//!
+//! The loop blocks until a window message arrives or its next timer is due.
+//! Hotkey releases are detected on a helper thread of the hotkey crate, so
+//! event handlers post a thread message to wake the loop for them.
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+#[cfg(windows)]
+use windows::Win32::Foundation::{LPARAM, WPARAM};
+#[cfg(windows)]
+use windows::Win32::System::Threading::GetCurrentThreadId;
+#[cfg(windows)]
+use windows::Win32::UI::WindowsAndMessaging::{
+    MWMO_INPUTAVAILABLE, MsgWaitForMultipleObjectsEx, PostThreadMessageW, QS_ALLINPUT, WM_APP,
+};
+
+/// Longest sleep without message support, matching the former polling interval
+#[cfg(not(windows))]
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Thread running the event loop, set once by [`init`]
+static LOOP_THREAD: OnceLock<u32> = OnceLock::new();
+
+/// Remember the calling thread as the one [`wake`] wakes up
+pub fn init() {
+    #[cfg(windows)]
+    // SAFETY: GetCurrentThreadId has no preconditions.
+    LOOP_THREAD.get_or_init(|| unsafe { GetCurrentThreadId() });
+    #[cfg(not(windows))]
+    LOOP_THREAD.get_or_init(|| 0);
+}
+
+/// Wake the event loop from any thread; harmless when it is already awake
+pub fn wake() {
+    #[cfg(windows)]
+    if let Some(&thread) = LOOP_THREAD.get() {
+        // SAFETY: posting a message without payload to a thread ID has no
+        // invariants; it fails harmlessly if the thread has no queue.
+        let _ = unsafe { PostThreadMessageW(thread, WM_APP, WPARAM(0), LPARAM(0)) };
+    }
+}
+
+/// Block until a message arrives for this thread or `timeout` has passed
+pub fn wait(timeout: Duration) {
+    #[cfg(windows)]
+    {
+        let millis = u32::try_from(timeout.as_millis()).unwrap_or(u32::MAX);
+        // SAFETY: no handles are passed; the call only blocks the current thread.
+        // MWMO_INPUTAVAILABLE also returns for messages that arrived before the call.
+        unsafe {
+            MsgWaitForMultipleObjectsEx(None, millis, QS_ALLINPUT, MWMO_INPUTAVAILABLE);
+        }
+    }
+    #[cfg(not(windows))]
+    std::thread::sleep(timeout.min(POLL_INTERVAL));
+}
@@ -0,0 +1,76 @@
+//! Mono WAV files of Whisper input.
+//!
+//! Samples are stored as 16-bit PCM, which halves the size of `f32` and is
+//! more than Whisper's 16 kHz input resolves. Files are written under a
+//! temporary name and renamed into place, so a crash never leaves half a file.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
+
+use crate::audio::{self, WHISPER_SAMPLE_RATE};
+
+/// Write 16 kHz mono `samples` to `path`, replacing any file there
+pub fn write(path: &Path, samples: &[f32]) -> Result<()> {
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: WHISPER_SAMPLE_RATE,
+        bits_per_sample: 16,
+        sample_format: SampleFormat::Int,
+    };
+    let partial = path.with_extension("wav.part");
+    let mut writer = WavWriter::create(&partial, spec)
+        .with_context(|| format!("Failed to create {}", partial.display()))?;
+    for &sample in samples {
+        writer
+            .write_sample(to_pcm(sample))
+            .context("Failed to write WAV sample")?;
+    }
+    writer.finalize().context("Failed to finish WAV file")?;
+    std::fs::rename(&partial, path)
+        .with_context(|| format!("Failed to move WAV file to {}", path.display()))
+}
+
+/// Read the WAV file at `path` as Whisper input, converting other formats
+pub fn read(path: &Path) -> Result<Vec<f32>> {
+    let mut reader =
+        WavReader::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let spec = reader.spec();
+    let samples = match spec.sample_format {
+        SampleFormat::Int => {
+            let scale = 2.0_f32.powi(i32::from(spec.bits_per_sample) - 1);
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|s| pcm_to_float(s, scale)))
+                .collect::<Result<Vec<_>, _>>()
+        }
+        SampleFormat::Float => reader.samples::<f32>().collect(),
+    }
+    .with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(audio::to_whisper_input(
+        &samples,
+        spec.channels,
+        spec.sample_rate,
+    ))
+}
+
+/// `sample` in 16-bit PCM, clipped to full scale
+#[allow(
+    clippy::as_conversions,
+    clippy::cast_possible_truncation,
+    reason = "the value is clamped to the i16 range before the cast"
+)]
+fn to_pcm(sample: f32) -> i16 {
+    (sample.clamp(-1.0, 1.0) * f32::from(i16::MAX)).round() as i16
+}
+
+/// PCM `sample` as a float, full scale at `scale`
+#[allow(
+    clippy::as_conversions,
+    clippy::cast_precision_loss,
+    reason = "PCM samples have at most 32 bits, read as floats for Whisper"
+)]
+fn pcm_to_float(sample: i32, scale: f32) -> f32 {
+    sample as f32 / scale
+}
@@ -0,0 +1,238 @@
+//! Posting finished dictations to an automation endpoint.
+//!
+//! Each typed transcription is sent as one JSON object to `webhook_url` by a
+//! worker thread, so a slow or missing endpoint never delays typing. Only
+//! plain `http://` is spoken, which is what local services such as n8n or
+//! Home Assistant listen on.
+
+use std::fmt;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::str::FromStr;
+use std::sync::mpsc::{self, Sender};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use tracing::{debug, warn};
+
+/// Longest a connection, the request or the response may take
+const TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Pause before the single retry of a failed delivery
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Endpoint parsed from `http://host[:port][/path]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebhookUrl {
+    /// Host name or IP address
+    host: String,
+    /// TCP port, 80 unless given
+    port: u16,
+    /// Path and query sent in the request line, at least `/`
+    path: String,
+}
+
+impl FromStr for WebhookUrl {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let Some(rest) = s.strip_prefix("http://") else {
+            if s.starts_with("https://") {
+                anyhow::bail!(
+                    "https is not supported, use an http:// endpoint on this machine or network"
+                );
+            }
+            anyhow::bail!("expected an http:// URL");
+        };
+        let (authority, path) = rest
+            .find(['/', '?'])
+            .map_or((rest, "/"), |at| rest.split_at(at));
+        // An IPv6 address is bracketed, and its colons are not a port
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => (
+                host,
+                port.parse()
+                    .with_context(|| format!("invalid port {port:?}"))?,
+            ),
+            _ => (authority, 80),
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if host.is_empty() || host.contains('@') {
+            anyhow::bail!("expected a host name after http://");
+        }
+        Ok(Self {
+            host: host.to_owned(),
+            port,
+            path: if path.starts_with('?') {
+                format!("/{path}")
+            } else {
+                path.to_owned()
+            },
+        })
+    }
+}
+
+impl WebhookUrl {
+    /// Host and port as written in a URL or `Host` header, an IPv6 address
+    /// in brackets
+    fn authority(&self) -> String {
+        if self.host.contains(':') {
+            format!("[{}]:{}", self.host, self.port)
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+}
+
+impl fmt::Display for WebhookUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "http://{}{}", self.authority(), self.path)
+    }
+}
+
+/// What is posted for one dictation
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookPayload {
+    /// Transcribed text, left out when the settings keep it private
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// Words typed
+    pub words: usize,
+    /// Length of the recording in seconds
+    pub duration_secs: f64,
+    /// Language the recording was transcribed in
+    pub language: String,
+    /// UTC time the text was typed, ISO-8601
+    pub timestamp: String,
+    /// Executable of the window the text was typed into, if known
+    pub app: Option<String>,
+    /// Title of that window
+    pub title: Option<String>,
+}
+
+/// Delivers payloads in order on a worker thread, which ends when this is dropped
+pub struct Webhook {
+    /// Payloads waiting for the worker
+    payloads: Sender<WebhookPayload>,
+}
+
+impl Webhook {
+    /// Start posting to `url`, retrying each failed delivery once if `retry` is set.
+    pub fn start(url: WebhookUrl, retry: bool) -> Self {
+        let (payloads, queue) = mpsc::channel::<WebhookPayload>();
+        let spawned = std::thread::Builder::new()
+            .name("webhook".to_owned())
+            .spawn(move || {
+                for payload in queue {
+                    let body = match serde_json::to_string(&payload) {
+                        Ok(body) => body,
+                        Err(e) => {
+                            warn!("Failed to encode webhook payload: {}", e);
+                            continue;
+                        }
+                    };
+                    let mut result = post(&url, &body);
+                    if retry && let Err(ref e) = result {
+                        debug!("Webhook delivery failed, retrying: {:#}", e);
+                        std::thread::sleep(RETRY_DELAY);
+                        result = post(&url, &body);
+                    }
+                    match result {
+                        Ok(()) => debug!("Posted transcription to {}", url),
+                        Err(e) => warn!("Failed to post transcription to {}: {:#}", url, e),
+                    }
+                }
+            });
+        if let Err(e) = spawned {
+            warn!("Failed to start webhook thread: {}", e);
+        }
+        Self { payloads }
+    }
+
+    /// Queue `payload` for delivery without waiting for it
+    pub fn send(&self, payload: WebhookPayload) {
+        if self.payloads.send(payload).is_err() {
+            warn!("Webhook thread is not running, transcription not posted");
+        }
+    }
+}
+
+/// POST `body` as JSON to `url`, succeeding on any 2xx status.
+fn post(url: &WebhookUrl, body: &str) -> Result<()> {
+    let address = (url.host.as_str(), url.port)
+        .to_socket_addrs()
+        .with_context(|| format!("Cannot resolve {}", url.host))?
+        .next()
+        .with_context(|| format!("{} has no address", url.host))?;
+    let mut stream = TcpStream::connect_timeout(&address, TIMEOUT).context("Failed to connect")?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: speedy-stt/{}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        url.path,
+        url.authority(),
+        env!("CARGO_PKG_VERSION"),
+        body.len()
+    );
+    stream
+        .write_all(request.as_bytes())
+        .context("Failed to send request")?;
+
+    let mut status_line = String::new();
+    BufReader::new(&stream)
+        .read_line(&mut status_line)
+        .context("Failed to read response")?;
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok())
+        .with_context(|| format!("Invalid response {:?}", status_line.trim()))?;
+    if !(200..300).contains(&status) {
+        anyhow::bail!("endpoint answered {}", status_line.trim());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `url` parsed as a webhook endpoint
+    fn parsed(url: &str) -> WebhookUrl {
+        url.parse().expect("webhook URL should parse")
+    }
+
+    #[test]
+    fn an_ipv6_host_is_bracketed_with_its_port() {
+        let url = parsed("http://[::1]:5678/hook");
+        assert_eq!(url.host, "::1", "brackets kept in the host");
+        assert_eq!(url.authority(), "[::1]:5678", "Host header not bracketed");
+        assert_eq!(
+            url.to_string(),
+            "http://[::1]:5678/hook",
+            "displayed target not bracketed"
+        );
+        assert_eq!(
+            parsed("http://[fe80::1]").authority(),
+            "[fe80::1]:80",
+            "default port not added after the brackets"
+        );
+    }
+
+    #[test]
+    fn a_name_or_ipv4_host_is_written_plainly() {
+        assert_eq!(
+            parsed("http://localhost:5678/hook").authority(),
+            "localhost:5678",
+            "host name written wrongly"
+        );
+        assert_eq!(
+            parsed("http://127.0.0.1?x=1").to_string(),
+            "http://127.0.0.1:80/?x=1",
+            "IPv4 target written wrongly"
+        );
+    }
+}
@@ -1,68 +1,544 @@
 //! Whisper model loading and inference
 
-use std::path::Path;
+use std::borrow::Cow;
+use std::fmt;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError, TryLockError};
+use std::time::Duration;
 
-use anyhow::{Context, Result};
 use tracing::info;
-use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+use whisper_rs::{
+    FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters, WhisperSegment,
+};
+
+use crate::spacing::Spacing;
+
+/// File signature of ggml model files: the `ggml` magic number in little-endian order
+const GGML_MAGIC: [u8; 4] = *b"lmgg";
+
+/// Why a model could not be loaded or audio not transcribed
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum EngineError {
+    /// The model path is not valid Unicode, which Whisper requires
+    #[error("Invalid model path")]
+    InvalidPath,
+    /// The model file could not be read
+    #[error("Cannot read model file {}", .path.display())]
+    Unreadable {
+        /// Model file
+        path: PathBuf,
+        /// What went wrong reading it
+        #[source]
+        source: io::Error,
+    },
+    /// The file is not a ggml Whisper model
+    #[error("{} is not a ggml Whisper model", .0.display())]
+    NotAModel(PathBuf),
+    /// Whisper rejected the model
+    #[error("Failed to load Whisper model")]
+    Load(#[source] whisper_rs::WhisperError),
+    /// Whisper could not set up a pass
+    #[error("Failed to create Whisper state")]
+    State(#[source] whisper_rs::WhisperError),
+    /// The pass failed or was aborted
+    #[error("Failed to transcribe audio")]
+    Inference(#[source] whisper_rs::WhisperError),
+    /// A segment's text is not valid UTF-8
+    #[error("Failed to get segment text")]
+    SegmentText(#[source] whisper_rs::WhisperError),
+    /// The options ask for something this build or combination cannot do
+    #[error("Unsupported Whisper options: {0}")]
+    Unsupported(&'static str),
+}
+
+/// Result of engine operations
+pub type Result<T, E = EngineError> = std::result::Result<T, E>;
+
+/// Language to transcribe in: a code Whisper knows, such as `en`, or `auto`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LanguageCode(String);
+
+impl LanguageCode {
+    /// Detect the spoken language
+    pub fn auto() -> Self {
+        Self("auto".to_owned())
+    }
+
+    /// The code as Whisper takes it
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Default for LanguageCode {
+    /// English
+    fn default() -> Self {
+        Self("en".to_owned())
+    }
+}
+
+/// A language code Whisper does not know
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("unknown language {0:?}, use an ISO 639-1 code such as \"en\", or \"auto\"")]
+pub struct UnknownLanguage(pub String);
+
+impl FromStr for LanguageCode {
+    type Err = UnknownLanguage;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let code = s.trim().to_lowercase();
+        if WhisperEngine::is_known_language(&code) {
+            Ok(Self(code))
+        } else {
+            Err(UnknownLanguage(s.trim().to_owned()))
+        }
+    }
+}
+
+impl fmt::Display for LanguageCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Whisper task requested for a recording session
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TranscriptionTask {
+    /// Transcribe in the spoken language
+    Transcribe,
+    /// Translate the speech to English
+    Translate,
+}
+
+/// A stretch of transcribed speech and where it lies in the audio
+#[derive(Debug, Clone)]
+pub struct Segment {
+    /// Offset of its start from the start of the audio
+    pub start: Duration,
+    /// Offset of its end
+    pub end: Duration,
+    /// Text as Whisper returned it, usually with a leading space
+    pub text: String,
+    /// Tokens it was decoded from, only collected when a full pass asks for them
+    pub tokens: Vec<Token>,
+}
+
+/// One token Whisper decoded, special ones such as timestamps included
+#[derive(Debug, Clone)]
+pub struct Token {
+    /// Id in the model's vocabulary
+    pub id: i32,
+    /// Text it stands for, lossily decoded where it splits a character
+    pub text: String,
+    /// Probability Whisper gave it
+    pub probability: f32,
+    /// Offset of its start from the start of the audio
+    pub start: Duration,
+    /// Offset of its end
+    pub end: Duration,
+}
+
+/// Segments of one pass and the language they are written in
+#[derive(Debug, Clone)]
+pub struct Transcript {
+    /// Segments in the order spoken
+    pub segments: Vec<Segment>,
+    /// Language code of the text: the requested one, the detected one for
+    /// `auto`, or `en` for translations
+    pub language: String,
+}
+
+impl Transcript {
+    /// The whole text, its segments joined as `spacing` says for its language
+    pub fn text(&self, spacing: &Spacing) -> String {
+        spacing.join(
+            self.segments.iter().map(|segment| segment.text.as_str()),
+            &self.language,
+        )
+    }
+}
+
+/// Settings fixed when a model is loaded, passed to whisper.cpp's context
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WhisperOptions {
+    /// Run on the GPU, which needs whisper-rs built with a GPU back-end
+    pub use_gpu: bool,
+    /// Index of the GPU to run on
+    pub gpu_device: u32,
+    /// Flash attention, faster on back-ends that support it
+    pub flash_attn: bool,
+}
+
+impl Default for WhisperOptions {
+    /// The GPU when this build has a back-end for it, as whisper-rs defaults to
+    fn default() -> Self {
+        Self {
+            use_gpu: Self::gpu_built(),
+            gpu_device: 0,
+            flash_attn: false,
+        }
+    }
+}
+
+impl WhisperOptions {
+    /// Whether whisper-rs was built with a GPU back-end such as CUDA
+    pub fn gpu_built() -> bool {
+        WhisperContextParameters::default().use_gpu
+    }
+
+    /// Context parameters for these options, refusing combinations that
+    /// would otherwise only fail, or silently do something else, in a pass
+    fn context_parameters(self) -> Result<WhisperContextParameters<'static>> {
+        if self.use_gpu && !Self::gpu_built() {
+            return Err(EngineError::Unsupported(
+                "the GPU is requested but this build has no GPU back-end",
+            ));
+        }
+        if !self.use_gpu && self.gpu_device != 0 {
+            return Err(EngineError::Unsupported(
+                "a GPU device is chosen but the GPU is off",
+            ));
+        }
+        let gpu_device = i32::try_from(self.gpu_device)
+            .map_err(|_| EngineError::Unsupported("the GPU device index is too large"))?;
+        let mut params = WhisperContextParameters::default();
+        params
+            .use_gpu(self.use_gpu)
+            .gpu_device(gpu_device)
+            .flash_attn(self.flash_attn);
+        Ok(params)
+    }
+}
 
 /// Whisper transcription engine
+///
+/// Passes run one at a time; a preview pass gives way to a full one.
 pub struct WhisperEngine {
     /// Whisper context
     ctx: WhisperContext,
     /// Number of threads for inference
     threads: usize,
-    /// Language code
-    language: String,
+    /// Held for the duration of every pass
+    pass: Mutex<()>,
+    /// Full passes waiting for [`Self::pass`], which abort a running preview
+    full_waiting: Arc<AtomicUsize>,
 }
 
 impl WhisperEngine {
     /// Load Whisper model
-    pub fn load(model_path: &Path, threads: usize, language: String) -> Result<Self> {
+    pub fn load(model_path: &Path, threads: usize) -> Result<Self> {
+        Self::load_with_options(model_path, threads, WhisperOptions::default())
+    }
+
+    /// Load Whisper model with context `options`, failing up front when
+    /// they cannot be used
+    pub fn load_with_options(
+        model_path: &Path,
+        threads: usize,
+        options: WhisperOptions,
+    ) -> Result<Self> {
         info!("Loading Whisper model from: {}", model_path.display());
+        let params = options.context_parameters()?;
+        info!(
+            "Whisper options: GPU {} (device {}), flash attention {}",
+            if options.use_gpu { "on" } else { "off" },
+            options.gpu_device,
+            if options.flash_attn { "on" } else { "off" }
+        );
+        info!("Whisper system info: {}", whisper_rs::print_system_info());
 
         let ctx = WhisperContext::new_with_params(
-            model_path.to_str().context("Invalid model path")?,
-            WhisperContextParameters::default(),
+            model_path.to_str().ok_or(EngineError::InvalidPath)?,
+            params,
         )
-        .context("Failed to load Whisper model")?;
+        .map_err(EngineError::Load)?;
 
-        info!("Whisper model loaded successfully");
+        info!(
+            "Whisper model loaded successfully, using {} threads",
+            threads
+        );
 
         Ok(Self {
             ctx,
             threads,
-            language,
+            pass: Mutex::new(()),
+            full_waiting: Arc::new(AtomicUsize::new(0)),
         })
     }
 
-    /// Transcribe audio samples
-    pub fn transcribe(&self, samples: &[f32]) -> Result<String> {
+    /// Whether Whisper recognizes `language` as a language code, or it is `auto`
+    pub fn is_known_language(language: &str) -> bool {
+        language == "auto"
+            || (!language.contains('\0') && whisper_rs::get_lang_id(language).is_some())
+    }
+
+    /// Thread count to use for a configured `whisper_threads` value on this machine
+    ///
+    /// `0` picks automatically; see [`thread_count`].
+    pub fn effective_threads(requested: usize) -> usize {
+        let logical = std::thread::available_parallelism().map_or(1, std::num::NonZero::get);
+        thread_count(requested, physical_core_count(), logical)
+    }
+
+    /// Check that `model_path` looks like a ggml Whisper model, without loading it
+    pub fn check_model(model_path: &Path) -> Result<()> {
+        let mut magic = [0u8; 4];
+        std::fs::File::open(model_path)
+            .and_then(|mut file| file.read_exact(&mut magic))
+            .map_err(|source| EngineError::Unreadable {
+                path: model_path.to_owned(),
+                source,
+            })?;
+        if magic != GGML_MAGIC {
+            return Err(EngineError::NotAModel(model_path.to_owned()));
+        }
+        Ok(())
+    }
+
+    /// Transcribe audio samples spoken in `language`, translating to English if requested
+    ///
+    /// With `tokens` each segment also carries the tokens it was decoded from,
+    /// with their probabilities and timestamps.
+    pub fn transcribe(
+        &self,
+        samples: &[f32],
+        language: &LanguageCode,
+        task: TranscriptionTask,
+        tokens: bool,
+    ) -> Result<Transcript> {
+        self.transcribe_with_progress(samples, language, task, tokens, None)
+    }
+
+    /// Transcribe like [`Self::transcribe`], calling `progress` with the
+    /// percentage done as inference advances.
+    pub fn transcribe_with_progress(
+        &self,
+        samples: &[f32],
+        language: &LanguageCode,
+        task: TranscriptionTask,
+        tokens: bool,
+        progress: Option<Box<dyn FnMut(i32)>>,
+    ) -> Result<Transcript> {
+        let language = language.as_str();
+        let mut params = self.params(language, task);
+        params.set_progress_callback_safe::<_, Box<dyn FnMut(i32)>>(progress);
+        params.set_token_timestamps(tokens);
+
+        self.full_waiting.fetch_add(1, Ordering::SeqCst);
+        let pass = self.pass.lock().unwrap_or_else(PoisonError::into_inner);
+        self.full_waiting.fetch_sub(1, Ordering::SeqCst);
+        let transcript = self.run(params, samples, language, task, tokens);
+        drop(pass);
+        transcript
+    }
+
+    /// Transcribe the audio of a recording still in progress for a preview.
+    ///
+    /// `None` when another pass is running or a full pass interrupted this one,
+    /// so previews never delay the text that is typed.
+    pub fn transcribe_preview(
+        &self,
+        samples: &[f32],
+        language: &LanguageCode,
+        task: TranscriptionTask,
+    ) -> Result<Option<Transcript>> {
+        let language = language.as_str();
+        let pass = match self.pass.try_lock() {
+            Ok(pass) => pass,
+            Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+            Err(TryLockError::WouldBlock) => return Ok(None),
+        };
+        let mut params = self.params(language, task);
+        let full_waiting = Arc::clone(&self.full_waiting);
+        let abort: Box<dyn FnMut() -> bool> =
+            Box::new(move || full_waiting.load(Ordering::SeqCst) > 0);
+        params.set_abort_callback_safe::<_, Box<dyn FnMut() -> bool>>(Some(abort));
+        let result = self.run(params, samples, language, task, false);
+        drop(pass);
+        match result {
+            Ok(transcript) => Ok(Some(transcript)),
+            Err(_) if self.full_waiting.load(Ordering::SeqCst) > 0 => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Parameters shared by every pass
+    fn params<'a>(&self, language: &'a str, task: TranscriptionTask) -> FullParams<'a, 'static> {
         let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
         params.set_n_threads(i32::try_from(self.threads).unwrap_or(4));
-        params.set_language(Some(&self.language));
+        params.set_language(Some(language));
+        params.set_translate(task == TranscriptionTask::Translate);
         params.set_print_progress(false);
         params.set_print_special(false);
         params.set_print_realtime(false);
+        params
+    }
 
-        let mut state = self
-            .ctx
-            .create_state()
-            .context("Failed to create Whisper state")?;
+    /// Run one pass over `samples`, collecting the tokens of each segment if
+    /// `tokens`; the caller holds [`Self::pass`].
+    fn run(
+        &self,
+        params: FullParams<'_, '_>,
+        samples: &[f32],
+        language: &str,
+        task: TranscriptionTask,
+        tokens: bool,
+    ) -> Result<Transcript> {
+        let mut state = self.ctx.create_state().map_err(EngineError::State)?;
         state
             .full(params, samples)
-            .context("Failed to transcribe audio")?;
+            .map_err(EngineError::Inference)?;
 
         let num_segments = state.full_n_segments();
-        let mut text = String::new();
+        let mut segments = Vec::new();
 
         for i in 0..num_segments {
             if let Some(segment) = state.get_segment(i) {
-                let segment_text = segment.to_str().context("Failed to get segment text")?;
-                text.push_str(segment_text);
+                let segment_text = segment.to_str().map_err(EngineError::SegmentText)?;
+                segments.push(Segment {
+                    start: centiseconds(segment.start_timestamp()),
+                    end: centiseconds(segment.end_timestamp()),
+                    text: segment_text.to_owned(),
+                    tokens: if tokens {
+                        segment_tokens(&segment)
+                    } else {
+                        Vec::new()
+                    },
+                });
             }
         }
 
-        Ok(text.trim().to_string())
+        let language = match task {
+            TranscriptionTask::Translate => "en",
+            TranscriptionTask::Transcribe if language == "auto" => {
+                whisper_rs::get_lang_str(state.full_lang_id_from_state()).unwrap_or(language)
+            }
+            TranscriptionTask::Transcribe => language,
+        };
+        Ok(Transcript {
+            segments,
+            language: language.to_owned(),
+        })
+    }
+}
+
+/// Tokens of `segment` in the order decoded
+fn segment_tokens(segment: &WhisperSegment<'_>) -> Vec<Token> {
+    (0..segment.n_tokens())
+        .filter_map(|i| segment.get_token(i))
+        .map(|token| {
+            let data = token.token_data();
+            Token {
+                id: token.token_id(),
+                text: token
+                    .to_str_lossy()
+                    .map(Cow::into_owned)
+                    .unwrap_or_default(),
+                probability: token.token_probability(),
+                start: centiseconds(data.t0),
+                end: centiseconds(data.t1),
+            }
+        })
+        .collect()
+}
+
+/// Duration of a Whisper timestamp, which counts hundredths of a second
+fn centiseconds(timestamp: i64) -> Duration {
+    Duration::from_millis(u64::try_from(timestamp).unwrap_or(0) * 10)
+}
+
+/// Inference threads for a requested count and the machine's core counts.
+///
+/// `0` means one less than the physical cores (falling back to logical cores),
+/// so recording, the tray and text injection keep a core while Whisper runs.
+/// Explicit counts are capped at the logical core count. Always at least 1.
+fn thread_count(requested: usize, physical: Option<usize>, logical: usize) -> usize {
+    let logical = logical.max(1);
+    if requested == 0 {
+        physical
+            .unwrap_or(logical)
+            .min(logical)
+            .saturating_sub(1)
+            .max(1)
+    } else {
+        requested.min(logical)
+    }
+}
+
+/// Number of physical processor cores, if the system reports it.
+#[cfg(windows)]
+fn physical_core_count() -> Option<usize> {
+    use windows::Win32::System::SystemInformation::{
+        GetLogicalProcessorInformation, RelationProcessorCore, SYSTEM_LOGICAL_PROCESSOR_INFORMATION,
+    };
+
+    let mut length = 0u32;
+    // SAFETY: a null buffer only queries the required length into `length`;
+    // the call is expected to fail with ERROR_INSUFFICIENT_BUFFER.
+    let _ = unsafe { GetLogicalProcessorInformation(None, &raw mut length) };
+    let entries = usize::try_from(length).ok()? / size_of::<SYSTEM_LOGICAL_PROCESSOR_INFORMATION>();
+    let mut buffer = vec![SYSTEM_LOGICAL_PROCESSOR_INFORMATION::default(); entries];
+
+    // SAFETY: `buffer` holds `length` bytes of properly aligned entries and
+    // stays alive for the duration of the call.
+    unsafe { GetLogicalProcessorInformation(Some(buffer.as_mut_ptr()), &raw mut length) }.ok()?;
+    let filled = usize::try_from(length).ok()? / size_of::<SYSTEM_LOGICAL_PROCESSOR_INFORMATION>();
+    let cores = buffer
+        .iter()
+        .take(filled)
+        .filter(|info| info.Relationship == RelationProcessorCore)
+        .count();
+    (cores > 0).then_some(cores)
+}
+
+/// Number of physical processor cores, if the system reports it.
+#[cfg(not(windows))]
+const fn physical_core_count() -> Option<usize> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn automatic_threads_leave_a_physical_core_free() {
+        assert_eq!(thread_count(0, Some(8), 16), 7, "hyper-threaded machine");
+        assert_eq!(thread_count(0, Some(4), 4), 3, "one thread per core");
+        assert_eq!(thread_count(0, None, 6), 5, "physical cores unknown");
+    }
+
+    #[test]
+    fn automatic_threads_never_drop_to_zero() {
+        assert_eq!(thread_count(0, Some(1), 1), 1, "single core");
+        assert_eq!(thread_count(0, Some(1), 2), 1, "one hyper-threaded core");
+        assert_eq!(thread_count(0, None, 0), 1, "no cores reported");
+        assert_eq!(
+            thread_count(0, Some(0), 4),
+            1,
+            "zero physical cores reported"
+        );
+    }
+
+    #[test]
+    fn automatic_threads_trust_the_logical_count_over_a_larger_physical_one() {
+        // Affinity or a container can allow fewer logical cores than exist
+        assert_eq!(thread_count(0, Some(16), 4), 3, "physical above logical");
+    }
+
+    #[test]
+    fn requested_threads_are_capped_at_the_logical_cores() {
+        assert_eq!(thread_count(4, Some(8), 16), 4, "within the logical cores");
+        assert_eq!(
+            thread_count(16, Some(8), 16),
+            16,
+            "exactly the logical cores"
+        );
+        assert_eq!(thread_count(64, Some(8), 16), 16, "above the logical cores");
+        assert_eq!(thread_count(3, None, 0), 1, "no cores reported");
     }
 }